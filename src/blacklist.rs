@@ -1,12 +1,17 @@
-use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_OPENCODE};
+use crate::atomic::write_atomic;
+use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_GEMINI, TOOL_OPENCODE};
 use crate::history::HistoryRecorder;
 use crate::markers::is_relay_generated_command_skill;
+use crate::sync::command_skill_name;
+use crate::tools::{is_known_tool, provider_entries};
 use std::fs;
 use std::io;
 use std::path::{Component, Path, PathBuf};
 
 pub(crate) const LEGACY_AGENTS_BLACKLIST_KEY: &str = "agents/AGENTS.md";
+pub(crate) const CLAUDE_AGENTS_BLACKLIST_KEY: &str = "agents/claude/CLAUDE.md";
 pub(crate) const CODEX_AGENTS_BLACKLIST_KEY: &str = "agents/codex/AGENTS.md";
+pub(crate) const GEMINI_AGENTS_BLACKLIST_KEY: &str = "agents/gemini/GEMINI.md";
 pub(crate) const OPENCODE_AGENTS_BLACKLIST_KEY: &str = "agents/opencode/AGENTS.md";
 pub(crate) const CODEX_RULES_BLACKLIST_KEY: &str = "rules/codex/default.rules";
 
@@ -14,7 +19,9 @@ pub(crate) fn collect_tool_flags(
     claude: bool,
     codex: bool,
     cursor: bool,
+    gemini: bool,
     opencode: bool,
+    extra: Vec<String>,
 ) -> Vec<String> {
     let mut tools = Vec::new();
     if claude {
@@ -26,15 +33,31 @@ pub(crate) fn collect_tool_flags(
     if cursor {
         tools.push(TOOL_CURSOR.to_string());
     }
+    if gemini {
+        tools.push(TOOL_GEMINI.to_string());
+    }
     if opencode {
         tools.push(TOOL_OPENCODE.to_string());
     }
+    for tool in extra {
+        if !tools.contains(&tool) {
+            tools.push(tool);
+        }
+    }
     tools
 }
 
 #[cfg_attr(any(test, coverage), allow(dead_code))]
 pub(crate) fn add_blacklist(cfg: &mut Config, path: &str, tools: &[String]) -> io::Result<()> {
-    validate_blacklist_path(path)?;
+    validate_blacklist_path(cfg, path)?;
+    if let Some(unknown) = tools.iter().find(|tool| !is_known_tool(cfg, tool)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unknown tool '{unknown}'; expected a built-in tool or a [providers.<id>] entry"
+            ),
+        ));
+    }
     let entry = cfg.blacklist.entry(path.to_string()).or_default();
     for tool in tools {
         if !entry.iter().any(|t| t == tool) {
@@ -57,7 +80,29 @@ pub(crate) fn remove_blacklist(cfg: &mut Config, path: &str, tools: &[String]) -
     save_config(cfg)
 }
 
-#[cfg_attr(any(test, coverage), allow(dead_code))]
+/// Moves a saved blacklist entry to a new path when a sync with `cfg` detects
+/// that the blacklisted item was renamed. Project syncs borrow the global
+/// blacklist, where the old entry still covers the global store and other
+/// repositories, so they only add the new path.
+pub(crate) fn rename_blacklist_entry(cfg: &Config, from: &str, to: &str) -> io::Result<()> {
+    let mut saved = Config::load_or_default()?;
+    let tools = if cfg.project_root.is_some() {
+        saved.blacklist.get(from).cloned()
+    } else {
+        saved.blacklist.remove(from)
+    };
+    let Some(tools) = tools else {
+        return Ok(());
+    };
+    let entry = saved.blacklist.entry(to.to_string()).or_default();
+    for tool in tools {
+        if !entry.contains(&tool) {
+            entry.push(tool);
+        }
+    }
+    save_config(&saved)
+}
+
 fn save_config(cfg: &Config) -> io::Result<()> {
     let config_path = Config::config_path()?;
     cfg.save(&config_path)
@@ -83,17 +128,8 @@ fn validated_tool_suffix(raw_suffix: &str) -> Option<&Path> {
     has_normal.then_some(suffix)
 }
 
-fn command_basename(suffix: &Path) -> Option<String> {
-    let mut components = suffix.components();
-    match (components.next(), components.next()) {
-        (Some(Component::Normal(name)), None) => Some(name.to_string_lossy().into_owned()),
-        _ => None,
-    }
-}
-
 fn codex_command_skill_candidate(codex_skills_dir: &Path, suffix: &Path) -> Option<PathBuf> {
-    let name = command_basename(suffix)?;
-    let skill_name = name.strip_suffix(".md")?;
+    let skill_name = command_skill_name(suffix.to_str()?)?;
     Some(codex_skills_dir.join(skill_name))
 }
 
@@ -106,20 +142,63 @@ fn push_unique(paths: &mut Vec<PathBuf>, candidate: PathBuf) {
 fn is_supported_agents_blacklist_path(path: &str) -> bool {
     matches!(
         path,
-        LEGACY_AGENTS_BLACKLIST_KEY | CODEX_AGENTS_BLACKLIST_KEY | OPENCODE_AGENTS_BLACKLIST_KEY
+        LEGACY_AGENTS_BLACKLIST_KEY
+            | CLAUDE_AGENTS_BLACKLIST_KEY
+            | CODEX_AGENTS_BLACKLIST_KEY
+            | GEMINI_AGENTS_BLACKLIST_KEY
+            | OPENCODE_AGENTS_BLACKLIST_KEY
     )
 }
 
-fn validate_blacklist_path(path: &str) -> io::Result<()> {
-    if path.starts_with("agents/") && !is_supported_agents_blacklist_path(path) {
+/// Blacklist keys for provider agents and rules files, paired with the
+/// provider id and the file they resolve to.
+fn provider_file_keys(cfg: &Config) -> Vec<(String, &str, &PathBuf)> {
+    let mut keys = Vec::new();
+    for provider in provider_entries(cfg) {
+        for (kind, file) in [
+            ("agents", provider.agents_file),
+            ("rules", provider.rules_file),
+        ] {
+            if let Some(file) = file {
+                if let Some(name) = provider.central_file_name(file) {
+                    keys.push((format!("{kind}/{name}"), provider.id, file));
+                }
+            }
+        }
+    }
+    keys
+}
+
+fn is_provider_file_key(cfg: &Config, path: &str) -> bool {
+    provider_file_keys(cfg)
+        .iter()
+        .any(|(key, _, _)| key == path)
+}
+
+fn validate_blacklist_path(cfg: &Config, path: &str) -> io::Result<()> {
+    if path.starts_with("agents/")
+        && !is_supported_agents_blacklist_path(path)
+        && !is_provider_file_key(cfg, path)
+    {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             format!(
-                "invalid agents blacklist path '{path}'; supported paths: {LEGACY_AGENTS_BLACKLIST_KEY}, {CODEX_AGENTS_BLACKLIST_KEY}, {OPENCODE_AGENTS_BLACKLIST_KEY}"
+                "invalid agents blacklist path '{path}'; supported paths: {LEGACY_AGENTS_BLACKLIST_KEY}, {CLAUDE_AGENTS_BLACKLIST_KEY}, {CODEX_AGENTS_BLACKLIST_KEY}, {GEMINI_AGENTS_BLACKLIST_KEY}, {OPENCODE_AGENTS_BLACKLIST_KEY}"
             ),
         ));
     }
-    if path.starts_with("rules/") && path != CODEX_RULES_BLACKLIST_KEY {
+    if let Some(name) = path.strip_prefix("mcp/") {
+        if name.is_empty() || name.contains('/') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid mcp blacklist path '{path}'; expected mcp/<server-name>"),
+            ));
+        }
+    }
+    if path.starts_with("rules/")
+        && path != CODEX_RULES_BLACKLIST_KEY
+        && !is_provider_file_key(cfg, path)
+    {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             format!(
@@ -133,6 +212,10 @@ fn validate_blacklist_path(path: &str) -> io::Result<()> {
 fn retroactive_delete(cfg: &Config, path: &str, tools: &[String]) -> io::Result<()> {
     let mut recorder = HistoryRecorder::new(cfg, &format!("blacklist:{path}"))?;
     for tool in tools {
+        if let Some(name) = path.strip_prefix("mcp/") {
+            remove_mcp_server(cfg, name, tool, &mut recorder)?;
+            continue;
+        }
         let targets = resolve_tool_paths(cfg, path, tool);
         for target in targets {
             if target.exists() {
@@ -154,6 +237,28 @@ fn retroactive_delete(cfg: &Config, path: &str, tools: &[String]) -> io::Result<
     Ok(())
 }
 
+/// MCP servers live inside shared tool config files, so blacklisting one
+/// removes just that entry instead of deleting the file.
+fn remove_mcp_server(
+    cfg: &Config,
+    name: &str,
+    tool: &str,
+    recorder: &mut HistoryRecorder,
+) -> io::Result<()> {
+    let Some((target, contents)) = crate::sync::mcp_server_removal(cfg, name, tool)? else {
+        return Ok(());
+    };
+    let before = recorder.capture_path(&target)?;
+    write_atomic(&target, &contents)?;
+    let after = recorder.capture_path(&target)?;
+    recorder.record_change(&target, before, after);
+    eprintln!(
+        "blacklist: removed mcp server {name} from {}",
+        target.display()
+    );
+    Ok(())
+}
+
 pub(crate) fn resolve_tool_paths(cfg: &Config, relative_path: &str, tool: &str) -> Vec<PathBuf> {
     let mut paths = Vec::new();
 
@@ -171,8 +276,21 @@ pub(crate) fn resolve_tool_paths(cfg: &Config, relative_path: &str, tool: &str)
                 }
             }
             TOOL_CURSOR => paths.push(cfg.cursor_dir.join(suffix)),
+            TOOL_GEMINI => {
+                let format = crate::tools::command_format(TOOL_GEMINI);
+                if let Some(file_name) = suffix.to_str().and_then(|name| format.file_name(name)) {
+                    paths.push(cfg.gemini_commands_dir.join(file_name));
+                }
+            }
             TOOL_OPENCODE => paths.push(cfg.opencode_commands_dir.join(suffix)),
-            _ => {}
+            _ => {
+                if let Some(dir) = provider_entries(cfg)
+                    .find(|provider| provider.id == tool)
+                    .and_then(|provider| provider.commands_dir)
+                {
+                    paths.push(dir.join(suffix));
+                }
+            }
         }
     } else if let Some(raw_suffix) = relative_path.strip_prefix("skills/") {
         let Some(suffix) = validated_tool_suffix(raw_suffix) else {
@@ -185,15 +303,41 @@ pub(crate) fn resolve_tool_paths(cfg: &Config, relative_path: &str, tool: &str)
             TOOL_CODEX if cfg.codex_skills_dir != cfg.central_skills_dir => {
                 paths.push(cfg.codex_skills_dir.join(suffix))
             }
+            TOOL_GEMINI if cfg.gemini_skills_dir != cfg.central_skills_dir => {
+                paths.push(cfg.gemini_skills_dir.join(suffix))
+            }
             TOOL_OPENCODE if cfg.opencode_skills_dir != cfg.central_skills_dir => {
                 paths.push(cfg.opencode_skills_dir.join(suffix))
             }
+            _ => {
+                if let Some(dir) = provider_entries(cfg)
+                    .find(|provider| provider.id == tool)
+                    .and_then(|provider| provider.skills_dir)
+                    .filter(|dir| **dir != cfg.central_skills_dir)
+                {
+                    paths.push(dir.join(suffix));
+                }
+            }
+        }
+    } else if let Some(raw_suffix) = relative_path.strip_prefix("subagents/") {
+        let Some(suffix) = validated_tool_suffix(raw_suffix) else {
+            return paths;
+        };
+        match tool {
+            TOOL_CLAUDE => paths.push(cfg.claude_subagents_dir.join(suffix)),
+            TOOL_OPENCODE => paths.push(cfg.opencode_subagents_dir.join(suffix)),
             _ => {}
         }
     } else if is_supported_agents_blacklist_path(relative_path) {
         match (relative_path, tool) {
+            (CLAUDE_AGENTS_BLACKLIST_KEY, TOOL_CLAUDE) => {
+                paths.push(cfg.claude_agents_file.clone())
+            }
             (LEGACY_AGENTS_BLACKLIST_KEY, TOOL_CODEX)
             | (CODEX_AGENTS_BLACKLIST_KEY, TOOL_CODEX) => paths.push(cfg.codex_agents_file.clone()),
+            (GEMINI_AGENTS_BLACKLIST_KEY, TOOL_GEMINI) => {
+                paths.push(cfg.gemini_agents_file.clone())
+            }
             (LEGACY_AGENTS_BLACKLIST_KEY, TOOL_OPENCODE)
             | (OPENCODE_AGENTS_BLACKLIST_KEY, TOOL_OPENCODE) => {
                 paths.push(cfg.opencode_agents_file.clone())
@@ -202,6 +346,11 @@ pub(crate) fn resolve_tool_paths(cfg: &Config, relative_path: &str, tool: &str)
         }
     } else if relative_path == CODEX_RULES_BLACKLIST_KEY && tool == TOOL_CODEX {
         paths.push(cfg.codex_rules_file.clone());
+    } else if let Some((_, _, file)) = provider_file_keys(cfg)
+        .into_iter()
+        .find(|(key, id, _)| key == relative_path && *id == tool)
+    {
+        paths.push(file.clone());
     }
 
     paths
@@ -211,25 +360,41 @@ pub(crate) fn resolve_tool_paths(cfg: &Config, relative_path: &str, tool: &str)
 mod tests {
     use super::*;
     use crate::markers::RELAY_COMMAND_SKILL_MARKER;
-    use crate::sync::test_support::{make_config, setup, write_plain, write_skill};
+    use crate::sync::test_support::{add_provider, make_config, setup, write_plain, write_skill};
     use tempfile::TempDir;
 
     #[test]
     fn collect_tool_flags_gathers_selected() {
-        let flags = collect_tool_flags(true, false, true, false);
+        let flags = collect_tool_flags(true, false, true, false, false, Vec::new());
         assert_eq!(flags, vec!["claude", "cursor"]);
     }
 
     #[test]
     fn collect_tool_flags_empty_when_none() {
-        let flags = collect_tool_flags(false, false, false, false);
+        let flags = collect_tool_flags(false, false, false, false, false, Vec::new());
         assert!(flags.is_empty());
     }
 
+    #[test]
+    fn collect_tool_flags_appends_tool_ids_once() {
+        let flags = collect_tool_flags(
+            false,
+            true,
+            false,
+            false,
+            false,
+            vec!["windsurf".to_string(), "codex".to_string()],
+        );
+        assert_eq!(flags, vec!["codex", "windsurf"]);
+    }
+
     #[test]
     fn collect_tool_flags_all() {
-        let flags = collect_tool_flags(true, true, true, true);
-        assert_eq!(flags, vec!["claude", "codex", "cursor", "opencode"]);
+        let flags = collect_tool_flags(true, true, true, true, true, Vec::new());
+        assert_eq!(
+            flags,
+            vec!["claude", "codex", "cursor", "gemini", "opencode"]
+        );
     }
 
     #[test]
@@ -246,6 +411,26 @@ mod tests {
         assert_eq!(paths, vec![cfg.opencode_commands_dir.join("review.md")]);
     }
 
+    #[test]
+    fn resolve_tool_paths_provider_entries() -> io::Result<()> {
+        let (tmp, mut cfg) = setup()?;
+        add_provider(&tmp, &mut cfg)?;
+        let root = tmp.path().join("windsurf");
+
+        for (path, expected) in [
+            ("commands/review.md", root.join("commands/review.md")),
+            ("skills/plan", root.join("skills/plan")),
+            ("agents/windsurf/AGENTS.md", root.join("AGENTS.md")),
+            ("rules/windsurf/rules.md", root.join("rules.md")),
+        ] {
+            assert_eq!(resolve_tool_paths(&cfg, path, "windsurf"), vec![expected]);
+        }
+        assert!(resolve_tool_paths(&cfg, "agents/windsurf/AGENTS.md", TOOL_CODEX).is_empty());
+        assert!(validate_blacklist_path(&cfg, "rules/windsurf/rules.md").is_ok());
+        assert!(validate_blacklist_path(&cfg, "rules/windsurf/other.md").is_err());
+        Ok(())
+    }
+
     #[test]
     fn resolve_tool_paths_codex_commands_without_wrapper_are_empty() {
         let tmp = TempDir::new().unwrap();
@@ -290,6 +475,51 @@ mod tests {
         assert!(resolve_tool_paths(&cfg, "skills/plan", TOOL_OPENCODE).is_empty());
     }
 
+    #[test]
+    fn resolve_tool_paths_subagents() {
+        let tmp = TempDir::new().unwrap();
+        let cfg = make_config(&tmp);
+        let paths = resolve_tool_paths(&cfg, "subagents/reviewer.md", TOOL_CLAUDE);
+        assert_eq!(paths, vec![cfg.claude_subagents_dir.join("reviewer.md")]);
+
+        let paths = resolve_tool_paths(&cfg, "subagents/reviewer.md", TOOL_OPENCODE);
+        assert_eq!(paths, vec![cfg.opencode_subagents_dir.join("reviewer.md")]);
+
+        assert!(resolve_tool_paths(&cfg, "subagents/reviewer.md", TOOL_CODEX).is_empty());
+        assert!(resolve_tool_paths(&cfg, "subagents/../reviewer.md", TOOL_CLAUDE).is_empty());
+    }
+
+    #[test]
+    fn resolve_tool_paths_gemini_commands_use_toml_names() {
+        let tmp = TempDir::new().unwrap();
+        let cfg = make_config(&tmp);
+        let paths = resolve_tool_paths(&cfg, "commands/review.md", TOOL_GEMINI);
+        assert_eq!(paths, vec![cfg.gemini_commands_dir.join("review.toml")]);
+        assert!(resolve_tool_paths(&cfg, "commands/notes.txt", TOOL_GEMINI).is_empty());
+    }
+
+    #[test]
+    fn resolve_tool_paths_nested_commands() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let wrapper = cfg.codex_skills_dir.join("frontend-component");
+        write_plain(
+            &wrapper.join(RELAY_COMMAND_SKILL_MARKER),
+            "generated by relay from commands\n",
+        )?;
+
+        let key = "commands/frontend/component.md";
+        assert_eq!(
+            resolve_tool_paths(&cfg, key, TOOL_CLAUDE),
+            vec![cfg.claude_dir.join("frontend/component.md")]
+        );
+        assert_eq!(
+            resolve_tool_paths(&cfg, key, TOOL_GEMINI),
+            vec![cfg.gemini_commands_dir.join("frontend/component.toml")]
+        );
+        assert_eq!(resolve_tool_paths(&cfg, key, TOOL_CODEX), vec![wrapper]);
+        Ok(())
+    }
+
     #[test]
     fn resolve_tool_paths_agents() {
         let tmp = TempDir::new().unwrap();
@@ -322,6 +552,11 @@ mod tests {
         let codex_mismatch = resolve_tool_paths(&cfg, "agents/codex/AGENTS.md", TOOL_OPENCODE);
         assert!(codex_mismatch.is_empty());
 
+        let claude_paths = resolve_tool_paths(&cfg, "agents/claude/CLAUDE.md", TOOL_CLAUDE);
+        assert_eq!(claude_paths, vec![cfg.claude_agents_file.clone()]);
+        let claude_mismatch = resolve_tool_paths(&cfg, "agents/claude/CLAUDE.md", TOOL_CODEX);
+        assert!(claude_mismatch.is_empty());
+
         let opencode_paths = resolve_tool_paths(&cfg, "agents/opencode/AGENTS.md", TOOL_OPENCODE);
         assert_eq!(opencode_paths, vec![cfg.opencode_agents_file.clone()]);
         let opencode_mismatch = resolve_tool_paths(&cfg, "agents/opencode/AGENTS.md", TOOL_CODEX);
@@ -354,6 +589,8 @@ mod tests {
 
     #[test]
     fn validate_blacklist_path_rejects_noncanonical_agents_and_rules() {
+        let tmp = TempDir::new().unwrap();
+        let cfg = make_config(&tmp);
         for path in [
             "agents/",
             "agents/foo",
@@ -361,18 +598,22 @@ mod tests {
             "rules/",
             "rules/foo",
             "rules/codex/other.rules",
+            "mcp/",
+            "mcp/nested/server",
         ] {
-            let err = validate_blacklist_path(path).unwrap_err();
+            let err = validate_blacklist_path(&cfg, path).unwrap_err();
             assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
         }
 
         for path in [
             "agents/AGENTS.md",
+            "agents/claude/CLAUDE.md",
             "agents/codex/AGENTS.md",
             "agents/opencode/AGENTS.md",
             "rules/codex/default.rules",
+            "mcp/docs",
         ] {
-            assert!(validate_blacklist_path(path).is_ok());
+            assert!(validate_blacklist_path(&cfg, path).is_ok());
         }
     }
 
@@ -463,6 +704,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn retroactive_delete_removes_only_the_mcp_server_entry() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.cursor_mcp_file,
+            r#"{"mcpServers": {"docs": {"command": "docs"}, "keep": {"command": "keep"}}}"#,
+        )?;
+
+        retroactive_delete(&cfg, "mcp/docs", &[TOOL_CURSOR.to_string()])?;
+
+        let remaining = fs::read_to_string(&cfg.cursor_mcp_file)?;
+        assert!(!remaining.contains("\"docs\""));
+        assert!(remaining.contains("\"keep\""));
+        Ok(())
+    }
+
     #[test]
     fn retroactive_delete_noop_for_missing() -> io::Result<()> {
         let (_tmp, cfg) = setup()?;
@@ -1,5 +1,6 @@
+use crate::sync::SyncItemKind;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs;
 use std::io;
@@ -8,8 +9,11 @@ use std::path::{Path, PathBuf};
 pub(crate) const TOOL_CLAUDE: &str = "claude";
 pub(crate) const TOOL_CODEX: &str = "codex";
 pub(crate) const TOOL_CURSOR: &str = "cursor";
+pub(crate) const TOOL_GEMINI: &str = "gemini";
 pub(crate) const TOOL_OPENCODE: &str = "opencode";
 
+const PROJECT_STORE_DIR: &str = ".relay";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct Config {
     pub enabled_tools: Vec<String>,
@@ -20,17 +24,165 @@ pub(crate) struct Config {
     pub central_skills_dir: PathBuf,
     pub central_agents_dir: PathBuf,
     pub central_rules_dir: PathBuf,
+    pub central_mcp_dir: PathBuf,
+    pub central_subagents_dir: PathBuf,
     pub claude_dir: PathBuf,
     pub claude_skills_dir: PathBuf,
+    pub claude_subagents_dir: PathBuf,
+    pub claude_agents_file: PathBuf,
     pub cursor_dir: PathBuf,
     pub opencode_commands_dir: PathBuf,
     #[serde(skip)]
     pub opencode_legacy_commands_dir: Option<PathBuf>,
     pub opencode_skills_dir: PathBuf,
     pub opencode_agents_file: PathBuf,
+    pub opencode_subagents_dir: PathBuf,
     pub codex_skills_dir: PathBuf,
     pub codex_rules_file: PathBuf,
     pub codex_agents_file: PathBuf,
+    pub gemini_commands_dir: PathBuf,
+    pub gemini_skills_dir: PathBuf,
+    pub gemini_agents_file: PathBuf,
+    pub claude_mcp_file: PathBuf,
+    pub codex_config_file: PathBuf,
+    pub cursor_mcp_file: PathBuf,
+    pub opencode_config_file: PathBuf,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub providers: BTreeMap<String, ProviderConfig>,
+    /// Set for `relay sync --project`, whose stores live inside one repository.
+    #[serde(skip)]
+    pub project_root: Option<PathBuf>,
+    /// Merge copies edited since the last sync line by line, using history as
+    /// the common ancestor, instead of keeping only the newest copy.
+    #[serde(default)]
+    pub three_way_merge: bool,
+    /// Save losing copies under the conflicts directory before a conflicting
+    /// sync overwrites them.
+    #[serde(default)]
+    pub preserve_conflicts: bool,
+    #[serde(default, skip_serializing_if = "ConflictPolicies::is_empty")]
+    pub conflict_policy: ConflictPolicies,
+    #[serde(default, skip_serializing_if = "HistoryRetention::is_unlimited")]
+    pub history: HistoryRetention,
+}
+
+/// A tool declared under `[providers.<id>]` rather than built into relay.
+/// Every ability is optional; declared providers are always enabled.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ProviderConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_bin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commands_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skills_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agents_file: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rules_file: Option<PathBuf>,
+}
+
+/// How competing edits to one kind of item pick the copy that wins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ConflictPolicy {
+    /// The most recently modified copy wins.
+    Newest,
+    /// The central store copy wins whenever it exists.
+    Central,
+    /// The first listed tool holding a copy wins (`central` may be listed);
+    /// unlisted tools fall back to newest.
+    Priority(Vec<String>),
+    /// Abort the sync before writing anything.
+    Fail,
+}
+
+impl ConflictPolicy {
+    pub(crate) fn label(&self) -> String {
+        match self {
+            Self::Newest => "newest".to_string(),
+            Self::Central => "central".to_string(),
+            Self::Priority(tools) => format!("priority [{}]", tools.join(", ")),
+            Self::Fail => "fail".to_string(),
+        }
+    }
+}
+
+static NEWEST: ConflictPolicy = ConflictPolicy::Newest;
+static CENTRAL: ConflictPolicy = ConflictPolicy::Central;
+
+/// `[history]` retention limits, applied after every recorded event and by
+/// `relay history prune`. Unset limits keep history forever.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct HistoryRetention {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_events: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+    /// Event files plus blobs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+}
+
+impl HistoryRetention {
+    pub(crate) fn is_unlimited(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// `[conflict_policy]`, one optional entry per item kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConflictPolicies {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commands: Option<ConflictPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skills: Option<ConflictPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agents: Option<ConflictPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rules: Option<ConflictPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp: Option<ConflictPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subagents: Option<ConflictPolicy>,
+}
+
+impl ConflictPolicies {
+    fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Skills have always kept the canonical copy; everything else keeps the
+    /// newest one.
+    pub(crate) fn for_kind(&self, kind: SyncItemKind) -> &ConflictPolicy {
+        let (configured, default) = match kind {
+            SyncItemKind::Command => (&self.commands, &NEWEST),
+            SyncItemKind::Skill => (&self.skills, &CENTRAL),
+            SyncItemKind::Agent => (&self.agents, &NEWEST),
+            SyncItemKind::Rule => (&self.rules, &NEWEST),
+            SyncItemKind::Mcp => (&self.mcp, &NEWEST),
+            SyncItemKind::Subagent => (&self.subagents, &NEWEST),
+        };
+        configured.as_ref().unwrap_or(default)
+    }
+
+    pub(crate) fn any_fail(&self) -> bool {
+        [
+            &self.commands,
+            &self.skills,
+            &self.agents,
+            &self.rules,
+            &self.mcp,
+            &self.subagents,
+        ]
+        .into_iter()
+        .any(|policy| policy == &Some(ConflictPolicy::Fail))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -42,16 +194,33 @@ struct PartialConfig {
     pub central_skills_dir: Option<PathBuf>,
     pub central_agents_dir: Option<PathBuf>,
     pub central_rules_dir: Option<PathBuf>,
+    pub central_mcp_dir: Option<PathBuf>,
+    pub central_subagents_dir: Option<PathBuf>,
     pub claude_dir: Option<PathBuf>,
     pub claude_skills_dir: Option<PathBuf>,
+    pub claude_subagents_dir: Option<PathBuf>,
+    pub claude_agents_file: Option<PathBuf>,
     pub cursor_dir: Option<PathBuf>,
     pub opencode_commands_dir: Option<PathBuf>,
     pub opencode_dir: Option<PathBuf>,
     pub opencode_skills_dir: Option<PathBuf>,
     pub opencode_agents_file: Option<PathBuf>,
+    pub opencode_subagents_dir: Option<PathBuf>,
     pub codex_skills_dir: Option<PathBuf>,
     pub codex_rules_file: Option<PathBuf>,
     pub codex_agents_file: Option<PathBuf>,
+    pub gemini_commands_dir: Option<PathBuf>,
+    pub gemini_skills_dir: Option<PathBuf>,
+    pub gemini_agents_file: Option<PathBuf>,
+    pub claude_mcp_file: Option<PathBuf>,
+    pub codex_config_file: Option<PathBuf>,
+    pub cursor_mcp_file: Option<PathBuf>,
+    pub opencode_config_file: Option<PathBuf>,
+    pub providers: Option<BTreeMap<String, ProviderConfig>>,
+    pub three_way_merge: Option<bool>,
+    pub preserve_conflicts: Option<bool>,
+    pub conflict_policy: Option<ConflictPolicies>,
+    pub history: Option<HistoryRetention>,
 }
 
 enum ConfigSource {
@@ -73,13 +242,22 @@ impl Config {
         let relay_config_root = resolve_relay_config_root(&home)?;
         let codex_root = resolve_tool_home(&home, "CODEX_HOME", ".codex")?;
         let claude_root = resolve_tool_home(&home, "CLAUDE_HOME", ".claude")?;
+        // Claude keeps user-scoped MCP servers in `~/.claude.json`, beside (not
+        // inside) `~/.claude`, unless its home is relocated.
+        let claude_mcp_file = if claude_root == home.join(".claude") {
+            home.join(".claude.json")
+        } else {
+            claude_root.join(".claude.json")
+        };
         let cursor_root = resolve_tool_home(&home, "CURSOR_HOME", ".cursor")?;
         let opencode_root = resolve_tool_home(&home, "OPENCODE_HOME", ".config/opencode")?;
+        let gemini_root = resolve_tool_home(&home, "GEMINI_HOME", ".gemini")?;
         Ok(Self {
             enabled_tools: vec![
                 TOOL_CLAUDE.to_string(),
                 TOOL_CODEX.to_string(),
                 TOOL_CURSOR.to_string(),
+                TOOL_GEMINI.to_string(),
                 TOOL_OPENCODE.to_string(),
             ],
             verified_versions: HashMap::new(),
@@ -88,16 +266,34 @@ impl Config {
             central_skills_dir: home.join(".agents/skills"),
             central_agents_dir: relay_config_root.join("agents"),
             central_rules_dir: relay_config_root.join("rules"),
+            central_mcp_dir: relay_config_root.join("mcp"),
+            central_subagents_dir: relay_config_root.join("subagents"),
             claude_dir: claude_root.join("commands"),
             claude_skills_dir: claude_root.join("skills"),
+            claude_subagents_dir: claude_root.join("agents"),
+            claude_agents_file: claude_root.join("CLAUDE.md"),
             cursor_dir: cursor_root.join("commands"),
             opencode_commands_dir: opencode_root.join("commands"),
             opencode_legacy_commands_dir: None,
             opencode_skills_dir: home.join(".agents/skills"),
             opencode_agents_file: opencode_root.join("AGENTS.md"),
+            opencode_subagents_dir: opencode_root.join("agent"),
             codex_skills_dir: home.join(".agents/skills"),
             codex_rules_file: codex_root.join("rules/default.rules"),
             codex_agents_file: codex_root.join("AGENTS.md"),
+            gemini_commands_dir: gemini_root.join("commands"),
+            gemini_skills_dir: home.join(".agents/skills"),
+            gemini_agents_file: gemini_root.join("GEMINI.md"),
+            claude_mcp_file,
+            codex_config_file: codex_root.join("config.toml"),
+            cursor_mcp_file: cursor_root.join("mcp.json"),
+            opencode_config_file: opencode_root.join("opencode.json"),
+            providers: BTreeMap::new(),
+            project_root: None,
+            three_way_merge: false,
+            preserve_conflicts: false,
+            conflict_policy: ConflictPolicies::default(),
+            history: HistoryRetention::default(),
         })
     }
 
@@ -203,8 +399,18 @@ impl Config {
                 .central_agents_dir
                 .unwrap_or(defaults.central_agents_dir),
             central_rules_dir: cfg.central_rules_dir.unwrap_or(defaults.central_rules_dir),
+            central_mcp_dir: cfg.central_mcp_dir.unwrap_or(defaults.central_mcp_dir),
+            central_subagents_dir: cfg
+                .central_subagents_dir
+                .unwrap_or(defaults.central_subagents_dir),
             claude_dir: cfg.claude_dir.unwrap_or(defaults.claude_dir),
             claude_skills_dir: cfg.claude_skills_dir.unwrap_or(defaults.claude_skills_dir),
+            claude_subagents_dir: cfg
+                .claude_subagents_dir
+                .unwrap_or(defaults.claude_subagents_dir),
+            claude_agents_file: cfg
+                .claude_agents_file
+                .unwrap_or(defaults.claude_agents_file),
             cursor_dir: cfg.cursor_dir.unwrap_or(defaults.cursor_dir),
             opencode_commands_dir,
             opencode_legacy_commands_dir,
@@ -215,9 +421,31 @@ impl Config {
             opencode_agents_file: cfg
                 .opencode_agents_file
                 .unwrap_or(defaults.opencode_agents_file),
+            opencode_subagents_dir: cfg
+                .opencode_subagents_dir
+                .unwrap_or(defaults.opencode_subagents_dir),
             codex_skills_dir: cfg.codex_skills_dir.unwrap_or(defaults.codex_skills_dir),
             codex_rules_file: cfg.codex_rules_file.unwrap_or(defaults.codex_rules_file),
             codex_agents_file: cfg.codex_agents_file.unwrap_or(defaults.codex_agents_file),
+            gemini_commands_dir: cfg
+                .gemini_commands_dir
+                .unwrap_or(defaults.gemini_commands_dir),
+            gemini_skills_dir: cfg.gemini_skills_dir.unwrap_or(defaults.gemini_skills_dir),
+            gemini_agents_file: cfg
+                .gemini_agents_file
+                .unwrap_or(defaults.gemini_agents_file),
+            claude_mcp_file: cfg.claude_mcp_file.unwrap_or(defaults.claude_mcp_file),
+            codex_config_file: cfg.codex_config_file.unwrap_or(defaults.codex_config_file),
+            cursor_mcp_file: cfg.cursor_mcp_file.unwrap_or(defaults.cursor_mcp_file),
+            opencode_config_file: cfg
+                .opencode_config_file
+                .unwrap_or(defaults.opencode_config_file),
+            providers: cfg.providers.unwrap_or_default(),
+            project_root: None,
+            three_way_merge: cfg.three_way_merge.unwrap_or(false),
+            preserve_conflicts: cfg.preserve_conflicts.unwrap_or(false),
+            conflict_policy: cfg.conflict_policy.unwrap_or_default(),
+            history: cfg.history.unwrap_or_default(),
         })
     }
 
@@ -246,7 +474,7 @@ fn serialize_error(err: toml::ser::Error) -> io::Error {
 
 impl Config {
     pub(crate) fn tool_enabled(&self, tool: &str) -> bool {
-        self.enabled_tools.iter().any(|name| name == tool)
+        self.enabled_tools.iter().any(|name| name == tool) || self.providers.contains_key(tool)
     }
 
     pub(crate) fn verified_version(&self, tool: &str) -> Option<&str> {
@@ -261,6 +489,58 @@ impl Config {
             .is_some_and(|tools| tools.iter().any(|t| t == tool))
     }
 
+    /// The kind of item stored at `path`, judged by the most specific
+    /// configured location containing it.
+    pub(crate) fn kind_of_path(&self, path: &Path) -> Option<SyncItemKind> {
+        let mut locations: Vec<(&Path, SyncItemKind)> = vec![
+            (&self.central_dir, SyncItemKind::Command),
+            (&self.claude_dir, SyncItemKind::Command),
+            (&self.cursor_dir, SyncItemKind::Command),
+            (&self.opencode_commands_dir, SyncItemKind::Command),
+            (&self.gemini_commands_dir, SyncItemKind::Command),
+            (&self.central_skills_dir, SyncItemKind::Skill),
+            (&self.claude_skills_dir, SyncItemKind::Skill),
+            (&self.opencode_skills_dir, SyncItemKind::Skill),
+            (&self.codex_skills_dir, SyncItemKind::Skill),
+            (&self.gemini_skills_dir, SyncItemKind::Skill),
+            (&self.central_agents_dir, SyncItemKind::Agent),
+            (&self.claude_agents_file, SyncItemKind::Agent),
+            (&self.opencode_agents_file, SyncItemKind::Agent),
+            (&self.codex_agents_file, SyncItemKind::Agent),
+            (&self.gemini_agents_file, SyncItemKind::Agent),
+            (&self.central_rules_dir, SyncItemKind::Rule),
+            (&self.codex_rules_file, SyncItemKind::Rule),
+            (&self.central_mcp_dir, SyncItemKind::Mcp),
+            (&self.claude_mcp_file, SyncItemKind::Mcp),
+            (&self.codex_config_file, SyncItemKind::Mcp),
+            (&self.cursor_mcp_file, SyncItemKind::Mcp),
+            (&self.opencode_config_file, SyncItemKind::Mcp),
+            (&self.central_subagents_dir, SyncItemKind::Subagent),
+            (&self.claude_subagents_dir, SyncItemKind::Subagent),
+            (&self.opencode_subagents_dir, SyncItemKind::Subagent),
+        ];
+        if let Some(dir) = self.opencode_legacy_commands_dir.as_deref() {
+            locations.push((dir, SyncItemKind::Command));
+        }
+        for provider in self.providers.values() {
+            for (location, kind) in [
+                (&provider.commands_dir, SyncItemKind::Command),
+                (&provider.skills_dir, SyncItemKind::Skill),
+                (&provider.agents_file, SyncItemKind::Agent),
+                (&provider.rules_file, SyncItemKind::Rule),
+            ] {
+                if let Some(location) = location.as_deref() {
+                    locations.push((location, kind));
+                }
+            }
+        }
+        locations
+            .into_iter()
+            .filter(|(location, _)| path.starts_with(location))
+            .max_by_key(|(location, _)| location.components().count())
+            .map(|(_, kind)| kind)
+    }
+
     /// Relay-owned state lives beside the command/agent/rule stores, not in the
     /// user-owned shared skills directory.
     pub(crate) fn skill_state_path(&self) -> io::Result<PathBuf> {
@@ -270,6 +550,42 @@ impl Config {
         Ok(root.join("runtime/skills-state.toml"))
     }
 
+    /// Conflict-marker sidecars and copies preserved from losing variants.
+    pub(crate) fn conflicts_dir(&self) -> io::Result<PathBuf> {
+        let root = self.central_dir.parent().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "central_dir has no parent")
+        })?;
+        Ok(root.join("conflicts"))
+    }
+
+    /// Last-synced MCP server hashes, used to tell which copy of a shared
+    /// config file actually changed.
+    pub(crate) fn mcp_state_path(&self) -> io::Result<PathBuf> {
+        let root = self.central_dir.parent().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "central_dir has no parent")
+        })?;
+        Ok(root.join("runtime/mcp-state.toml"))
+    }
+
+    /// Last-synced command hashes per location, used to tell a deleted
+    /// command from one that was never synced there.
+    pub(crate) fn command_state_path(&self) -> io::Result<PathBuf> {
+        let root = self.central_dir.parent().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "central_dir has no parent")
+        })?;
+        Ok(root.join("runtime/commands-state.toml"))
+    }
+
+    /// Last-synced hashes per file for `kind`, used to tell which copies were
+    /// edited since. Commands, skills and MCP servers keep richer state in
+    /// their own files at the same `<kind>-state.toml` path.
+    pub(crate) fn synced_state_path(&self, kind: SyncItemKind) -> io::Result<PathBuf> {
+        let root = self.central_dir.parent().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "central_dir has no parent")
+        })?;
+        Ok(root.join(format!("runtime/{}-state.toml", kind.store_dir())))
+    }
+
     /// Locations used by older Relay releases or by tools before they adopted
     /// the shared skills directory. These are migration/import sources only.
     pub(crate) fn legacy_skill_import_dirs(&self) -> io::Result<Vec<PathBuf>> {
@@ -308,6 +624,89 @@ impl Config {
     pub(crate) fn is_initialized() -> io::Result<bool> {
         Ok(!matches!(Self::config_source()?, ConfigSource::Defaults))
     }
+
+    /// Repository-scoped variant of this config: tool paths point at the
+    /// project's dot-directories, the central store is `<repo>/.relay/` (skills
+    /// use the shared `<repo>/.agents/skills`), and history and runtime state
+    /// live under `.relay/` too. Tool selection and blacklists carry over;
+    /// declared providers do not, since their paths are global.
+    pub(crate) fn for_project(&self, root: &Path) -> Self {
+        let store = root.join(PROJECT_STORE_DIR);
+        let shared_skills = root.join(".agents/skills");
+        Self {
+            enabled_tools: self.enabled_tools.clone(),
+            verified_versions: self.verified_versions.clone(),
+            blacklist: self.blacklist.clone(),
+            central_dir: store.join("commands"),
+            central_skills_dir: shared_skills.clone(),
+            central_agents_dir: store.join("agents"),
+            central_rules_dir: store.join("rules"),
+            central_mcp_dir: store.join("mcp"),
+            central_subagents_dir: store.join("subagents"),
+            claude_dir: root.join(".claude/commands"),
+            claude_skills_dir: root.join(".claude/skills"),
+            claude_subagents_dir: root.join(".claude/agents"),
+            claude_agents_file: root.join("CLAUDE.md"),
+            cursor_dir: root.join(".cursor/commands"),
+            opencode_commands_dir: root.join(".opencode/commands"),
+            opencode_legacy_commands_dir: None,
+            opencode_skills_dir: root.join(".opencode/skills"),
+            opencode_agents_file: root.join("AGENTS.md"),
+            opencode_subagents_dir: root.join(".opencode/agent"),
+            codex_skills_dir: shared_skills.clone(),
+            codex_rules_file: root.join(".codex/rules/default.rules"),
+            codex_agents_file: root.join("AGENTS.md"),
+            gemini_commands_dir: root.join(".gemini/commands"),
+            gemini_skills_dir: shared_skills,
+            gemini_agents_file: root.join("GEMINI.md"),
+            claude_mcp_file: root.join(".mcp.json"),
+            codex_config_file: root.join(".codex/config.toml"),
+            cursor_mcp_file: root.join(".cursor/mcp.json"),
+            opencode_config_file: root.join("opencode.json"),
+            providers: BTreeMap::new(),
+            project_root: Some(root.to_path_buf()),
+            three_way_merge: self.three_way_merge,
+            preserve_conflicts: self.preserve_conflicts,
+            conflict_policy: self.conflict_policy.clone(),
+            history: self.history.clone(),
+        }
+    }
+
+    /// Mutating operations serialize on one lock per store: the global runtime
+    /// lock, or `.relay/runtime/relay.lock` inside a project.
+    pub(crate) fn store_lock_path(&self) -> io::Result<PathBuf> {
+        match self.project_root.as_deref() {
+            Some(root) => Ok(root.join(PROJECT_STORE_DIR).join("runtime/relay.lock")),
+            None => Self::lock_path(),
+        }
+    }
+}
+
+/// Walks up from `start` to the nearest directory containing `.git` (a
+/// directory, or a file for worktrees and submodules).
+pub(crate) fn find_project_root(start: &Path) -> io::Result<PathBuf> {
+    let start = fs::canonicalize(start)?;
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not inside a git repository", start.display()),
+            )
+        })
+}
+
+/// Keeps relay's own bookkeeping out of commits while the synced stores under
+/// `.relay/` stay trackable.
+pub(crate) fn ensure_project_gitignore(root: &Path) -> io::Result<()> {
+    let path = root.join(PROJECT_STORE_DIR).join(".gitignore");
+    if path.exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(root.join(PROJECT_STORE_DIR))?;
+    fs::write(path, "conflicts/\nhistory/\nruntime/\n")
 }
 
 fn relay_home_is_explicit() -> bool {
@@ -471,20 +870,76 @@ fn normalize_partial_config_paths(mut cfg: PartialConfig) -> io::Result<PartialC
         central_skills_dir,
         central_agents_dir,
         central_rules_dir,
+        central_mcp_dir,
+        central_subagents_dir,
         claude_dir,
         claude_skills_dir,
+        claude_subagents_dir,
+        claude_agents_file,
         cursor_dir,
         opencode_commands_dir,
         opencode_dir,
         opencode_skills_dir,
         opencode_agents_file,
+        opencode_subagents_dir,
         codex_skills_dir,
         codex_rules_file,
         codex_agents_file,
+        gemini_commands_dir,
+        gemini_skills_dir,
+        gemini_agents_file,
+        claude_mcp_file,
+        codex_config_file,
+        cursor_mcp_file,
+        opencode_config_file,
     );
+    if let Some(providers) = cfg.providers.as_mut() {
+        for (id, provider) in providers.iter_mut() {
+            validate_provider_id(id)?;
+            for (field, value) in [
+                ("commands_dir", &mut provider.commands_dir),
+                ("skills_dir", &mut provider.skills_dir),
+                ("agents_file", &mut provider.agents_file),
+                ("rules_file", &mut provider.rules_file),
+            ] {
+                *value = normalize_optional_config_path(
+                    &format!("providers.{id}.{field}"),
+                    value.take(),
+                )?;
+            }
+        }
+    }
     Ok(cfg)
 }
 
+/// Provider ids become blacklist tool names, history origins and central
+/// store directories, so they are restricted to a path-safe alphabet and may
+/// not shadow a built-in tool.
+fn validate_provider_id(id: &str) -> io::Result<()> {
+    let valid_chars = !id.is_empty()
+        && id
+            .chars()
+            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-' || ch == '_');
+    let reserved = [
+        TOOL_CLAUDE,
+        TOOL_CODEX,
+        TOOL_CURSOR,
+        TOOL_GEMINI,
+        TOOL_OPENCODE,
+        "central",
+    ]
+    .contains(&id);
+    if valid_chars && !reserved {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "invalid provider id `{id}`; use lowercase letters, digits, `-` or `_`, and avoid built-in tool names"
+        ),
+    ))
+}
+
 fn normalize_optional_config_path(
     field_name: &str,
     value: Option<PathBuf>,
@@ -705,16 +1160,34 @@ mod tests {
             central_skills_dir: PathBuf::from("/tmp/skills"),
             central_agents_dir: PathBuf::from("/tmp/agents"),
             central_rules_dir: PathBuf::from("/tmp/rules"),
+            central_mcp_dir: PathBuf::from("/tmp/mcp"),
+            central_subagents_dir: PathBuf::from("/tmp/subagents"),
             claude_dir: PathBuf::from("/tmp/claude"),
             claude_skills_dir: PathBuf::from("/tmp/claude_skills"),
+            claude_subagents_dir: PathBuf::from("/tmp/claude_agents"),
+            claude_agents_file: PathBuf::from("/tmp/CLAUDE.md"),
             cursor_dir: PathBuf::from("/tmp/cursor"),
             opencode_commands_dir: PathBuf::from("/tmp/oc"),
             opencode_legacy_commands_dir: None,
             opencode_skills_dir: PathBuf::from("/tmp/os"),
             opencode_agents_file: PathBuf::from("/tmp/oa"),
+            opencode_subagents_dir: PathBuf::from("/tmp/oc_agent"),
             codex_skills_dir: PathBuf::from("/tmp/codex_skills"),
             codex_rules_file: PathBuf::from("/tmp/rules"),
             codex_agents_file: PathBuf::from("/tmp/agents"),
+            gemini_commands_dir: PathBuf::from("/tmp/gemini_commands"),
+            gemini_skills_dir: PathBuf::from("/tmp/gemini_skills"),
+            gemini_agents_file: PathBuf::from("/tmp/GEMINI.md"),
+            claude_mcp_file: PathBuf::from("/tmp/claude.json"),
+            codex_config_file: PathBuf::from("/tmp/codex.toml"),
+            cursor_mcp_file: PathBuf::from("/tmp/cursor.json"),
+            opencode_config_file: PathBuf::from("/tmp/opencode.json"),
+            providers: std::collections::BTreeMap::new(),
+            project_root: None,
+            three_way_merge: false,
+            preserve_conflicts: false,
+            conflict_policy: ConflictPolicies::default(),
+            history: HistoryRetention::default(),
         };
 
         assert!(!cfg.is_blacklisted("commands/review.md", "claude"));
@@ -738,6 +1211,7 @@ mod tests {
         set_env("CLAUDE_HOME", None);
         set_env("OPENCODE_HOME", None);
         set_env("CURSOR_HOME", None);
+        set_env("GEMINI_HOME", None);
         set_env("XDG_CONFIG_HOME", None);
         lock
     }
@@ -753,6 +1227,7 @@ mod tests {
         set_env("CLAUDE_HOME", Some("~/claude_root"));
         set_env("OPENCODE_HOME", Some("~/opencode_root"));
         set_env("CURSOR_HOME", Some("/tmp/cursor_root"));
+        set_env("GEMINI_HOME", Some("~/gemini_root"));
 
         let cfg = Config::default_paths()?;
         assert_eq!(cfg.central_dir, home.join(".config/relay/commands"));
@@ -770,12 +1245,16 @@ mod tests {
             home.join("opencode_root/AGENTS.md")
         );
         assert_eq!(cfg.cursor_dir, PathBuf::from("/tmp/cursor_root/commands"));
+        assert_eq!(cfg.gemini_commands_dir, home.join("gemini_root/commands"));
+        assert_eq!(cfg.gemini_agents_file, home.join("gemini_root/GEMINI.md"));
+        assert_eq!(cfg.gemini_skills_dir, home.join(".agents/skills"));
 
         set_env("RELAY_HOME", None);
         set_env("CODEX_HOME", None);
         set_env("CLAUDE_HOME", None);
         set_env("OPENCODE_HOME", None);
         set_env("CURSOR_HOME", None);
+        set_env("GEMINI_HOME", None);
         Ok(())
     }
 
@@ -1166,6 +1645,211 @@ central_dir = "${UNSUPPORTED_VAR}/relay/commands"
         Ok(())
     }
 
+    #[test]
+    fn for_project_scopes_stores_to_repository() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let repo = tmp.path().join("repo");
+        fs::create_dir_all(repo.join(".git"))?;
+        fs::create_dir_all(repo.join("src/nested"))?;
+
+        let root = find_project_root(&repo.join("src/nested"))?;
+        assert_eq!(root, fs::canonicalize(&repo)?);
+
+        let mut global = crate::sync::test_support::make_config(&tmp);
+        global
+            .providers
+            .insert("windsurf".to_string(), ProviderConfig::default());
+        let cfg = global.for_project(&root);
+        assert_eq!(cfg.enabled_tools, global.enabled_tools);
+        assert_eq!(cfg.central_dir, root.join(".relay/commands"));
+        assert_eq!(cfg.central_skills_dir, root.join(".agents/skills"));
+        assert_eq!(cfg.claude_dir, root.join(".claude/commands"));
+        assert_eq!(cfg.opencode_skills_dir, root.join(".opencode/skills"));
+        assert!(cfg.providers.is_empty());
+        assert_eq!(
+            cfg.skill_state_path()?,
+            root.join(".relay/runtime/skills-state.toml")
+        );
+        assert_eq!(
+            cfg.store_lock_path()?,
+            root.join(".relay/runtime/relay.lock")
+        );
+
+        ensure_project_gitignore(&root)?;
+        assert_eq!(
+            fs::read_to_string(root.join(".relay/.gitignore"))?,
+            "conflicts/\nhistory/\nruntime/\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn kind_of_path_picks_the_most_specific_location() {
+        let tmp = TempDir::new().unwrap();
+        let mut cfg = crate::sync::test_support::make_config(&tmp);
+        cfg.providers.insert(
+            "windsurf".to_string(),
+            ProviderConfig {
+                rules_file: Some(tmp.path().join("windsurf/rules.md")),
+                ..ProviderConfig::default()
+            },
+        );
+
+        let kind = |path: PathBuf| cfg.kind_of_path(&path);
+        assert_eq!(
+            kind(cfg.claude_dir.join("frontend/review.md")),
+            Some(SyncItemKind::Command)
+        );
+        assert_eq!(
+            kind(cfg.central_skills_dir.join("plan/SKILL.md")),
+            Some(SyncItemKind::Skill)
+        );
+        assert_eq!(
+            kind(cfg.codex_agents_file.clone()),
+            Some(SyncItemKind::Agent)
+        );
+        assert_eq!(
+            kind(tmp.path().join("windsurf/rules.md")),
+            Some(SyncItemKind::Rule)
+        );
+        assert_eq!(kind(tmp.path().join("elsewhere.md")), None);
+    }
+
+    #[test]
+    fn find_project_root_errors_outside_repository() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let err = find_project_root(tmp.path()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        Ok(())
+    }
+
+    #[test]
+    fn load_from_file_reads_providers_and_expands_paths() -> io::Result<()> {
+        let _lock = env_lock();
+        let tmp = TempDir::new()?;
+        let home = tmp.path().join("home");
+        fs::create_dir_all(&home)?;
+        set_env("RELAY_HOME", Some(home.to_string_lossy().as_ref()));
+        let path = tmp.path().join("config.toml");
+        fs::write(
+            &path,
+            r#"
+enabled_tools = ["claude"]
+
+[providers.windsurf]
+label = "Windsurf"
+version_bin = "windsurf"
+commands_dir = "~/.windsurf/commands"
+rules_file = "~/.windsurf/rules.md"
+"#,
+        )?;
+
+        let cfg = Config::load_from_file(&path)?;
+        let provider = &cfg.providers["windsurf"];
+        assert_eq!(provider.label.as_deref(), Some("Windsurf"));
+        assert_eq!(provider.commands_dir, Some(home.join(".windsurf/commands")));
+        assert_eq!(provider.rules_file, Some(home.join(".windsurf/rules.md")));
+        assert_eq!(provider.skills_dir, None);
+        assert!(cfg.tool_enabled("windsurf"));
+
+        set_env("RELAY_HOME", None);
+        Ok(())
+    }
+
+    #[test]
+    fn load_from_file_rejects_invalid_provider_ids() -> io::Result<()> {
+        let _lock = env_lock();
+        let tmp = TempDir::new()?;
+        let path = tmp.path().join("config.toml");
+        for id in ["claude", "central", "Wind Surf"] {
+            fs::write(&path, format!("[providers.\"{id}\"]\nlabel = \"x\"\n"))?;
+            let err = Config::load_from_file(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{id}");
+        }
+
+        fs::write(&path, "[providers.windsurf]\nunknown = \"x\"\n")?;
+        let err = Config::load_from_file(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[test]
+    fn load_from_file_reads_conflict_policy_per_kind() -> io::Result<()> {
+        let _lock = env_lock();
+        let tmp = TempDir::new()?;
+        let path = tmp.path().join("config.toml");
+        fs::write(
+            &path,
+            r#"
+enabled_tools = ["claude"]
+
+[conflict_policy]
+rules = "central"
+commands = "newest"
+agents = { priority = ["claude", "codex"] }
+mcp = "fail"
+"#,
+        )?;
+
+        let cfg = Config::load_from_file(&path)?;
+        let policies = &cfg.conflict_policy;
+        assert_eq!(
+            policies.for_kind(SyncItemKind::Rule),
+            &ConflictPolicy::Central
+        );
+        assert_eq!(
+            policies.for_kind(SyncItemKind::Agent),
+            &ConflictPolicy::Priority(vec!["claude".to_string(), "codex".to_string()])
+        );
+        assert_eq!(
+            policies.for_kind(SyncItemKind::Skill),
+            &ConflictPolicy::Central
+        );
+        assert_eq!(
+            policies.for_kind(SyncItemKind::Subagent),
+            &ConflictPolicy::Newest
+        );
+        assert!(policies.any_fail());
+
+        let saved = tmp.path().join("saved.toml");
+        cfg.save(&saved)?;
+        assert_eq!(&Config::load_from_file(&saved)?.conflict_policy, policies);
+
+        fs::write(&path, "[conflict_policy]\nrules = \"oldest\"\n")?;
+        let err = Config::load_from_file(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[test]
+    fn load_from_file_reads_history_retention() -> io::Result<()> {
+        let _lock = env_lock();
+        let tmp = TempDir::new()?;
+        let path = tmp.path().join("config.toml");
+        fs::write(
+            &path,
+            "enabled_tools = [\"claude\"]\n\n[history]\nmax_events = 500\nmax_age_days = 30\n",
+        )?;
+
+        let cfg = Config::load_from_file(&path)?;
+        assert_eq!(
+            cfg.history,
+            HistoryRetention {
+                max_events: Some(500),
+                max_age_days: Some(30),
+                max_bytes: None,
+            }
+        );
+        let saved = tmp.path().join("saved.toml");
+        cfg.save(&saved)?;
+        assert_eq!(Config::load_from_file(&saved)?.history, cfg.history);
+
+        fs::write(&path, "[history]\nmax_size = 10\n")?;
+        let err = Config::load_from_file(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
     #[test]
     fn expand_tilde_uses_relay_home() -> io::Result<()> {
         let _lock = env_lock();
@@ -0,0 +1,420 @@
+use crate::atomic::write_atomic;
+use crate::config::Config;
+use crate::history::HistoryRecorder;
+use crate::sync::SyncConflict;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MANIFEST_FILE: &str = "manifest.toml";
+
+/// Everything preserved for one conflicted item, stored next to the copies in
+/// `<conflicts dir>/<kind>/<name>/manifest.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ConflictManifest {
+    pub kind: String,
+    pub name: String,
+    #[serde(default)]
+    pub variants: Vec<PreservedVariant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PreservedVariant {
+    pub tool: String,
+    /// Copy name inside the item directory, `<tool>-<timestamp_ms>`.
+    pub file: String,
+    /// Where the losing copy lived; `resolve --take` restores it there.
+    pub source: PathBuf,
+    pub winner: String,
+    pub timestamp_ms: u64,
+    #[serde(default)]
+    pub dir: bool,
+}
+
+impl ConflictManifest {
+    pub(crate) fn key(&self) -> String {
+        format!("{}/{}", self.kind, self.name)
+    }
+
+    /// Tool whose copy the most recent sync kept.
+    pub(crate) fn winner(&self) -> Option<&str> {
+        self.variants
+            .iter()
+            .max_by_key(|variant| variant.timestamp_ms)
+            .map(|variant| variant.winner.as_str())
+    }
+}
+
+/// A losing copy as it was just before the winner overwrote it.
+pub(crate) enum LosingCopy<'a> {
+    Bytes(&'a [u8]),
+    Dir(&'a Path),
+}
+
+pub(crate) struct LosingVariant<'a> {
+    pub tool: &'a str,
+    pub source: &'a Path,
+    pub copy: LosingCopy<'a>,
+}
+
+/// Saves each loser of `conflict` under `<conflicts dir>/<kind>/<name>/` and
+/// appends it to the item's manifest. Returns the item directory.
+pub(crate) fn preserve_losers(
+    cfg: &Config,
+    conflict: &SyncConflict,
+    losers: &[LosingVariant<'_>],
+) -> io::Result<Option<PathBuf>> {
+    if losers.is_empty() {
+        return Ok(None);
+    }
+    let kind = conflict.kind.store_dir();
+    let item_dir = item_dir(cfg, kind, &conflict.name)?;
+    fs::create_dir_all(&item_dir)?;
+    let mut manifest = read_manifest(&item_dir)?.unwrap_or_else(|| ConflictManifest {
+        kind: kind.to_string(),
+        name: conflict.name.clone(),
+        variants: Vec::new(),
+    });
+    let timestamp_ms = now_millis();
+    for loser in losers {
+        let mut file = format!("{}-{timestamp_ms}", loser.tool);
+        let mut suffix = 1;
+        while item_dir.join(&file).exists() {
+            suffix += 1;
+            file = format!("{}-{timestamp_ms}-{suffix}", loser.tool);
+        }
+        let target = item_dir.join(&file);
+        let dir = match loser.copy {
+            LosingCopy::Bytes(bytes) => {
+                write_atomic(&target, bytes)?;
+                false
+            }
+            LosingCopy::Dir(path) => {
+                copy_dir(path, &target)?;
+                true
+            }
+        };
+        manifest.variants.push(PreservedVariant {
+            tool: loser.tool.to_string(),
+            file,
+            source: loser.source.to_path_buf(),
+            winner: conflict.winner.clone(),
+            timestamp_ms,
+            dir,
+        });
+    }
+    write_manifest(&item_dir, &manifest)?;
+    Ok(Some(item_dir))
+}
+
+pub(crate) fn list_conflicts(cfg: &Config) -> io::Result<Vec<(PathBuf, ConflictManifest)>> {
+    let mut found = Vec::new();
+    let root = cfg.conflicts_dir()?;
+    if root.is_dir() {
+        collect_manifests(&root, &mut found)?;
+    }
+    found.sort_by_key(|(_, manifest)| manifest.key());
+    Ok(found)
+}
+
+/// Finds a conflict by `<name>` or, when several kinds share a name,
+/// `<kind>/<name>`.
+pub(crate) fn find_conflict(cfg: &Config, name: &str) -> io::Result<(PathBuf, ConflictManifest)> {
+    let mut matches: Vec<(PathBuf, ConflictManifest)> = list_conflicts(cfg)?
+        .into_iter()
+        .filter(|(_, manifest)| manifest.key() == name)
+        .collect();
+    if matches.is_empty() {
+        matches = list_conflicts(cfg)?
+            .into_iter()
+            .filter(|(_, manifest)| manifest.name == name)
+            .collect();
+    }
+    match matches.len() {
+        0 => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no preserved conflict named {name}"),
+        )),
+        1 => Ok(matches.remove(0)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{name} is ambiguous; use one of: {}",
+                matches
+                    .iter()
+                    .map(|(_, manifest)| manifest.key())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        )),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ResolveReport {
+    pub key: String,
+    /// Path the taken copy was restored to; `None` when the winner was kept.
+    pub restored: Option<PathBuf>,
+    pub history_event_id: Option<String>,
+}
+
+/// Keeps `tool`'s copy of a conflicted item and discards the rest. Taking a
+/// preserved loser writes it back to where it came from, so the next sync
+/// propagates it as the newest edit; taking the winner only clears the record.
+pub(crate) fn resolve_conflict(cfg: &Config, name: &str, tool: &str) -> io::Result<ResolveReport> {
+    let (item_dir, manifest) = find_conflict(cfg, name)?;
+    let key = manifest.key();
+    let taken = manifest
+        .variants
+        .iter()
+        .filter(|variant| variant.tool == tool)
+        .max_by_key(|variant| variant.timestamp_ms);
+    let mut restored = None;
+    let mut history_event_id = None;
+    match taken {
+        Some(variant) => {
+            let copy = item_dir.join(&variant.file);
+            let mut recorder = HistoryRecorder::new(cfg, "conflicts:resolve")?;
+            let before = recorder.capture_path(&variant.source)?;
+            if let Some(parent) = variant.source.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if variant.dir {
+                restore_dir(&copy, &variant.source)?;
+            } else {
+                write_atomic(&variant.source, &fs::read(&copy)?)?;
+            }
+            let after = recorder.capture_path(&variant.source)?;
+            recorder.record_change(&variant.source, before, after);
+            history_event_id = recorder.finish()?;
+            restored = Some(variant.source.clone());
+        }
+        None if manifest.winner() == Some(tool) => {}
+        None => {
+            let mut tools: Vec<&str> = manifest.winner().into_iter().collect();
+            for variant in &manifest.variants {
+                if !tools.contains(&variant.tool.as_str()) {
+                    tools.push(&variant.tool);
+                }
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{key} has no copy from {tool}; choose one of: {}",
+                    tools.join(", ")
+                ),
+            ));
+        }
+    }
+    crate::path_cleanup::remove_with_owner_access(&item_dir)?;
+    // The merge markers three-way merge left for the item are settled too.
+    match fs::remove_file(sidecar_path(cfg, &manifest.kind, &manifest.name)?) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    Ok(ResolveReport {
+        key,
+        restored,
+        history_event_id,
+    })
+}
+
+fn item_dir(cfg: &Config, kind: &str, name: &str) -> io::Result<PathBuf> {
+    Ok(cfg.conflicts_dir()?.join(kind).join(name))
+}
+
+/// Where three-way merge writes the conflict markers for an item, next to
+/// its preserved copies: `<conflicts dir>/<kind>/<name>.conflict`.
+pub(crate) fn sidecar_path(cfg: &Config, kind: &str, name: &str) -> io::Result<PathBuf> {
+    item_dir(cfg, kind, &format!("{name}.conflict"))
+}
+
+fn read_manifest(item_dir: &Path) -> io::Result<Option<ConflictManifest>> {
+    let path = item_dir.join(MANIFEST_FILE);
+    let raw = match fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    toml::from_str(&raw).map(Some).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid conflict manifest {}: {err}", path.display()),
+        )
+    })
+}
+
+fn write_manifest(item_dir: &Path, manifest: &ConflictManifest) -> io::Result<()> {
+    let raw = toml::to_string(manifest).map_err(io::Error::other)?;
+    write_atomic(&item_dir.join(MANIFEST_FILE), raw.as_bytes())
+}
+
+/// Item directories hold preserved copies (some of them skill folders), so the
+/// walk stops at the first manifest on each branch.
+fn collect_manifests(dir: &Path, found: &mut Vec<(PathBuf, ConflictManifest)>) -> io::Result<()> {
+    if let Some(manifest) = read_manifest(dir)? {
+        found.push((dir.to_path_buf(), manifest));
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            collect_manifests(&entry.path(), found)?;
+        }
+    }
+    Ok(())
+}
+
+fn copy_dir(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let metadata = fs::metadata(entry.path())?;
+        let to = dst.join(entry.file_name());
+        if metadata.is_dir() {
+            copy_dir(&entry.path(), &to)?;
+        } else if metadata.is_file() {
+            fs::copy(entry.path(), &to)?;
+        }
+    }
+    Ok(())
+}
+
+/// Assembles the copy beside `target` first so a failed copy never leaves a
+/// half-restored folder in place.
+fn restore_dir(copy: &Path, target: &Path) -> io::Result<()> {
+    let mut staging = target.as_os_str().to_os_string();
+    staging.push(".relay-restore");
+    let staging = PathBuf::from(staging);
+    crate::path_cleanup::remove_with_owner_access(&staging)?;
+    copy_dir(copy, &staging)?;
+    crate::path_cleanup::remove_with_owner_access(target)?;
+    fs::rename(&staging, target)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::test_support::{setup, write_plain};
+    use crate::sync::SyncItemKind;
+
+    fn conflict(kind: SyncItemKind, name: &str) -> SyncConflict {
+        SyncConflict {
+            kind,
+            name: name.to_string(),
+            winner: "claude".to_string(),
+            others: vec!["cursor".to_string()],
+        }
+    }
+
+    #[test]
+    fn preserve_losers_writes_copy_and_manifest() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let source = cfg.cursor_dir.join("review.md");
+        let item = preserve_losers(
+            &cfg,
+            &conflict(SyncItemKind::Command, "review.md"),
+            &[LosingVariant {
+                tool: "cursor",
+                source: &source,
+                copy: LosingCopy::Bytes(b"cursor body"),
+            }],
+        )?
+        .unwrap();
+
+        assert_eq!(item, cfg.conflicts_dir()?.join("commands/review.md"));
+        let (_, manifest) = find_conflict(&cfg, "review.md")?;
+        assert_eq!(manifest.key(), "commands/review.md");
+        assert_eq!(manifest.winner(), Some("claude"));
+        let variant = &manifest.variants[0];
+        assert!(variant.file.starts_with("cursor-"));
+        assert_eq!(variant.source, source);
+        assert_eq!(fs::read(item.join(&variant.file))?, b"cursor body");
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_take_restores_loser_and_clears_record() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let source = cfg.cursor_dir.join("review.md");
+        write_plain(&source, "claude body")?;
+        preserve_losers(
+            &cfg,
+            &conflict(SyncItemKind::Command, "review.md"),
+            &[LosingVariant {
+                tool: "cursor",
+                source: &source,
+                copy: LosingCopy::Bytes(b"cursor body"),
+            }],
+        )?;
+
+        let report = resolve_conflict(&cfg, "commands/review.md", "cursor")?;
+
+        assert_eq!(report.restored.as_deref(), Some(source.as_path()));
+        assert!(report.history_event_id.is_some());
+        assert_eq!(fs::read_to_string(&source)?, "cursor body");
+        assert!(list_conflicts(&cfg)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_removes_the_merge_sidecar() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let source = cfg.cursor_dir.join("git/commit.md");
+        let sidecar = sidecar_path(&cfg, "commands", "git/commit.md")?;
+        let other = sidecar_path(&cfg, "commands", "review.md")?;
+        write_plain(&sidecar, "<<<<<<< claude\n")?;
+        write_plain(&other, "<<<<<<< claude\n")?;
+        preserve_losers(
+            &cfg,
+            &conflict(SyncItemKind::Command, "git/commit.md"),
+            &[LosingVariant {
+                tool: "cursor",
+                source: &source,
+                copy: LosingCopy::Bytes(b"cursor body"),
+            }],
+        )?;
+
+        resolve_conflict(&cfg, "git/commit.md", "claude")?;
+
+        assert!(!sidecar.exists());
+        assert!(other.exists());
+        assert!(list_conflicts(&cfg)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_take_winner_discards_and_unknown_tool_errors() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let skill = cfg.claude_skills_dir.join("plan");
+        write_plain(&skill.join("SKILL.md"), "loser")?;
+        preserve_losers(
+            &cfg,
+            &conflict(SyncItemKind::Skill, "plan"),
+            &[LosingVariant {
+                tool: "cursor",
+                source: &skill,
+                copy: LosingCopy::Dir(&skill),
+            }],
+        )?;
+
+        let err = resolve_conflict(&cfg, "plan", "codex").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("claude, cursor"));
+
+        let report = resolve_conflict(&cfg, "plan", "claude")?;
+        assert_eq!(report.restored, None);
+        assert!(list_conflicts(&cfg)?.is_empty());
+        Ok(())
+    }
+}
@@ -557,16 +557,34 @@ mod tests {
             central_skills_dir: tmp.path().join("relay/skills"),
             central_agents_dir: tmp.path().join("relay/agents"),
             central_rules_dir: tmp.path().join("relay/rules"),
+            central_mcp_dir: tmp.path().join("relay/mcp"),
+            central_subagents_dir: tmp.path().join("relay/subagents"),
             claude_dir: tmp.path().join("claude/commands"),
             claude_skills_dir: tmp.path().join("claude/skills"),
+            claude_subagents_dir: tmp.path().join("claude/agents"),
+            claude_agents_file: tmp.path().join("claude/CLAUDE.md"),
             cursor_dir: tmp.path().join("cursor/commands"),
             opencode_commands_dir: tmp.path().join("opencode/commands"),
             opencode_legacy_commands_dir: None,
             opencode_skills_dir: tmp.path().join("opencode/skills"),
             opencode_agents_file: tmp.path().join("opencode/AGENTS.md"),
+            opencode_subagents_dir: tmp.path().join("opencode/agent"),
             codex_skills_dir: tmp.path().join("codex/skills"),
             codex_rules_file: tmp.path().join("codex/rules/default.rules"),
             codex_agents_file: tmp.path().join("codex/AGENTS.md"),
+            gemini_commands_dir: tmp.path().join("gemini/commands"),
+            gemini_skills_dir: tmp.path().join("gemini/skills"),
+            gemini_agents_file: tmp.path().join("gemini/GEMINI.md"),
+            claude_mcp_file: tmp.path().join("claude/.claude.json"),
+            codex_config_file: tmp.path().join("codex/config.toml"),
+            cursor_mcp_file: tmp.path().join("cursor/mcp.json"),
+            opencode_config_file: tmp.path().join("opencode/opencode.json"),
+            providers: std::collections::BTreeMap::new(),
+            project_root: None,
+            three_way_merge: false,
+            preserve_conflicts: false,
+            conflict_policy: Default::default(),
+            history: Default::default(),
         }
     }
 
@@ -0,0 +1,412 @@
+use crate::history::EventDetail;
+use crate::sync::{PlannedContent, SyncReport};
+use console::style;
+use similar::{ChangeTag, TextDiff};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+
+const CONTEXT_LINES: usize = 3;
+
+/// Renders `relay sync --plan --diff`: a unified diff for every file target
+/// and a per-file tree for directory targets such as skills. Colour follows
+/// `console`, so it only appears when stdout is a terminal.
+pub(crate) fn render_plan_diff(report: &SyncReport) -> io::Result<String> {
+    let mut out = String::new();
+    for (_, action) in report.targets() {
+        let path = action.change.path.as_path();
+        let before = PlannedContent::read(path)?;
+        render_target(
+            &mut out,
+            path,
+            before.as_ref(),
+            action.change.content.as_ref(),
+        );
+    }
+    Ok(out)
+}
+
+/// Renders `relay history show`: the recording command and any conflicts it
+/// resolved, then every written path with its before and after kind and the
+/// item it belongs to, followed by the same diffs `sync --plan --diff`
+/// prints, or a per-path line count with `stat`.
+pub(crate) fn render_history_event(detail: &EventDetail, stat: bool) -> String {
+    let summary = &detail.summary;
+    let mut out = format!("event {}\n", summary.headline());
+    if let Some(context) = &summary.context {
+        let _ = writeln!(out, "argv: {}", context.argv.join(" "));
+    }
+    for conflict in &summary.conflicts {
+        let _ = writeln!(
+            out,
+            "conflict {} {} winner={} losers={} policy={}",
+            conflict.kind,
+            conflict.name,
+            conflict.winner,
+            conflict.losers.join(","),
+            conflict.policy
+        );
+    }
+    let (mut insertions, mut deletions) = (0, 0);
+    for write in &detail.writes {
+        let before = write.before.as_ref();
+        let after = write.after.as_ref();
+        let kinds = format!("{}->{}", kind_label(before), kind_label(after));
+        if !stat {
+            let item = write
+                .item
+                .as_ref()
+                .map(|item| format!(" ({} {} {})", item.kind, item.name, item.tool))
+                .unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "{}",
+                style(format!("{kinds} {}{item}", write.path.display())).bold()
+            );
+            render_target(&mut out, &write.path, before, after);
+            continue;
+        }
+        match line_counts(before, after) {
+            Some((added, removed)) => {
+                insertions += added;
+                deletions += removed;
+                let _ = writeln!(
+                    out,
+                    "  {kinds} {} {} {}",
+                    write.path.display(),
+                    style(format!("+{added}")).green(),
+                    style(format!("-{removed}")).red()
+                );
+            }
+            None => {
+                let _ = writeln!(out, "  {kinds} {} binary", write.path.display());
+            }
+        }
+    }
+    if stat {
+        let _ = writeln!(
+            out,
+            "{} paths changed, {insertions} insertions(+), {deletions} deletions(-)",
+            detail.writes.len()
+        );
+    }
+    out
+}
+
+fn kind_label(content: Option<&PlannedContent>) -> &'static str {
+    match content {
+        None => "missing",
+        Some(PlannedContent::File(_)) => "file",
+        Some(PlannedContent::Dir(_)) => "dir",
+    }
+}
+
+/// Inserted and deleted lines across every file of a target, or `None` when
+/// a changed file is not UTF-8.
+fn line_counts(
+    before: Option<&PlannedContent>,
+    after: Option<&PlannedContent>,
+) -> Option<(usize, usize)> {
+    let before = files_of(before);
+    let after = files_of(after);
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let (mut added, mut removed) = (0, 0);
+    for name in names {
+        let old = before.get(name).copied().unwrap_or_default();
+        let new = after.get(name).copied().unwrap_or_default();
+        if old == new {
+            continue;
+        }
+        let (Ok(old), Ok(new)) = (std::str::from_utf8(old), std::str::from_utf8(new)) else {
+            return None;
+        };
+        for change in TextDiff::from_lines(old, new).iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Insert => added += 1,
+                ChangeTag::Delete => removed += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+    }
+    Some((added, removed))
+}
+
+fn render_target(
+    out: &mut String,
+    path: &Path,
+    before: Option<&PlannedContent>,
+    after: Option<&PlannedContent>,
+) {
+    let is_dir = |content: Option<&PlannedContent>| matches!(content, Some(PlannedContent::Dir(_)));
+    if !is_dir(before) && !is_dir(after) {
+        render_file(
+            out,
+            &path.display().to_string(),
+            file_bytes(before),
+            file_bytes(after),
+        );
+        return;
+    }
+
+    let before = files_of(before);
+    let after = files_of(after);
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let mut changed = Vec::new();
+    for name in names {
+        let old = before.get(name).copied();
+        let new = after.get(name).copied();
+        let marker = match (old, new) {
+            (None, Some(_)) => "A",
+            (Some(_), None) => "D",
+            (Some(old), Some(new)) if old != new => "M",
+            _ => continue,
+        };
+        changed.push((marker, name, old, new));
+    }
+    let _ = writeln!(
+        out,
+        "{}",
+        style(format!("diff tree {}/", path.display())).bold()
+    );
+    for (marker, name, _, _) in &changed {
+        let _ = writeln!(out, "  {marker} {name}");
+    }
+    for (_, name, old, new) in changed {
+        let label = if name.is_empty() {
+            path.display().to_string()
+        } else {
+            path.join(name).display().to_string()
+        };
+        render_file(out, &label, old, new);
+    }
+}
+
+fn file_bytes(content: Option<&PlannedContent>) -> Option<&[u8]> {
+    match content {
+        Some(PlannedContent::File(bytes)) => Some(bytes),
+        _ => None,
+    }
+}
+
+/// A file standing where a directory is expected is shown under an empty name.
+fn files_of(content: Option<&PlannedContent>) -> BTreeMap<String, &[u8]> {
+    match content {
+        Some(PlannedContent::Dir(files)) => files
+            .iter()
+            .map(|file| (file.rel.clone(), file.bytes.as_slice()))
+            .collect(),
+        Some(PlannedContent::File(bytes)) => BTreeMap::from([(String::new(), bytes.as_slice())]),
+        None => BTreeMap::new(),
+    }
+}
+
+fn render_file(out: &mut String, label: &str, before: Option<&[u8]>, after: Option<&[u8]>) {
+    if before == after {
+        return;
+    }
+    let old_label = before.map_or("/dev/null", |_| label);
+    let new_label = after.map_or("/dev/null", |_| label);
+    let _ = writeln!(out, "{}", style(format!("--- {old_label}")).bold());
+    let _ = writeln!(out, "{}", style(format!("+++ {new_label}")).bold());
+
+    let (Ok(old), Ok(new)) = (
+        std::str::from_utf8(before.unwrap_or_default()),
+        std::str::from_utf8(after.unwrap_or_default()),
+    ) else {
+        let _ = writeln!(out, "Binary files differ");
+        return;
+    };
+    let diff = TextDiff::from_lines(old, new);
+    for hunk in diff
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .iter_hunks()
+    {
+        let _ = writeln!(out, "{}", style(hunk.header()).cyan());
+        for change in hunk.iter_changes() {
+            let line = format!(
+                "{}{}",
+                sign(change.tag()),
+                change.value().trim_end_matches('\n')
+            );
+            let _ = match change.tag() {
+                ChangeTag::Delete => writeln!(out, "{}", style(line).red()),
+                ChangeTag::Insert => writeln!(out, "{}", style(line).green()),
+                ChangeTag::Equal => writeln!(out, "{line}"),
+            };
+            if change.missing_newline() {
+                let _ = writeln!(out, "\\ No newline at end of file");
+            }
+        }
+    }
+}
+
+fn sign(tag: ChangeTag) -> char {
+    match tag {
+        ChangeTag::Delete => '-',
+        ChangeTag::Insert => '+',
+        ChangeTag::Equal => ' ',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{EventContext, HistorySummary, RecordedConflict, WriteDetail, WriteItem};
+    use crate::sync::FileChange;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn planned(path: &Path, content: PlannedContent) -> FileChange {
+        FileChange {
+            content: Some(content),
+            ..FileChange::write(path, None, 0)
+        }
+    }
+
+    #[test]
+    fn render_plan_diff_shows_unified_file_diff() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let path = tmp.path().join("AGENTS.md");
+        fs::write(&path, "# Agents\nkeep\nold line\n")?;
+        let mut report = SyncReport::default();
+        report.agents.record(
+            "AGENTS.md",
+            "codex",
+            Some(planned(
+                &path,
+                PlannedContent::File(b"# Agents\nkeep\nnew line\n".to_vec()),
+            )),
+        );
+
+        let rendered = render_plan_diff(&report)?;
+
+        let label = path.display();
+        assert_eq!(
+            rendered,
+            format!(
+                "--- {label}\n+++ {label}\n@@ -1,3 +1,3 @@\n # Agents\n keep\n-old line\n+new line\n"
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn render_plan_diff_lists_directory_files_as_a_tree() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let skill = tmp.path().join("review");
+        fs::create_dir_all(&skill)?;
+        fs::write(skill.join("SKILL.md"), "Body\n")?;
+        fs::write(skill.join("old.md"), "gone\n")?;
+        let source = tmp.path().join("source");
+        fs::create_dir_all(&source)?;
+        fs::write(source.join("SKILL.md"), "Body\n")?;
+        fs::write(source.join("notes.md"), "new\n")?;
+        let planned_dir = PlannedContent::read(&source)?.expect("source exists");
+        let mut report = SyncReport::default();
+        report
+            .skills
+            .record("review", "claude", Some(planned(&skill, planned_dir)));
+        report.skills.record(
+            "stale",
+            "claude",
+            Some(FileChange::delete(&tmp.path().join("missing"), None)),
+        );
+
+        let rendered = render_plan_diff(&report)?;
+
+        let notes = skill.join("notes.md");
+        let old = skill.join("old.md");
+        assert_eq!(
+            rendered,
+            format!(
+                "diff tree {}/\n  A notes.md\n  D old.md\n--- /dev/null\n+++ {}\n@@ -0,0 +1 @@\n+new\n--- {}\n+++ /dev/null\n@@ -1 +0,0 @@\n-gone\n",
+                skill.display(),
+                notes.display(),
+                old.display()
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn render_history_event_lists_kinds_then_diffs_or_stats() {
+        let detail = EventDetail {
+            summary: HistorySummary {
+                id: "evt-1".to_string(),
+                timestamp_ms: 42,
+                origin: "sync".to_string(),
+                writes: 2,
+                paths: Vec::new(),
+                context: None,
+                conflicts: Vec::new(),
+            },
+            writes: vec![
+                WriteDetail {
+                    path: PathBuf::from("/relay/commands/review.md"),
+                    item: None,
+                    before: Some(PlannedContent::File(b"one\ntwo\n".to_vec())),
+                    after: Some(PlannedContent::File(b"one\nthree\nfour\n".to_vec())),
+                },
+                WriteDetail {
+                    path: PathBuf::from("/relay/commands/old.md"),
+                    item: None,
+                    before: Some(PlannedContent::File(vec![0xff, 0x00])),
+                    after: None,
+                },
+            ],
+        };
+
+        assert_eq!(
+            render_history_event(&detail, true),
+            "event evt-1 ts_ms=42 origin=sync writes=2\n  file->file /relay/commands/review.md +2 -1\n  file->missing /relay/commands/old.md binary\n2 paths changed, 2 insertions(+), 1 deletions(-)\n"
+        );
+        assert_eq!(
+            render_history_event(&detail, false),
+            "event evt-1 ts_ms=42 origin=sync writes=2\nfile->file /relay/commands/review.md\n--- /relay/commands/review.md\n+++ /relay/commands/review.md\n@@ -1,2 +1,3 @@\n one\n-two\n+three\n+four\nfile->missing /relay/commands/old.md\n--- /relay/commands/old.md\n+++ /dev/null\nBinary files differ\n"
+        );
+    }
+
+    #[test]
+    fn render_history_event_shows_context_conflicts_and_items() {
+        let detail = EventDetail {
+            summary: HistorySummary {
+                id: "evt-2".to_string(),
+                timestamp_ms: 42,
+                origin: "sync".to_string(),
+                writes: 1,
+                paths: Vec::new(),
+                context: Some(EventContext {
+                    relay_version: "0.2.6".to_string(),
+                    hostname: "laptop".to_string(),
+                    pid: 7,
+                    argv: vec!["relay".to_string(), "sync".to_string()],
+                }),
+                conflicts: vec![RecordedConflict {
+                    kind: "command".to_string(),
+                    name: "review.md".to_string(),
+                    winner: "claude".to_string(),
+                    losers: vec!["codex".to_string(), "opencode".to_string()],
+                    policy: "newest".to_string(),
+                }],
+            },
+            writes: vec![WriteDetail {
+                path: PathBuf::from("/codex/prompts/review.md"),
+                item: Some(WriteItem {
+                    kind: "command".to_string(),
+                    name: "review.md".to_string(),
+                    tool: "codex".to_string(),
+                }),
+                before: Some(PlannedContent::File(b"old\n".to_vec())),
+                after: Some(PlannedContent::File(b"new\n".to_vec())),
+            }],
+        };
+
+        assert_eq!(
+            render_history_event(&detail, false),
+            "event evt-2 ts_ms=42 origin=sync writes=1 relay=0.2.6 host=laptop pid=7 conflicts=1\nargv: relay sync\nconflict command review.md winner=claude losers=codex,opencode policy=newest\nfile->file /codex/prompts/review.md (command review.md codex)\n--- /codex/prompts/review.md\n+++ /codex/prompts/review.md\n@@ -1 +1 @@\n-old\n+new\n"
+        );
+    }
+}
@@ -1,13 +1,41 @@
-use crate::config::Config;
+use crate::atomic::write_atomic;
+use crate::config::{Config, HistoryRetention};
+use crate::sync::{PlannedContent, PlannedFile, SyncConflict, SyncItemKind, SyncReport};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use glob::Pattern;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io;
+use std::io::{self, Read as _, Seek as _, SeekFrom, Write as _};
 use std::path::{Component, Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 const DIR_SNAPSHOT_MAGIC_V1: &[u8] = b"RLYD1";
 const DIR_SNAPSHOT_MAGIC_V2: &[u8] = b"RLYD2";
+const DIR_SNAPSHOT_MAGIC_V3: &[u8] = b"RLYD3";
+const INDEX_FILE: &str = "index.jsonl";
+const PACK_INDEX_FILE: &str = "index.jsonl";
+/// Loose blobs written since compression carry this extension; blobs
+/// without it are raw bytes from older relay versions.
+const LOOSE_BLOB_EXT: &str = "z";
+/// Blobs that store smaller than this go into a pack file.
+const PACK_BLOB_LIMIT: usize = 16 * 1024;
+/// A pack that would grow past this starts the next one.
+const PACK_FILE_LIMIT: u64 = 8 * 1024 * 1024;
+/// Starts the header in front of every packed blob: this magic, then the
+/// blob id length (u16), the blob id, the packed time in ms (u64) and the
+/// stored length (u64), big-endian. It lets `fsck --repair` rebuild the
+/// pack index from the packs alone.
+const PACK_ENTRY_MAGIC: &[u8] = b"RLYP";
+const CODEC_STORED: u8 = 0;
+const CODEC_ZLIB: u8 = 1;
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+/// Unreferenced blobs younger than this survive `gc`: they may be the
+/// recovery snapshot named by a recent failed sync.
+const GC_GRACE_MS: u64 = DAY_MS;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum DirSnapshotEntry {
@@ -22,6 +50,61 @@ enum DirSnapshotEntry {
     },
 }
 
+/// What history snapshots for a path, before it is stored as blobs.
+enum Entity {
+    File(Vec<u8>),
+    Dir(Vec<DirSnapshotEntry>),
+}
+
+/// One entry of an `RLYD3` directory manifest; `blob` names a file's
+/// contents and is `None` for directories.
+struct ManifestEntry {
+    rel: String,
+    mode: u32,
+    blob: Option<String>,
+}
+
+/// One line of `history/packs/index.jsonl`: where a packed blob lives.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct PackEntry {
+    blob: String,
+    pack: u32,
+    offset: u64,
+    len: u64,
+    /// When the blob was packed, for the `gc` grace period.
+    ms: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct PackIndex {
+    entries: HashMap<String, PackEntry>,
+}
+
+impl PackIndex {
+    fn last_pack(&self) -> u32 {
+        self.entries
+            .values()
+            .map(|entry| entry.pack)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BlobLocation {
+    /// Uncompressed, as written before blobs were compressed.
+    Raw(PathBuf),
+    Loose(PathBuf),
+    Packed(PackEntry),
+}
+
+/// A blob as it sits on disk, for retention and `gc`.
+#[derive(Debug, Clone, Copy)]
+struct StoredBlob {
+    size: u64,
+    modified: SystemTime,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 enum EntityKind {
@@ -42,16 +125,130 @@ struct HistoryWrite {
     path: String,
     before: EntityRef,
     after: EntityRef,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    item: Option<WriteItem>,
 }
 
+/// The synced item a write belongs to. State files and other bookkeeping
+/// writes have none.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct WriteItem {
+    pub kind: String,
+    pub name: String,
+    pub tool: String,
+}
+
+/// The relay process that recorded an event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct EventContext {
+    pub relay_version: String,
+    pub hostname: String,
+    pub pid: u32,
+    pub argv: Vec<String>,
+}
+
+impl EventContext {
+    fn current() -> Self {
+        Self {
+            relay_version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname: hostname(),
+            pid: std::process::id(),
+            argv: std::env::args().collect(),
+        }
+    }
+}
+
+/// A conflict the recording sync resolved, with the policy that chose the
+/// winner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RecordedConflict {
+    pub kind: String,
+    pub name: String,
+    pub winner: String,
+    pub losers: Vec<String>,
+    pub policy: String,
+}
+
+/// Events recorded before relay kept a context or conflicts read with none.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct HistoryEvent {
     id: String,
     timestamp_ms: u64,
     origin: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    context: Option<EventContext>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    conflicts: Vec<RecordedConflict>,
     writes: Vec<HistoryWrite>,
 }
 
+/// One line of `history/index.jsonl`: enough of an event to list and filter
+/// it without parsing the event file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct IndexEntry {
+    id: String,
+    timestamp_ms: u64,
+    origin: String,
+    writes: usize,
+    paths: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    context: Option<EventContext>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    conflicts: Vec<RecordedConflict>,
+    /// `None` on lines written before the index carried blob references.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    refs: Option<EventRefs>,
+}
+
+/// The blobs an event references, so `prune` can count references from the
+/// index instead of parsing every event file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct EventRefs {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    blobs: Vec<String>,
+    /// The directory manifests among `blobs`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    manifests: Vec<String>,
+}
+
+impl EventRefs {
+    fn of(event: &HistoryEvent) -> Self {
+        let mut refs = Self::default();
+        for blob in event_blobs(event) {
+            if !refs.blobs.iter().any(|seen| seen == blob) {
+                refs.blobs.push(blob.to_string());
+            }
+        }
+        for blob in event_manifests(event) {
+            if !refs.manifests.iter().any(|seen| seen == blob) {
+                refs.manifests.push(blob.to_string());
+            }
+        }
+        refs
+    }
+}
+
+impl IndexEntry {
+    fn of(event: &HistoryEvent) -> Self {
+        let mut paths: Vec<String> = Vec::new();
+        for write in &event.writes {
+            if !paths.contains(&write.path) {
+                paths.push(write.path.clone());
+            }
+        }
+        Self {
+            id: event.id.clone(),
+            timestamp_ms: event.timestamp_ms,
+            origin: event.origin.clone(),
+            writes: event.writes.len(),
+            paths,
+            context: event.context.clone(),
+            conflicts: event.conflicts.clone(),
+            refs: Some(EventRefs::of(event)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct EntityState {
     kind: EntityKind,
@@ -84,6 +281,109 @@ pub(crate) struct HistorySummary {
     pub timestamp_ms: u64,
     pub origin: String,
     pub writes: usize,
+    /// Paths the event wrote; only the matching ones when a filter selects
+    /// paths.
+    pub paths: Vec<PathBuf>,
+    pub context: Option<EventContext>,
+    pub conflicts: Vec<RecordedConflict>,
+}
+
+impl HistorySummary {
+    fn from_index(entry: IndexEntry, paths: Vec<PathBuf>) -> Self {
+        Self {
+            id: entry.id,
+            timestamp_ms: entry.timestamp_ms,
+            origin: entry.origin,
+            writes: entry.writes,
+            paths,
+            context: entry.context,
+            conflicts: entry.conflicts,
+        }
+    }
+
+    /// One line for `relay history`; the process fields and conflict count
+    /// only appear on events that recorded them.
+    pub(crate) fn headline(&self) -> String {
+        let mut line = format!(
+            "{} ts_ms={} origin={} writes={}",
+            self.id, self.timestamp_ms, self.origin, self.writes
+        );
+        if let Some(context) = &self.context {
+            line.push_str(&format!(
+                " relay={} host={} pid={}",
+                context.relay_version, context.hostname, context.pid
+            ));
+        }
+        if !self.conflicts.is_empty() {
+            line.push_str(&format!(" conflicts={}", self.conflicts.len()));
+        }
+        line
+    }
+}
+
+/// Narrows `relay history`; every criterion that is set must hold. Path and
+/// kind criteria must hold for at least one written path.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HistoryFilter<'a> {
+    /// Matched against the full path, or only the file name when the pattern
+    /// has no `/`.
+    pub path: Option<Pattern>,
+    pub origin: Option<String>,
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+    /// Classified against the locations in this config.
+    pub kind: Option<(SyncItemKind, &'a Config)>,
+}
+
+impl HistoryFilter<'_> {
+    fn selects_paths(&self) -> bool {
+        self.path.is_some() || self.kind.is_some()
+    }
+
+    fn matches_event(&self, entry: &IndexEntry) -> bool {
+        self.origin
+            .as_deref()
+            .is_none_or(|prefix| entry.origin.starts_with(prefix))
+            && self
+                .since_ms
+                .is_none_or(|since| entry.timestamp_ms >= since)
+            && self
+                .until_ms
+                .is_none_or(|until| entry.timestamp_ms <= until)
+    }
+
+    fn matches_path(&self, path: &Path) -> bool {
+        let path_matches = self.path.as_ref().is_none_or(|pattern| {
+            if pattern.as_str().contains('/') {
+                pattern.matches_path(path)
+            } else {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| pattern.matches(name))
+            }
+        });
+        path_matches
+            && self
+                .kind
+                .is_none_or(|(kind, cfg)| cfg.kind_of_path(path) == Some(kind))
+    }
+}
+
+/// A recorded event with the before and after contents of each write decoded
+/// from its blobs; `None` means the path was absent.
+#[cfg_attr(any(test, coverage), allow(dead_code))]
+#[derive(Debug, Clone)]
+pub(crate) struct EventDetail {
+    pub summary: HistorySummary,
+    pub writes: Vec<WriteDetail>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct WriteDetail {
+    pub path: PathBuf,
+    pub item: Option<WriteItem>,
+    pub before: Option<PlannedContent>,
+    pub after: Option<PlannedContent>,
 }
 
 #[cfg_attr(any(test, coverage), allow(dead_code))]
@@ -94,9 +394,46 @@ pub(crate) struct RollbackReport {
     pub rollback_event_id: Option<String>,
 }
 
+#[cfg_attr(any(test, coverage), allow(dead_code))]
+#[derive(Debug, Clone)]
+pub(crate) struct RestoreReport {
+    pub restored: Vec<PathBuf>,
+    pub event_id: Option<String>,
+}
+
+/// What `prune` or `gc` removed, or would remove on a dry run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct PruneReport {
+    pub events: usize,
+    pub blobs: usize,
+    pub bytes: u64,
+}
+
+/// What `fsck` found. `quarantine` names the directory broken events were
+/// moved to by a repair.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FsckReport {
+    pub events: usize,
+    pub blobs: usize,
+    pub broken: Vec<BrokenEvent>,
+    pub orphans: Vec<String>,
+    pub quarantine: Option<PathBuf>,
+    /// Packed blobs whose headers `packs/index.jsonl` has no line for.
+    pub unindexed: usize,
+    pub rebuilt_pack_index: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BrokenEvent {
+    pub id: String,
+    pub path: PathBuf,
+    pub problem: String,
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct HistoryStore {
     root: PathBuf,
+    retention: HistoryRetention,
 }
 
 impl HistoryStore {
@@ -116,6 +453,7 @@ impl HistoryStore {
         let root = first.to_path_buf();
         Ok(Self {
             root: root.join("history"),
+            retention: cfg.history.clone(),
         })
     }
 
@@ -140,7 +478,95 @@ impl HistoryStore {
         self.blobs_dir().join(blob_id)
     }
 
-    fn read_events(&self) -> io::Result<Vec<HistoryEvent>> {
+    fn loose_blob_path(&self, blob_id: &str) -> PathBuf {
+        self.blobs_dir().join(format!("{blob_id}.{LOOSE_BLOB_EXT}"))
+    }
+
+    fn packs_dir(&self) -> PathBuf {
+        self.root.join("packs")
+    }
+
+    fn pack_path(&self, pack: u32) -> PathBuf {
+        self.packs_dir().join(format!("{pack:06}.pack"))
+    }
+
+    fn pack_index_path(&self) -> PathBuf {
+        self.packs_dir().join(PACK_INDEX_FILE)
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.root.join(INDEX_FILE)
+    }
+
+    /// Ids of every event file, in the order `read_events` returns them.
+    fn event_ids(&self) -> io::Result<Vec<String>> {
+        let dir = self.events_dir();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("toml") {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                ids.push(id.to_string());
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// The index, rebuilt from the event files when it is missing, unreadable
+    /// or lists a different set of events.
+    fn read_index(&self) -> io::Result<Vec<IndexEntry>> {
+        let ids = self.event_ids()?;
+        if let Some(mut entries) = self.load_index()? {
+            entries.sort_by(|a, b| a.id.cmp(&b.id));
+            if entries.iter().map(|entry| &entry.id).eq(ids.iter()) {
+                return Ok(entries);
+            }
+        }
+        let entries: Vec<IndexEntry> = self.read_events()?.iter().map(IndexEntry::of).collect();
+        if !entries.is_empty() {
+            let mut raw = String::new();
+            for entry in &entries {
+                raw.push_str(&index_line(entry)?);
+            }
+            write_atomic(&self.index_path(), raw.as_bytes())?;
+        }
+        Ok(entries)
+    }
+
+    fn load_index(&self) -> io::Result<Option<Vec<IndexEntry>>> {
+        let raw = match fs::read_to_string(self.index_path()) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let entries: Result<Vec<IndexEntry>, _> = raw
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect();
+        Ok(entries.ok())
+    }
+
+    fn append_index(&self, entry: &IndexEntry) -> io::Result<()> {
+        let line = index_line(entry)?;
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.index_path())?
+            .write_all(line.as_bytes())
+    }
+
+    fn quarantine_dir(&self) -> PathBuf {
+        self.root.join("quarantine")
+    }
+
+    fn event_files(&self) -> io::Result<Vec<PathBuf>> {
         let dir = self.events_dir();
         if !dir.exists() {
             return Ok(Vec::new());
@@ -158,18 +584,14 @@ impl HistoryStore {
             }
         }
         paths.sort();
-        let mut events = Vec::new();
-        for path in paths {
-            let raw = fs::read_to_string(&path)?;
-            let event: HistoryEvent = toml::from_str(&raw).map_err(|err| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("invalid history event in {}: {err}", path.display()),
-                )
-            })?;
-            events.push(event);
-        }
-        Ok(events)
+        Ok(paths)
+    }
+
+    fn read_events(&self) -> io::Result<Vec<HistoryEvent>> {
+        self.event_files()?
+            .iter()
+            .map(|path| read_event_file(path))
+            .collect()
     }
 
     fn append_event(&self, event: &HistoryEvent) -> io::Result<()> {
@@ -187,62 +609,852 @@ impl HistoryStore {
         let path = self.event_path(&event.id);
         let serialized = toml::to_string_pretty(event)
             .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
-        fs::write(path, serialized)
+        fs::write(path, serialized)?;
+        // The event file is the record; a missed index line only means the
+        // next read rebuilds the index.
+        let _ = self.append_index(&IndexEntry::of(event));
+        Ok(())
     }
 
     fn store_blob(&self, bytes: &[u8]) -> io::Result<String> {
+        self.store_blob_with(&mut self.load_packs()?, bytes, SystemTime::now())
+    }
+
+    /// Stores `bytes` compressed, in a pack when small, and names the blob
+    /// after the hash of the uncompressed bytes.
+    fn store_blob_with(
+        &self,
+        packs: &mut PackIndex,
+        bytes: &[u8],
+        now: SystemTime,
+    ) -> io::Result<String> {
         self.ensure_dirs()?;
         let base = hash_hex(bytes);
         let mut candidate = base.clone();
         let mut counter = 0usize;
-        loop {
-            let path = self.blob_path(&candidate);
-            if !path.exists() {
-                fs::write(path, bytes)?;
-                return Ok(candidate);
-            }
-            if fs::read(&path)? == bytes {
+        while let Some(location) = self.find_blob(packs, &candidate)? {
+            if self.read_location(&location)? == bytes {
                 return Ok(candidate);
             }
             counter += 1;
             candidate = format!("{base}-{counter}");
         }
+        let stored = compress_blob(bytes)?;
+        if stored.len() >= PACK_BLOB_LIMIT {
+            fs::write(self.loose_blob_path(&candidate), stored)?;
+        } else {
+            self.append_to_pack(packs, &candidate, &stored, now)?;
+        }
+        Ok(candidate)
+    }
+
+    /// Appends to the newest pack, or starts the next one when it is full.
+    /// The pack bytes land before the index line that points at them.
+    fn append_to_pack(
+        &self,
+        packs: &mut PackIndex,
+        blob_id: &str,
+        stored: &[u8],
+        now: SystemTime,
+    ) -> io::Result<()> {
+        fs::create_dir_all(self.packs_dir())?;
+        let ms = system_time_millis(now);
+        let record = pack_record(blob_id, ms, stored)?;
+        let mut pack = packs.last_pack().max(1);
+        let mut start = file_len(&self.pack_path(pack))?;
+        if start > 0 && start + record.len() as u64 > PACK_FILE_LIMIT {
+            pack += 1;
+            start = file_len(&self.pack_path(pack))?;
+        }
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.pack_path(pack))?
+            .write_all(&record)?;
+        let entry = PackEntry {
+            blob: blob_id.to_string(),
+            pack,
+            offset: start + (record.len() - stored.len()) as u64,
+            len: stored.len() as u64,
+            ms,
+        };
+        let mut index = fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(self.pack_index_path())?;
+        drop_torn_line(&mut index)?;
+        index.write_all(pack_index_line(&entry)?.as_bytes())?;
+        packs.entries.insert(entry.blob.clone(), entry);
+        Ok(())
+    }
+
+    fn load_packs(&self) -> io::Result<PackIndex> {
+        let path = self.pack_index_path();
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(PackIndex::default()),
+            Err(err) => return Err(err),
+        };
+        // A final line without its newline is an append that never
+        // finished; the blob it names was never used.
+        let complete = match raw.rfind('\n') {
+            Some(end) => &raw[..=end],
+            None => "",
+        };
+        let mut packs = PackIndex::default();
+        for line in complete.lines().filter(|line| !line.trim().is_empty()) {
+            let entry: PackEntry = serde_json::from_str(line).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid pack index {}: {err}", path.display()),
+                )
+            })?;
+            packs.entries.insert(entry.blob.clone(), entry);
+        }
+        Ok(packs)
+    }
+
+    /// Every blob the pack headers describe, for rebuilding the index.
+    /// Bytes that do not form a whole, decodable entry (a torn append) are
+    /// skipped up to the next header.
+    fn scan_packs(&self) -> io::Result<PackIndex> {
+        let mut packs = PackIndex::default();
+        let dir = match fs::read_dir(self.packs_dir()) {
+            Ok(dir) => dir,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(packs),
+            Err(err) => return Err(err),
+        };
+        for entry in dir {
+            let path = entry?.path();
+            let Some(pack) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".pack"))
+                .and_then(|stem| stem.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let bytes = fs::read(&path)?;
+            let mut pos = 0;
+            while let Some(found) = find_bytes(&bytes[pos..], PACK_ENTRY_MAGIC) {
+                let start = pos + found;
+                pos = start + 1;
+                let Some((blob, ms, offset, len)) = parse_pack_header(&bytes, start) else {
+                    continue;
+                };
+                let Some(stored) = bytes.get(offset..offset + len) else {
+                    continue;
+                };
+                if decompress_blob(stored).is_err() {
+                    continue;
+                }
+                pos = offset + len;
+                packs.entries.insert(
+                    blob.clone(),
+                    PackEntry {
+                        blob,
+                        pack,
+                        offset: offset as u64,
+                        len: len as u64,
+                        ms,
+                    },
+                );
+            }
+        }
+        Ok(packs)
+    }
+
+    fn write_pack_index(&self, entries: &[PackEntry]) -> io::Result<()> {
+        let mut raw = String::new();
+        for entry in entries {
+            raw.push_str(&pack_index_line(entry)?);
+        }
+        write_atomic(&self.pack_index_path(), raw.as_bytes())
+    }
+
+    /// Raw blobs from older versions win over compressed ones of the same
+    /// name, then loose blobs over packed ones.
+    fn find_blob(&self, packs: &PackIndex, blob_id: &str) -> io::Result<Option<BlobLocation>> {
+        let raw = self.blob_path(blob_id);
+        if raw.is_file() {
+            return Ok(Some(BlobLocation::Raw(raw)));
+        }
+        let loose = self.loose_blob_path(blob_id);
+        if loose.is_file() {
+            return Ok(Some(BlobLocation::Loose(loose)));
+        }
+        Ok(packs
+            .entries
+            .get(blob_id)
+            .cloned()
+            .map(BlobLocation::Packed))
+    }
+
+    fn read_location(&self, location: &BlobLocation) -> io::Result<Vec<u8>> {
+        match location {
+            BlobLocation::Raw(path) => fs::read(path),
+            BlobLocation::Loose(path) => decompress_blob(&fs::read(path)?),
+            BlobLocation::Packed(entry) => decompress_blob(&self.read_stored(entry)?),
+        }
     }
 
     fn read_blob(&self, blob_id: &str) -> io::Result<Vec<u8>> {
-        fs::read(self.blob_path(blob_id))
+        self.read_blob_with(&self.load_packs()?, blob_id)
     }
 
+    fn read_blob_with(&self, packs: &PackIndex, blob_id: &str) -> io::Result<Vec<u8>> {
+        match self.find_blob(packs, blob_id)? {
+            Some(location) => self.read_location(&location),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("history blob not found: {blob_id}"),
+            )),
+        }
+    }
+
+    /// Where a blob is kept, for error messages that point at a recovery
+    /// snapshot.
+    fn describe_blob_location(&self, blob_id: &str) -> String {
+        let location = self
+            .load_packs()
+            .and_then(|packs| self.find_blob(&packs, blob_id));
+        match location {
+            Ok(Some(BlobLocation::Raw(path) | BlobLocation::Loose(path))) => {
+                path.display().to_string()
+            }
+            Ok(Some(BlobLocation::Packed(entry))) => format!(
+                "{} at offset {}",
+                self.pack_path(entry.pack).display(),
+                entry.offset
+            ),
+            _ => self.blob_path(blob_id).display().to_string(),
+        }
+    }
+
+    /// Every stored blob, loose or packed.
+    fn stored_blobs(&self, packs: &PackIndex) -> io::Result<HashMap<String, StoredBlob>> {
+        let mut blobs = HashMap::new();
+        for entry in packs.entries.values() {
+            blobs.insert(
+                entry.blob.clone(),
+                StoredBlob {
+                    size: entry.len,
+                    modified: UNIX_EPOCH + std::time::Duration::from_millis(entry.ms),
+                },
+            );
+        }
+        if !self.blobs_dir().exists() {
+            return Ok(blobs);
+        }
+        for entry in fs::read_dir(self.blobs_dir())? {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let blob_id = name
+                .strip_suffix(&format!(".{LOOSE_BLOB_EXT}"))
+                .map_or(name.clone(), str::to_string);
+            blobs.insert(
+                blob_id,
+                StoredBlob {
+                    size: metadata.len(),
+                    modified: metadata.modified()?,
+                },
+            );
+        }
+        Ok(blobs)
+    }
+
+    /// Deletes `blob_ids` wherever they are stored. Packs holding any of
+    /// them are rewritten into one new pack without them before the old
+    /// packs are removed.
+    fn remove_blobs(&self, packs: &PackIndex, blob_ids: &HashSet<String>) -> io::Result<()> {
+        for blob_id in blob_ids {
+            remove_path_if_exists(&self.blob_path(blob_id))?;
+            remove_path_if_exists(&self.loose_blob_path(blob_id))?;
+        }
+        let affected: BTreeSet<u32> = packs
+            .entries
+            .values()
+            .filter(|entry| blob_ids.contains(&entry.blob))
+            .map(|entry| entry.pack)
+            .collect();
+        if affected.is_empty() {
+            return Ok(());
+        }
+        let new_pack = packs.last_pack() + 1;
+        let mut kept: Vec<PackEntry> = packs
+            .entries
+            .values()
+            .filter(|entry| !blob_ids.contains(&entry.blob))
+            .cloned()
+            .collect();
+        kept.sort_by_key(|entry| (entry.pack, entry.offset));
+        let mut repacked = Vec::new();
+        for entry in &mut kept {
+            if !affected.contains(&entry.pack) {
+                continue;
+            }
+            let stored = self.read_stored(entry)?;
+            let record = pack_record(&entry.blob, entry.ms, &stored)?;
+            *entry = PackEntry {
+                pack: new_pack,
+                offset: (repacked.len() + record.len() - stored.len()) as u64,
+                ..entry.clone()
+            };
+            repacked.extend_from_slice(&record);
+        }
+        if !repacked.is_empty() {
+            write_atomic(&self.pack_path(new_pack), &repacked)?;
+        }
+        self.write_pack_index(&kept)?;
+        for pack in affected {
+            remove_path_if_exists(&self.pack_path(pack))?;
+        }
+        Ok(())
+    }
+
+    /// A packed blob's bytes as stored, without decompressing them.
+    fn read_stored(&self, entry: &PackEntry) -> io::Result<Vec<u8>> {
+        let len = usize::try_from(entry.len)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "packed blob is too large"))?;
+        let mut file = fs::File::open(self.pack_path(entry.pack))?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut stored = vec![0; len];
+        file.read_exact(&mut stored)?;
+        Ok(stored)
+    }
+
+    /// The file blobs each `RLYD3` directory manifest that `events` record
+    /// refers to. Older directory snapshots hold their files inline and
+    /// have none; neither does a manifest that is gone or damaged, since
+    /// nothing can be restored through it.
+    fn manifest_children<'a>(
+        &self,
+        packs: &PackIndex,
+        manifests: impl IntoIterator<Item = &'a str>,
+    ) -> io::Result<HashMap<String, Vec<String>>> {
+        let mut children = HashMap::new();
+        for blob_id in manifests {
+            if children.contains_key(blob_id) {
+                continue;
+            }
+            let bytes = match self.read_blob_with(packs, blob_id) {
+                Ok(bytes) => bytes,
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::NotFound | io::ErrorKind::InvalidData
+                    ) =>
+                {
+                    continue
+                }
+                Err(err) => return Err(err),
+            };
+            let mut files = Vec::new();
+            if bytes.starts_with(DIR_SNAPSHOT_MAGIC_V3) {
+                for entry in decode_dir_manifest(&bytes).unwrap_or_default() {
+                    if let Some(blob) = entry.blob {
+                        if !files.contains(&blob) {
+                            files.push(blob);
+                        }
+                    }
+                }
+            }
+            children.insert(blob_id.to_string(), files);
+        }
+        Ok(children)
+    }
+
+    /// A directory snapshot in any format, with `RLYD3` file contents read
+    /// from their blobs.
+    fn load_dir_snapshot(
+        &self,
+        packs: &PackIndex,
+        bytes: &[u8],
+    ) -> io::Result<Vec<DirSnapshotEntry>> {
+        if !bytes.starts_with(DIR_SNAPSHOT_MAGIC_V3) {
+            return decode_dir_snapshot(bytes);
+        }
+        let entries = decode_dir_manifest(bytes)?
+            .into_iter()
+            .map(|entry| {
+                Ok(match entry.blob {
+                    None => DirSnapshotEntry::Directory {
+                        rel: entry.rel,
+                        mode: entry.mode,
+                    },
+                    Some(blob) => DirSnapshotEntry::File {
+                        rel: entry.rel,
+                        mode: entry.mode,
+                        contents: self.read_blob_with(packs, &blob)?,
+                    },
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        validate_snapshot_entries(&entries, true)?;
+        Ok(entries)
+    }
+
+    #[cfg(test)]
+    fn has_blob(&self, blob_id: &str) -> io::Result<bool> {
+        Ok(self.find_blob(&self.load_packs()?, blob_id)?.is_some())
+    }
+
+    /// For every file relay has written, the blob of its most recent write and
+    /// that write's position in history (higher is newer). Files whose latest
+    /// recorded state is not a file are omitted.
+    pub(crate) fn last_written_files(&self) -> io::Result<HashMap<PathBuf, (usize, String)>> {
+        let mut latest = HashMap::new();
+        let mut seq = 0usize;
+        for event in self.read_events()? {
+            for write in event.writes {
+                seq += 1;
+                let path = PathBuf::from(write.path);
+                match (write.after.kind, write.after.blob) {
+                    (EntityKind::File, Some(blob)) => {
+                        latest.insert(path, (seq, blob));
+                    }
+                    _ => {
+                        latest.remove(&path);
+                    }
+                }
+            }
+        }
+        Ok(latest)
+    }
+
+    /// Deletes the oldest events beyond the `[history]` limits, then the blobs
+    /// only they referenced. The latest event to write each path is always
+    /// kept, so the current state of every path can still be rolled back.
+    pub(crate) fn prune(&self, dry_run: bool) -> io::Result<PruneReport> {
+        let mut entries = self.read_index()?;
+        let mut latest_for_path = HashMap::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            for path in &entry.paths {
+                latest_for_path.insert(path.as_str(), idx);
+            }
+        }
+        let protected: HashSet<usize> = latest_for_path.into_values().collect();
+
+        // Index lines from before blob references were indexed are filled in
+        // from their event file once and written back below.
+        let mut backfilled = false;
+        for entry in &mut entries {
+            if entry.refs.is_none() {
+                let event = read_event_file(&self.event_path(&entry.id))?;
+                entry.refs = Some(EventRefs::of(&event));
+                backfilled = true;
+            }
+        }
+        let event_refs: Vec<&EventRefs> = entries.iter().flat_map(|entry| &entry.refs).collect();
+
+        // A file blob is held by every event that records it and by every
+        // directory manifest that lists it.
+        let packs = self.load_packs()?;
+        let children = self.manifest_children(
+            &packs,
+            event_refs
+                .iter()
+                .flat_map(|refs| &refs.manifests)
+                .map(String::as_str),
+        )?;
+        let mut refs: HashMap<&str, usize> = HashMap::new();
+        for blob in event_refs
+            .iter()
+            .flat_map(|refs| &refs.blobs)
+            .chain(children.values().flatten())
+        {
+            *refs.entry(blob.as_str()).or_default() += 1;
+        }
+        let blob_sizes: HashMap<String, u64> = self
+            .stored_blobs(&packs)?
+            .into_iter()
+            .map(|(blob, stored)| (blob, stored.size))
+            .collect();
+        let mut event_sizes = Vec::with_capacity(entries.len());
+        for entry in &entries {
+            event_sizes.push(fs::metadata(self.event_path(&entry.id))?.len());
+        }
+        let mut total = blob_sizes.values().sum::<u64>() + event_sizes.iter().sum::<u64>();
+        let age_cutoff = self
+            .retention
+            .max_age_days
+            .map(|days| now_millis().saturating_sub(days.saturating_mul(DAY_MS)));
+
+        let mut remaining = entries.len();
+        let mut report = PruneReport::default();
+        let mut freed = HashSet::new();
+        let mut removed = HashSet::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            // Events run oldest first, so once none of the limits is exceeded
+            // no later event can exceed them either.
+            let over_limit = self.retention.max_events.is_some_and(|max| remaining > max)
+                || age_cutoff.is_some_and(|cutoff| entry.timestamp_ms < cutoff)
+                || self.retention.max_bytes.is_some_and(|max| total > max);
+            if !over_limit {
+                break;
+            }
+            if protected.contains(&idx) {
+                continue;
+            }
+            remaining -= 1;
+            total = total.saturating_sub(event_sizes[idx]);
+            report.events += 1;
+            report.bytes += event_sizes[idx];
+            let mut released: Vec<&str> =
+                event_refs[idx].blobs.iter().map(String::as_str).collect();
+            while let Some(blob) = released.pop() {
+                let count = refs.get_mut(blob).expect("counted above");
+                *count -= 1;
+                if *count > 0 {
+                    continue;
+                }
+                if let Some(size) = blob_sizes.get(blob) {
+                    total = total.saturating_sub(*size);
+                    report.blobs += 1;
+                    report.bytes += size;
+                    freed.insert(blob.to_string());
+                }
+                if let Some(files) = children.get(blob) {
+                    released.extend(files.iter().map(String::as_str));
+                }
+            }
+            removed.insert(entry.id.as_str());
+        }
+        if dry_run || (removed.is_empty() && !backfilled) {
+            return Ok(report);
+        }
+
+        for id in &removed {
+            fs::remove_file(self.event_path(id))?;
+        }
+        let mut raw = String::new();
+        for entry in entries
+            .iter()
+            .filter(|entry| !removed.contains(entry.id.as_str()))
+        {
+            raw.push_str(&index_line(entry)?);
+        }
+        write_atomic(&self.index_path(), raw.as_bytes())?;
+        self.remove_blobs(&packs, &freed)?;
+        Ok(report)
+    }
+
+    /// Mark and sweep: deletes every blob no event references, except those
+    /// written in the last day.
+    pub(crate) fn gc(&self) -> io::Result<PruneReport> {
+        let events = self.read_events()?;
+        let packs = self.load_packs()?;
+        let children = self.manifest_children(&packs, events.iter().flat_map(event_manifests))?;
+        let live: HashSet<&str> = events
+            .iter()
+            .flat_map(event_blobs)
+            .chain(children.values().flatten().map(String::as_str))
+            .collect();
+        let cutoff = SystemTime::now()
+            .checked_sub(std::time::Duration::from_millis(GC_GRACE_MS))
+            .unwrap_or(UNIX_EPOCH);
+        let mut report = PruneReport::default();
+        let mut swept = HashSet::new();
+        for (blob, stored) in self.stored_blobs(&packs)? {
+            if live.contains(blob.as_str()) || stored.modified > cutoff {
+                continue;
+            }
+            report.blobs += 1;
+            report.bytes += stored.size;
+            swept.insert(blob);
+        }
+        self.remove_blobs(&packs, &swept)?;
+        Ok(report)
+    }
+
+    /// Checks every event file and the blobs it references; with `repair`,
+    /// rebuilds a pack index that lost entries from the pack headers and
+    /// moves broken events to `history/quarantine/` so the rest of history
+    /// stays usable. Orphaned blobs are only reported: `gc` removes them.
+    pub(crate) fn fsck(&self, repair: bool) -> io::Result<FsckReport> {
+        let mut report = FsckReport::default();
+        let scanned = self.scan_packs()?;
+        let packs = match self.load_packs() {
+            Ok(packs) => {
+                report.unindexed = scanned
+                    .entries
+                    .keys()
+                    .filter(|blob| !packs.entries.contains_key(*blob))
+                    .count();
+                packs
+            }
+            Err(_) if repair => {
+                report.unindexed = scanned.entries.len();
+                PackIndex::default()
+            }
+            Err(err) => {
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("{err}; run `relay history fsck --repair` to rebuild it"),
+                ))
+            }
+        };
+        let packs = if repair && report.unindexed > 0 {
+            let mut rebuilt = packs;
+            for (blob, entry) in scanned.entries {
+                rebuilt.entries.entry(blob).or_insert(entry);
+            }
+            let mut entries: Vec<PackEntry> = rebuilt.entries.values().cloned().collect();
+            entries.sort_by_key(|entry| (entry.pack, entry.offset));
+            self.write_pack_index(&entries)?;
+            report.rebuilt_pack_index = true;
+            rebuilt
+        } else {
+            packs
+        };
+        let mut parsed = Vec::new();
+        let mut verified: HashMap<String, Result<(), String>> = HashMap::new();
+        for path in self.event_files()? {
+            report.events += 1;
+            let id = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let problem = match read_event_file(&path) {
+                Ok(event) => {
+                    let problem = self.check_event(&packs, &id, &event, &mut verified).err();
+                    parsed.push(event);
+                    problem
+                }
+                Err(err) => Some(err.to_string()),
+            };
+            if let Some(problem) = problem {
+                report.broken.push(BrokenEvent { id, path, problem });
+            }
+        }
+        report.blobs = verified.len();
+        let children = self.manifest_children(&packs, parsed.iter().flat_map(event_manifests))?;
+        let live: HashSet<&str> = parsed
+            .iter()
+            .flat_map(event_blobs)
+            .chain(children.values().flatten().map(String::as_str))
+            .collect();
+        let mut orphans: Vec<String> = self
+            .stored_blobs(&packs)?
+            .into_keys()
+            .filter(|blob| !live.contains(blob.as_str()))
+            .collect();
+        orphans.sort();
+        report.orphans = orphans;
+
+        if repair && !report.broken.is_empty() {
+            fs::create_dir_all(self.quarantine_dir())?;
+            for broken in &report.broken {
+                let Some(name) = broken.path.file_name() else {
+                    continue;
+                };
+                fs::rename(&broken.path, self.quarantine_dir().join(name))?;
+            }
+            report.quarantine = Some(self.quarantine_dir());
+            // The index no longer matches the event files, so this rebuilds it.
+            self.read_index()?;
+        }
+        Ok(report)
+    }
+
+    /// The first problem found in `event`; blob results are cached in
+    /// `verified` because events share blobs.
+    fn check_event(
+        &self,
+        packs: &PackIndex,
+        id: &str,
+        event: &HistoryEvent,
+        verified: &mut HashMap<String, Result<(), String>>,
+    ) -> Result<(), String> {
+        if event.id != id {
+            return Err(format!(
+                "event id {} does not match its file name",
+                event.id
+            ));
+        }
+        for write in &event.writes {
+            for (label, state) in [("before", &write.before), ("after", &write.after)] {
+                let context =
+                    |problem: String| format!("{label} state of {}: {problem}", write.path);
+                match (state.kind, state.blob.as_deref()) {
+                    (EntityKind::Missing, None) => {}
+                    (EntityKind::Missing, Some(_)) => {
+                        return Err(context("missing path has a blob".to_string()));
+                    }
+                    (_, None) => return Err(context("no blob reference".to_string())),
+                    (kind, Some(blob)) => {
+                        let result = verified
+                            .entry(blob.to_string())
+                            .or_insert_with(|| self.check_blob(packs, blob, kind))
+                            .clone();
+                        result.map_err(context)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A blob is named after the hash of its contents, with a `-N` suffix
+    /// when that name was already taken by different bytes. A directory
+    /// manifest also needs every file blob it lists.
+    fn check_blob(&self, packs: &PackIndex, blob_id: &str, kind: EntityKind) -> Result<(), String> {
+        let bytes = match self.read_blob_with(packs, blob_id) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(format!("blob {blob_id} is missing"));
+            }
+            Err(err) => return Err(format!("blob {blob_id} is unreadable: {err}")),
+        };
+        let hash = if is_legacy_blob_id(blob_id) {
+            legacy_hash_hex(&bytes)
+        } else {
+            hash_hex(&bytes)
+        };
+        if hash != blob_base(blob_id) {
+            return Err(format!("blob {blob_id} does not match its hash"));
+        }
+        if kind != EntityKind::Dir {
+            return Ok(());
+        }
+        let invalid =
+            |err: io::Error| format!("blob {blob_id} is not a valid directory snapshot: {err}");
+        if !bytes.starts_with(DIR_SNAPSHOT_MAGIC_V3) {
+            return decode_dir_snapshot(&bytes).map(|_| ()).map_err(invalid);
+        }
+        for entry in decode_dir_manifest(&bytes).map_err(invalid)? {
+            if let Some(file) = entry.blob {
+                self.check_blob(packs, &file, EntityKind::File)?;
+            }
+        }
+        self.load_dir_snapshot(packs, &bytes)
+            .map(|_| ())
+            .map_err(invalid)
+    }
+
+    pub(crate) fn blob_contents(&self, blob_id: &str) -> io::Result<Vec<u8>> {
+        self.read_blob(blob_id)
+    }
+
+    #[cfg(any(test, coverage))]
     pub(crate) fn list_recent(&self, limit: usize) -> io::Result<Vec<HistorySummary>> {
-        let mut out: Vec<HistorySummary> = self
-            .read_events()?
+        self.list_matching(&HistoryFilter::default(), limit)
+    }
+
+    /// The newest `limit` events passing `filter`, oldest first.
+    pub(crate) fn list_matching(
+        &self,
+        filter: &HistoryFilter,
+        limit: usize,
+    ) -> io::Result<Vec<HistorySummary>> {
+        let mut out = Vec::new();
+        for entry in self.read_index()?.into_iter().rev() {
+            if out.len() == limit {
+                break;
+            }
+            if !filter.matches_event(&entry) {
+                continue;
+            }
+            let paths: Vec<PathBuf> = entry
+                .paths
+                .iter()
+                .map(PathBuf::from)
+                .filter(|path| filter.matches_path(path))
+                .collect();
+            if filter.selects_paths() && paths.is_empty() {
+                continue;
+            }
+            out.push(HistorySummary::from_index(entry, paths));
+        }
+        out.reverse();
+        Ok(out)
+    }
+
+    #[cfg_attr(any(test, coverage), allow(dead_code))]
+    pub(crate) fn latest_event_id(&self) -> io::Result<Option<String>> {
+        Ok(self.read_index()?.pop().map(|entry| entry.id))
+    }
+
+    /// Parses only the requested event file, once the index knows the id; an
+    /// id that is not an indexed event never becomes a path.
+    fn find_event(&self, event_id: &str) -> io::Result<HistoryEvent> {
+        if !self.read_index()?.iter().any(|entry| entry.id == event_id) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("history event not found: {event_id}"),
+            ));
+        }
+        read_event_file(&self.event_path(event_id))
+    }
+
+    pub(crate) fn show_event(&self, event_id: &str) -> io::Result<EventDetail> {
+        let event = self.find_event(event_id)?;
+        let index = IndexEntry::of(&event);
+        let writes = event
+            .writes
+            .iter()
+            .map(|write| {
+                Ok(WriteDetail {
+                    path: PathBuf::from(&write.path),
+                    item: write.item.clone(),
+                    before: self.entity_contents(&write.before)?,
+                    after: self.entity_contents(&write.after)?,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        let paths = index.paths.iter().map(PathBuf::from).collect();
+        Ok(EventDetail {
+            summary: HistorySummary::from_index(index, paths),
+            writes,
+        })
+    }
+
+    /// Regular files only: empty directories and directory modes in a
+    /// snapshot have no content to show.
+    fn entity_contents(&self, state: &EntityRef) -> io::Result<Option<PlannedContent>> {
+        if state.kind == EntityKind::Missing {
+            return Ok(None);
+        }
+        let Some(blob_id) = state.blob.as_deref() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "missing blob reference",
+            ));
+        };
+        let packs = self.load_packs()?;
+        let bytes = self.read_blob_with(&packs, blob_id)?;
+        if state.kind == EntityKind::File {
+            return Ok(Some(PlannedContent::File(bytes)));
+        }
+        let mut files: Vec<PlannedFile> = self
+            .load_dir_snapshot(&packs, &bytes)?
             .into_iter()
-            .rev()
-            .take(limit)
-            .map(|event| HistorySummary {
-                id: event.id,
-                timestamp_ms: event.timestamp_ms,
-                origin: event.origin,
-                writes: event.writes.len(),
+            .filter_map(|entry| match entry {
+                DirSnapshotEntry::File {
+                    rel,
+                    mode,
+                    contents,
+                } => Some(PlannedFile {
+                    rel,
+                    mode,
+                    bytes: contents,
+                }),
+                DirSnapshotEntry::Directory { .. } => None,
             })
             .collect();
-        out.reverse();
-        Ok(out)
-    }
-
-    #[cfg_attr(any(test, coverage), allow(dead_code))]
-    pub(crate) fn latest_event_id(&self) -> io::Result<Option<String>> {
-        Ok(self.read_events()?.into_iter().last().map(|event| event.id))
+        files.sort_by(|a, b| a.rel.cmp(&b.rel));
+        Ok(Some(PlannedContent::Dir(files)))
     }
 
     pub(crate) fn rollback(&self, event_id: &str, force: bool) -> io::Result<RollbackReport> {
-        let events = self.read_events()?;
-        let Some(event) = events.into_iter().find(|event| event.id == event_id) else {
-            return Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("history event not found: {event_id}"),
-            ));
-        };
+        let event = self.find_event(event_id)?;
         let mut recorder = HistoryRecorder::with_store(
             self.clone(),
             format!("rollback:{event_id}"),
@@ -277,6 +1489,127 @@ impl HistoryStore {
         })
     }
 
+    /// Restores one path to the state `event_id` left it in, or with `before`
+    /// to the state that event replaced.
+    pub(crate) fn restore_from_event(
+        &self,
+        path: &Path,
+        event_id: &str,
+        before: bool,
+        force: bool,
+    ) -> io::Result<RestoreReport> {
+        let event = self.find_event(event_id)?;
+        let Some(write) = event
+            .writes
+            .iter()
+            .rev()
+            .find(|write| Path::new(&write.path) == path)
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("history event {event_id} did not write {}", path.display()),
+            ));
+        };
+        let target = if before { &write.before } else { &write.after };
+        let origin = format!("restore:{event_id}");
+        self.restore_states(&origin, vec![(path.to_path_buf(), target.clone())], force)
+    }
+
+    /// Restores every recorded path matching `filter` (all of them without
+    /// one) to its state at `at_ms`: what the last write at or before then
+    /// left, or what the first later write replaced.
+    pub(crate) fn restore_at(
+        &self,
+        at_ms: u64,
+        filter: &HistoryFilter,
+        force: bool,
+    ) -> io::Result<RestoreReport> {
+        let mut events = self.read_events()?;
+        events.sort_by_key(|event| event.timestamp_ms);
+        let mut states: BTreeMap<PathBuf, EntityRef> = BTreeMap::new();
+        for event in &events {
+            for write in &event.writes {
+                let path = PathBuf::from(&write.path);
+                if !filter.matches_path(&path) {
+                    continue;
+                }
+                if event.timestamp_ms <= at_ms {
+                    states.insert(path, write.after.clone());
+                } else {
+                    states.entry(path).or_insert_with(|| write.before.clone());
+                }
+            }
+        }
+        if states.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no recorded paths match",
+            ));
+        }
+        self.restore_states(
+            &format!("restore:at:{at_ms}"),
+            states.into_iter().collect(),
+            force,
+        )
+    }
+
+    /// Checks every path against the state history last recorded for it
+    /// before writing any, then restores the ones that differ from `target`
+    /// as one event.
+    fn restore_states(
+        &self,
+        origin: &str,
+        targets: Vec<(PathBuf, EntityRef)>,
+        force: bool,
+    ) -> io::Result<RestoreReport> {
+        let mut last_recorded: HashMap<&str, &EntityRef> = HashMap::new();
+        let mut events = self.read_events()?;
+        events.sort_by_key(|event| event.timestamp_ms);
+        for write in events.iter().flat_map(|event| &event.writes) {
+            last_recorded.insert(write.path.as_str(), &write.after);
+        }
+        let mut pending = Vec::new();
+        for (path, target) in targets {
+            let current = self.capture_path(&path)?;
+            if self.state_matches(&path, &current, &target)? {
+                continue;
+            }
+            let expected = path.to_str().and_then(|path| last_recorded.get(path));
+            let unchanged = match expected {
+                Some(expected) => self.state_matches(&path, &current, expected)?,
+                None => false,
+            };
+            if !force && !unchanged {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!(
+                        "refusing to restore changed path without --force: {}",
+                        path.display()
+                    ),
+                ));
+            }
+            pending.push((path, target, current));
+        }
+
+        let mut recorder = HistoryRecorder::with_store(
+            self.clone(),
+            origin.to_string(),
+            now_millis(),
+            unique_event_id(),
+        );
+        let mut restored = Vec::new();
+        for (path, target, current) in pending {
+            self.restore_path(&path, &target)?;
+            let restored_state = self.capture_path(&path)?;
+            recorder.record_change(&path, current, restored_state);
+            restored.push(path);
+        }
+        Ok(RestoreReport {
+            restored,
+            event_id: recorder.finish()?,
+        })
+    }
+
     fn restore_path(&self, path: &Path, state: &EntityRef) -> io::Result<()> {
         match state.kind {
             EntityKind::Missing => remove_path_if_exists(path),
@@ -299,8 +1632,9 @@ impl HistoryStore {
                         "missing dir blob reference",
                     ));
                 };
-                let snapshot = self.read_blob(blob_id)?;
-                let entries = decode_dir_snapshot(&snapshot)?;
+                let packs = self.load_packs()?;
+                let snapshot = self.read_blob_with(&packs, blob_id)?;
+                let entries = self.load_dir_snapshot(&packs, &snapshot)?;
                 restore_directory_snapshot(path, &entries)
             }
         }
@@ -318,62 +1652,59 @@ impl HistoryStore {
         if current.hash == expected.hash {
             return Ok(true);
         }
+        // Events recorded before SHA-256 naming name the same bytes differently.
+        if let (Some(current_blob), Some(expected_blob)) =
+            (current.blob.as_deref(), expected.blob.as_deref())
+        {
+            if is_legacy_blob_id(expected_blob)
+                && self.read_blob(current_blob)? == self.read_blob(expected_blob)?
+            {
+                return Ok(true);
+            }
+        }
         if expected.kind != EntityKind::Dir {
             return Ok(false);
         }
         let Some(blob_id) = expected.blob.as_deref() else {
             return Ok(false);
         };
-        let expected_snapshot = self.read_blob(blob_id)?;
-        if !expected_snapshot.starts_with(DIR_SNAPSHOT_MAGIC_V1) {
+        let packs = self.load_packs()?;
+        let expected_snapshot = self.read_blob_with(&packs, blob_id)?;
+        if expected_snapshot.starts_with(DIR_SNAPSHOT_MAGIC_V1) {
+            return Ok(encode_dir_snapshot_v1(path)? == expected_snapshot);
+        }
+        // `RLYD2` snapshots inline their files, so the same tree hashes
+        // differently from the manifest a capture records now.
+        if !expected_snapshot.starts_with(DIR_SNAPSHOT_MAGIC_V2) {
             return Ok(false);
         }
-        Ok(encode_dir_snapshot_v1(path)? == expected_snapshot)
+        let Some(Entity::Dir(current_entries)) = read_entity(path)? else {
+            return Ok(false);
+        };
+        Ok(self.load_dir_snapshot(&packs, &expected_snapshot)? == current_entries)
     }
 
     pub(crate) fn capture_path(&self, path: &Path) -> io::Result<EntityState> {
-        if path.to_str().is_none() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("history path is not valid UTF-8: {}", path.display()),
-            ));
-        }
-        let metadata = match fs::symlink_metadata(path) {
-            Ok(metadata) => metadata,
-            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(EntityState::missing()),
-            Err(err) => return Err(err),
+        let Some(entity) = read_entity(path)? else {
+            return Ok(EntityState::missing());
         };
-        if metadata.is_file() {
-            let bytes = fs::read(path)?;
-            let blob = self.store_blob(&bytes)?;
-            return Ok(EntityState {
-                kind: EntityKind::File,
-                hash: Some(blob.clone()),
-                blob: Some(blob),
-            });
-        }
-        if metadata.is_dir() {
-            let snapshot = encode_dir_snapshot(path)?;
-            let blob = self.store_blob(&snapshot)?;
-            return Ok(EntityState {
-                kind: EntityKind::Dir,
-                hash: Some(blob.clone()),
-                blob: Some(blob),
-            });
-        }
-        if metadata.file_type().is_symlink() {
-            let target_meta = fs::metadata(path);
-            if target_meta.as_ref().is_ok_and(|meta| meta.is_file()) {
-                let bytes = fs::read(path)?;
-                let blob = self.store_blob(&bytes)?;
-                return Ok(EntityState {
-                    kind: EntityKind::File,
-                    hash: Some(blob.clone()),
-                    blob: Some(blob),
-                });
-            }
-        }
-        Ok(EntityState::missing())
+        let mut packs = self.load_packs()?;
+        let now = SystemTime::now();
+        let (kind, bytes) = match entity {
+            Entity::File(bytes) => (EntityKind::File, bytes),
+            Entity::Dir(entries) => (
+                EntityKind::Dir,
+                encode_dir_manifest(&entries, |contents| {
+                    self.store_blob_with(&mut packs, contents, now)
+                })?,
+            ),
+        };
+        let blob = self.store_blob_with(&mut packs, &bytes, now)?;
+        Ok(EntityState {
+            kind,
+            hash: Some(blob.clone()),
+            blob: Some(blob),
+        })
     }
 
     pub(crate) fn snapshot_file_bytes(&self, contents: &[u8]) -> io::Result<EntityState> {
@@ -409,6 +1740,8 @@ impl HistoryRecorder {
                 id,
                 timestamp_ms,
                 origin,
+                context: Some(EventContext::current()),
+                conflicts: Vec::new(),
                 writes: Vec::new(),
             },
         }
@@ -430,9 +1763,47 @@ impl HistoryRecorder {
             path: path.to_string_lossy().to_string(),
             before: before.as_ref(),
             after: after.as_ref(),
+            item: None,
         });
     }
 
+    /// Tags the writes already recorded at `path` with the item they belong to.
+    pub(crate) fn label_item(&mut self, path: &Path, item: &WriteItem) {
+        let path = path.to_string_lossy();
+        for write in &mut self.event.writes {
+            if write.path == path {
+                write.item = Some(item.clone());
+            }
+        }
+    }
+
+    /// Labels every write with the item that produced it and keeps the
+    /// conflicts the sync resolved, along with the policy for each kind.
+    pub(crate) fn describe_sync(
+        &mut self,
+        cfg: &Config,
+        report: &SyncReport,
+        conflicts: &[SyncConflict],
+    ) {
+        for (kind, action) in report.targets() {
+            let item = WriteItem {
+                kind: kind.id().to_string(),
+                name: action.name.clone(),
+                tool: action.tool.clone(),
+            };
+            self.label_item(&action.change.path, &item);
+        }
+        self.event
+            .conflicts
+            .extend(conflicts.iter().map(|conflict| RecordedConflict {
+                kind: conflict.kind.id().to_string(),
+                name: conflict.name.clone(),
+                winner: conflict.winner.clone(),
+                losers: conflict.others.clone(),
+                policy: cfg.conflict_policy.for_kind(conflict.kind).label(),
+            }));
+    }
+
     pub(crate) fn finish(self) -> io::Result<Option<String>> {
         if self.event.writes.is_empty() {
             return Ok(None);
@@ -454,6 +1825,12 @@ impl HistoryRecorder {
                 )),
             };
         }
+        if !self.store.retention.is_unlimited() {
+            // The sync itself succeeded; an over-full history is only logged.
+            if let Err(err) = self.store.prune(false) {
+                crate::logging::debug(&format!("history retention failed: {err}"));
+            }
+        }
         Ok(Some(self.event.id))
     }
 
@@ -474,7 +1851,7 @@ impl HistoryRecorder {
                         .before
                         .blob
                         .as_deref()
-                        .map(|blob| self.store.blob_path(blob).display().to_string())
+                        .map(|blob| self.store.describe_blob_location(blob))
                         .unwrap_or_else(|| "no snapshot blob".to_string());
                     failures.push(format!(
                         "{} from {}: {err}",
@@ -519,13 +1896,192 @@ impl HistoryRecorder {
     }
 }
 
-fn now_millis() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
+fn event_blobs(event: &HistoryEvent) -> impl Iterator<Item = &str> {
+    event.writes.iter().flat_map(|write| {
+        [write.before.blob.as_deref(), write.after.blob.as_deref()]
+            .into_iter()
+            .flatten()
+    })
+}
+
+/// The directory manifests among an event's blobs.
+fn event_manifests(event: &HistoryEvent) -> impl Iterator<Item = &str> {
+    event
+        .writes
+        .iter()
+        .flat_map(|write| [&write.before, &write.after])
+        .filter(|state| state.kind == EntityKind::Dir)
+        .filter_map(|state| state.blob.as_deref())
+}
+
+fn is_legacy_blob_id(blob_id: &str) -> bool {
+    blob_base(blob_id).len() == 16
+}
+
+/// The content hash a blob is named after, without the `-N` suffix
+/// `store_blob` adds when that name already holds other bytes.
+fn blob_base(blob_id: &str) -> &str {
+    blob_id.split_once('-').map_or(blob_id, |(base, _)| base)
+}
+
+/// A hex hash with an optional `-N` suffix, as `store_blob` names blobs.
+fn is_blob_id(blob_id: &str) -> bool {
+    let (base, suffix) = blob_id.split_once('-').unwrap_or((blob_id, "0"));
+    !base.is_empty()
+        && base.bytes().all(|byte| byte.is_ascii_hexdigit())
+        && !suffix.is_empty()
+        && suffix.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+fn read_event_file(path: &Path) -> io::Result<HistoryEvent> {
+    let raw = fs::read_to_string(path)?;
+    toml::from_str(&raw).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid history event in {}: {err}", path.display()),
+        )
+    })
+}
+
+fn index_line(entry: &IndexEntry) -> io::Result<String> {
+    let mut line = serde_json::to_string(entry)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    line.push('\n');
+    Ok(line)
+}
+
+pub(crate) fn now_millis() -> u64 {
+    system_time_millis(SystemTime::now())
+}
+
+fn system_time_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
         .map(|duration| duration.as_millis() as u64)
         .unwrap_or(0)
 }
 
+fn file_len(path: &Path) -> io::Result<u64> {
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+/// A one-byte codec tag, then the bytes: zlib-compressed unless that would
+/// not make them smaller.
+/// `stored` with the header `scan_packs` reads in front of it.
+fn pack_record(blob_id: &str, ms: u64, stored: &[u8]) -> io::Result<Vec<u8>> {
+    let id_len = u16::try_from(blob_id.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "blob id is too long"))?;
+    let mut record = Vec::with_capacity(PACK_ENTRY_MAGIC.len() + 18 + blob_id.len() + stored.len());
+    record.extend_from_slice(PACK_ENTRY_MAGIC);
+    record.extend_from_slice(&id_len.to_be_bytes());
+    record.extend_from_slice(blob_id.as_bytes());
+    record.extend_from_slice(&ms.to_be_bytes());
+    record.extend_from_slice(&(stored.len() as u64).to_be_bytes());
+    record.extend_from_slice(stored);
+    Ok(record)
+}
+
+/// The blob id, packed time, offset and length of the stored bytes of the
+/// entry whose header starts at `start`.
+fn parse_pack_header(bytes: &[u8], start: usize) -> Option<(String, u64, usize, usize)> {
+    let mut pos = start + PACK_ENTRY_MAGIC.len();
+    let mut take = |len: usize| {
+        let field = bytes.get(pos..pos + len)?;
+        pos += len;
+        Some(field)
+    };
+    let id_len = u16::from_be_bytes(take(2)?.try_into().ok()?) as usize;
+    let blob = std::str::from_utf8(take(id_len)?).ok()?.to_string();
+    let ms = u64::from_be_bytes(take(8)?.try_into().ok()?);
+    let len = usize::try_from(u64::from_be_bytes(take(8)?.try_into().ok()?)).ok()?;
+    Some((blob, ms, pos, len))
+}
+
+fn pack_index_line(entry: &PackEntry) -> io::Result<String> {
+    let mut line = serde_json::to_string(entry)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    line.push('\n');
+    Ok(line)
+}
+
+/// Cuts a final line an interrupted append left without its newline, so
+/// the next line starts on its own.
+fn drop_torn_line(file: &mut fs::File) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(());
+    }
+    let mut last = [0u8];
+    file.seek(SeekFrom::Start(len - 1))?;
+    file.read_exact(&mut last)?;
+    if last[0] == b'\n' {
+        return Ok(());
+    }
+    let mut raw = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut raw)?;
+    let keep = raw
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |end| end + 1);
+    file.set_len(keep as u64)
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn compress_blob(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(vec![CODEC_ZLIB], Compression::default());
+    encoder.write_all(bytes)?;
+    let compressed = encoder.finish()?;
+    if compressed.len() <= bytes.len() {
+        return Ok(compressed);
+    }
+    let mut stored = Vec::with_capacity(bytes.len() + 1);
+    stored.push(CODEC_STORED);
+    stored.extend_from_slice(bytes);
+    Ok(stored)
+}
+
+fn decompress_blob(stored: &[u8]) -> io::Result<Vec<u8>> {
+    match stored.split_first() {
+        Some((&CODEC_STORED, bytes)) => Ok(bytes.to_vec()),
+        Some((&CODEC_ZLIB, compressed)) => {
+            let mut bytes = Vec::new();
+            ZlibDecoder::new(compressed).read_to_end(&mut bytes)?;
+            Ok(bytes)
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unknown history blob encoding",
+        )),
+    }
+}
+
+/// Empty when the host name cannot be read.
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer outlives the call and `gethostname` writes at most `len` bytes.
+    let result = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if result != 0 {
+        return String::new();
+    }
+    let len = buf.iter().position(|byte| *byte == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(not(unix))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
 fn unique_event_id() -> String {
     let nanos = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -534,7 +2090,17 @@ fn unique_event_id() -> String {
     format!("{nanos}-{}", std::process::id())
 }
 
+/// Names blobs and fingerprints content: the SHA-256 of `bytes` in hex.
 fn hash_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Blobs written before SHA-256 naming are named by this 64-bit FNV-1a hash.
+/// They stay readable; only `fsck` and state checks need to tell them apart.
+fn legacy_hash_hex(bytes: &[u8]) -> String {
     let mut hash = 0xcbf29ce484222325u64;
     for byte in bytes {
         hash ^= u64::from(*byte);
@@ -543,6 +2109,85 @@ fn hash_hex(bytes: &[u8]) -> String {
     format!("{hash:016x}")
 }
 
+/// Reads what history snapshots for `path`: file bytes or the entries of a
+/// directory tree, or `None` when nothing is there.
+fn read_entity(path: &Path) -> io::Result<Option<Entity>> {
+    if path.to_str().is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("history path is not valid UTF-8: {}", path.display()),
+        ));
+    }
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if metadata.is_file() {
+        return Ok(Some(Entity::File(fs::read(path)?)));
+    }
+    if metadata.is_dir() {
+        return Ok(Some(Entity::Dir(dir_entries(path)?)));
+    }
+    if metadata.file_type().is_symlink() {
+        let target_meta = fs::metadata(path);
+        if target_meta.as_ref().is_ok_and(|meta| meta.is_file()) {
+            return Ok(Some(Entity::File(fs::read(path)?)));
+        }
+    }
+    Ok(None)
+}
+
+/// Identifies what is at `path` with the hash a history event would record,
+/// without storing a blob; `None` when nothing is there.
+pub(crate) fn path_fingerprint(path: &Path) -> io::Result<Option<String>> {
+    let bytes = match read_entity(path)? {
+        None => return Ok(None),
+        Some(Entity::File(bytes)) => bytes,
+        Some(Entity::Dir(entries)) => {
+            encode_dir_manifest(&entries, |contents| Ok(hash_hex(contents)))?
+        }
+    };
+    Ok(Some(hash_hex(&bytes)))
+}
+
+/// Replaces `path` with a directory holding exactly `files`, given as
+/// relative path, mode and contents. Paths are validated like snapshot
+/// entries, so nothing can be written outside `path`.
+pub(crate) fn write_directory<'a>(
+    path: &Path,
+    files: impl IntoIterator<Item = (&'a str, u32, &'a [u8])>,
+) -> io::Result<()> {
+    let root_mode = fs::metadata(path)
+        .ok()
+        .filter(|metadata| metadata.is_dir())
+        .map_or(0o755, |metadata| snapshot_mode(&metadata));
+    let mut entries = vec![DirSnapshotEntry::Directory {
+        rel: String::new(),
+        mode: root_mode,
+    }];
+    let mut directories = std::collections::BTreeSet::new();
+    for (rel, mode, contents) in files {
+        let mut ancestor = Path::new(rel).parent();
+        while let Some(parent) = ancestor.filter(|parent| !parent.as_os_str().is_empty()) {
+            directories.insert(parent.to_string_lossy().into_owned());
+            ancestor = parent.parent();
+        }
+        entries.push(DirSnapshotEntry::File {
+            rel: rel.to_string(),
+            mode,
+            contents: contents.to_vec(),
+        });
+    }
+    entries.extend(
+        directories
+            .into_iter()
+            .map(|rel| DirSnapshotEntry::Directory { rel, mode: 0o755 }),
+    );
+    validate_snapshot_entries(&entries, true)?;
+    restore_directory_snapshot(path, &entries)
+}
+
 fn remove_path_if_exists(path: &Path) -> io::Result<()> {
     crate::path_cleanup::remove_with_owner_access(path)
 }
@@ -646,37 +2291,122 @@ fn materialize_directory_snapshot(root: &Path, entries: &[DirSnapshotEntry]) ->
             set_snapshot_permissions(&root.join(rel), *mode)?;
         }
     }
-    Ok(())
-}
-
-fn unused_sibling_path(path: &Path, role: &str) -> io::Result<PathBuf> {
-    let parent = path.parent().unwrap_or_else(|| Path::new("."));
-    let name = path
-        .file_name()
-        .and_then(|name| name.to_str())
-        .filter(|name| !name.is_empty())
-        .unwrap_or("relay");
-    for attempt in 0..100u32 {
-        let candidate = parent.join(format!(
-            ".{name}.relay-{role}-{}-{attempt}",
-            unique_event_id()
+    Ok(())
+}
+
+fn unused_sibling_path(path: &Path, role: &str) -> io::Result<PathBuf> {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("relay");
+    for attempt in 0..100u32 {
+        let candidate = parent.join(format!(
+            ".{name}.relay-{role}-{}-{attempt}",
+            unique_event_id()
+        ));
+        match fs::symlink_metadata(&candidate) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(candidate),
+            Ok(_) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("could not reserve a restore path beside {}", path.display()),
+    ))
+}
+
+fn dir_entries(root: &Path) -> io::Result<Vec<DirSnapshotEntry>> {
+    let mut entries = Vec::new();
+    collect_dir_entries(root, root, &mut entries)?;
+    entries.sort_by(|a, b| snapshot_entry_rel(a).cmp(snapshot_entry_rel(b)));
+    Ok(entries)
+}
+
+/// Encodes `entries` as an `RLYD3` manifest: the same entries as `RLYD2`,
+/// but each file names the blob `store` puts its contents in, so snapshots
+/// of a directory share the files that did not change.
+fn encode_dir_manifest(
+    entries: &[DirSnapshotEntry],
+    mut store: impl FnMut(&[u8]) -> io::Result<String>,
+) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    out.extend_from_slice(DIR_SNAPSHOT_MAGIC_V3);
+    out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for entry in entries {
+        let (kind, rel, mode, contents) = match entry {
+            DirSnapshotEntry::Directory { rel, mode } => (1u8, rel, mode, None),
+            DirSnapshotEntry::File {
+                rel,
+                mode,
+                contents,
+            } => (2u8, rel, mode, Some(contents)),
+        };
+        out.push(kind);
+        out.extend_from_slice(&(rel.len() as u32).to_le_bytes());
+        out.extend_from_slice(rel.as_bytes());
+        out.extend_from_slice(&mode.to_le_bytes());
+        if let Some(contents) = contents {
+            let blob = store(contents)?;
+            out.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+            out.extend_from_slice(blob.as_bytes());
+        }
+    }
+    Ok(out)
+}
+
+fn decode_dir_manifest(bytes: &[u8]) -> io::Result<Vec<ManifestEntry>> {
+    if !bytes.starts_with(DIR_SNAPSHOT_MAGIC_V3) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid directory manifest header",
+        ));
+    }
+    let mut idx = DIR_SNAPSHOT_MAGIC_V3.len();
+    let count = read_u32(bytes, &mut idx)? as usize;
+    let mut out = Vec::new();
+    for _ in 0..count {
+        let kind = read_u8(bytes, &mut idx)?;
+        let rel = read_string(bytes, &mut idx)?;
+        let mode = read_u32(bytes, &mut idx)?;
+        let blob = match kind {
+            1 => None,
+            2 => {
+                let blob = read_string(bytes, &mut idx)?;
+                // Blob names become paths under `history/blobs`.
+                if !is_blob_id(&blob) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid blob name in directory manifest: {blob}"),
+                    ));
+                }
+                Some(blob)
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid directory snapshot entry kind",
+                ));
+            }
+        };
+        out.push(ManifestEntry { rel, mode, blob });
+    }
+    if idx != bytes.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "directory snapshot has trailing data",
         ));
-        match fs::symlink_metadata(&candidate) {
-            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(candidate),
-            Ok(_) => continue,
-            Err(err) => return Err(err),
-        }
     }
-    Err(io::Error::new(
-        io::ErrorKind::AlreadyExists,
-        format!("could not reserve a restore path beside {}", path.display()),
-    ))
+    Ok(out)
 }
 
+/// The `RLYD2` encoding, which inlines every file. Only older events hold
+/// these; new directory snapshots are `RLYD3` manifests.
+#[cfg(test)]
 fn encode_dir_snapshot(root: &Path) -> io::Result<Vec<u8>> {
-    let mut entries = Vec::new();
-    collect_dir_entries(root, root, &mut entries)?;
-    entries.sort_by(|a, b| snapshot_entry_rel(a).cmp(snapshot_entry_rel(b)));
+    let entries = dir_entries(root)?;
 
     let mut out = Vec::new();
     out.extend_from_slice(DIR_SNAPSHOT_MAGIC_V2);
@@ -932,6 +2662,27 @@ fn decode_dir_snapshot_v1(bytes: &[u8], idx: &mut usize) -> io::Result<Vec<DirSn
     Ok(out)
 }
 
+fn read_string(bytes: &[u8], idx: &mut usize) -> io::Result<String> {
+    let len = read_u32(bytes, idx)? as usize;
+    let end = idx
+        .checked_add(len)
+        .filter(|end| *end <= bytes.len())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "directory snapshot path overflow",
+            )
+        })?;
+    let value = String::from_utf8(bytes[*idx..end].to_vec()).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid utf8 path: {err}"),
+        )
+    })?;
+    *idx = end;
+    Ok(value)
+}
+
 fn read_u8(bytes: &[u8], idx: &mut usize) -> io::Result<u8> {
     if *idx >= bytes.len() {
         return Err(io::Error::new(
@@ -1088,6 +2839,7 @@ fn snapshot_relative_utf8(root: &Path, path: &Path) -> io::Result<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sync::FileChange;
     use tempfile::TempDir;
 
     fn make_config(tmp: &TempDir) -> Config {
@@ -1099,16 +2851,34 @@ mod tests {
             central_skills_dir: tmp.path().join("relay/skills"),
             central_agents_dir: tmp.path().join("relay/agents"),
             central_rules_dir: tmp.path().join("relay/rules"),
+            central_mcp_dir: tmp.path().join("relay/mcp"),
+            central_subagents_dir: tmp.path().join("relay/subagents"),
             claude_dir: tmp.path().join("claude/commands"),
             claude_skills_dir: tmp.path().join("claude/skills"),
+            claude_subagents_dir: tmp.path().join("claude/agents"),
+            claude_agents_file: tmp.path().join("claude/CLAUDE.md"),
             cursor_dir: tmp.path().join("cursor/commands"),
             opencode_commands_dir: tmp.path().join("opencode/commands"),
             opencode_legacy_commands_dir: None,
             opencode_skills_dir: tmp.path().join("opencode/skills"),
             opencode_agents_file: tmp.path().join("opencode/AGENTS.md"),
+            opencode_subagents_dir: tmp.path().join("opencode/agent"),
             codex_skills_dir: tmp.path().join("codex/skills"),
             codex_rules_file: tmp.path().join("codex/rules/default.rules"),
             codex_agents_file: tmp.path().join("codex/AGENTS.md"),
+            gemini_commands_dir: tmp.path().join("gemini/commands"),
+            gemini_skills_dir: tmp.path().join("gemini/skills"),
+            gemini_agents_file: tmp.path().join("gemini/GEMINI.md"),
+            claude_mcp_file: tmp.path().join("claude/.claude.json"),
+            codex_config_file: tmp.path().join("codex/config.toml"),
+            cursor_mcp_file: tmp.path().join("cursor/mcp.json"),
+            opencode_config_file: tmp.path().join("opencode/opencode.json"),
+            providers: std::collections::BTreeMap::new(),
+            project_root: None,
+            three_way_merge: false,
+            preserve_conflicts: false,
+            conflict_policy: Default::default(),
+            history: Default::default(),
         }
     }
 
@@ -1157,6 +2927,636 @@ mod tests {
         Ok(())
     }
 
+    fn record_write(
+        store: &HistoryStore,
+        origin: &str,
+        timestamp_ms: u64,
+        id: &str,
+        path: &Path,
+    ) -> io::Result<()> {
+        let mut recorder = HistoryRecorder::with_store(
+            store.clone(),
+            origin.to_string(),
+            timestamp_ms,
+            id.to_string(),
+        );
+        let before = recorder.capture_path(path)?;
+        fs::write(path, id)?;
+        let after = recorder.capture_path(path)?;
+        recorder.record_change(path, before, after);
+        recorder.finish().map(|_| ())
+    }
+
+    #[test]
+    fn list_matching_filters_by_path_origin_time_and_kind() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let command = cfg.central_dir.join("review.md");
+        let agents = cfg.codex_agents_file.clone();
+        fs::create_dir_all(&cfg.central_dir)?;
+        fs::create_dir_all(agents.parent().unwrap())?;
+        record_write(&store, "sync", 1_000, "1", &command)?;
+        record_write(&store, "watch:claude:review.md", 2_000, "2", &command)?;
+        record_write(&store, "watch:codex:AGENTS.md", 3_000, "3", &agents)?;
+
+        let ids = |filter: &HistoryFilter| -> io::Result<Vec<String>> {
+            Ok(store
+                .list_matching(filter, 10)?
+                .into_iter()
+                .map(|event| event.id)
+                .collect())
+        };
+        let by_name = HistoryFilter {
+            path: Some(Pattern::new("AGENTS.md").unwrap()),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(ids(&by_name)?, ["3"]);
+        assert_eq!(store.list_matching(&by_name, 10)?[0].paths, [agents]);
+        let by_glob = HistoryFilter {
+            path: Some(Pattern::new("*/commands/*.md").unwrap()),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(ids(&by_glob)?, ["1", "2"]);
+        let watch = HistoryFilter {
+            origin: Some("watch:".to_string()),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(ids(&watch)?, ["2", "3"]);
+        let window = HistoryFilter {
+            since_ms: Some(1_500),
+            until_ms: Some(2_500),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(ids(&window)?, ["2"]);
+        let commands = HistoryFilter {
+            kind: Some((SyncItemKind::Command, &cfg)),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(ids(&commands)?, ["1", "2"]);
+        assert_eq!(
+            store
+                .list_matching(&commands, 1)?
+                .into_iter()
+                .map(|event| event.id)
+                .collect::<Vec<_>>(),
+            ["2"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn history_index_is_used_when_consistent_and_rebuilt_otherwise() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let path = tmp.path().join("relay/commands/test.md");
+        fs::create_dir_all(path.parent().unwrap())?;
+        record_write(&store, "sync", 1_000, "1", &path)?;
+        record_write(&store, "sync", 2_000, "2", &path)?;
+        let index = store.index_path();
+        assert_eq!(fs::read_to_string(&index)?.lines().count(), 2);
+
+        // A consistent index is trusted without parsing the event files.
+        let raw =
+            fs::read_to_string(&index)?.replace("\"origin\":\"sync\"", "\"origin\":\"indexed\"");
+        fs::write(&index, raw)?;
+        assert!(store
+            .list_recent(10)?
+            .iter()
+            .all(|event| event.origin == "indexed"));
+
+        // An index missing an event, or one that does not parse, is rebuilt.
+        for broken in ["", "not json\n"] {
+            fs::write(&index, broken)?;
+            let events = store.list_recent(10)?;
+            assert_eq!(events.len(), 2);
+            assert!(events.iter().all(|event| event.origin == "sync"));
+            assert_eq!(fs::read_to_string(&index)?.lines().count(), 2);
+        }
+        fs::remove_file(&index)?;
+        assert_eq!(store.list_recent(10)?.len(), 2);
+        assert!(index.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn latest_and_lookup_read_the_index_and_only_the_requested_event() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let path = tmp.path().join("relay/commands/test.md");
+        fs::create_dir_all(path.parent().unwrap())?;
+        record_write(&store, "sync", 1_000, "1", &path)?;
+        record_write(&store, "sync", 2_000, "2", &path)?;
+
+        // An unparseable event no longer stops lookups of the others.
+        fs::write(store.event_path("1"), "not toml")?;
+        assert_eq!(store.latest_event_id()?.as_deref(), Some("2"));
+        assert_eq!(store.show_event("2")?.summary.id, "2");
+        assert_eq!(store.rollback("2", false)?.restored, 1);
+        assert!(store.show_event("1").is_err());
+
+        let err = store.show_event("../events/2").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        Ok(())
+    }
+
+    #[test]
+    fn prune_drops_oldest_events_but_keeps_the_latest_write_of_each_path() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let mut cfg = make_config(&tmp);
+        cfg.history.max_events = Some(2);
+        let store = HistoryStore::from_config(&cfg)?;
+        let a = tmp.path().join("relay/commands/a.md");
+        let b = tmp.path().join("relay/commands/b.md");
+        fs::create_dir_all(a.parent().unwrap())?;
+        // Recording through `store` directly skips the automatic prune.
+        let unlimited = HistoryStore {
+            retention: HistoryRetention::default(),
+            ..store.clone()
+        };
+        record_write(&unlimited, "sync", 1_000, "1", &a)?;
+        record_write(&unlimited, "sync", 2_000, "2", &a)?;
+        record_write(&unlimited, "sync", 3_000, "3", &b)?;
+        record_write(&unlimited, "sync", 4_000, "4", &a)?;
+        let first_blob = hash_hex(b"1");
+        assert!(store.has_blob(&first_blob)?);
+
+        let planned = store.prune(true)?;
+        assert_eq!((planned.events, planned.blobs), (2, 1));
+        assert_eq!(store.list_recent(10)?.len(), 4);
+
+        assert_eq!(store.prune(false)?, planned);
+        let ids: Vec<String> = store.list_recent(10)?.into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, ["3", "4"]);
+        assert!(!store.has_blob(&first_blob)?);
+        assert_eq!(store.read_blob(&hash_hex(b"2"))?, b"2");
+        assert_eq!(fs::read_to_string(store.index_path())?.lines().count(), 2);
+        // Only latest writes remain, and age cannot remove those.
+        let by_age = HistoryStore {
+            retention: HistoryRetention {
+                max_age_days: Some(1),
+                ..HistoryRetention::default()
+            },
+            ..store.clone()
+        };
+        assert_eq!(by_age.prune(false)?.events, 0);
+        store.rollback("4", false)?;
+        assert_eq!(fs::read_to_string(&a)?, "2");
+        Ok(())
+    }
+
+    #[test]
+    fn prune_reads_only_the_events_it_deletes() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let mut cfg = make_config(&tmp);
+        cfg.history.max_events = Some(1);
+        let store = HistoryStore::from_config(&cfg)?;
+        let unlimited = HistoryStore {
+            retention: HistoryRetention::default(),
+            ..store.clone()
+        };
+        let path = tmp.path().join("relay/commands/a.md");
+        fs::create_dir_all(path.parent().unwrap())?;
+        for (ts, id) in [(1_000, "1"), (2_000, "2"), (3_000, "3")] {
+            record_write(&unlimited, "sync", ts, id, &path)?;
+        }
+        // The kept event is never parsed, so damage to it cannot stop prune.
+        let kept = fs::read(store.event_path("3"))?;
+        fs::write(store.event_path("3"), "not toml")?;
+
+        assert_eq!(store.prune(false)?.events, 2);
+        assert_eq!(store.event_ids()?, ["3"]);
+        assert!(!store.has_blob(&hash_hex(b"1"))?);
+
+        // Index lines without blob references are filled in once.
+        fs::write(store.event_path("3"), kept)?;
+        let index = fs::read_to_string(store.index_path())?;
+        let legacy: serde_json::Value = serde_json::from_str(index.trim())?;
+        let mut legacy = legacy.as_object().cloned().unwrap_or_default();
+        legacy.remove("refs");
+        fs::write(
+            store.index_path(),
+            format!("{}\n", serde_json::Value::from(legacy)),
+        )?;
+        assert_eq!(store.prune(false)?.events, 0);
+        assert_eq!(fs::read_to_string(store.index_path())?, index);
+        Ok(())
+    }
+
+    #[test]
+    fn finish_enforces_configured_retention() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let mut cfg = make_config(&tmp);
+        cfg.history.max_bytes = Some(1);
+        let store = HistoryStore::from_config(&cfg)?;
+        let path = tmp.path().join("relay/commands/test.md");
+        fs::create_dir_all(path.parent().unwrap())?;
+        for id in ["1", "2", "3"] {
+            record_write(&store, "sync", 1_000, id, &path)?;
+        }
+
+        let ids: Vec<String> = store.list_recent(10)?.into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, ["3"]);
+        Ok(())
+    }
+
+    #[test]
+    fn gc_sweeps_unreferenced_blobs_older_than_the_grace_period() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let path = tmp.path().join("relay/commands/test.md");
+        fs::create_dir_all(path.parent().unwrap())?;
+        // Packed blobs age by when they were packed, loose ones by mtime.
+        let old = UNIX_EPOCH + std::time::Duration::from_secs(1);
+        let mut packs = store.load_packs()?;
+        let live = store.store_blob_with(&mut packs, b"1", old)?;
+        let stale = store.store_blob_with(&mut packs, b"stale orphan", old)?;
+        let recent = store.store_blob(b"recent orphan")?;
+        let legacy = legacy_hash_hex(b"legacy orphan");
+        fs::write(store.blob_path(&legacy), "legacy orphan")?;
+        crate::sync::test_support::set_mtime(&store.blob_path(&legacy), 1_000)?;
+        record_write(&store, "sync", 1_000, "1", &path)?;
+        let stored = store.stored_blobs(&store.load_packs()?)?;
+
+        let report = store.gc()?;
+
+        assert_eq!(report.blobs, 2);
+        assert_eq!(report.bytes, stored[&stale].size + stored[&legacy].size);
+        assert!(!store.has_blob(&stale)? && !store.has_blob(&legacy)?);
+        // Repacking kept the live and recent blobs readable.
+        assert_eq!(store.read_blob(&live)?, b"1");
+        assert_eq!(store.read_blob(&recent)?, b"recent orphan");
+        Ok(())
+    }
+
+    #[test]
+    fn blobs_are_compressed_and_small_ones_packed() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let big: String = (0..20_000).map(|n| format!("line {n}\n")).collect();
+
+        let big_id = store.store_blob(big.as_bytes())?;
+        let small_id = store.store_blob(b"small")?;
+
+        assert_eq!(big_id, hash_hex(big.as_bytes()));
+        assert!(fs::metadata(store.loose_blob_path(&big_id))?.len() < big.len() as u64 / 2);
+        assert!(!store.blob_path(&small_id).exists());
+        assert!(!store.loose_blob_path(&small_id).exists());
+        let packs = store.load_packs()?;
+        assert!(matches!(
+            store.find_blob(&packs, &small_id)?,
+            Some(BlobLocation::Packed(_))
+        ));
+        assert_eq!(store.read_blob(&big_id)?, big.as_bytes());
+        assert_eq!(store.read_blob(&small_id)?, b"small");
+        assert_eq!(store.store_blob(b"small")?, small_id);
+        assert_eq!(store.load_packs()?.entries.len(), 1);
+        // Bytes zlib cannot shrink are stored as they are.
+        assert_eq!(compress_blob(b"ab")?, [CODEC_STORED, b'a', b'b']);
+        assert_eq!(decompress_blob(&compress_blob(b"ab")?)?, b"ab");
+        Ok(())
+    }
+
+    #[test]
+    fn directory_snapshots_share_unchanged_files_and_rlyd2_events_still_roll_back() -> io::Result<()>
+    {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let skill = tmp.path().join("relay/skills/review");
+        fs::create_dir_all(&skill)?;
+        let asset = "asset line\n".repeat(50_000);
+        fs::write(skill.join("asset.txt"), &asset)?;
+        fs::write(skill.join("SKILL.md"), "v1\n")?;
+
+        let mut recorder =
+            HistoryRecorder::with_store(store.clone(), "sync".to_string(), 1_000, "1".to_string());
+        let before = recorder.capture_path(&skill)?;
+        fs::write(skill.join("SKILL.md"), "v2\n")?;
+        let after = recorder.capture_path(&skill)?;
+        assert_eq!(after.hash.as_deref(), path_fingerprint(&skill)?.as_deref());
+        recorder.record_change(&skill, before, after);
+        recorder.finish()?;
+
+        // Two manifests, two versions of SKILL.md and one copy of the asset.
+        assert_eq!(store.stored_blobs(&store.load_packs()?)?.len(), 5);
+        assert!(store.has_blob(&hash_hex(asset.as_bytes()))?);
+        assert!(store.fsck(false)?.broken.is_empty());
+        store.rollback("1", false)?;
+        assert_eq!(fs::read_to_string(skill.join("SKILL.md"))?, "v1\n");
+        assert_eq!(fs::read_to_string(skill.join("asset.txt"))?, asset);
+
+        fs::write(skill.join("SKILL.md"), "v0\n")?;
+        let v0 = store.store_blob(&encode_dir_snapshot(&skill)?)?;
+        fs::write(skill.join("SKILL.md"), "v1\n")?;
+        let v1 = store.store_blob(&encode_dir_snapshot(&skill)?)?;
+        let dir_state = |blob: String| EntityRef {
+            kind: EntityKind::Dir,
+            hash: Some(blob.clone()),
+            blob: Some(blob),
+        };
+        store.append_event(&HistoryEvent {
+            id: "3".to_string(),
+            timestamp_ms: 3_000,
+            origin: "sync".to_string(),
+            context: None,
+            conflicts: Vec::new(),
+            writes: vec![HistoryWrite {
+                path: skill.to_string_lossy().to_string(),
+                before: dir_state(v0),
+                after: dir_state(v1),
+                item: None,
+            }],
+        })?;
+
+        assert_eq!(store.show_event("3")?.writes.len(), 1);
+        store.rollback("3", false)?;
+        assert_eq!(fs::read_to_string(skill.join("SKILL.md"))?, "v0\n");
+        assert_eq!(fs::read_to_string(skill.join("asset.txt"))?, asset);
+        Ok(())
+    }
+
+    fn record_files(
+        store: &HistoryStore,
+        timestamp_ms: u64,
+        id: &str,
+        files: &[(&Path, &str)],
+    ) -> io::Result<()> {
+        let mut recorder = HistoryRecorder::with_store(
+            store.clone(),
+            "sync".to_string(),
+            timestamp_ms,
+            id.to_string(),
+        );
+        for (path, contents) in files {
+            let before = recorder.capture_path(path)?;
+            fs::write(path, contents)?;
+            let after = recorder.capture_path(path)?;
+            recorder.record_change(path, before, after);
+        }
+        recorder.finish().map(|_| ())
+    }
+
+    #[test]
+    fn restore_from_event_restores_one_path_after_checking_it() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let a = tmp.path().join("relay/commands/a.md");
+        let b = tmp.path().join("relay/commands/b.md");
+        fs::create_dir_all(a.parent().unwrap())?;
+        record_files(&store, 1_000, "1", &[(&a, "a1"), (&b, "b1")])?;
+        record_files(&store, 2_000, "2", &[(&a, "a2"), (&b, "b2")])?;
+
+        let report = store.restore_from_event(&a, "1", false, false)?;
+        assert_eq!(report.restored, std::slice::from_ref(&a));
+        assert!(report.event_id.is_some());
+        assert_eq!(fs::read_to_string(&a)?, "a1");
+        assert_eq!(fs::read_to_string(&b)?, "b2");
+
+        store.restore_from_event(&a, "1", true, false)?;
+        assert!(!a.exists());
+
+        fs::write(&b, "edited")?;
+        let err = store.restore_from_event(&b, "1", false, false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&b)?, "edited");
+        store.restore_from_event(&b, "1", false, true)?;
+        assert_eq!(fs::read_to_string(&b)?, "b1");
+
+        let other = tmp.path().join("relay/commands/other.md");
+        let err = store
+            .restore_from_event(&other, "1", false, false)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        Ok(())
+    }
+
+    #[test]
+    fn restore_at_reconstructs_matching_paths_at_a_moment() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let a = tmp.path().join("relay/commands/a.md");
+        let b = tmp.path().join("relay/commands/b.md");
+        fs::create_dir_all(a.parent().unwrap())?;
+        record_files(&store, 1_000, "1", &[(&a, "a1")])?;
+        record_files(&store, 2_000, "2", &[(&a, "a2"), (&b, "b2")])?;
+        record_files(&store, 3_000, "3", &[(&a, "a3")])?;
+
+        let only_a = HistoryFilter {
+            path: Some(Pattern::new("a.md").unwrap()),
+            ..HistoryFilter::default()
+        };
+        let report = store.restore_at(2_500, &only_a, false)?;
+        assert_eq!(report.restored, std::slice::from_ref(&a));
+        assert_eq!(fs::read_to_string(&a)?, "a2");
+        assert_eq!(fs::read_to_string(&b)?, "b2");
+
+        // `b` did not exist yet at 1500, so it is removed.
+        let report = store.restore_at(1_500, &HistoryFilter::default(), false)?;
+        assert_eq!(report.restored, [a.clone(), b.clone()]);
+        assert_eq!(fs::read_to_string(&a)?, "a1");
+        assert!(!b.exists());
+
+        // Restores are recorded, so the latest state is restorable too.
+        store.restore_at(now_millis(), &HistoryFilter::default(), false)?;
+        assert_eq!(fs::read_to_string(&a)?, "a1");
+        fs::write(&a, "edited")?;
+        let err = store
+            .restore_at(3_500, &HistoryFilter::default(), false)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(!b.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn fsck_reports_broken_events_and_repair_quarantines_them() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let a = tmp.path().join("relay/commands/a.md");
+        let b = tmp.path().join("relay/commands/b.md");
+        let skill = tmp.path().join("relay/skills/review");
+        fs::create_dir_all(a.parent().unwrap())?;
+        record_files(&store, 1_000, "1", &[(&a, "a1")])?;
+
+        let mut recorder =
+            HistoryRecorder::with_store(store.clone(), "sync".to_string(), 2_000, "2".to_string());
+        let before = recorder.capture_path(&skill)?;
+        fs::create_dir_all(&skill)?;
+        fs::write(skill.join("SKILL.md"), "Review\n")?;
+        let after = recorder.capture_path(&skill)?;
+        let snapshot = after.blob.clone().unwrap();
+        recorder.record_change(&skill, before, after);
+        recorder.finish()?;
+        let bad_snapshot = store.store_blob(DIR_SNAPSHOT_MAGIC_V2)?;
+        let event_path = store.event_path("2");
+        let raw = fs::read_to_string(&event_path)?;
+        fs::write(&event_path, raw.replace(&snapshot, &bad_snapshot))?;
+
+        record_files(&store, 3_000, "3", &[(&b, "b1")])?;
+        fs::write(store.blob_path(&hash_hex(b"b1")), "tampered")?;
+        fs::write(store.event_path("4"), "not an event")?;
+
+        let report = store.fsck(false)?;
+        assert_eq!(report.events, 4);
+        let broken: Vec<&str> = report.broken.iter().map(|b| b.id.as_str()).collect();
+        assert_eq!(broken, ["2", "3", "4"]);
+        assert!(report.broken[0]
+            .problem
+            .contains("not a valid directory snapshot"));
+        assert!(report.broken[1].problem.contains("does not match its hash"));
+        assert!(report.broken[2].problem.contains("invalid history event"));
+        // The replaced manifest and the file blob only it listed.
+        let mut orphans = vec![snapshot, hash_hex(b"Review\n")];
+        orphans.sort();
+        assert_eq!(report.orphans, orphans);
+        assert!(report.quarantine.is_none());
+        assert!(store.list_recent(10).is_err());
+
+        let report = store.fsck(true)?;
+        assert_eq!(report.quarantine, Some(store.root.join("quarantine")));
+        assert!(store.root.join("quarantine/4.toml").exists());
+        let ids: Vec<String> = store.list_recent(10)?.into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, ["1"]);
+        store.rollback("1", false)?;
+        assert!(!a.exists());
+
+        let report = store.fsck(false)?;
+        assert!(report.broken.is_empty());
+        assert_eq!(report.events, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn torn_pack_index_line_is_ignored_and_cut_by_the_next_sync() -> io::Result<()> {
+        use crate::sync::{self, ExecutionMode, LogMode};
+        let (_tmp, cfg) = crate::sync::test_support::setup()?;
+        let command = cfg.claude_dir.join("review.md");
+        fs::write(&command, "v1\n")?;
+        sync::sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+        let store = HistoryStore::from_config(&cfg)?;
+        let indexed = store.load_packs()?.entries.len();
+        assert!(indexed > 0);
+        let mut index = fs::OpenOptions::new()
+            .append(true)
+            .open(store.pack_index_path())?;
+        index.write_all(br#"{"blob":"0123","pack":1,"off"#)?;
+        assert_eq!(store.load_packs()?.entries.len(), indexed);
+
+        fs::write(&command, "v2\n")?;
+        let outcome = sync::sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        assert!(outcome.history_event_id.is_some());
+        let raw = fs::read_to_string(store.pack_index_path())?;
+        assert!(raw.ends_with('\n') && !raw.contains("\"off\"{"));
+        assert!(store.load_packs()?.entries.len() > indexed);
+        let report = store.fsck(false)?;
+        assert!(report.broken.is_empty());
+        assert_eq!(report.unindexed, 0);
+        store.rollback(&outcome.history_event_id.unwrap(), false)?;
+        assert_eq!(
+            fs::read_to_string(cfg.central_dir.join("review.md"))?,
+            "v1\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fsck_repair_rebuilds_the_pack_index_from_pack_headers() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let a = tmp.path().join("relay/commands/a.md");
+        let b = tmp.path().join("relay/commands/b.md");
+        fs::create_dir_all(a.parent().unwrap())?;
+        record_files(&store, 1_000, "1", &[(&a, "a1"), (&b, "b1")])?;
+        let orphan = store.store_blob(b"orphan")?;
+        // A pack append that never finished is skipped over.
+        fs::OpenOptions::new()
+            .append(true)
+            .open(store.pack_path(1))?
+            .write_all(&pack_record("feed", 0, &compress_blob(b"torn")?)?[..12])?;
+        let original = store.load_packs()?.entries;
+        store.store_blob(b"after the torn append")?;
+        let original_raw = fs::read_to_string(store.pack_index_path())?;
+        assert_eq!(store.load_packs()?.entries.len(), original.len() + 1);
+
+        fs::write(store.pack_index_path(), "not json\n")?;
+        let err = store.fsck(false).unwrap_err();
+        assert!(err.to_string().contains("--repair"));
+        let report = store.fsck(true)?;
+        assert!(report.rebuilt_pack_index);
+        assert!(report.broken.is_empty());
+        assert_eq!(fs::read_to_string(store.pack_index_path())?, original_raw);
+
+        fs::write(store.pack_index_path(), "")?;
+        let report = store.fsck(false)?;
+        assert_eq!(report.unindexed, original.len() + 1);
+        assert!(!report.rebuilt_pack_index);
+        store.fsck(true)?;
+        assert_eq!(store.read_blob(&orphan)?, b"orphan");
+        store.rollback("1", false)?;
+        assert!(!a.exists() && !b.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn show_event_decodes_file_and_directory_blobs() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let file = tmp.path().join("relay/commands/test.md");
+        let skill = tmp.path().join("relay/skills/review");
+        fs::create_dir_all(skill.join("empty"))?;
+        fs::create_dir_all(file.parent().unwrap())?;
+        fs::write(skill.join("SKILL.md"), "Review")?;
+
+        let mut recorder = HistoryRecorder::new(&cfg, "sync")?;
+        let file_before = recorder.capture_path(&file)?;
+        fs::write(&file, "after")?;
+        let file_after = recorder.capture_path(&file)?;
+        recorder.record_change(&file, file_before, file_after);
+        let skill_before = recorder.capture_path(&skill)?;
+        fs::write(skill.join("notes.md"), "Notes")?;
+        let skill_after = recorder.capture_path(&skill)?;
+        recorder.record_change(&skill, skill_before, skill_after);
+        let event_id = recorder.finish()?.expect("event id");
+
+        let detail = HistoryStore::from_config(&cfg)?.show_event(&event_id)?;
+
+        assert_eq!(detail.summary.id, event_id);
+        assert_eq!(detail.summary.writes, 2);
+        assert_eq!(detail.writes[0].path, file);
+        assert_eq!(detail.writes[0].before, None);
+        assert_eq!(
+            detail.writes[0].after,
+            Some(PlannedContent::File(b"after".to_vec()))
+        );
+        let rels = |content: &Option<PlannedContent>| match content {
+            Some(PlannedContent::Dir(files)) => files
+                .iter()
+                .map(|file| file.rel.clone())
+                .collect::<Vec<_>>(),
+            other => panic!("expected directory, got {other:?}"),
+        };
+        assert_eq!(rels(&detail.writes[1].before), ["SKILL.md"]);
+        assert_eq!(rels(&detail.writes[1].after), ["SKILL.md", "notes.md"]);
+        assert_eq!(
+            HistoryStore::from_config(&cfg)?
+                .show_event("missing")
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+        Ok(())
+    }
+
     #[cfg(unix)]
     #[test]
     fn directory_history_restores_hidden_files_empty_directories_and_modes() -> io::Result<()> {
@@ -1773,6 +4173,61 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn hash_hex_is_sha256() {
+        assert_eq!(
+            hash_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(legacy_hash_hex(b"").len(), 16);
+    }
+
+    #[test]
+    fn fnv_named_blobs_still_verify_and_match_current_files() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        let path = tmp.path().join("relay/commands/review.md");
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, "Review\n")?;
+        store.ensure_dirs()?;
+        let blob = legacy_hash_hex(b"Review\n");
+        fs::write(store.blob_path(&blob), "Review\n")?;
+        let after = EntityRef {
+            kind: EntityKind::File,
+            hash: Some(blob.clone()),
+            blob: Some(blob.clone()),
+        };
+        store.append_event(&HistoryEvent {
+            id: "fnv-event".to_string(),
+            timestamp_ms: 1,
+            origin: "sync".to_string(),
+            context: None,
+            conflicts: Vec::new(),
+            writes: vec![HistoryWrite {
+                path: path.to_string_lossy().to_string(),
+                before: EntityRef {
+                    kind: EntityKind::Missing,
+                    hash: None,
+                    blob: None,
+                },
+                after,
+                item: None,
+            }],
+        })?;
+        assert!(store.fsck(false)?.broken.is_empty());
+
+        let current = store.capture_path(&path)?;
+        assert_ne!(current.hash.as_deref(), Some(blob.as_str()));
+        store.rollback("fnv-event", false)?;
+        assert!(!path.exists());
+
+        fs::write(store.blob_path(&blob), "Tampered\n")?;
+        let report = store.fsck(false)?;
+        assert!(report.broken[0].problem.contains("does not match its hash"));
+        Ok(())
+    }
+
     #[test]
     fn legacy_directory_event_matches_current_files_for_non_force_rollback() -> io::Result<()> {
         let tmp = TempDir::new()?;
@@ -1798,6 +4253,8 @@ mod tests {
             id: event_id.to_string(),
             timestamp_ms: 1,
             origin: "sync".to_string(),
+            context: None,
+            conflicts: Vec::new(),
             writes: vec![HistoryWrite {
                 path: path.to_string_lossy().to_string(),
                 before: EntityRef {
@@ -1806,6 +4263,7 @@ mod tests {
                     blob: Some(before_blob),
                 },
                 after: after.clone(),
+                item: None,
             }],
         })?;
 
@@ -1839,4 +4297,72 @@ mod tests {
         assert_eq!(removed_err.kind(), io::ErrorKind::AlreadyExists);
         Ok(())
     }
+
+    #[test]
+    fn events_record_process_items_and_conflicts_and_old_events_still_read() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let store = HistoryStore::from_config(&cfg)?;
+        store.ensure_dirs()?;
+        fs::write(
+            store.event_path("event-1"),
+            "id = \"event-1\"\ntimestamp_ms = 1\norigin = \"sync\"\n\n[[writes]]\npath = \"/relay/commands/gone.md\"\n\n[writes.before]\nkind = \"missing\"\n\n[writes.after]\nkind = \"missing\"\n",
+        )?;
+
+        let path = tmp.path().join("relay/commands/review.md");
+        fs::create_dir_all(path.parent().unwrap())?;
+        let mut recorder = HistoryRecorder::with_store(
+            store.clone(),
+            "sync".to_string(),
+            2,
+            "event-2".to_string(),
+        );
+        let before = recorder.capture_path(&path)?;
+        fs::write(&path, "Review\n")?;
+        let after = recorder.capture_path(&path)?;
+        recorder.record_change(&path, before, after);
+        let mut report = SyncReport::default();
+        report.commands.record(
+            "review.md",
+            "central",
+            Some(FileChange::write(&path, None, 0)),
+        );
+        let conflict = SyncConflict {
+            kind: SyncItemKind::Command,
+            name: "review.md".to_string(),
+            winner: "claude".to_string(),
+            others: vec!["codex".to_string()],
+        };
+        recorder.describe_sync(&cfg, &report, &[conflict]);
+        recorder.finish()?;
+
+        let events = store.list_recent(10)?;
+        assert_eq!(events.len(), 2);
+        assert!(events[0].context.is_none() && events[0].conflicts.is_empty());
+        let context = events[1].context.as_ref().expect("context");
+        assert_eq!(context.relay_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(context.pid, std::process::id());
+        assert!(!context.argv.is_empty());
+        assert_eq!(
+            events[1].conflicts,
+            vec![RecordedConflict {
+                kind: "command".to_string(),
+                name: "review.md".to_string(),
+                winner: "claude".to_string(),
+                losers: vec!["codex".to_string()],
+                policy: "newest".to_string(),
+            }]
+        );
+
+        assert!(store.show_event("event-1")?.writes[0].item.is_none());
+        assert_eq!(
+            store.show_event("event-2")?.writes[0].item,
+            Some(WriteItem {
+                kind: "command".to_string(),
+                name: "review.md".to_string(),
+                tool: "central".to_string(),
+            })
+        );
+        Ok(())
+    }
 }
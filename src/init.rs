@@ -1,9 +1,11 @@
 use crate::config::{
-    resolve_home_dir, Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_OPENCODE,
+    resolve_home_dir, Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_GEMINI, TOOL_OPENCODE,
 };
 use crate::report::print_sync_summary;
 use crate::sync::{self, LogMode};
-use crate::tools::{tool_detected, tool_expected_paths, TOOL_DEFINITIONS};
+#[cfg(not(any(test, coverage)))]
+use crate::tools::TOOL_DEFINITIONS;
+use crate::tools::{tool_detected, tool_entries, tool_expected_paths};
 use std::io;
 use std::path::{Path, PathBuf};
 
@@ -23,6 +25,8 @@ pub(crate) fn init() -> io::Result<()> {
     let central_skills_dir = defaults.central_skills_dir.clone();
     let central_agents_dir = central_root.join("agents");
     let central_rules_dir = central_root.join("rules");
+    let central_mcp_dir = central_root.join("mcp");
+    let central_subagents_dir = central_root.join("subagents");
     let prompt_tool_base = |tool: &str, label: &str, path: &Path| {
         prompt_base_if_missing(
             enabled_tools.iter().any(|enabled| enabled == tool),
@@ -38,6 +42,11 @@ pub(crate) fn init() -> io::Result<()> {
         "Codex base directory",
         &defaults.codex_agents_file,
     )?;
+    let gemini_base = prompt_tool_base(
+        TOOL_GEMINI,
+        "Gemini base directory",
+        &defaults.gemini_commands_dir,
+    )?;
     let opencode_base = prompt_tool_base(
         TOOL_OPENCODE,
         "OpenCode base directory",
@@ -50,7 +59,22 @@ pub(crate) fn init() -> io::Result<()> {
         &defaults.claude_skills_dir,
         "skills",
     );
+    let claude_subagents_dir = derive_from_base(
+        claude_base.as_deref(),
+        &defaults.claude_subagents_dir,
+        "agents",
+    );
+    let claude_agents_file = derive_from_base(
+        claude_base.as_deref(),
+        &defaults.claude_agents_file,
+        "CLAUDE.md",
+    );
     let cursor_dir = derive_from_base(cursor_base.as_deref(), &defaults.cursor_dir, "commands");
+    let cursor_mcp_file = derive_from_base(
+        cursor_base.as_deref(),
+        &defaults.cursor_mcp_file,
+        "mcp.json",
+    );
     let codex_skills_dir = defaults.codex_skills_dir.clone();
     let codex_rules_file = derive_from_base(
         codex_base.as_deref(),
@@ -62,6 +86,22 @@ pub(crate) fn init() -> io::Result<()> {
         &defaults.codex_agents_file,
         "AGENTS.md",
     );
+    let codex_config_file = derive_from_base(
+        codex_base.as_deref(),
+        &defaults.codex_config_file,
+        "config.toml",
+    );
+    let gemini_commands_dir = derive_from_base(
+        gemini_base.as_deref(),
+        &defaults.gemini_commands_dir,
+        "commands",
+    );
+    let gemini_skills_dir = defaults.gemini_skills_dir.clone();
+    let gemini_agents_file = derive_from_base(
+        gemini_base.as_deref(),
+        &defaults.gemini_agents_file,
+        "GEMINI.md",
+    );
     let oc_dir = derive_from_base(
         opencode_base.as_deref(),
         &defaults.opencode_commands_dir,
@@ -73,6 +113,16 @@ pub(crate) fn init() -> io::Result<()> {
         &defaults.opencode_agents_file,
         "AGENTS.md",
     );
+    let oc_subagents_dir = derive_from_base(
+        opencode_base.as_deref(),
+        &defaults.opencode_subagents_dir,
+        "agent",
+    );
+    let oc_config_file = derive_from_base(
+        opencode_base.as_deref(),
+        &defaults.opencode_config_file,
+        "opencode.json",
+    );
 
     let cfg = Config {
         enabled_tools,
@@ -82,16 +132,34 @@ pub(crate) fn init() -> io::Result<()> {
         central_skills_dir,
         central_agents_dir,
         central_rules_dir,
+        central_mcp_dir,
+        central_subagents_dir,
         claude_dir,
         claude_skills_dir,
+        claude_subagents_dir,
+        claude_agents_file,
         cursor_dir,
         opencode_commands_dir: oc_dir,
         opencode_legacy_commands_dir: None,
         opencode_skills_dir: os_dir,
         opencode_agents_file: oa_file,
+        opencode_subagents_dir: oc_subagents_dir,
         codex_skills_dir,
         codex_rules_file,
         codex_agents_file,
+        gemini_commands_dir,
+        gemini_skills_dir,
+        gemini_agents_file,
+        claude_mcp_file: defaults.claude_mcp_file.clone(),
+        codex_config_file,
+        cursor_mcp_file,
+        opencode_config_file: oc_config_file,
+        providers: std::collections::BTreeMap::new(),
+        project_root: None,
+        three_way_merge: false,
+        preserve_conflicts: false,
+        conflict_policy: Default::default(),
+        history: Default::default(),
     };
     let (config_path, report) = {
         let _lock = crate::process_lock::ProcessLock::acquire("init")?;
@@ -179,7 +247,11 @@ fn prompt_yes_no(_label: &str) -> io::Result<bool> {
 
 fn ensure_tool_bases(cfg: &Config) -> io::Result<()> {
     if cfg.tool_enabled(TOOL_CLAUDE) {
-        ensure_existing_paths(&[&cfg.claude_dir, &cfg.claude_skills_dir])?;
+        ensure_existing_paths(&[
+            &cfg.claude_dir,
+            &cfg.claude_skills_dir,
+            &cfg.claude_agents_file,
+        ])?;
     }
     if cfg.tool_enabled(TOOL_CURSOR) {
         ensure_existing_paths(&[&cfg.cursor_dir])?;
@@ -191,6 +263,9 @@ fn ensure_tool_bases(cfg: &Config) -> io::Result<()> {
             &cfg.codex_agents_file,
         ])?;
     }
+    if cfg.tool_enabled(TOOL_GEMINI) {
+        ensure_existing_paths(&[&cfg.gemini_commands_dir, &cfg.gemini_agents_file])?;
+    }
     if cfg.tool_enabled(TOOL_OPENCODE) {
         ensure_existing_paths(&[
             &cfg.opencode_commands_dir,
@@ -442,9 +517,9 @@ fn print_selected_undetected_note(defaults: &Config, selected: &[String]) {
 
 fn print_tool_detection_note(cfg: &Config) {
     let mut notes: Vec<String> = Vec::new();
-    for spec in TOOL_DEFINITIONS {
-        if cfg.tool_enabled(spec.id) && !tool_detected(cfg, spec.id) {
-            if let Some(message) = tool_expected_paths(cfg, spec.id) {
+    for tool in tool_entries(cfg) {
+        if cfg.tool_enabled(tool.id) && !tool_detected(cfg, tool.id) {
+            if let Some(message) = tool_expected_paths(cfg, tool.id) {
                 notes.push(message);
             }
         }
@@ -495,16 +570,34 @@ mod tests {
             central_skills_dir: tmp.path().join("central/skills"),
             central_agents_dir: tmp.path().join("central/agents"),
             central_rules_dir: tmp.path().join("central/rules"),
+            central_mcp_dir: tmp.path().join("central/mcp"),
+            central_subagents_dir: tmp.path().join("central/subagents"),
             claude_dir: tmp.path().join("claude/commands"),
             claude_skills_dir: tmp.path().join("claude/skills"),
+            claude_subagents_dir: tmp.path().join("claude/agents"),
+            claude_agents_file: tmp.path().join("claude/CLAUDE.md"),
             cursor_dir: tmp.path().join("cursor/commands"),
             opencode_commands_dir: tmp.path().join("opencode/commands"),
             opencode_legacy_commands_dir: None,
             opencode_skills_dir: tmp.path().join("opencode/skills"),
             opencode_agents_file: tmp.path().join("opencode/AGENTS.md"),
+            opencode_subagents_dir: tmp.path().join("opencode/agent"),
             codex_skills_dir: tmp.path().join("codex/skills"),
             codex_rules_file: tmp.path().join("codex/rules/default.rules"),
             codex_agents_file: tmp.path().join("codex/AGENTS.md"),
+            gemini_commands_dir: tmp.path().join("gemini/commands"),
+            gemini_skills_dir: tmp.path().join("gemini/skills"),
+            gemini_agents_file: tmp.path().join("gemini/GEMINI.md"),
+            claude_mcp_file: tmp.path().join("claude/.claude.json"),
+            codex_config_file: tmp.path().join("codex/config.toml"),
+            cursor_mcp_file: tmp.path().join("cursor/mcp.json"),
+            opencode_config_file: tmp.path().join("opencode/opencode.json"),
+            providers: std::collections::BTreeMap::new(),
+            project_root: None,
+            three_way_merge: false,
+            preserve_conflicts: false,
+            conflict_policy: Default::default(),
+            history: Default::default(),
         }
     }
 
@@ -1,7 +1,9 @@
 mod atomic;
 mod blacklist;
 mod config;
+mod conflicts;
 mod daemon;
+mod diff;
 mod history;
 mod init;
 mod logging;
@@ -9,12 +11,13 @@ mod markers;
 mod path_cleanup;
 mod process_lock;
 mod report;
+mod saved_plan;
 mod sync;
 mod tools;
 mod versions;
 mod watch;
 
-use clap::{Parser, Subcommand, ValueHint};
+use clap::{ArgGroup, Parser, Subcommand, ValueHint};
 use std::path::PathBuf;
 
 #[cfg(test)]
@@ -52,21 +55,52 @@ enum Commands {
         /// Suppress all output
         #[arg(short = 'q', long, global = true, conflicts_with = "verbose")]
         quiet: bool,
+        /// Print a versioned JSON document of every action instead of a summary
+        #[arg(long, global = true, conflicts_with_all = ["verbose", "quiet"])]
+        json: bool,
         /// Prompt if verified tool versions differ
         #[arg(short = 'c', long, global = true)]
         confirm_versions: bool,
         /// Preview changes without writing files
         #[arg(short = 'p', long, global = true, conflicts_with = "apply")]
         plan: bool,
+        /// Show a unified diff of every planned write (requires --plan)
+        #[arg(long, global = true, requires = "plan", conflicts_with_all = ["quiet", "json"])]
+        diff: bool,
+        /// Save the plan to FILE for a later `relay apply` (requires --plan)
+        #[arg(long, global = true, value_name = "FILE", requires = "plan", value_hint = ValueHint::FilePath)]
+        out: Option<PathBuf>,
         /// Explicitly apply changes (default behavior)
         #[arg(short = 'a', long, global = true, conflicts_with = "plan")]
         apply: bool,
         /// Abort without writing if sync detects conflicts
         #[arg(long, global = true)]
         fail_on_conflict: bool,
+        /// Sync the repository containing DIR (default: current directory)
+        #[arg(
+            long,
+            global = true,
+            value_name = "DIR",
+            num_args = 0..=1,
+            default_missing_value = ".",
+            value_hint = ValueHint::DirPath
+        )]
+        project: Option<PathBuf>,
         #[command(subcommand)]
         scope: Option<SyncScope>,
     },
+    /// Apply a plan saved with `relay sync --plan --out`
+    Apply {
+        /// Plan file to apply
+        #[arg(value_hint = ValueHint::FilePath)]
+        plan: PathBuf,
+        /// Apply even if targets changed since the plan was saved
+        #[arg(short = 'f', long)]
+        force: bool,
+        /// Suppress all output
+        #[arg(short = 'q', long)]
+        quiet: bool,
+    },
     /// Show supported machine-readable capabilities
     Capabilities {
         /// Print the versioned capability document as JSON
@@ -86,19 +120,64 @@ enum Commands {
         /// Prompt if verified tool versions differ
         #[arg(short = 'c', long)]
         confirm_versions: bool,
+        /// Watch the repository containing DIR (default: current directory)
+        #[arg(
+            long,
+            value_name = "DIR",
+            num_args = 0..=1,
+            default_missing_value = ".",
+            conflicts_with = "daemon",
+            value_hint = ValueHint::DirPath
+        )]
+        project: Option<PathBuf>,
     },
     /// Show background service status
-    Status,
+    Status {
+        /// Print the versioned status document as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Manage background watch service (launchd/systemd)
     Daemon {
         #[command(subcommand)]
         command: DaemonCommand,
     },
     /// Show recent sync/watch history events
+    #[command(args_conflicts_with_subcommands = true)]
     History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
         /// Number of events to show
         #[arg(short = 'n', long, default_value = "20")]
         limit: usize,
+        /// Print the versioned history document as JSON
+        #[arg(long)]
+        json: bool,
+        /// Only events that wrote a path matching GLOB (a GLOB without `/` matches file names)
+        #[arg(long, value_name = "GLOB", value_parser = parse_path_glob)]
+        path: Option<glob::Pattern>,
+        /// Only events whose origin starts with PREFIX (e.g. watch:claude)
+        #[arg(long, value_name = "PREFIX")]
+        origin: Option<String>,
+        /// Only events at or after TIME (2h, 2026-10-16, 2026-10-16T14:00:00Z or epoch ms)
+        #[arg(long, value_name = "TIME", value_parser = parse_history_time)]
+        since: Option<u64>,
+        /// Only events at or before TIME
+        #[arg(long, value_name = "TIME", value_parser = parse_history_time)]
+        until: Option<u64>,
+        /// Only events that wrote an item of KIND (command, skill, agent, rule, mcp, subagent)
+        #[arg(long, value_name = "KIND", value_parser = parse_item_kind)]
+        kind: Option<sync::SyncItemKind>,
+        /// Use the history of the repository containing DIR (default: current directory)
+        #[arg(
+            long,
+            global = true,
+            value_name = "DIR",
+            num_args = 0..=1,
+            default_missing_value = ".",
+            value_hint = ValueHint::DirPath
+        )]
+        project: Option<PathBuf>,
     },
     /// Roll back a specific history event
     Rollback {
@@ -110,6 +189,61 @@ enum Commands {
         /// Skip hash safety checks
         #[arg(short = 'f', long)]
         force: bool,
+        /// Roll back in the repository containing DIR (default: current directory)
+        #[arg(
+            long,
+            value_name = "DIR",
+            num_args = 0..=1,
+            default_missing_value = ".",
+            value_hint = ValueHint::DirPath
+        )]
+        project: Option<PathBuf>,
+    },
+    /// Restore single paths from history (--event) or as of a time (--at)
+    #[command(group(ArgGroup::new("source").required(true).args(["event", "at"])))]
+    Restore {
+        /// Path to restore from --event
+        #[arg(requires = "event", value_hint = ValueHint::AnyPath)]
+        path: Option<PathBuf>,
+        /// Event whose write of PATH to restore
+        #[arg(long, value_name = "ID", requires = "path")]
+        event: Option<String>,
+        /// Restore what the event replaced instead of what it wrote
+        #[arg(long, requires = "event")]
+        before: bool,
+        /// Restore every recorded path as it was at TIME (same formats as `history --since`)
+        #[arg(long, value_name = "TIME", value_parser = parse_history_time)]
+        at: Option<u64>,
+        /// With --at, only paths matching GLOB
+        #[arg(long = "path", id = "only", value_name = "GLOB", requires = "at", value_parser = parse_path_glob)]
+        only: Option<glob::Pattern>,
+        /// Skip hash safety checks
+        #[arg(short = 'f', long)]
+        force: bool,
+        /// Restore from the history of the repository containing DIR (default: current directory)
+        #[arg(
+            long,
+            value_name = "DIR",
+            num_args = 0..=1,
+            default_missing_value = ".",
+            value_hint = ValueHint::DirPath
+        )]
+        project: Option<PathBuf>,
+    },
+    /// Inspect and resolve losing copies saved by `preserve_conflicts`
+    Conflicts {
+        #[command(subcommand)]
+        command: ConflictsCommand,
+        /// Use the conflicts of the repository containing DIR (default: current directory)
+        #[arg(
+            long,
+            global = true,
+            value_name = "DIR",
+            num_args = 0..=1,
+            default_missing_value = ".",
+            value_hint = ValueHint::DirPath
+        )]
+        project: Option<PathBuf>,
     },
     /// Exclude an item from syncing to specific tools
     Blacklist {
@@ -124,9 +258,15 @@ enum Commands {
         /// Exclude from Cursor
         #[arg(long)]
         cursor: bool,
+        /// Exclude from Gemini CLI
+        #[arg(long)]
+        gemini: bool,
         /// Exclude from OpenCode
         #[arg(long)]
         opencode: bool,
+        /// Exclude from a tool by id, including `[providers.<id>]` entries (repeatable)
+        #[arg(long = "tool", value_name = "ID")]
+        tool: Vec<String>,
     },
     /// Re-allow a previously blacklisted item for specific tools
     Allow {
@@ -141,9 +281,15 @@ enum Commands {
         /// Allow for Cursor
         #[arg(long)]
         cursor: bool,
+        /// Allow for Gemini CLI
+        #[arg(long)]
+        gemini: bool,
         /// Allow for OpenCode
         #[arg(long)]
         opencode: bool,
+        /// Allow for a tool by id, including `[providers.<id>]` entries (repeatable)
+        #[arg(long = "tool", value_name = "ID")]
+        tool: Vec<String>,
     },
 }
 
@@ -155,6 +301,57 @@ enum SyncScope {
         #[arg(required = true, num_args = 1.., value_hint = ValueHint::AnyPath)]
         paths: Vec<PathBuf>,
     },
+    /// Sync only commands selected by path
+    Command {
+        /// Command markdown files or directories of them
+        #[arg(required = true, num_args = 1.., value_hint = ValueHint::AnyPath)]
+        paths: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Show every path an event wrote, with before/after diffs
+    Show {
+        /// Event id (as listed by `relay history`)
+        event_id: String,
+        /// Print line counts per path instead of diffs
+        #[arg(long)]
+        stat: bool,
+    },
+    /// Delete events beyond the `[history]` retention limits
+    Prune {
+        /// Report what would be deleted without deleting it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete blobs that no remaining event references
+    Gc,
+    /// Check every event and the blobs it references
+    Fsck {
+        /// Move broken events to history/quarantine so the rest stays usable
+        #[arg(long)]
+        repair: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConflictsCommand {
+    /// List items with preserved losing copies
+    List,
+    /// Show the preserved copies of an item
+    Show {
+        /// Item name (e.g. review.md) or <kind>/<name> (e.g. commands/review.md)
+        name: String,
+    },
+    /// Keep one tool's copy and discard the rest
+    Resolve {
+        /// Item name (e.g. review.md) or <kind>/<name> (e.g. commands/review.md)
+        name: String,
+        /// Tool whose copy to keep; a preserved copy is restored to where it came from
+        #[arg(long, value_name = "TOOL")]
+        take: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -277,10 +474,49 @@ fn require_tool_flags(tools: Vec<String>) -> std::io::Result<Vec<String>> {
     }
     Err(std::io::Error::new(
         std::io::ErrorKind::InvalidInput,
-        "at least one tool flag is required (--claude, --codex, --cursor, --opencode)",
+        "at least one tool flag is required (--claude, --codex, --cursor, --gemini, --opencode, --tool <id>)",
     ))
 }
 
+fn parse_path_glob(raw: &str) -> Result<glob::Pattern, String> {
+    glob::Pattern::new(raw).map_err(|err| err.to_string())
+}
+
+fn parse_item_kind(raw: &str) -> Result<sync::SyncItemKind, String> {
+    sync::SyncItemKind::from_id(raw).ok_or_else(|| {
+        let ids: Vec<&str> = sync::SyncItemKind::ALL
+            .iter()
+            .map(|kind| kind.id())
+            .collect();
+        format!("expected one of {}", ids.join(", "))
+    })
+}
+
+/// Accepts a relative age (`2h`, `3days`), a UTC date or time
+/// (`2026-10-16`, `2026-10-16T14:00:00Z`) or epoch milliseconds, as shown by
+/// `relay history`.
+fn parse_history_time(raw: &str) -> Result<u64, String> {
+    if let Ok(ms) = raw.parse::<u64>() {
+        return Ok(ms);
+    }
+    if let Ok(age) = humantime::parse_duration(raw) {
+        return Ok(history::now_millis().saturating_sub(age.as_millis() as u64));
+    }
+    let time = if raw.len() == 10 {
+        format!("{raw}T00:00:00Z")
+    } else {
+        raw.to_string()
+    };
+    humantime::parse_rfc3339_weak(&time)
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|since_epoch| since_epoch.as_millis() as u64)
+        .ok_or_else(|| {
+            "expected an age like 2h, a UTC time like 2026-10-16T14:00:00Z, or epoch milliseconds"
+                .to_string()
+        })
+}
+
 #[cfg_attr(test, allow(dead_code))]
 fn rollback_target_event_id(
     store: &history::HistoryStore,
@@ -330,6 +566,32 @@ fn run_scoped_sync_command(
     })
 }
 
+#[cfg_attr(test, allow(dead_code))]
+fn run_scoped_command_sync(
+    cfg: &config::Config,
+    selected: &[sync::ScopedCommand],
+    log_mode: sync::LogMode,
+    quiet: bool,
+    mode: sync::ExecutionMode,
+    fail_on_conflict: bool,
+) -> std::io::Result<sync::SyncOutcome> {
+    run_sync_command_with(
+        log_mode,
+        quiet,
+        mode,
+        fail_on_conflict,
+        |run_log_mode, run_mode| {
+            sync::sync_scoped_commands_with_mode(
+                cfg,
+                selected,
+                run_log_mode,
+                run_mode,
+                "sync:scoped",
+            )
+        },
+    )
+}
+
 fn run_scoped_sync_command_with<F>(
     log_mode: sync::LogMode,
     quiet: bool,
@@ -346,10 +608,13 @@ where
         if !quiet {
             report::print_scoped_conflict_summary(&outcome.conflicts);
         }
-        Err(std::io::Error::other(format!(
-            "scoped sync aborted due to canonical conflicts ({})",
-            outcome.conflicts.len()
-        )))
+        Err(std::io::Error::other(sync::ConflictAbort {
+            message: format!(
+                "scoped sync aborted due to canonical conflicts ({})",
+                outcome.conflicts.len()
+            ),
+            conflicts: outcome.conflicts,
+        }))
     };
 
     let preflight = reject_conflicts(run_sync(sync::LogMode::Quiet, sync::ExecutionMode::Plan)?)?;
@@ -383,15 +648,18 @@ where
         if !quiet {
             report::print_conflict_summary(&preflight.conflicts);
         }
-        return Err(std::io::Error::other(format!(
-            "sync aborted due to {} conflict{}",
-            preflight.conflicts.len(),
-            if preflight.conflicts.len() == 1 {
-                ""
-            } else {
-                "s"
-            }
-        )));
+        return Err(std::io::Error::other(sync::ConflictAbort {
+            message: format!(
+                "sync aborted due to {} conflict{}",
+                preflight.conflicts.len(),
+                if preflight.conflicts.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                }
+            ),
+            conflicts: preflight.conflicts,
+        }));
     }
 
     if mode == sync::ExecutionMode::Plan {
@@ -424,6 +692,34 @@ where
     run()
 }
 
+#[cfg_attr(test, allow(dead_code))]
+fn with_store_lock<T, F>(cfg: &config::Config, operation: &str, run: F) -> std::io::Result<T>
+where
+    F: FnOnce() -> std::io::Result<T>,
+{
+    let _lock = process_lock::ProcessLock::acquire_for(cfg, operation)?;
+    run()
+}
+
+/// Project runs only borrow tool selection and blacklists from the global
+/// config, so they work before `relay init` and without the uninitialized hint.
+#[cfg_attr(test, allow(dead_code))]
+fn load_project_cfg(dir: &std::path::Path) -> std::io::Result<config::Config> {
+    let root = config::find_project_root(dir)?;
+    let global = load_cfg_with_hint(false, false)?;
+    logging::debug(&format!("project root={}", root.display()));
+    Ok(global.for_project(&root))
+}
+
+/// The store `--project` selects, or the global one.
+#[cfg_attr(test, allow(dead_code))]
+fn load_store_cfg(project: Option<&std::path::Path>) -> std::io::Result<config::Config> {
+    match project {
+        Some(dir) => load_project_cfg(dir),
+        None => load_cfg(true),
+    }
+}
+
 #[cfg(all(not(any(test, coverage)), not(windows)))]
 fn main() -> std::io::Result<()> {
     let Cli {
@@ -441,82 +737,113 @@ fn main() -> std::io::Result<()> {
         Commands::Sync {
             verbose,
             quiet,
+            json,
             confirm_versions,
             plan,
+            diff,
+            out,
             apply: _apply,
             fail_on_conflict,
+            project,
             scope,
         } => {
-            let skill_paths = scope.map(|SyncScope::Skill { paths }| paths);
+            let (scoped_skills, scoped_commands) = match &scope {
+                Some(SyncScope::Skill { paths }) => (paths.len(), 0),
+                Some(SyncScope::Command { paths }) => (0, paths.len()),
+                None => (0, 0),
+            };
             let mode = if plan {
                 sync::ExecutionMode::Plan
             } else {
                 sync::ExecutionMode::Apply
             };
             logging::debug(&format!(
-                "command=sync mode={mode:?} verbose={verbose} quiet={quiet} confirm_versions={confirm_versions} fail_on_conflict={fail_on_conflict} scoped_skills={}",
-                skill_paths.as_ref().map_or(0, Vec::len)
+                "command=sync mode={mode:?} verbose={verbose} quiet={quiet} json={json} confirm_versions={confirm_versions} fail_on_conflict={fail_on_conflict} scoped_skills={scoped_skills} scoped_commands={scoped_commands}"
             ));
             let log_mode = if verbose {
                 sync::LogMode::Actions
             } else {
                 sync::LogMode::Quiet
             };
-            let cfg = load_cfg_with_hint(sync_requires_initialized_config(mode), !quiet)?;
+            // JSON replaces the human summary, so route it through the quiet paths.
+            let quiet = quiet || json;
+            let cfg = match project.as_deref() {
+                Some(dir) => load_project_cfg(dir)?,
+                None => load_cfg_with_hint(sync_requires_initialized_config(mode), !quiet)?,
+            };
             if !confirm_versions_or_continue(&cfg, confirm_versions)? {
                 return Ok(());
             }
-            if let Some(skill_paths) = skill_paths.as_ref() {
-                let run = || {
-                    let selected = sync::discover_scoped_skills(skill_paths)?;
-                    run_scoped_sync_command(&cfg, &selected, log_mode, quiet, mode)
-                };
-                let outcome = if sync_requires_process_lock(mode) {
-                    with_process_lock("sync:scoped", run)?
-                } else {
-                    run()?
-                };
-                logging::debug(&format!(
-                    "sync finished commands={} skills={} agents={} rules={} conflicts={} history_event_id={}",
-                    outcome.report.commands.updated,
-                    outcome.report.skills.updated,
-                    outcome.report.agents.updated,
-                    outcome.report.rules.updated,
-                    outcome.conflicts.len(),
-                    outcome.history_event_id.as_deref().unwrap_or("none")
-                ));
-                if !quiet {
-                    if mode == sync::ExecutionMode::Plan {
-                        report::print_plan_summary(&outcome.report);
-                    } else {
-                        report::print_sync_summary(&outcome.report);
-                        if let Some(event_id) = outcome.history_event_id.as_deref() {
-                            println!("history: recorded event {event_id}");
-                        }
-                    }
+            if let Some(root) = cfg.project_root.as_deref() {
+                if mode == sync::ExecutionMode::Apply {
+                    config::ensure_project_gitignore(root)?;
                 }
-                return Ok(());
             }
-
-            let outcome = if sync_requires_process_lock(mode) {
-                with_process_lock("sync", || {
-                    run_sync_command(&cfg, log_mode, quiet, mode, fail_on_conflict)
-                })?
+            let operation = if scope.is_some() {
+                "sync:scoped"
+            } else {
+                "sync"
+            };
+            let run = || match &scope {
+                Some(SyncScope::Skill { paths }) => {
+                    let selected = sync::discover_scoped_skills(paths)?;
+                    run_scoped_sync_command(&cfg, &selected, log_mode, quiet, mode)
+                }
+                Some(SyncScope::Command { paths }) => {
+                    let selected = sync::discover_scoped_commands(&cfg, paths)?;
+                    run_scoped_command_sync(
+                        &cfg,
+                        &selected,
+                        log_mode,
+                        quiet,
+                        mode,
+                        fail_on_conflict,
+                    )
+                }
+                None => run_sync_command(&cfg, log_mode, quiet, mode, fail_on_conflict),
+            };
+            let result = if sync_requires_process_lock(mode) {
+                with_store_lock(&cfg, operation, run)
             } else {
-                run_sync_command(&cfg, log_mode, quiet, mode, fail_on_conflict)?
+                run()
+            };
+            let outcome = match result {
+                Ok(outcome) => outcome,
+                Err(err) => {
+                    // JSON callers still get a document naming what stopped the run.
+                    if let Some(conflicts) = sync::aborted_conflicts(&err).filter(|_| json) {
+                        println!("{}", report::sync_aborted_json(mode, conflicts));
+                    }
+                    return Err(err);
+                }
             };
             logging::debug(&format!(
-                "sync finished commands={} skills={} agents={} rules={} conflicts={} history_event_id={}",
-                outcome.report.commands.updated,
-                outcome.report.skills.updated,
-                outcome.report.agents.updated,
-                outcome.report.rules.updated,
+                "sync finished commands={} skills={} agents={} rules={} mcp={} subagents={} conflicts={} history_event_id={}",
+                outcome.report.commands.updated(),
+                outcome.report.skills.updated(),
+                outcome.report.agents.updated(),
+                outcome.report.rules.updated(),
+                outcome.report.mcp.updated(),
+                outcome.report.subagents.updated(),
                 outcome.conflicts.len(),
                 outcome.history_event_id.as_deref().unwrap_or("none")
             ));
-            if !quiet {
+            let saved = out
+                .as_deref()
+                .map(|out| saved_plan::save_plan(out, &cfg, &outcome).map(|writes| (out, writes)))
+                .transpose()?;
+            if json {
+                println!("{}", report::sync_json(mode, &outcome));
+            } else if !quiet {
                 if mode == sync::ExecutionMode::Plan {
                     report::print_plan_summary(&outcome.report);
+                    report::print_plan_conflicts(&cfg.conflict_policy, &outcome.conflicts);
+                    if diff {
+                        print!("{}", diff::render_plan_diff(&outcome.report)?);
+                    }
+                    if let Some((out, writes)) = saved {
+                        println!("plan: saved {writes} writes to {}", out.display());
+                    }
                 } else {
                     report::print_sync_summary(&outcome.report);
                     if let Some(event_id) = outcome.history_event_id.as_deref() {
@@ -526,8 +853,32 @@ fn main() -> std::io::Result<()> {
             }
             Ok(())
         }
+        Commands::Apply { plan, force, quiet } => {
+            logging::debug(&format!(
+                "command=apply plan={} force={force} quiet={quiet}",
+                plan.display()
+            ));
+            let saved = saved_plan::SavedPlan::load(&plan)?;
+            let cfg = match saved.project_root() {
+                Some(root) => load_project_cfg(root)?,
+                None => load_cfg_with_hint(true, !quiet)?,
+            };
+            let applied = with_store_lock(&cfg, "apply", || {
+                saved_plan::apply_plan(&cfg, &saved, force)
+            })?;
+            if !quiet {
+                for path in &applied.drifted {
+                    eprintln!("warning: applied over changes to {}", path.display());
+                }
+                println!("apply: wrote {} paths", applied.applied);
+                if let Some(event_id) = applied.history_event_id.as_deref() {
+                    println!("history: recorded event {event_id}");
+                }
+            }
+            Ok(())
+        }
         Commands::Capabilities { json: _ } => {
-            const JSON: &str = r#"{"schema_version":1,"capabilities":{"skills.sync.scoped":1}}"#;
+            const JSON: &str = r#"{"schema_version":1,"capabilities":{"commands.sync.scoped":1,"output.json":1,"skills.sync.scoped":1}}"#;
             logging::debug("command=capabilities json=true");
             println!("{JSON}");
             Ok(())
@@ -537,11 +888,19 @@ fn main() -> std::io::Result<()> {
             quiet,
             daemon,
             confirm_versions,
+            project,
         } => {
             logging::debug(&format!(
-                "command=watch debounce_ms={debounce_ms} quiet={quiet} daemon={daemon} confirm_versions={confirm_versions}"
+                "command=watch debounce_ms={debounce_ms} quiet={quiet} daemon={daemon} confirm_versions={confirm_versions} project={}",
+                project.is_some()
             ));
-            let cfg = load_cfg(true)?;
+            let cfg = match project.as_deref() {
+                Some(dir) => load_project_cfg(dir)?,
+                None => load_cfg(true)?,
+            };
+            if let Some(root) = cfg.project_root.as_deref() {
+                config::ensure_project_gitignore(root)?;
+            }
             if !confirm_versions_or_continue(&cfg, confirm_versions)? {
                 return Ok(());
             }
@@ -562,14 +921,28 @@ fn main() -> std::io::Result<()> {
             } else {
                 sync::LogMode::Actions
             };
-            let _ = with_process_lock("watch-start", || {
+            let started = with_store_lock(&cfg, "watch-start", || {
                 sync::sync_all_with_mode(&cfg, log_mode, sync::ExecutionMode::Apply, "watch-start")
-            })?;
+            });
+            match started {
+                Ok(_) => {}
+                Err(err) if sync::is_conflict_policy_abort(&err) => {
+                    eprintln!("warning: {err}; waiting for the next change");
+                }
+                Err(err) => return Err(err),
+            }
             watch::watch(&cfg, debounce_ms, log_mode)
         }
-        Commands::Status => {
-            logging::debug("command=status");
-            let cfg = load_cfg(true)?;
+        Commands::Status { json } => {
+            logging::debug(&format!("command=status json={json}"));
+            let cfg = load_cfg_with_hint(true, !json)?;
+            if json {
+                let status = daemon::watch_service_status(&cfg)?;
+                let skills = sync::skill_diagnostics(&cfg)?;
+                let doc = report::status_json(&status, &tool_states(&cfg), &skills);
+                println!("{doc}");
+                return Ok(());
+            }
             print_service_status(&cfg)
         }
         Commands::Daemon { command } => {
@@ -621,32 +994,238 @@ fn main() -> std::io::Result<()> {
                 }
             }
         }
-        Commands::History { limit } => {
-            logging::debug(&format!("command=history limit={limit}"));
-            let cfg = load_cfg(true)?;
+        Commands::History {
+            command: Some(HistoryCommand::Show { event_id, stat }),
+            project,
+            ..
+        } => {
+            logging::debug(&format!(
+                "command=history.show event_id={event_id} stat={stat}"
+            ));
+            let cfg = load_store_cfg(project.as_deref())?;
             let store = history::HistoryStore::from_config(&cfg)?;
-            let events = store.list_recent(limit)?;
+            let detail = store.show_event(&event_id)?;
+            print!("{}", diff::render_history_event(&detail, stat));
+            Ok(())
+        }
+        Commands::History {
+            command: Some(HistoryCommand::Prune { dry_run }),
+            project,
+            ..
+        } => {
+            logging::debug(&format!("command=history.prune dry_run={dry_run}"));
+            let cfg = load_store_cfg(project.as_deref())?;
+            let store = history::HistoryStore::from_config(&cfg)?;
+            if dry_run {
+                let report = store.prune(true)?;
+                println!(
+                    "history: would prune {} events and {} blobs ({} bytes)",
+                    report.events, report.blobs, report.bytes
+                );
+                return Ok(());
+            }
+            let report = with_store_lock(&cfg, "history-prune", || store.prune(false))?;
+            println!(
+                "history: pruned {} events and {} blobs ({} bytes)",
+                report.events, report.blobs, report.bytes
+            );
+            Ok(())
+        }
+        Commands::History {
+            command: Some(HistoryCommand::Gc),
+            project,
+            ..
+        } => {
+            logging::debug("command=history.gc");
+            let cfg = load_store_cfg(project.as_deref())?;
+            let store = history::HistoryStore::from_config(&cfg)?;
+            let report = with_store_lock(&cfg, "history-gc", || store.gc())?;
+            println!(
+                "history: removed {} unreferenced blobs ({} bytes)",
+                report.blobs, report.bytes
+            );
+            Ok(())
+        }
+        Commands::History {
+            command: Some(HistoryCommand::Fsck { repair }),
+            project,
+            ..
+        } => {
+            logging::debug(&format!("command=history.fsck repair={repair}"));
+            let cfg = load_store_cfg(project.as_deref())?;
+            let store = history::HistoryStore::from_config(&cfg)?;
+            let report = with_store_lock(&cfg, "history-fsck", || store.fsck(repair))?;
+            println!(
+                "history: checked {} events and {} blobs",
+                report.events, report.blobs
+            );
+            for broken in &report.broken {
+                println!("broken: {}: {}", broken.id, broken.problem);
+            }
+            for blob in &report.orphans {
+                println!("orphan: {blob}");
+            }
+            if report.rebuilt_pack_index {
+                println!(
+                    "history: rebuilt the pack index with {} packed blobs it was missing",
+                    report.unindexed
+                );
+            } else if report.unindexed > 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "history pack index is missing {} packed blobs; run `relay history fsck --repair` to rebuild it",
+                        report.unindexed
+                    ),
+                ));
+            }
+            if let Some(quarantine) = &report.quarantine {
+                println!(
+                    "history: quarantined {} events in {}",
+                    report.broken.len(),
+                    quarantine.display()
+                );
+            } else if !report.broken.is_empty() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "history has {} broken events; run `relay history fsck --repair` to quarantine them",
+                        report.broken.len()
+                    ),
+                ));
+            }
+            Ok(())
+        }
+        Commands::History {
+            command: None,
+            limit,
+            json,
+            path,
+            origin,
+            since,
+            until,
+            kind,
+            project,
+        } => {
+            logging::debug(&format!(
+                "command=history limit={limit} json={json} path={path:?} origin={origin:?} since={since:?} until={until:?} kind={kind:?}"
+            ));
+            let cfg = match project.as_deref() {
+                Some(dir) => load_project_cfg(dir)?,
+                None => load_cfg_with_hint(true, !json)?,
+            };
+            let store = history::HistoryStore::from_config(&cfg)?;
+            let show_paths = path.is_some() || kind.is_some();
+            let filter = history::HistoryFilter {
+                path,
+                origin,
+                since_ms: since,
+                until_ms: until,
+                kind: kind.map(|kind| (kind, &cfg)),
+            };
+            let events = store.list_matching(&filter, limit)?;
+            if json {
+                println!("{}", report::history_json(&events));
+                return Ok(());
+            }
             if events.is_empty() {
                 println!("history: no events");
                 return Ok(());
             }
             for event in events {
-                println!(
-                    "{} ts_ms={} origin={} writes={}",
-                    event.id, event.timestamp_ms, event.origin, event.writes
-                );
+                println!("{}", event.headline());
+                if show_paths {
+                    for path in &event.paths {
+                        println!("  {}", path.display());
+                    }
+                }
             }
             Ok(())
         }
+        Commands::Conflicts { command, project } => {
+            let cfg = load_store_cfg(project.as_deref())?;
+            match command {
+                ConflictsCommand::List => {
+                    logging::debug("command=conflicts.list");
+                    let found = conflicts::list_conflicts(&cfg)?;
+                    if found.is_empty() {
+                        println!("conflicts: none");
+                    }
+                    for (_, manifest) in found {
+                        let tools: Vec<&str> = manifest
+                            .variants
+                            .iter()
+                            .map(|variant| variant.tool.as_str())
+                            .collect();
+                        println!(
+                            "{} winner={} preserved={}",
+                            manifest.key(),
+                            manifest.winner().unwrap_or("unknown"),
+                            tools.join(",")
+                        );
+                    }
+                    Ok(())
+                }
+                ConflictsCommand::Show { name } => {
+                    logging::debug(&format!("command=conflicts.show name={name}"));
+                    let (dir, manifest) = conflicts::find_conflict(&cfg, &name)?;
+                    println!(
+                        "conflict: {} winner={}",
+                        manifest.key(),
+                        manifest.winner().unwrap_or("unknown")
+                    );
+                    for variant in &manifest.variants {
+                        let copy = dir.join(&variant.file);
+                        println!(
+                            "--- {} ts_ms={} source={} copy={}",
+                            variant.tool,
+                            variant.timestamp_ms,
+                            variant.source.display(),
+                            copy.display()
+                        );
+                        if !variant.dir {
+                            let contents = std::fs::read(&copy)?;
+                            print!("{}", String::from_utf8_lossy(&contents));
+                            if !contents.ends_with(b"\n") {
+                                println!();
+                            }
+                        }
+                    }
+                    Ok(())
+                }
+                ConflictsCommand::Resolve { name, take } => {
+                    logging::debug(&format!(
+                        "command=conflicts.resolve name={name} take={take}"
+                    ));
+                    let report = with_store_lock(&cfg, "conflicts-resolve", || {
+                        conflicts::resolve_conflict(&cfg, &name, &take)
+                    })?;
+                    match report.restored.as_deref() {
+                        Some(path) => println!(
+                            "conflicts: restored {take} copy of {} to {}; run `relay sync` to propagate it",
+                            report.key,
+                            path.display()
+                        ),
+                        None => println!("conflicts: kept {take} copy of {}", report.key),
+                    }
+                    if let Some(event_id) = report.history_event_id.as_deref() {
+                        println!("history: recorded event {event_id}");
+                    }
+                    Ok(())
+                }
+            }
+        }
         Commands::Blacklist {
             path,
             claude,
             codex,
             cursor,
+            gemini,
             opencode,
+            tool,
         } => {
             let tools = require_tool_flags(blacklist::collect_tool_flags(
-                claude, codex, cursor, opencode,
+                claude, codex, cursor, gemini, opencode, tool,
             ))?;
             logging::debug(&format!("command=blacklist path={path} tools={tools:?}"));
             with_process_lock("blacklist", || {
@@ -661,10 +1240,12 @@ fn main() -> std::io::Result<()> {
             claude,
             codex,
             cursor,
+            gemini,
             opencode,
+            tool,
         } => {
             let tools = require_tool_flags(blacklist::collect_tool_flags(
-                claude, codex, cursor, opencode,
+                claude, codex, cursor, gemini, opencode, tool,
             ))?;
             logging::debug(&format!("command=allow path={path} tools={tools:?}"));
             with_process_lock("allow", || {
@@ -678,13 +1259,14 @@ fn main() -> std::io::Result<()> {
             event_id,
             latest,
             force,
+            project,
         } => {
             logging::debug(&format!(
                 "command=rollback latest={latest} force={force} event_id={}",
                 event_id.as_deref().unwrap_or("none")
             ));
-            let cfg = load_cfg(true)?;
-            let report = with_process_lock("rollback", || {
+            let cfg = load_store_cfg(project.as_deref())?;
+            let report = with_store_lock(&cfg, "rollback", || {
                 let store = history::HistoryStore::from_config(&cfg)?;
                 let target_event_id = rollback_target_event_id(&store, event_id, latest)?;
                 store.rollback(&target_event_id, force)
@@ -698,6 +1280,44 @@ fn main() -> std::io::Result<()> {
             }
             Ok(())
         }
+        Commands::Restore {
+            path,
+            event,
+            before,
+            at,
+            only,
+            force,
+            project,
+        } => {
+            logging::debug(&format!(
+                "command=restore event={event:?} before={before} at={at:?} only={only:?} force={force}"
+            ));
+            let cfg = load_store_cfg(project.as_deref())?;
+            let report = with_store_lock(&cfg, "restore", || {
+                let store = history::HistoryStore::from_config(&cfg)?;
+                match (path, event, at) {
+                    (Some(path), Some(event), _) => {
+                        store.restore_from_event(&std::path::absolute(path)?, &event, before, force)
+                    }
+                    (_, _, Some(at)) => {
+                        let filter = history::HistoryFilter {
+                            path: only,
+                            ..history::HistoryFilter::default()
+                        };
+                        store.restore_at(at, &filter, force)
+                    }
+                    _ => unreachable!("clap requires --event with a path, or --at"),
+                }
+            })?;
+            println!("restore: restored {} paths", report.restored.len());
+            for path in &report.restored {
+                println!("  {}", path.display());
+            }
+            if let Some(event_id) = report.event_id {
+                println!("history: recorded event {event_id}");
+            }
+            Ok(())
+        }
     }
 }
 
@@ -717,18 +1337,37 @@ fn print_service_status(cfg: &config::Config) -> std::io::Result<()> {
     if let Some(logs_hint) = status.logs_hint.as_ref() {
         println!("status: logs={logs_hint}");
     }
-    for line in sync::skill_diagnostics(cfg)? {
+    for (id, state) in tool_states(cfg) {
+        println!("status: tool.{id}={state}");
+    }
+    for line in sync::skill_diagnostics(cfg)?.lines() {
         println!("{line}");
     }
     Ok(())
 }
 
+#[cfg(all(not(any(test, coverage)), not(windows)))]
+fn tool_states(cfg: &config::Config) -> Vec<(&str, &'static str)> {
+    tools::tool_entries(cfg)
+        .into_iter()
+        .filter(|tool| cfg.tool_enabled(tool.id))
+        .map(|tool| {
+            let state = if tools::tool_detected(cfg, tool.id) {
+                "detected"
+            } else {
+                "missing"
+            };
+            (tool.id, state)
+        })
+        .collect()
+}
+
 #[cfg(any(test, coverage))]
 fn main() {}
 
 #[cfg(test)]
 mod tests {
-    use super::{Cli, Commands, SyncScope};
+    use super::{Cli, Commands, ConflictsCommand, HistoryCommand, SyncScope};
     use crate::history::{HistoryRecorder, HistoryStore};
     use crate::sync;
     use crate::sync::test_support::{setup, write_plain};
@@ -767,6 +1406,198 @@ mod tests {
         super::main();
     }
 
+    #[test]
+    fn cli_parses_project_flags() {
+        let cli = Cli::try_parse_from(["relay", "sync", "--project"]).unwrap();
+        match cli.command {
+            Commands::Sync { project, .. } => assert_eq!(project, Some(PathBuf::from("."))),
+            _ => panic!("expected sync command"),
+        }
+        let cli = Cli::try_parse_from(["relay", "watch", "--project", "repo"]).unwrap();
+        match cli.command {
+            Commands::Watch { project, .. } => assert_eq!(project, Some(PathBuf::from("repo"))),
+            _ => panic!("expected watch command"),
+        }
+        assert!(Cli::try_parse_from(["relay", "watch", "--project", "--daemon"]).is_err());
+        for args in [
+            &["relay", "history", "--project", "repo"][..],
+            &["relay", "history", "show", "evt-1", "--project", "repo"],
+            &["relay", "history", "fsck", "--repair", "--project", "repo"],
+            &["relay", "rollback", "--latest", "--project", "repo"],
+            &["relay", "restore", "--at", "2h", "--project", "repo"],
+            &["relay", "conflicts", "list", "--project", "repo"],
+        ] {
+            let project = match Cli::try_parse_from(args).unwrap().command {
+                Commands::History { project, .. }
+                | Commands::Rollback { project, .. }
+                | Commands::Restore { project, .. }
+                | Commands::Conflicts { project, .. } => project,
+                _ => panic!("unexpected command for {args:?}"),
+            };
+            assert_eq!(project, Some(PathBuf::from("repo")), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn cli_parses_conflicts_resolve() {
+        let cli = Cli::try_parse_from([
+            "relay",
+            "conflicts",
+            "resolve",
+            "review.md",
+            "--take",
+            "cursor",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Conflicts {
+                command: ConflictsCommand::Resolve { name, take },
+                ..
+            } => {
+                assert_eq!(name, "review.md");
+                assert_eq!(take, "cursor");
+            }
+            _ => panic!("expected conflicts resolve command"),
+        }
+        assert!(Cli::try_parse_from(["relay", "conflicts", "resolve", "review.md"]).is_err());
+    }
+
+    #[test]
+    fn cli_parses_history_show_and_keeps_list_flags() {
+        let cli = Cli::try_parse_from(["relay", "history", "show", "evt-1", "--stat"]).unwrap();
+        match cli.command {
+            Commands::History {
+                command: Some(HistoryCommand::Show { event_id, stat }),
+                ..
+            } => {
+                assert_eq!(event_id, "evt-1");
+                assert!(stat);
+            }
+            _ => panic!("expected history show command"),
+        }
+        let cli = Cli::try_parse_from(["relay", "history", "-n", "5"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::History {
+                command: None,
+                limit: 5,
+                ..
+            }
+        ));
+        assert!(Cli::try_parse_from(["relay", "history", "--json", "show", "evt-1"]).is_err());
+    }
+
+    #[test]
+    fn cli_parses_restore_sources() {
+        let cli = Cli::try_parse_from([
+            "relay",
+            "restore",
+            "review.md",
+            "--event",
+            "evt-1",
+            "--before",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Restore {
+                path,
+                event,
+                before,
+                at,
+                ..
+            } => {
+                assert_eq!(path.as_deref(), Some(std::path::Path::new("review.md")));
+                assert_eq!(event.as_deref(), Some("evt-1"));
+                assert!(before);
+                assert_eq!(at, None);
+            }
+            _ => panic!("expected restore command"),
+        }
+        let cli = Cli::try_parse_from([
+            "relay",
+            "restore",
+            "--at",
+            "1791000000000",
+            "--path",
+            "*.md",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Restore { at, only, path, .. } => {
+                assert_eq!(at, Some(1_791_000_000_000));
+                assert_eq!(only.unwrap().as_str(), "*.md");
+                assert_eq!(path, None);
+            }
+            _ => panic!("expected restore command"),
+        }
+        for args in [
+            &["relay", "restore"][..],
+            &["relay", "restore", "review.md"],
+            &["relay", "restore", "--event", "evt-1"],
+            &[
+                "relay",
+                "restore",
+                "review.md",
+                "--event",
+                "evt-1",
+                "--at",
+                "2h",
+            ],
+            &["relay", "restore", "--at", "2h", "--before"],
+        ] {
+            assert!(Cli::try_parse_from(args).is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn cli_parses_history_filters() {
+        let cli = Cli::try_parse_from([
+            "relay",
+            "history",
+            "--path",
+            "*AGENTS.md",
+            "--origin",
+            "watch:",
+            "--since",
+            "2026-10-16",
+            "--until",
+            "1791000000000",
+            "--kind",
+            "agent",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::History {
+                path,
+                origin,
+                since,
+                until,
+                kind,
+                ..
+            } => {
+                assert_eq!(path.unwrap().as_str(), "*AGENTS.md");
+                assert_eq!(origin.as_deref(), Some("watch:"));
+                assert_eq!(since, Some(1_792_108_800_000));
+                assert_eq!(until, Some(1_791_000_000_000));
+                assert_eq!(kind, Some(sync::SyncItemKind::Agent));
+            }
+            _ => panic!("expected history command"),
+        }
+        assert!(Cli::try_parse_from(["relay", "history", "--kind", "widget"]).is_err());
+        assert!(Cli::try_parse_from(["relay", "history", "--since", "yesterday"]).is_err());
+    }
+
+    #[test]
+    fn parse_history_time_accepts_relative_ages_and_utc_times() {
+        let now = crate::history::now_millis();
+        let two_hours_ago = super::parse_history_time("2h").unwrap();
+        assert!(now - two_hours_ago >= 7_200_000 && now - two_hours_ago < 7_260_000);
+        assert_eq!(
+            super::parse_history_time("2026-10-16T14:00:00Z"),
+            Ok(1_792_159_200_000)
+        );
+    }
+
     #[test]
     fn cli_parses_sync_fail_on_conflict() {
         let cli = Cli::try_parse_from(["relay", "sync", "--fail-on-conflict"]).unwrap();
@@ -867,6 +1698,37 @@ mod tests {
         assert!(err.to_string().contains("<PATHS>"));
     }
 
+    #[test]
+    fn cli_parses_scoped_command_sync() {
+        let cli = Cli::try_parse_from([
+            "relay",
+            "sync",
+            "command",
+            "/tmp/review.md",
+            "--fail-on-conflict",
+            "/tmp/commands",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Sync {
+                fail_on_conflict,
+                scope: Some(SyncScope::Command { paths }),
+                ..
+            } => {
+                assert!(fail_on_conflict);
+                assert_eq!(
+                    paths,
+                    [
+                        PathBuf::from("/tmp/review.md"),
+                        PathBuf::from("/tmp/commands")
+                    ]
+                );
+            }
+            _ => panic!("expected scoped command sync"),
+        }
+        assert!(Cli::try_parse_from(["relay", "sync", "command"]).is_err());
+    }
+
     #[test]
     fn cli_parses_capabilities_json() {
         let cli = Cli::try_parse_from(["relay", "capabilities", "--json"]).unwrap();
@@ -938,11 +1800,18 @@ mod tests {
             true,
             |log_mode, mode| {
                 calls.push((log_mode, mode));
+                let mut report = sync::SyncReport::default();
+                report.commands.record(
+                    "review.md",
+                    "claude",
+                    Some(sync::FileChange::write(
+                        std::path::Path::new("review.md"),
+                        None,
+                        1,
+                    )),
+                );
                 Ok(sync::SyncOutcome {
-                    report: sync::SyncReport {
-                        commands: sync::SyncStats { updated: 1 },
-                        ..sync::SyncReport::default()
-                    },
+                    report,
                     conflicts: Vec::new(),
                     history_event_id: None,
                 })
@@ -957,7 +1826,7 @@ mod tests {
                 (sync::LogMode::Actions, sync::ExecutionMode::Plan),
             ]
         );
-        assert_eq!(outcome.report.commands.updated, 1);
+        assert_eq!(outcome.report.commands.updated(), 1);
     }
 
     #[test]
@@ -974,8 +1843,8 @@ mod tests {
                     vec![sync::SyncConflict {
                         kind: sync::SyncItemKind::Skill,
                         name: "raced".to_string(),
-                        winner: "central",
-                        others: vec!["selected input"],
+                        winner: "central".to_string(),
+                        others: vec!["selected input".to_string()],
                     }]
                 } else {
                     Vec::new()
@@ -19,6 +19,13 @@ impl ProcessLock {
         Self::acquire_at(&path, operation, false)
     }
 
+    /// Like [`acquire`](Self::acquire), but uses the lock of `cfg`'s store so
+    /// project syncs only serialize with other runs in the same repository.
+    pub(crate) fn acquire_for(cfg: &Config, operation: &str) -> io::Result<Self> {
+        let path = cfg.store_lock_path()?;
+        Self::acquire_at(&path, operation, false)
+    }
+
     #[cfg(unix)]
     fn acquire_at(path: &Path, operation: &str, nonblocking: bool) -> io::Result<Self> {
         if let Some(parent) = path.parent() {
@@ -1,4 +1,13 @@
-use crate::sync::{SyncConflict, SyncItemKind, SyncReport};
+use crate::config::{ConflictPolicies, ConflictPolicy};
+use crate::daemon::ServiceStatus;
+use crate::history::HistorySummary;
+use crate::sync::{
+    ExecutionMode, SkillDiagnostics, SyncConflict, SyncItemKind, SyncOutcome, SyncReport,
+};
+use serde_json::{json, Value};
+
+/// Bumped whenever a field in the `--json` documents changes meaning or goes away.
+pub(crate) const JSON_SCHEMA_VERSION: u32 = 1;
 
 pub(crate) fn print_sync_summary(report: &SyncReport) {
     if report.is_empty() {
@@ -6,8 +15,13 @@ pub(crate) fn print_sync_summary(report: &SyncReport) {
         return;
     }
     println!(
-        "sync: commands updated={}; skills updated={}; agents updated={}; rules updated={}",
-        report.commands.updated, report.skills.updated, report.agents.updated, report.rules.updated
+        "sync: commands updated={}; skills updated={}; agents updated={}; rules updated={}; mcp updated={}; subagents updated={}",
+        report.commands.updated(),
+        report.skills.updated(),
+        report.agents.updated(),
+        report.rules.updated(),
+        report.mcp.updated(),
+        report.subagents.updated()
     );
 }
 
@@ -17,8 +31,13 @@ pub(crate) fn print_plan_summary(report: &SyncReport) {
         return;
     }
     println!(
-        "plan: commands would_update={}; skills would_update={}; agents would_update={}; rules would_update={}",
-        report.commands.updated, report.skills.updated, report.agents.updated, report.rules.updated
+        "plan: commands would_update={}; skills would_update={}; agents would_update={}; rules would_update={}; mcp would_update={}; subagents would_update={}",
+        report.commands.updated(),
+        report.skills.updated(),
+        report.agents.updated(),
+        report.rules.updated(),
+        report.mcp.updated(),
+        report.subagents.updated()
     );
 }
 
@@ -38,43 +57,216 @@ pub(crate) fn print_scoped_conflict_summary(conflicts: &[SyncConflict]) {
     );
 }
 
+/// Plan output names the policy behind each winner, so a surprising choice
+/// can be traced back to `[conflict_policy]`.
+pub(crate) fn print_plan_conflicts(policies: &ConflictPolicies, conflicts: &[SyncConflict]) {
+    if conflicts.is_empty() {
+        return;
+    }
+    println!("conflicts: {} detected", conflicts.len());
+    for conflict in conflicts {
+        println!(
+            "{}",
+            conflict_line(conflict, Some(policies.for_kind(conflict.kind)))
+        );
+    }
+}
+
+/// Versioned `relay sync --json` document: every planned or applied action
+/// plus the conflicts sync resolved along the way.
+pub(crate) fn sync_json(mode: ExecutionMode, outcome: &SyncOutcome) -> Value {
+    let actions: Vec<Value> = outcome
+        .report
+        .by_kind()
+        .into_iter()
+        .flat_map(|(kind, stats)| {
+            stats.actions.iter().map(move |action| {
+                json!({
+                    "kind": kind.id(),
+                    "name": action.name,
+                    "tool": action.tool,
+                    "path": action.change.path.display().to_string(),
+                    "op": action.change.op.as_str(),
+                    "before": action.change.before.map(hash_id),
+                    "after": action.change.after.map(hash_id),
+                })
+            })
+        })
+        .collect();
+    json!({
+        "schema_version": JSON_SCHEMA_VERSION,
+        "command": "sync",
+        "mode": match mode {
+            ExecutionMode::Plan => "plan",
+            ExecutionMode::Apply => "apply",
+        },
+        "actions": actions,
+        "conflicts": outcome.conflicts.iter().map(conflict_json).collect::<Vec<_>>(),
+        "history_event_id": outcome.history_event_id,
+        "status": "completed",
+    })
+}
+
+/// The `relay sync --json` document for a run that conflicts stopped before
+/// it wrote anything.
+#[cfg_attr(any(test, coverage), allow(dead_code))]
+pub(crate) fn sync_aborted_json(mode: ExecutionMode, conflicts: &[SyncConflict]) -> Value {
+    let mut doc = sync_json(
+        mode,
+        &SyncOutcome {
+            report: SyncReport::default(),
+            conflicts: conflicts.to_vec(),
+            history_event_id: None,
+        },
+    );
+    doc["status"] = json!("aborted");
+    doc
+}
+
+/// Versioned `relay status --json` document. `tools` maps each enabled tool
+/// id to `detected` or `missing`.
+pub(crate) fn status_json(
+    status: &ServiceStatus,
+    tools: &[(&str, &str)],
+    skills: &SkillDiagnostics,
+) -> Value {
+    let tools: serde_json::Map<String, Value> = tools
+        .iter()
+        .map(|(id, state)| (id.to_string(), json!(state)))
+        .collect();
+    let locations: Vec<Value> = skills
+        .locations
+        .iter()
+        .map(|location| {
+            json!({
+                "label": location.label,
+                "role": location.role,
+                "path": location.path.display().to_string(),
+                "count": location.count,
+                "owned": location.owned,
+                "divergent": location.divergent,
+                "collisions": location.collisions,
+            })
+        })
+        .collect();
+    json!({
+        "schema_version": JSON_SCHEMA_VERSION,
+        "command": "status",
+        "service": {
+            "manager": status.manager.as_str(),
+            "name": status.service_name,
+            "state": status.state.as_str(),
+            "service_file": status.paths.service_file.display().to_string(),
+            "log_file": status.paths.log_file.as_ref().map(|path| path.display().to_string()),
+            "logs_hint": status.logs_hint,
+        },
+        "tools": tools,
+        "skills": {
+            "canonical": skills.canonical.display().to_string(),
+            "count": skills.count,
+            "tombstones": skills.tombstones,
+            "locations": locations,
+        },
+    })
+}
+
+/// Versioned `relay history --json` document, newest event first.
+pub(crate) fn history_json(events: &[HistorySummary]) -> Value {
+    let events: Vec<Value> = events
+        .iter()
+        .map(|event| {
+            json!({
+                "id": event.id,
+                "timestamp_ms": event.timestamp_ms,
+                "origin": event.origin,
+                "writes": event.writes,
+                "paths": event.paths,
+                "context": event.context,
+                "conflicts": event.conflicts,
+            })
+        })
+        .collect();
+    json!({
+        "schema_version": JSON_SCHEMA_VERSION,
+        "command": "history",
+        "events": events,
+    })
+}
+
+fn conflict_json(conflict: &SyncConflict) -> Value {
+    json!({
+        "kind": conflict.kind.id(),
+        "name": conflict.name,
+        "winner": conflict.winner,
+        "losers": conflict.others,
+    })
+}
+
+fn hash_id(hash: u64) -> String {
+    format!("{hash:016x}")
+}
+
 fn print_conflict_details(conflicts: &[SyncConflict]) {
     for conflict in conflicts {
-        let kind = match conflict.kind {
-            SyncItemKind::Command => "command",
-            SyncItemKind::Skill => "skill",
-            SyncItemKind::Agent => "agent",
-            SyncItemKind::Rule => "rule",
-        };
-        if conflict.others.is_empty() {
-            println!("  {kind} `{}`: chose `{}`", conflict.name, conflict.winner);
-        } else {
-            println!(
-                "  {kind} `{}`: chose `{}`; also changed in `{}`",
-                conflict.name,
-                conflict.winner,
-                conflict.others.join("`, `")
-            );
-        }
+        println!("{}", conflict_line(conflict, None));
+    }
+}
+
+fn conflict_line(conflict: &SyncConflict, policy: Option<&ConflictPolicy>) -> String {
+    let kind = match conflict.kind {
+        SyncItemKind::Command => "command",
+        SyncItemKind::Skill => "skill",
+        SyncItemKind::Agent => "agent",
+        SyncItemKind::Rule => "rule",
+        SyncItemKind::Mcp => "mcp server",
+        SyncItemKind::Subagent => "subagent",
+    };
+    let choice = match policy {
+        Some(ConflictPolicy::Fail) => "would abort (policy fail)".to_string(),
+        Some(policy) => format!("chose `{}` (policy {})", conflict.winner, policy.label()),
+        None => format!("chose `{}`", conflict.winner),
+    };
+    if conflict.others.is_empty() {
+        format!("  {kind} `{}`: {choice}", conflict.name)
+    } else {
+        format!(
+            "  {kind} `{}`: {choice}; also changed in `{}`",
+            conflict.name,
+            conflict.others.join("`, `")
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::history::{EventContext, RecordedConflict};
     use crate::sync;
+    use std::path::{Path, PathBuf};
 
     #[test]
     fn print_sync_summary_variants() {
         let empty = SyncReport::default();
         print_sync_summary(&empty);
         print_plan_summary(&empty);
-        let report = SyncReport {
-            commands: sync::SyncStats { updated: 1 },
-            skills: sync::SyncStats { updated: 2 },
-            agents: sync::SyncStats { updated: 0 },
-            rules: sync::SyncStats { updated: 3 },
-        };
+        let mut report = SyncReport::default();
+        report.commands.record(
+            "review.md",
+            "claude",
+            Some(sync::FileChange::write(
+                Path::new("/tmp/review.md"),
+                None,
+                7,
+            )),
+        );
+        report.rules.record(
+            "default.rules",
+            "codex",
+            Some(sync::FileChange::delete(
+                Path::new("/tmp/default.rules"),
+                Some(9),
+            )),
+        );
         print_sync_summary(&report);
         print_plan_summary(&report);
     }
@@ -85,17 +277,173 @@ mod tests {
             SyncConflict {
                 kind: SyncItemKind::Command,
                 name: "review.md".to_string(),
-                winner: "cursor",
-                others: vec!["claude"],
+                winner: "cursor".to_string(),
+                others: vec!["claude".to_string()],
             },
             SyncConflict {
                 kind: SyncItemKind::Rule,
                 name: "codex/default.rules".to_string(),
-                winner: "central",
+                winner: "central".to_string(),
                 others: Vec::new(),
             },
         ];
         print_conflict_summary(&conflicts);
         print_scoped_conflict_summary(&conflicts);
     }
+
+    #[test]
+    fn conflict_line_names_the_policy() {
+        let conflict = SyncConflict {
+            kind: SyncItemKind::Agent,
+            name: "AGENTS.md".to_string(),
+            winner: "claude".to_string(),
+            others: vec!["codex".to_string()],
+        };
+        assert_eq!(
+            conflict_line(&conflict, None),
+            "  agent `AGENTS.md`: chose `claude`; also changed in `codex`"
+        );
+        let priority = ConflictPolicy::Priority(vec!["claude".to_string()]);
+        assert_eq!(
+            conflict_line(&conflict, Some(&priority)),
+            "  agent `AGENTS.md`: chose `claude` (policy priority [claude]); also changed in `codex`"
+        );
+        assert_eq!(
+            conflict_line(&conflict, Some(&ConflictPolicy::Fail)),
+            "  agent `AGENTS.md`: would abort (policy fail); also changed in `codex`"
+        );
+        print_plan_conflicts(&ConflictPolicies::default(), &[conflict]);
+    }
+
+    #[test]
+    fn sync_json_lists_actions_and_conflicts() {
+        let mut report = SyncReport::default();
+        report.skills.record(
+            "review",
+            "codex",
+            Some(sync::FileChange::write(
+                Path::new("/tmp/review"),
+                Some(1),
+                255,
+            )),
+        );
+        let outcome = SyncOutcome {
+            report,
+            conflicts: vec![SyncConflict {
+                kind: SyncItemKind::Skill,
+                name: "review".to_string(),
+                winner: "claude".to_string(),
+                others: vec!["codex".to_string()],
+            }],
+            history_event_id: Some("evt-1".to_string()),
+        };
+
+        let doc = sync_json(ExecutionMode::Apply, &outcome);
+
+        assert_eq!(doc["schema_version"], JSON_SCHEMA_VERSION);
+        assert_eq!(doc["mode"], "apply");
+        assert_eq!(
+            doc["actions"],
+            json!([{
+                "kind": "skill",
+                "name": "review",
+                "tool": "codex",
+                "path": "/tmp/review",
+                "op": "update",
+                "before": "0000000000000001",
+                "after": "00000000000000ff",
+            }])
+        );
+        assert_eq!(
+            doc["conflicts"],
+            json!([{"kind": "skill", "name": "review", "winner": "claude", "losers": ["codex"]}])
+        );
+        assert_eq!(doc["history_event_id"], "evt-1");
+        assert_eq!(doc["status"], "completed");
+    }
+
+    #[test]
+    fn sync_aborted_json_keeps_the_conflicts_without_actions() {
+        let conflicts = vec![SyncConflict {
+            kind: SyncItemKind::Command,
+            name: "review.md".to_string(),
+            winner: "claude".to_string(),
+            others: vec!["cursor".to_string()],
+        }];
+
+        let doc = sync_aborted_json(ExecutionMode::Apply, &conflicts);
+
+        assert_eq!(doc["schema_version"], JSON_SCHEMA_VERSION);
+        assert_eq!(doc["status"], "aborted");
+        assert_eq!(doc["actions"], json!([]));
+        assert_eq!(doc["conflicts"][0]["name"], "review.md");
+        assert!(doc["history_event_id"].is_null());
+    }
+
+    #[test]
+    fn history_json_lists_events() {
+        let doc = history_json(&[HistorySummary {
+            id: "evt-2".to_string(),
+            timestamp_ms: 42,
+            origin: "sync".to_string(),
+            writes: 3,
+            paths: vec![PathBuf::from("/relay/commands/review.md")],
+            context: Some(EventContext {
+                relay_version: "0.2.6".to_string(),
+                hostname: "laptop".to_string(),
+                pid: 7,
+                argv: vec!["relay".to_string(), "sync".to_string()],
+            }),
+            conflicts: vec![RecordedConflict {
+                kind: "command".to_string(),
+                name: "review.md".to_string(),
+                winner: "claude".to_string(),
+                losers: vec!["codex".to_string()],
+                policy: "newest".to_string(),
+            }],
+        }]);
+        assert_eq!(doc["command"], "history");
+        assert_eq!(
+            doc["events"],
+            json!([{
+                "id": "evt-2",
+                "timestamp_ms": 42,
+                "origin": "sync",
+                "writes": 3,
+                "paths": ["/relay/commands/review.md"],
+                "context": {"relay_version": "0.2.6", "hostname": "laptop", "pid": 7, "argv": ["relay", "sync"]},
+                "conflicts": [{"kind": "command", "name": "review.md", "winner": "claude", "losers": ["codex"], "policy": "newest"}],
+            }])
+        );
+    }
+
+    #[test]
+    fn status_json_reports_service_tools_and_skills() -> std::io::Result<()> {
+        let (_tmp, cfg) = sync::test_support::setup()?;
+        let status = ServiceStatus {
+            manager: crate::daemon::ServiceManager::SystemdUser,
+            service_name: "relay-watch",
+            state: crate::daemon::ServiceState::Stopped,
+            paths: crate::daemon::ServicePaths {
+                service_file: Path::new("/tmp/relay-watch.service").to_path_buf(),
+                log_file: None,
+            },
+            logs_hint: Some("journalctl --user -u relay-watch".to_string()),
+        };
+        let skills = sync::skill_diagnostics(&cfg)?;
+
+        let doc = status_json(&status, &[("claude", "detected")], &skills);
+
+        assert_eq!(doc["command"], "status");
+        assert_eq!(doc["service"]["manager"], "systemd-user");
+        assert_eq!(doc["service"]["state"], "stopped");
+        assert_eq!(doc["service"]["log_file"], Value::Null);
+        assert_eq!(doc["tools"], json!({"claude": "detected"}));
+        assert_eq!(doc["skills"]["count"], skills.count);
+        assert_eq!(
+            doc["skills"]["locations"].as_array().map(Vec::len),
+            Some(skills.locations.len())
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,510 @@
+use crate::atomic::write_atomic;
+use crate::config::{Config, ConflictPolicy};
+use crate::history::{self, HistoryRecorder, WriteItem};
+use crate::sync::{ChangeOp, FileChange, PlannedContent, SyncOutcome};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PLAN_SCHEMA_VERSION: u32 = 2;
+
+/// A plan written by `relay sync --plan --out` for `relay apply` to replay.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SavedPlan {
+    schema_version: u32,
+    created_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project_root: Option<PathBuf>,
+    writes: Vec<PlannedWrite>,
+    /// Relay's state files as the planned sync would leave them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    state: Vec<StateWrite>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PlannedWrite {
+    kind: String,
+    name: String,
+    tool: String,
+    path: PathBuf,
+    op: WriteOp,
+    /// History fingerprint of what was at `path` when the plan was saved.
+    expected: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content: Option<SavedContent>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StateWrite {
+    path: PathBuf,
+    /// History fingerprint of the state file when the plan was saved.
+    expected: Option<String>,
+    content: Blob,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WriteOp {
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SavedContent {
+    File(Blob),
+    Dir(Vec<SavedFile>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedFile {
+    path: String,
+    mode: u32,
+    #[serde(flatten)]
+    blob: Blob,
+}
+
+/// Text stays readable in the plan for review; anything else is hex.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Blob {
+    Text(String),
+    Hex(String),
+}
+
+#[cfg_attr(any(test, coverage), allow(dead_code))]
+#[derive(Debug, Clone)]
+pub(crate) struct ApplyReport {
+    pub applied: usize,
+    pub drifted: Vec<PathBuf>,
+    pub history_event_id: Option<String>,
+}
+
+impl SavedPlan {
+    pub(crate) fn load(path: &Path) -> io::Result<Self> {
+        let raw = fs::read_to_string(path)?;
+        let plan: Self = serde_json::from_str(&raw).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid plan {}: {err}", path.display()),
+            )
+        })?;
+        if plan.schema_version != PLAN_SCHEMA_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "unsupported plan schema_version {} in {}",
+                    plan.schema_version,
+                    path.display()
+                ),
+            ));
+        }
+        if let Some(path) = plan.paths().find(|path| !path.is_absolute()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("plan target is not an absolute path: {}", path.display()),
+            ));
+        }
+        Ok(plan)
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &Path> {
+        let writes = self.writes.iter().map(|write| write.path.as_path());
+        writes.chain(self.state.iter().map(|write| write.path.as_path()))
+    }
+
+    pub(crate) fn project_root(&self) -> Option<&Path> {
+        self.project_root.as_deref()
+    }
+
+    /// Targets whose current state no longer matches the one the plan saw.
+    pub(crate) fn drifted(&self) -> io::Result<Vec<PathBuf>> {
+        let expected = self.writes.iter().map(|write| &write.expected);
+        let expected = expected.chain(self.state.iter().map(|write| &write.expected));
+        let mut drifted = Vec::new();
+        for (path, expected) in self.paths().zip(expected) {
+            if history::path_fingerprint(path)? != *expected {
+                drifted.push(path.to_path_buf());
+            }
+        }
+        Ok(drifted)
+    }
+}
+
+/// Saves every write in a plan-mode `outcome` to `out`. Conflicts under a
+/// `fail` policy would have stopped an apply, so they stop the save as well.
+pub(crate) fn save_plan(out: &Path, cfg: &Config, outcome: &SyncOutcome) -> io::Result<usize> {
+    let blocked: Vec<String> = outcome
+        .conflicts
+        .iter()
+        .filter(|conflict| cfg.conflict_policy.for_kind(conflict.kind) == &ConflictPolicy::Fail)
+        .map(|conflict| format!("{}/{}", conflict.kind.store_dir(), conflict.name))
+        .collect();
+    if !blocked.is_empty() {
+        return Err(io::Error::other(format!(
+            "not saving plan: conflict policy `fail` blocks {}",
+            blocked.join(", ")
+        )));
+    }
+
+    let mut writes = Vec::new();
+    for (kind, action) in outcome.report.targets() {
+        let change = &action.change;
+        let op = match change.op {
+            ChangeOp::Create => WriteOp::Create,
+            ChangeOp::Update => WriteOp::Update,
+            ChangeOp::Delete => WriteOp::Delete,
+        };
+        if op != WriteOp::Delete && change.content.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("planned write has no content: {}", change.path.display()),
+            ));
+        }
+        writes.push(PlannedWrite {
+            kind: kind.id().to_string(),
+            name: action.name.clone(),
+            tool: action.tool.to_string(),
+            path: change.path.clone(),
+            op,
+            expected: history::path_fingerprint(&change.path)?,
+            content: change.content.as_ref().map(SavedContent::from_planned),
+        });
+    }
+    let state = outcome
+        .report
+        .state_changes()
+        .map(StateWrite::from_planned)
+        .collect::<io::Result<Vec<_>>>()?;
+    let plan = SavedPlan {
+        schema_version: PLAN_SCHEMA_VERSION,
+        created_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0),
+        project_root: cfg.project_root.clone(),
+        writes,
+        state,
+    };
+    let mut raw = serde_json::to_string_pretty(&plan).map_err(io::Error::other)?;
+    raw.push('\n');
+    write_atomic(out, raw.as_bytes())?;
+    Ok(plan.writes.len())
+}
+
+/// Applies `plan` as one history event. Every target must still match the
+/// state the plan recorded unless `force` is set; a failed write reverts the
+/// ones before it. The state files are written last, into the same event.
+pub(crate) fn apply_plan(cfg: &Config, plan: &SavedPlan, force: bool) -> io::Result<ApplyReport> {
+    let drifted = plan.drifted()?;
+    if !drifted.is_empty() && !force {
+        let paths: Vec<String> = drifted
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "refusing to apply stale plan without --force; changed since it was saved: {}",
+                paths.join(", ")
+            ),
+        ));
+    }
+
+    let mut recorder = HistoryRecorder::new(cfg, "apply")?;
+    let applied = plan
+        .writes
+        .iter()
+        .try_for_each(|write| apply_write(write, &mut recorder))
+        .and_then(|()| {
+            plan.state
+                .iter()
+                .try_for_each(|write| apply_state_write(write, &mut recorder))
+        });
+    if let Err(err) = applied {
+        return Err(match recorder.rollback_pending() {
+            Ok(_) => err,
+            Err(rollback_err) => io::Error::new(
+                err.kind(),
+                format!(
+                    "apply failed ({err}) and failed to revert earlier writes ({rollback_err})"
+                ),
+            ),
+        });
+    }
+    Ok(ApplyReport {
+        applied: plan.writes.len(),
+        drifted,
+        history_event_id: recorder.finish()?,
+    })
+}
+
+fn apply_write(write: &PlannedWrite, recorder: &mut HistoryRecorder) -> io::Result<()> {
+    let path = write.path.as_path();
+    let before = recorder.capture_path(path)?;
+    match (&write.content, write.op) {
+        (_, WriteOp::Delete) => crate::path_cleanup::remove_with_owner_access(path)?,
+        (Some(SavedContent::File(blob)), _) => write_atomic(path, &blob.decode()?)?,
+        (Some(SavedContent::Dir(files)), _) => {
+            let decoded = files
+                .iter()
+                .map(|file| Ok((file.path.as_str(), file.mode, file.blob.decode()?)))
+                .collect::<io::Result<Vec<_>>>()?;
+            history::write_directory(
+                path,
+                decoded
+                    .iter()
+                    .map(|(rel, mode, bytes)| (*rel, *mode, bytes.as_slice())),
+            )?;
+        }
+        (None, _) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("planned write has no content: {}", path.display()),
+            ))
+        }
+    }
+    let after = recorder.capture_path(path)?;
+    recorder.record_change(path, before, after);
+    recorder.label_item(
+        path,
+        &WriteItem {
+            kind: write.kind.clone(),
+            name: write.name.clone(),
+            tool: write.tool.clone(),
+        },
+    );
+    Ok(())
+}
+
+fn apply_state_write(write: &StateWrite, recorder: &mut HistoryRecorder) -> io::Result<()> {
+    let path = write.path.as_path();
+    let before = recorder.capture_path(path)?;
+    write_atomic(path, &write.content.decode()?)?;
+    let after = recorder.capture_path(path)?;
+    recorder.record_change(path, before, after);
+    Ok(())
+}
+
+impl StateWrite {
+    fn from_planned(change: &FileChange) -> io::Result<Self> {
+        let Some(PlannedContent::File(bytes)) = &change.content else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("planned state has no content: {}", change.path.display()),
+            ));
+        };
+        Ok(Self {
+            path: change.path.clone(),
+            expected: history::path_fingerprint(&change.path)?,
+            content: Blob::encode(bytes),
+        })
+    }
+}
+
+impl SavedContent {
+    fn from_planned(content: &PlannedContent) -> Self {
+        match content {
+            PlannedContent::File(bytes) => Self::File(Blob::encode(bytes)),
+            PlannedContent::Dir(files) => Self::Dir(
+                files
+                    .iter()
+                    .map(|file| SavedFile {
+                        path: file.rel.clone(),
+                        mode: file.mode,
+                        blob: Blob::encode(&file.bytes),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Blob {
+    fn encode(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Self::Text(text.to_string()),
+            Err(_) => Self::Hex(bytes.iter().map(|byte| format!("{byte:02x}")).collect()),
+        }
+    }
+
+    fn decode(&self) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Text(text) => Ok(text.as_bytes().to_vec()),
+            Self::Hex(hex) => {
+                let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid hex in plan");
+                if hex.len() % 2 != 0 {
+                    return Err(invalid());
+                }
+                (0..hex.len())
+                    .step_by(2)
+                    .map(|idx| {
+                        hex.get(idx..idx + 2)
+                            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                            .ok_or_else(invalid)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryStore;
+    use crate::sync::test_support::{doc, setup, write_plain, write_skill};
+    use crate::sync::{self, ExecutionMode, LogMode};
+
+    fn plan_to(cfg: &Config, out: &Path) -> io::Result<usize> {
+        let outcome = sync::sync_all_with_mode(cfg, LogMode::Quiet, ExecutionMode::Plan, "sync")?;
+        save_plan(out, cfg, &outcome)
+    }
+
+    #[test]
+    fn saved_plan_applies_the_planned_writes_as_one_event() -> io::Result<()> {
+        let (tmp, cfg) = setup()?;
+        write_plain(&cfg.claude_dir.join("review.md"), "Review the diff\n")?;
+        write_skill(
+            &cfg.central_skills_dir,
+            "notes",
+            &doc("notes", "Take notes"),
+        )?;
+        let out = tmp.path().join("plan.json");
+
+        let saved = plan_to(&cfg, &out)?;
+        assert!(saved > 0);
+        assert!(!cfg.central_dir.join("review.md").exists());
+
+        let plan = SavedPlan::load(&out)?;
+        assert_eq!(plan.writes.len(), saved);
+        assert_eq!(plan.project_root(), cfg.project_root.as_deref());
+        let report = apply_plan(&cfg, &plan, false)?;
+
+        assert_eq!(report.applied, saved);
+        assert!(report.drifted.is_empty());
+        assert_eq!(
+            fs::read_to_string(cfg.central_dir.join("review.md"))?,
+            "Review the diff\n"
+        );
+        assert!(cfg.claude_skills_dir.join("notes/SKILL.md").exists());
+        let events = HistoryStore::from_config(&cfg)?.list_recent(1)?;
+        assert_eq!(events[0].origin, "apply");
+        assert_eq!(Some(events[0].id.clone()), report.history_event_id);
+        // The planned writes plus the state files the plan carried.
+        assert_eq!(events[0].writes, saved + plan.state.len());
+        assert!(events[0].paths.contains(&cfg.skill_state_path()?));
+        Ok(())
+    }
+
+    #[test]
+    fn applied_plan_updates_state_so_later_deletions_spread() -> io::Result<()> {
+        let (tmp, cfg) = setup()?;
+        write_plain(&cfg.claude_dir.join("review.md"), "Review the diff\n")?;
+        let out = tmp.path().join("plan.json");
+        plan_to(&cfg, &out)?;
+        let plan = SavedPlan::load(&out)?;
+        assert!(plan
+            .state
+            .iter()
+            .any(|write| write.path == cfg.command_state_path().unwrap()));
+        let report = apply_plan(&cfg, &plan, false)?;
+        assert!(cfg.command_state_path()?.exists());
+        let events = HistoryStore::from_config(&cfg)?.list_recent(1)?;
+        assert_eq!(Some(events[0].id.clone()), report.history_event_id);
+        assert!(events[0].paths.contains(&cfg.command_state_path()?));
+
+        fs::remove_file(cfg.claude_dir.join("review.md"))?;
+        sync::sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        assert!(!cfg.central_dir.join("review.md").exists());
+        assert!(!cfg.cursor_dir.join("review.md").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn applied_plan_leaves_the_state_a_sync_would() -> io::Result<()> {
+        let (tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.claude_dir.join("review.md"),
+            "---\ndescription: Review\n---\nReview the diff\n",
+        )?;
+        write_skill(
+            &cfg.central_skills_dir,
+            "notes",
+            &doc("notes", "Take notes"),
+        )?;
+        write_plain(&cfg.claude_agents_file, "Be brief\n")?;
+        let out = tmp.path().join("plan.json");
+        plan_to(&cfg, &out)?;
+        apply_plan(&cfg, &SavedPlan::load(&out)?, false)?;
+
+        let outcome = sync::sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+        assert!(outcome.report.is_empty());
+        assert!(outcome.report.state_changes().next().is_none());
+        assert!(outcome.history_event_id.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn stale_plan_is_refused_unless_forced() -> io::Result<()> {
+        let (tmp, cfg) = setup()?;
+        write_plain(&cfg.claude_dir.join("review.md"), "Review\n")?;
+        write_plain(&cfg.cursor_dir.join("review.md"), "Review\n")?;
+        let out = tmp.path().join("plan.json");
+        plan_to(&cfg, &out)?;
+        write_plain(&cfg.central_dir.join("review.md"), "Edited meanwhile\n")?;
+
+        let plan = SavedPlan::load(&out)?;
+        let err = apply_plan(&cfg, &plan, false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(err.to_string().contains("review.md"));
+        assert_eq!(
+            fs::read_to_string(cfg.central_dir.join("review.md"))?,
+            "Edited meanwhile\n"
+        );
+
+        let report = apply_plan(&cfg, &plan, true)?;
+        assert_eq!(report.drifted, vec![cfg.central_dir.join("review.md")]);
+        assert_eq!(
+            fs::read_to_string(cfg.central_dir.join("review.md"))?,
+            "Review\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn plan_blobs_round_trip_text_and_binary() -> io::Result<()> {
+        for bytes in [b"text\n".to_vec(), vec![0xff, 0x00, 0x10]] {
+            assert_eq!(Blob::encode(&bytes).decode()?, bytes);
+        }
+        assert!(Blob::Hex("zz".to_string()).decode().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn load_rejects_relative_targets_and_unknown_versions() -> io::Result<()> {
+        let (tmp, _cfg) = setup()?;
+        let out = tmp.path().join("plan.json");
+        fs::write(
+            &out,
+            r#"{"schema_version":2,"created_ms":0,"writes":[{"kind":"command","name":"x","tool":"central","path":"x.md","op":"create","expected":null,"content":{"file":{"text":"x"}}}]}"#,
+        )?;
+        assert!(SavedPlan::load(&out).is_err());
+        // Version 1 plans fingerprinted targets with the old history hash.
+        for version in [1, 3] {
+            fs::write(
+                &out,
+                format!(r#"{{"schema_version":{version},"created_ms":0,"writes":[]}}"#),
+            )?;
+            assert!(SavedPlan::load(&out)
+                .unwrap_err()
+                .to_string()
+                .contains(&format!("schema_version {version}")));
+        }
+        Ok(())
+    }
+}
@@ -1,20 +1,26 @@
+use super::merge::{merge_markdown_variants, MarkdownMerge, MergeBases};
 use super::shared::{
-    conflict_for_variants, log_action, read_markdown_variant, select_markdown_winner,
-    update_markdown_target, MarkdownVariant, TOOL_CENTRAL,
+    conflict_for_variants, log_action, preserve_markdown_conflict, read_markdown_variant,
+    select_markdown_winner, update_markdown_target, MarkdownVariant, SyncedHashes, TOOL_CENTRAL,
 };
 use super::{ExecutionMode, LogMode, SyncConflict, SyncItemKind, SyncStats};
 use crate::blacklist::{
-    CODEX_AGENTS_BLACKLIST_KEY, LEGACY_AGENTS_BLACKLIST_KEY, OPENCODE_AGENTS_BLACKLIST_KEY,
+    CLAUDE_AGENTS_BLACKLIST_KEY, CODEX_AGENTS_BLACKLIST_KEY, GEMINI_AGENTS_BLACKLIST_KEY,
+    LEGACY_AGENTS_BLACKLIST_KEY, OPENCODE_AGENTS_BLACKLIST_KEY,
 };
-use crate::config::{Config, TOOL_CODEX, TOOL_OPENCODE};
+use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_GEMINI, TOOL_OPENCODE};
 use crate::history::HistoryRecorder;
+use crate::tools::provider_entries;
 use std::io;
+use std::path::{Path, PathBuf};
 
 #[cfg(test)]
 use std::fs;
 
 fn is_agent_target_blacklisted(cfg: &Config, tool: &str) -> bool {
     match tool {
+        TOOL_CLAUDE => cfg.is_blacklisted(CLAUDE_AGENTS_BLACKLIST_KEY, TOOL_CLAUDE),
+        TOOL_GEMINI => cfg.is_blacklisted(GEMINI_AGENTS_BLACKLIST_KEY, TOOL_GEMINI),
         TOOL_CODEX => {
             cfg.is_blacklisted(CODEX_AGENTS_BLACKLIST_KEY, TOOL_CODEX)
                 || cfg.is_blacklisted(LEGACY_AGENTS_BLACKLIST_KEY, TOOL_CODEX)
@@ -23,7 +29,14 @@ fn is_agent_target_blacklisted(cfg: &Config, tool: &str) -> bool {
             cfg.is_blacklisted(OPENCODE_AGENTS_BLACKLIST_KEY, TOOL_OPENCODE)
                 || cfg.is_blacklisted(LEGACY_AGENTS_BLACKLIST_KEY, TOOL_OPENCODE)
         }
-        _ => false,
+        provider => provider_entries(cfg)
+            .filter(|entry| entry.id == provider)
+            .filter_map(|entry| {
+                entry
+                    .agents_file
+                    .and_then(|file| entry.central_file_name(file))
+            })
+            .any(|name| cfg.is_blacklisted(&format!("agents/{name}"), provider)),
     }
 }
 
@@ -49,31 +62,81 @@ pub(crate) fn sync_agents_with_mode(
 ) -> io::Result<SyncStats> {
     let mut stats = SyncStats::default();
 
+    let claude_enabled = cfg.tool_enabled(TOOL_CLAUDE)
+        && cfg
+            .claude_agents_file
+            .parent()
+            .is_some_and(|parent| parent.exists());
     let codex_enabled = cfg.tool_enabled(TOOL_CODEX)
         && cfg
             .codex_agents_file
             .parent()
             .is_some_and(|parent| parent.exists());
+    let gemini_enabled = cfg.tool_enabled(TOOL_GEMINI)
+        && cfg
+            .gemini_agents_file
+            .parent()
+            .is_some_and(|parent| parent.exists());
     let opencode_enabled = cfg.tool_enabled(TOOL_OPENCODE)
         && cfg
             .opencode_agents_file
             .parent()
             .is_some_and(|parent| parent.exists());
 
+    let central_claude = cfg.central_agents_dir.join("claude/CLAUDE.md");
     let central_codex = cfg.central_agents_dir.join("codex/AGENTS.md");
+    let central_gemini = cfg.central_agents_dir.join("gemini/GEMINI.md");
     let central_opencode = cfg.central_agents_dir.join("opencode/AGENTS.md");
 
+    // Declared providers keep their central copy at `agents/<id>/<file name>`,
+    // which is also their blacklist key.
+    let mut providers: Vec<(&str, bool, &PathBuf, PathBuf)> = Vec::new();
+    for provider in provider_entries(cfg) {
+        let Some(file) = provider.agents_file else {
+            continue;
+        };
+        let Some(central_name) = provider.central_file_name(file) else {
+            continue;
+        };
+        let enabled = file.parent().is_some_and(|parent| parent.exists());
+        providers.push((
+            provider.id,
+            enabled,
+            file,
+            cfg.central_agents_dir.join(central_name),
+        ));
+    }
+
     let mut agent_variants: Vec<MarkdownVariant> = Vec::new();
+    if claude_enabled && cfg.claude_agents_file.exists() {
+        agent_variants.push(read_markdown_variant(TOOL_CLAUDE, &cfg.claude_agents_file)?);
+    }
     if codex_enabled && cfg.codex_agents_file.exists() {
         agent_variants.push(read_markdown_variant(TOOL_CODEX, &cfg.codex_agents_file)?);
     }
+    if gemini_enabled && cfg.gemini_agents_file.exists() {
+        agent_variants.push(read_markdown_variant(TOOL_GEMINI, &cfg.gemini_agents_file)?);
+    }
     if opencode_enabled && cfg.opencode_agents_file.exists() {
         agent_variants.push(read_markdown_variant(
             TOOL_OPENCODE,
             &cfg.opencode_agents_file,
         )?);
     }
-    for path in [&central_codex, &central_opencode] {
+    for (tool, enabled, file, _) in &providers {
+        if *enabled && file.exists() {
+            agent_variants.push(read_markdown_variant(tool, file)?);
+        }
+    }
+    for path in [
+        &central_claude,
+        &central_codex,
+        &central_gemini,
+        &central_opencode,
+    ]
+    .into_iter()
+    .chain(providers.iter().map(|(_, _, _, central)| central))
+    {
         if path.exists() {
             agent_variants.push(read_markdown_variant(TOOL_CENTRAL, path)?);
         }
@@ -83,54 +146,140 @@ pub(crate) fn sync_agents_with_mode(
         return Ok(stats);
     }
 
-    let winner = select_markdown_winner(&agent_variants);
-    if let Some(conflict) = conflict_for_variants(
-        "AGENTS.md",
+    let state_path = cfg.synced_state_path(SyncItemKind::Agent)?;
+    let previous_state = SyncedHashes::load(&state_path)?;
+    let changed =
+        |variant: &MarkdownVariant| previous_state.changed(&variant.path, variant.doc.body_hash);
+    let policy = cfg.conflict_policy.for_kind(SyncItemKind::Agent);
+    let winner = select_markdown_winner(policy, &agent_variants, changed);
+    let mut base_paths: Vec<&Path> = vec![
+        &central_claude,
+        &central_codex,
+        &central_gemini,
+        &central_opencode,
+    ];
+    base_paths.extend(providers.iter().map(|(_, _, _, central)| central.as_path()));
+    base_paths.extend(agent_variants.iter().map(|variant| variant.path.as_path()));
+    let merged_doc;
+    let source = match merge_markdown_variants(
+        cfg,
+        &MergeBases::new(cfg)?,
         SyncItemKind::Agent,
+        "AGENTS.md",
         &agent_variants,
-        winner.tool,
-        winner.doc.body_hash,
-    ) {
-        conflicts.push(conflict);
-        log_action(
-            log_mode,
-            &format!(
-                "warning: agents edited in multiple tools; last-write-wins chose {}",
-                winner.tool
-            ),
-        );
-    }
-    let source = &winner.doc;
+        winner,
+        &base_paths,
+        log_mode,
+        mode,
+        conflicts,
+    )? {
+        MarkdownMerge::Merged(doc) => {
+            merged_doc = doc;
+            &merged_doc
+        }
+        MarkdownMerge::Conflicted => {
+            if let Some(conflict) = conflicts.last() {
+                preserve_markdown_conflict(
+                    cfg,
+                    conflict,
+                    &agent_variants,
+                    winner.doc.body_hash,
+                    log_mode,
+                    mode,
+                )?;
+            }
+            &winner.doc
+        }
+        MarkdownMerge::NotAttempted => {
+            if let Some(conflict) = conflict_for_variants(
+                "AGENTS.md",
+                SyncItemKind::Agent,
+                &agent_variants,
+                changed,
+                winner.tool,
+                winner.doc.body_hash,
+            ) {
+                preserve_markdown_conflict(
+                    cfg,
+                    &conflict,
+                    &agent_variants,
+                    winner.doc.body_hash,
+                    log_mode,
+                    mode,
+                )?;
+                conflicts.push(conflict);
+                log_action(
+                    log_mode,
+                    &format!(
+                        "warning: agents edited in multiple tools; conflict policy {} chose {}",
+                        policy.label(),
+                        winner.tool
+                    ),
+                );
+            }
+            &winner.doc
+        }
+    };
 
+    let mut state = SyncedHashes::default();
     for (tool, enabled, path) in [
+        (TOOL_CLAUDE, claude_enabled, &cfg.claude_agents_file),
         (TOOL_CODEX, codex_enabled, &cfg.codex_agents_file),
+        (TOOL_GEMINI, gemini_enabled, &cfg.gemini_agents_file),
         (TOOL_OPENCODE, opencode_enabled, &cfg.opencode_agents_file),
+        (TOOL_CENTRAL, true, &central_claude),
         (TOOL_CENTRAL, true, &central_codex),
+        (TOOL_CENTRAL, true, &central_gemini),
         (TOOL_CENTRAL, true, &central_opencode),
-    ] {
-        if !enabled {
-            continue;
-        }
-        if tool != TOOL_CENTRAL && is_agent_target_blacklisted(cfg, tool) {
-            continue;
-        }
+    ]
+    .into_iter()
+    .chain(providers.iter().flat_map(|(tool, enabled, file, central)| {
+        [(*tool, *enabled, *file), (TOOL_CENTRAL, true, central)]
+    })) {
         let existing = agent_variants
             .iter()
             .find(|variant| variant.path == *path)
             .map(|variant| &variant.doc);
+        if !enabled || (tool != TOOL_CENTRAL && is_agent_target_blacklisted(cfg, tool)) {
+            if let Some(doc) = existing {
+                state.insert(path, doc.body_hash);
+            }
+            continue;
+        }
         let label = format!("agents: {}", path.display());
-        let updated = update_markdown_target(
+        let change = update_markdown_target(
             source, existing, path, true, log_mode, mode, history, &label,
         )?;
-        stats.updated += usize::from(updated);
+        state.insert_written_markdown(path, change.as_ref())?;
+        stats.record("AGENTS.md", tool, change);
     }
+    stats.record_state(state.save_if_changed(&previous_state, &state_path, mode, history)?);
 
     Ok(stats)
 }
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sync::test_support::{doc, read_body, read_frontmatter, setup, write_plain};
+    use crate::sync::test_support::{
+        add_provider, doc, read_body, read_frontmatter, setup, write_plain,
+    };
+
+    #[test]
+    fn sync_agents_mirrors_provider_file_to_its_central_copy() -> io::Result<()> {
+        let (tmp, mut cfg) = setup()?;
+        add_provider(&tmp, &mut cfg)?;
+        let provider_file = tmp.path().join("windsurf/AGENTS.md");
+        write_plain(&provider_file, &doc("windsurf", "Windsurf agent"))?;
+
+        sync_agents(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(
+            read_body(&cfg.central_agents_dir.join("windsurf/AGENTS.md"))?,
+            "Windsurf agent"
+        );
+        assert_eq!(read_body(&cfg.codex_agents_file)?, "Windsurf agent");
+        Ok(())
+    }
 
     #[test]
     fn sync_agents_last_write_wins() -> io::Result<()> {
@@ -259,6 +408,64 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sync_agents_propagates_claude_md_to_all_tools() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(&cfg.codex_agents_file, &doc("codex", "Old"))?;
+        write_plain(&cfg.claude_agents_file, &doc("claude", "From Claude"))?;
+        crate::sync::test_support::set_mtime(&cfg.claude_agents_file, 2_700_000_200)?;
+
+        sync_agents(&cfg, LogMode::Quiet)?;
+
+        for path in [
+            &cfg.codex_agents_file,
+            &cfg.opencode_agents_file,
+            &cfg.central_agents_dir.join("claude/CLAUDE.md"),
+        ] {
+            assert_eq!(read_body(path)?, "From Claude");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sync_agents_propagates_gemini_md() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(&cfg.codex_agents_file, &doc("codex", "Old"))?;
+        write_plain(&cfg.gemini_agents_file, &doc("gemini", "From Gemini"))?;
+        crate::sync::test_support::set_mtime(&cfg.gemini_agents_file, 2_900_000_200)?;
+
+        sync_agents(&cfg, LogMode::Quiet)?;
+
+        for path in [
+            &cfg.codex_agents_file,
+            &cfg.claude_agents_file,
+            &cfg.central_agents_dir.join("gemini/GEMINI.md"),
+        ] {
+            assert_eq!(read_body(path)?, "From Gemini");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sync_agents_blacklist_claude_key_skips_claude_only() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+
+        write_plain(&cfg.claude_agents_file, &doc("claude", "Claude old"))?;
+        write_plain(&cfg.codex_agents_file, &doc("codex", "Codex new"))?;
+        crate::sync::test_support::set_mtime(&cfg.codex_agents_file, 2_800_000_200)?;
+
+        cfg.blacklist
+            .entry(CLAUDE_AGENTS_BLACKLIST_KEY.to_string())
+            .or_default()
+            .push(TOOL_CLAUDE.to_string());
+
+        sync_agents(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(read_body(&cfg.claude_agents_file)?, "Claude old");
+        assert_eq!(read_body(&cfg.opencode_agents_file)?, "Codex new");
+        Ok(())
+    }
+
     #[test]
     fn sync_agents_skips_disabled_tool() -> io::Result<()> {
         let (_tmp, cfg) = setup()?;
@@ -303,8 +510,8 @@ mod tests {
             vec![SyncConflict {
                 kind: SyncItemKind::Agent,
                 name: "AGENTS.md".to_string(),
-                winner: TOOL_OPENCODE,
-                others: vec![TOOL_CODEX],
+                winner: TOOL_OPENCODE.to_string(),
+                others: vec![TOOL_CODEX.to_string()],
             }]
         );
         Ok(())
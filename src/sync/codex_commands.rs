@@ -1,5 +1,6 @@
 use super::shared::{
-    log_action, merge_frontmatter, read_visible_entry, write_raw_if_changed, MarkdownDoc,
+    hash_bytes, log_action, merge_frontmatter, read_visible_entry, write_raw_if_changed,
+    FileChange, MarkdownDoc,
 };
 use super::{ExecutionMode, LogMode};
 use crate::history::HistoryRecorder;
@@ -9,10 +10,15 @@ use std::fs;
 use std::io;
 use std::path::Path;
 
-pub(super) fn command_skill_name(command_name: &str) -> Option<&str> {
-    command_name
-        .strip_suffix(".md")
-        .filter(|name| !name.is_empty())
+/// Codex skill names are flat, so a namespaced command such as
+/// `frontend/component.md` maps to the `frontend-component` wrapper.
+pub(crate) fn command_skill_name(command_name: &str) -> Option<String> {
+    let stem = command_name.strip_suffix(".md")?;
+    let segments: Vec<&str> = stem.split('/').collect();
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return None;
+    }
+    Some(segments.join("-"))
 }
 
 fn codex_command_skill_frontmatter(skill_name: &str) -> String {
@@ -29,21 +35,21 @@ pub(crate) fn sync_codex_command_skill_wrapper(
     mode: ExecutionMode,
     history: &mut Option<HistoryRecorder>,
     reserved_skill_names: &HashSet<String>,
-) -> io::Result<bool> {
+) -> io::Result<Vec<FileChange>> {
     let Some(skill_name) = command_skill_name(command_name) else {
-        return Ok(false);
+        return Ok(Vec::new());
     };
-    let skill_dir = codex_skills_dir.join(skill_name);
+    let skill_dir = codex_skills_dir.join(&skill_name);
     let skill_path = skill_dir.join("SKILL.md");
     let marker_path = skill_dir.join(RELAY_COMMAND_SKILL_MARKER);
     let label = format!("commands: codex skill {}", skill_path.display());
 
-    if reserved_skill_names.contains(skill_name) {
+    if reserved_skill_names.contains(&skill_name) {
         log_action(
             log_mode,
             &format!("{label}: skipped; real skill owns this name"),
         );
-        return Ok(false);
+        return Ok(Vec::new());
     }
 
     if skill_dir.exists() && !is_relay_generated_command_skill(&skill_dir) {
@@ -51,43 +57,45 @@ pub(crate) fn sync_codex_command_skill_wrapper(
             log_mode,
             &format!("{label}: skipped existing non-relay skill"),
         );
-        return Ok(false);
+        return Ok(Vec::new());
     }
 
-    let frontmatter = codex_command_skill_frontmatter(skill_name);
+    let frontmatter = codex_command_skill_frontmatter(&skill_name);
     let contents = merge_frontmatter(Some(&frontmatter), &source.body);
     let marker = b"generated by relay from commands\n";
 
-    if mode == ExecutionMode::Plan {
-        let skill_would_change = fs::read(&skill_path).ok().as_deref() != Some(contents.as_bytes());
-        let marker_would_change = fs::read(&marker_path).ok().as_deref() != Some(marker);
-        if skill_would_change || marker_would_change {
-            log_action(log_mode, &format!("{label}: would update"));
-            return Ok(true);
-        }
-        return Ok(false);
-    }
-
-    let marker_changed = write_raw_if_changed(&marker_path, marker, mode, history)?;
-    let skill_changed = write_raw_if_changed(&skill_path, contents.as_bytes(), mode, history)?;
-    if skill_changed || marker_changed {
-        log_action(log_mode, &format!("{label}: updated"));
+    // Both files are reported: a saved plan replays exactly these, and a
+    // wrapper without its marker would be imported as a real skill.
+    let changes: Vec<FileChange> = [
+        write_raw_if_changed(&marker_path, marker, mode, history)?,
+        write_raw_if_changed(&skill_path, contents.as_bytes(), mode, history)?,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !changes.is_empty() {
+        let verb = if mode == ExecutionMode::Plan {
+            "would update"
+        } else {
+            "updated"
+        };
+        log_action(log_mode, &format!("{label}: {verb}"));
     }
-    Ok(skill_changed || marker_changed)
+    Ok(changes)
 }
 
 pub(crate) fn prune_stale_codex_command_skill_wrappers(
     codex_skills_dir: &Path,
-    active_command_names: &BTreeSet<String>,
+    active_skill_names: &BTreeSet<String>,
     log_mode: LogMode,
     mode: ExecutionMode,
     history: &mut Option<HistoryRecorder>,
-) -> io::Result<usize> {
+) -> io::Result<Vec<(String, FileChange)>> {
     if !codex_skills_dir.exists() {
-        return Ok(0);
+        return Ok(Vec::new());
     }
 
-    let mut removed = 0usize;
+    let mut removed = Vec::new();
     for entry in fs::read_dir(codex_skills_dir)? {
         let entry = entry?;
         let Some((skill_name, skill_dir, meta)) = read_visible_entry(entry, false)? else {
@@ -97,15 +105,21 @@ pub(crate) fn prune_stale_codex_command_skill_wrappers(
             continue;
         }
 
-        let command_name = format!("{skill_name}.md");
-        if active_command_names.contains(&command_name) {
+        if active_skill_names.contains(&skill_name) {
             continue;
         }
 
         let label = format!("commands: codex skill {}", skill_dir.display());
+        let change = FileChange::delete(
+            &skill_dir,
+            fs::read(skill_dir.join("SKILL.md"))
+                .ok()
+                .as_deref()
+                .map(hash_bytes),
+        );
         if mode == ExecutionMode::Plan {
             log_action(log_mode, &format!("{label}: would remove"));
-            removed += 1;
+            removed.push((skill_name, change));
             continue;
         }
 
@@ -123,13 +137,14 @@ pub(crate) fn prune_stale_codex_command_skill_wrappers(
             );
         }
         log_action(log_mode, &format!("{label}: removed"));
-        removed += 1;
+        removed.push((skill_name, change));
     }
     Ok(removed)
 }
 
 #[cfg(test)]
 mod tests {
+    use super::command_skill_name;
     use crate::markers::RELAY_COMMAND_SKILL_MARKER;
     use crate::sync::commands::sync_commands;
     use crate::sync::test_support::{setup, write_plain};
@@ -158,6 +173,17 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn command_skill_name_flattens_namespaces() {
+        assert_eq!(command_skill_name("review.md").as_deref(), Some("review"));
+        assert_eq!(
+            command_skill_name("frontend/component.md").as_deref(),
+            Some("frontend-component")
+        );
+        assert_eq!(command_skill_name("frontend//x.md"), None);
+        assert_eq!(command_skill_name("notes.txt"), None);
+    }
+
     #[test]
     fn sync_commands_creates_codex_skill_wrapper() -> io::Result<()> {
         let (_tmp, cfg) = setup()?;
@@ -0,0 +1,265 @@
+use super::shared::{
+    file_mtime_value, list_files_recursive, merge_frontmatter, parse_markdown,
+    parse_required_frontmatter, select_frontmatter_for_target, update_markdown_target,
+    write_text_target, FileChange, MarkdownDoc, MarkdownVariant,
+};
+use super::{ExecutionMode, LogMode};
+use crate::history::HistoryRecorder;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Value};
+
+const MARKDOWN_ARGS: &str = "$ARGUMENTS";
+const GEMINI_ARGS: &str = "{{args}}";
+
+/// On-disk shape of a tool's command files. The central store is always
+/// markdown; every other format converts to and from it at the tool boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CommandFormat {
+    Markdown,
+    /// `<name>.toml` with `description` and `prompt` keys (Gemini CLI).
+    GeminiToml,
+}
+
+impl CommandFormat {
+    /// Lists a tool's command directory, including nested namespaces, keyed by
+    /// central (markdown) relative path.
+    pub(crate) fn list(self, dir: &Path) -> io::Result<HashMap<String, PathBuf>> {
+        let files = list_files_recursive(dir)?;
+        Ok(match self {
+            Self::Markdown => files,
+            Self::GeminiToml => files
+                .into_iter()
+                .filter_map(|(name, path)| {
+                    let stem = name.strip_suffix(".toml")?;
+                    Some((format!("{stem}.md"), path))
+                })
+                .collect(),
+        })
+    }
+
+    /// Maps a central command file name to this format's file name, or `None`
+    /// when the command cannot be expressed in this format.
+    pub(crate) fn file_name(self, name: &str) -> Option<String> {
+        match self {
+            Self::Markdown => Some(name.to_string()),
+            Self::GeminiToml => name.strip_suffix(".md").map(|stem| format!("{stem}.toml")),
+        }
+    }
+
+    pub(crate) fn read_variant<'a>(
+        self,
+        tool: &'a str,
+        name: &str,
+        path: &Path,
+    ) -> io::Result<MarkdownVariant<'a>> {
+        let doc = self.parse(fs::read_to_string(path)?, name, path)?;
+        Ok(MarkdownVariant {
+            tool,
+            path: path.to_path_buf(),
+            doc,
+            mtime: file_mtime_value(path),
+        })
+    }
+
+    /// Parses `raw`, the contents of command `name` at `path`.
+    pub(crate) fn parse(self, raw: String, name: &str, path: &Path) -> io::Result<MarkdownDoc> {
+        Ok(match self {
+            Self::Markdown => parse_markdown(raw),
+            Self::GeminiToml => parse_markdown(gemini_to_markdown(&raw, name, path)?),
+        })
+    }
+
+    /// Renders `source` into `target_path`, keeping the target's own
+    /// frontmatter (or format-specific keys) where the format allows it.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn update_target(
+        self,
+        source: &MarkdownDoc,
+        existing: Option<&MarkdownDoc>,
+        target_path: &Path,
+        log_mode: LogMode,
+        mode: ExecutionMode,
+        history: &mut Option<HistoryRecorder>,
+        label: &str,
+    ) -> io::Result<Option<FileChange>> {
+        match self {
+            Self::Markdown => update_markdown_target(
+                source,
+                existing,
+                target_path,
+                true,
+                log_mode,
+                mode,
+                history,
+                label,
+            ),
+            Self::GeminiToml => {
+                let frontmatter =
+                    select_frontmatter_for_target(source, existing, true, log_mode, label);
+                let markdown = merge_frontmatter(frontmatter.as_deref(), &source.body);
+                let current = match existing {
+                    Some(_) => Some(fs::read_to_string(target_path)?),
+                    None => None,
+                };
+                let rendered = markdown_to_gemini(&markdown, current.as_deref());
+                write_text_target(
+                    current.as_deref(),
+                    &rendered,
+                    target_path,
+                    log_mode,
+                    mode,
+                    history,
+                    label,
+                )
+            }
+        }
+    }
+}
+
+/// Gemini has no `name:` key; the file stem is the command name, so it fills
+/// the frontmatter name when the TOML carries a description. Namespaced
+/// commands (`git/commit.toml`) use the last path segment.
+fn gemini_to_markdown(raw: &str, name: &str, path: &Path) -> io::Result<String> {
+    let invalid = |detail: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid Gemini command {}: {detail}", path.display()),
+        )
+    };
+    let doc: DocumentMut = raw.parse().map_err(|err| invalid(format!("{err}")))?;
+    let prompt = doc
+        .get("prompt")
+        .and_then(Item::as_str)
+        .ok_or_else(|| invalid("missing string `prompt`".to_string()))?;
+    let body = prompt.replace(GEMINI_ARGS, MARKDOWN_ARGS);
+    let description = doc
+        .get("description")
+        .and_then(Item::as_str)
+        .map(|value| value.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|value| !value.is_empty());
+    Ok(match description {
+        Some(description) => {
+            let stem = name.strip_suffix(".md").unwrap_or(name);
+            let stem = stem.rsplit('/').next().unwrap_or(stem);
+            format!("---\nname: {stem}\ndescription: {description}\n---\n{body}")
+        }
+        None => body,
+    })
+}
+
+/// Updates `description` and `prompt` in place so any other keys the user
+/// added to the TOML survive.
+fn markdown_to_gemini(markdown: &str, existing: Option<&str>) -> String {
+    let doc = parse_markdown(markdown.to_string());
+    let mut toml = existing
+        .and_then(|raw| raw.parse::<DocumentMut>().ok())
+        .unwrap_or_default();
+    match parse_required_frontmatter(doc.frontmatter.as_deref()) {
+        Some(required) => toml["description"] = toml_edit::value(required.description),
+        None => {
+            toml.remove("description");
+        }
+    }
+    let prompt = doc.body.replace(MARKDOWN_ARGS, GEMINI_ARGS);
+    toml["prompt"] = Item::Value(multiline_string(&prompt));
+    toml.to_string()
+}
+
+/// Prompts read best as TOML multi-line basic strings. The newline after the
+/// opening delimiter is trimmed by TOML, so the body round-trips exactly.
+fn multiline_string(text: &str) -> Value {
+    let mut out = String::from("\"\"\"\n");
+    let mut quotes = 0;
+    for ch in text.chars() {
+        if ch == '"' {
+            quotes += 1;
+            if quotes == 3 {
+                out.push_str("\\\"");
+                quotes = 0;
+            } else {
+                out.push('"');
+            }
+            continue;
+        }
+        quotes = 0;
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '\n' | '\t' => out.push(ch),
+            '\r' => out.push_str("\\r"),
+            ch if ch.is_control() => out.push_str(&format!("\\u{:04X}", u32::from(ch))),
+            ch => out.push(ch),
+        }
+    }
+    out.push_str("\"\"\"");
+    out.parse()
+        .unwrap_or_else(|_| Value::from(text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gemini_round_trip_translates_arguments_placeholder() -> io::Result<()> {
+        let markdown =
+            "---\nname: review\ndescription: Review a diff\n---\nReview $ARGUMENTS carefully.\n";
+        let toml = markdown_to_gemini(markdown, None);
+        assert_eq!(
+            toml,
+            "description = \"Review a diff\"\nprompt = \"\"\"\nReview {{args}} carefully.\n\"\"\"\n"
+        );
+
+        let back = gemini_to_markdown(&toml, "review.md", Path::new("review.toml"))?;
+        assert_eq!(back, markdown);
+        Ok(())
+    }
+
+    #[test]
+    fn gemini_render_keeps_unrelated_keys() {
+        let existing = "# user notes\nmodel = \"flash\"\ndescription = \"Old\"\nprompt = \"Old\"\n";
+        let toml = markdown_to_gemini("Body only", Some(existing));
+        let doc: DocumentMut = toml.parse().unwrap();
+        assert!(doc.get("description").is_none());
+        assert_eq!(doc["prompt"].as_str(), Some("Body only"));
+        assert_eq!(doc["model"].as_str(), Some("flash"));
+        assert!(toml.starts_with("# user notes\n"));
+    }
+
+    #[test]
+    fn multiline_string_escapes_quotes_and_backslashes() {
+        for text in [
+            "say \"\"\"hi\"\"\"",
+            "path\\to\\file",
+            "ends with quote\"",
+            "\u{7}bell\r\n",
+        ] {
+            let value = multiline_string(text);
+            let reparsed: Value = value.to_string().parse().unwrap();
+            assert_eq!(reparsed.as_str(), Some(text));
+        }
+    }
+
+    #[test]
+    fn gemini_list_maps_toml_files_to_markdown_names() -> io::Result<()> {
+        let tmp = tempfile::TempDir::new()?;
+        fs::write(tmp.path().join("review.toml"), "prompt = \"x\"")?;
+        fs::write(tmp.path().join("notes.txt"), "ignored")?;
+
+        let listed = CommandFormat::GeminiToml.list(tmp.path())?;
+
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed["review.md"], tmp.path().join("review.toml"));
+        assert_eq!(CommandFormat::GeminiToml.file_name("notes.txt"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn gemini_read_rejects_missing_prompt() {
+        let err =
+            gemini_to_markdown("description = \"x\"", "a.md", Path::new("a.toml")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
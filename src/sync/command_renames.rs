@@ -0,0 +1,526 @@
+use super::command_skill_name;
+use super::commands::{command_targets, CommandState};
+use super::shared::{
+    hash_bytes, log_action, parse_markdown, persisted_hash, write_raw_if_changed, ChangeOp,
+    FileChange, PlannedContent,
+};
+use super::skills::codex_skills_target_enabled;
+use super::{ExecutionMode, LogMode, SyncStats};
+use crate::atomic::write_atomic;
+use crate::config::Config;
+use crate::config::TOOL_CODEX;
+use crate::history::{EntityState, HistoryRecorder};
+use crate::markers::is_relay_generated_command_skill;
+use crate::sync::CommandFormat;
+use crate::tools::command_format;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A command that vanished from one location while a new name carrying the
+/// same body appeared there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct CommandRename {
+    pub(super) from: String,
+    pub(super) to: String,
+    pub(super) tool: String,
+}
+
+/// Detects renamed commands and moves every other copy, the central file and
+/// the Codex wrapper to the new name. Each rename is recorded as its own
+/// history event so it can be rolled back on its own. In plan mode nothing
+/// moves and `state` is left as loaded.
+pub(super) fn apply_command_renames(
+    cfg: &Config,
+    state: &mut CommandState,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    record_history: bool,
+    stats: &mut SyncStats,
+) -> io::Result<Vec<CommandRename>> {
+    let renames = detect_command_renames(cfg, state)?;
+    for rename in &renames {
+        log_action(
+            log_mode,
+            &format!(
+                "commands: '{}' renamed to '{}' in {}",
+                rename.from, rename.to, rename.tool
+            ),
+        );
+        let mut recorder = if mode == ExecutionMode::Apply && record_history {
+            let origin = format!("rename:commands/{}->commands/{}", rename.from, rename.to);
+            Some(HistoryRecorder::new(cfg, &origin)?)
+        } else {
+            None
+        };
+        match move_command_copies(cfg, rename, log_mode, mode, &mut recorder) {
+            Ok((moved, renamed)) => {
+                for (tool, [removed, created]) in moved {
+                    stats.record(&rename.from, tool, Some(removed));
+                    stats.record(&rename.to, tool, Some(created));
+                }
+                if let Some(change) = renamed {
+                    stats.record(&rename.to, &rename.tool, Some(change));
+                }
+            }
+            Err(err) => {
+                if let Some(recorder) = recorder {
+                    recorder.rollback_pending()?;
+                }
+                return Err(err);
+            }
+        }
+        if let Some(event_id) = recorder.map(HistoryRecorder::finish).transpose()?.flatten() {
+            log_action(
+                log_mode,
+                &format!("history: recorded rename event {event_id}"),
+            );
+        }
+        if mode == ExecutionMode::Apply {
+            if let Some(entry) = state.commands.remove(&rename.from) {
+                state.commands.insert(rename.to.clone(), entry);
+            }
+        }
+    }
+    Ok(renames)
+}
+
+/// Blacklist entries follow a renamed command. Returns the config to use for
+/// the rest of the sync when any entry moved; apply mode also saves it.
+pub(super) fn carry_blacklist(
+    cfg: &Config,
+    renames: &[CommandRename],
+    mode: ExecutionMode,
+) -> io::Result<Option<Config>> {
+    let mut carried: Option<Config> = None;
+    for rename in renames {
+        let from = format!("commands/{}", rename.from);
+        let to = format!("commands/{}", rename.to);
+        let Some(tools) = cfg.blacklist.get(&from) else {
+            continue;
+        };
+        let renamed = carried.get_or_insert_with(|| cfg.clone());
+        renamed.blacklist.remove(&from);
+        let entry = renamed.blacklist.entry(to.clone()).or_default();
+        for tool in tools {
+            if !entry.contains(tool) {
+                entry.push(tool.clone());
+            }
+        }
+        if mode == ExecutionMode::Apply {
+            crate::blacklist::rename_blacklist_entry(cfg, &from, &to)?;
+        }
+    }
+    Ok(carried)
+}
+
+fn detect_command_renames(cfg: &Config, state: &CommandState) -> io::Result<Vec<CommandRename>> {
+    let mut listings = Vec::new();
+    for (tool, enabled, dir) in command_targets(cfg) {
+        if enabled && dir.exists() {
+            listings.push((tool, command_format(tool).list(dir)?));
+        }
+    }
+
+    let mut renames = Vec::new();
+    let mut claimed = BTreeSet::new();
+    for (name, entry) in &state.commands {
+        let Some(body_hash) = entry.body_hash.filter(|_| !entry.tombstoned) else {
+            continue;
+        };
+        for (tool, listing) in &listings {
+            if !entry.hashes.contains_key(*tool) || listing.contains_key(name) {
+                continue;
+            }
+            let mut matches = Vec::new();
+            for (candidate, path) in listing {
+                if state.commands.contains_key(candidate) || claimed.contains(candidate) {
+                    continue;
+                }
+                match command_format(tool).read_variant(tool, candidate, path) {
+                    Ok(variant) if persisted_hash(variant.doc.body_hash) == body_hash => {
+                        matches.push(candidate);
+                    }
+                    Ok(_) => {}
+                    Err(err) if err.kind() == io::ErrorKind::InvalidData => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            // Several new files with the same body are ambiguous; treat them
+            // as a deletion plus new commands instead of guessing.
+            if let [candidate] = matches[..] {
+                claimed.insert(candidate.clone());
+                renames.push(CommandRename {
+                    from: name.clone(),
+                    to: candidate.clone(),
+                    tool: tool.to_string(),
+                });
+                break;
+            }
+        }
+    }
+    Ok(renames)
+}
+
+/// A tool's copy removed under the old name and created under the new one.
+type MovedCopy<'a> = (&'a str, [FileChange; 2]);
+
+/// Moves every copy to the new name, rewriting a frontmatter `name:` that
+/// still names the old command, and fixes that name in the copy the user
+/// renamed too (returned separately).
+fn move_command_copies<'a>(
+    cfg: &'a Config,
+    rename: &CommandRename,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    recorder: &mut Option<HistoryRecorder>,
+) -> io::Result<(Vec<MovedCopy<'a>>, Option<FileChange>)> {
+    let mut moved = Vec::new();
+    let mut renamed = None;
+    for (tool, enabled, dir) in command_targets(cfg) {
+        if !enabled {
+            continue;
+        }
+        let format = command_format(tool);
+        let (Some(from), Some(to)) = (format.file_name(&rename.from), format.file_name(&rename.to))
+        else {
+            continue;
+        };
+        let (from, to) = (dir.join(from), dir.join(to));
+        // Gemini has no `name:` key; its file stem is the name.
+        let rewrite = |path: &Path| {
+            (format == CommandFormat::Markdown)
+                .then(|| fs::read_to_string(path).ok())
+                .flatten()
+                .and_then(|raw| rename_frontmatter_name(&raw, rename))
+        };
+        if tool == rename.tool {
+            if let Some(contents) = rewrite(&to) {
+                renamed = write_raw_if_changed(&to, contents.as_bytes(), mode, recorder)?;
+            }
+            continue;
+        }
+        let contents = rewrite(&from).map(String::into_bytes);
+        if let Some(changes) = move_path(&from, &to, contents, log_mode, mode, recorder)? {
+            moved.push((tool, changes));
+        }
+    }
+
+    if !codex_skills_target_enabled(cfg) {
+        return Ok((moved, renamed));
+    }
+    if let (Some(from), Some(to)) = (
+        command_skill_name(&rename.from),
+        command_skill_name(&rename.to),
+    ) {
+        let from_dir = cfg.codex_skills_dir.join(&from);
+        if is_relay_generated_command_skill(&from_dir) {
+            let to_dir = cfg.codex_skills_dir.join(to);
+            if let Some(changes) = move_path(&from_dir, &to_dir, None, log_mode, mode, recorder)? {
+                moved.push((TOOL_CODEX, changes));
+            }
+        }
+    }
+    Ok((moved, renamed))
+}
+
+/// `raw` with a top-level frontmatter `name:` that still names the command
+/// before the rename pointed at the new name, or `None` when there is none.
+fn rename_frontmatter_name(raw: &str, rename: &CommandRename) -> Option<String> {
+    let (Some(old), Some(new)) = (frontmatter_name(&rename.from), frontmatter_name(&rename.to))
+    else {
+        return None;
+    };
+    let doc = parse_markdown(raw.to_string());
+    let frontmatter = doc.frontmatter?;
+    let mut rewritten = String::with_capacity(raw.len());
+    let mut changed = false;
+    for line in frontmatter.split_inclusive('\n') {
+        let value = line
+            .strip_prefix("name:")
+            .map(|value| value.trim().trim_matches(['"', '\'']));
+        if !changed && value == Some(old) {
+            let end = &line[line.trim_end_matches(['\n', '\r']).len()..];
+            rewritten.push_str(&format!("name: {new}{end}"));
+            changed = true;
+        } else {
+            rewritten.push_str(line);
+        }
+    }
+    changed.then(|| rewritten + &doc.body)
+}
+
+/// A command's frontmatter name is its file stem without the namespace.
+fn frontmatter_name(command: &str) -> Option<&str> {
+    command.strip_suffix(".md")?.rsplit('/').next()
+}
+
+/// Moves `from` to `to`; `contents`, when set, replaces what a moved file
+/// holds.
+fn move_path(
+    from: &Path,
+    to: &Path,
+    contents: Option<Vec<u8>>,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    recorder: &mut Option<HistoryRecorder>,
+) -> io::Result<Option<[FileChange; 2]>> {
+    if !from.exists() || to.exists() {
+        return Ok(None);
+    }
+    let label = format!("commands: {} -> {}", from.display(), to.display());
+    // Codex wrappers move as a directory; their SKILL.md identifies them.
+    let file = if from.is_dir() {
+        from.join("SKILL.md")
+    } else {
+        from.to_path_buf()
+    };
+    let hash = fs::read(file).ok().as_deref().map(hash_bytes);
+    let changes = [
+        FileChange::delete(from, hash),
+        FileChange {
+            path: to.to_path_buf(),
+            op: ChangeOp::Create,
+            before: None,
+            after: contents.as_deref().map(hash_bytes).or(hash),
+            content: None,
+        }
+        .planned(mode, || match &contents {
+            Some(bytes) => Ok(Some(PlannedContent::File(bytes.clone()))),
+            None => PlannedContent::read(from),
+        })?,
+    ];
+    if mode == ExecutionMode::Plan {
+        log_action(log_mode, &format!("{label}: would rename"));
+        return Ok(Some(changes));
+    }
+    let before = recorder
+        .as_ref()
+        .map(|recorder| {
+            Ok::<_, io::Error>((recorder.capture_path(from)?, recorder.capture_path(to)?))
+        })
+        .transpose()?;
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match &contents {
+        Some(bytes) => {
+            write_atomic(to, bytes)?;
+            fs::set_permissions(to, fs::metadata(from)?.permissions())?;
+            fs::remove_file(from)?;
+        }
+        None => fs::rename(from, to)?,
+    }
+    if let (Some(recorder), Some((from_before, to_before))) = (recorder.as_mut(), before) {
+        let to_after = recorder.capture_path(to)?;
+        recorder.record_change(from, from_before, EntityState::missing());
+        recorder.record_change(to, to_before, to_after);
+    }
+    log_action(log_mode, &format!("{label}: renamed"));
+    Ok(Some(changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rename_frontmatter_name, CommandRename};
+    use crate::history::{HistoryRecorder, HistoryStore};
+    use crate::sync::commands::{sync_commands, sync_commands_with_mode};
+    use crate::sync::test_support::{doc, read_body, setup, write_plain};
+    use crate::sync::{ExecutionMode, LogMode};
+    use std::fs;
+    use std::io;
+
+    #[test]
+    fn rename_in_one_tool_moves_every_copy_and_wrapper() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.claude_dir.join("review.md"),
+            &doc("review", "Review body"),
+        )?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        fs::rename(
+            cfg.claude_dir.join("review.md"),
+            cfg.claude_dir.join("code-review.md"),
+        )?;
+        let mut history = Some(HistoryRecorder::new(&cfg, "sync")?);
+        let mut conflicts = Vec::new();
+        sync_commands_with_mode(
+            &cfg,
+            LogMode::Quiet,
+            ExecutionMode::Apply,
+            &mut history,
+            &mut conflicts,
+        )?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        for dir in [
+            &cfg.central_dir,
+            &cfg.cursor_dir,
+            &cfg.opencode_commands_dir,
+        ] {
+            assert_eq!(read_body(&dir.join("code-review.md"))?, "Review body");
+            assert!(!dir.join("review.md").exists());
+        }
+        for dir in [&cfg.claude_dir, &cfg.central_dir, &cfg.cursor_dir] {
+            let renamed = fs::read_to_string(dir.join("code-review.md"))?;
+            assert!(renamed.contains("name: code-review\n"), "{renamed}");
+        }
+        assert!(cfg.gemini_commands_dir.join("code-review.toml").exists());
+        assert!(!cfg.gemini_commands_dir.join("review.toml").exists());
+        assert!(cfg.codex_skills_dir.join("code-review/SKILL.md").exists());
+        assert!(!cfg.codex_skills_dir.join("review").exists());
+
+        let events = HistoryStore::from_config(&cfg)?.list_recent(10)?;
+        let renames: Vec<_> = events
+            .iter()
+            .filter(|event| event.origin.starts_with("rename:"))
+            .collect();
+        assert_eq!(renames.len(), 1);
+        assert_eq!(
+            renames[0].origin,
+            "rename:commands/review.md->commands/code-review.md"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rename_plan_moves_nothing() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.claude_dir.join("review.md"),
+            &doc("review", "Review body"),
+        )?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+        fs::rename(
+            cfg.claude_dir.join("review.md"),
+            cfg.claude_dir.join("code-review.md"),
+        )?;
+
+        let mut history = None;
+        let mut conflicts = Vec::new();
+        let stats = sync_commands_with_mode(
+            &cfg,
+            LogMode::Quiet,
+            ExecutionMode::Plan,
+            &mut history,
+            &mut conflicts,
+        )?;
+
+        assert!(stats.updated() > 0);
+        assert!(cfg.central_dir.join("review.md").exists());
+        assert!(!cfg.central_dir.join("code-review.md").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn rename_carries_blacklist_entries() -> io::Result<()> {
+        let _env = crate::ENV_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (tmp, mut cfg) = setup()?;
+        let previous_home = std::env::var_os("RELAY_HOME");
+        std::env::set_var("RELAY_HOME", tmp.path().join("home"));
+
+        let result = (|| -> io::Result<()> {
+            let config_path = crate::config::Config::config_path()?;
+            write_plain(
+                &config_path,
+                "[blacklist]\n\"commands/review.md\" = [\"cursor\"]\n",
+            )?;
+            cfg.blacklist
+                .insert("commands/review.md".to_string(), vec!["cursor".to_string()]);
+            write_plain(
+                &cfg.claude_dir.join("review.md"),
+                &doc("review", "Review body"),
+            )?;
+            sync_commands(&cfg, LogMode::Quiet)?;
+            fs::rename(
+                cfg.claude_dir.join("review.md"),
+                cfg.claude_dir.join("code-review.md"),
+            )?;
+
+            sync_commands(&cfg, LogMode::Quiet)?;
+
+            assert!(!cfg.cursor_dir.join("code-review.md").exists());
+            let saved = fs::read_to_string(&config_path)?;
+            assert!(saved.contains("\"commands/code-review.md\" = [\"cursor\"]"));
+            assert!(!saved.contains("\"commands/review.md\""));
+            Ok(())
+        })();
+
+        match previous_home {
+            Some(value) => std::env::set_var("RELAY_HOME", value),
+            None => std::env::remove_var("RELAY_HOME"),
+        }
+        result
+    }
+
+    #[test]
+    fn project_rename_adds_blacklist_entry_and_keeps_the_global_one() -> io::Result<()> {
+        let _env = crate::ENV_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (tmp, global) = setup()?;
+        let previous_home = std::env::var_os("RELAY_HOME");
+        std::env::set_var("RELAY_HOME", tmp.path().join("home"));
+
+        let result = (|| -> io::Result<()> {
+            let config_path = crate::config::Config::config_path()?;
+            write_plain(
+                &config_path,
+                "[blacklist]\n\"commands/review.md\" = [\"cursor\"]\n",
+            )?;
+            let repo = tmp.path().join("repo");
+            fs::create_dir_all(&repo)?;
+            let mut cfg = global.for_project(&repo);
+            cfg.blacklist
+                .insert("commands/review.md".to_string(), vec!["cursor".to_string()]);
+            write_plain(
+                &cfg.claude_dir.join("review.md"),
+                &doc("review", "Review body"),
+            )?;
+            sync_commands(&cfg, LogMode::Quiet)?;
+            fs::rename(
+                cfg.claude_dir.join("review.md"),
+                cfg.claude_dir.join("code-review.md"),
+            )?;
+
+            sync_commands(&cfg, LogMode::Quiet)?;
+
+            assert!(!cfg.cursor_dir.join("code-review.md").exists());
+            let saved = fs::read_to_string(&config_path)?;
+            assert!(saved.contains("\"commands/code-review.md\" = [\"cursor\"]"));
+            assert!(saved.contains("\"commands/review.md\" = [\"cursor\"]"));
+            Ok(())
+        })();
+
+        match previous_home {
+            Some(value) => std::env::set_var("RELAY_HOME", value),
+            None => std::env::remove_var("RELAY_HOME"),
+        }
+        result
+    }
+
+    #[test]
+    fn rename_frontmatter_name_only_rewrites_the_old_name() {
+        let rename = CommandRename {
+            from: "git/commit.md".to_string(),
+            to: "git/save.md".to_string(),
+            tool: "claude".to_string(),
+        };
+        assert_eq!(
+            rename_frontmatter_name("---\nname: commit\r\ndescription: d\n---\nBody\n", &rename)
+                .as_deref(),
+            Some("---\nname: save\r\ndescription: d\n---\nBody\n")
+        );
+        assert_eq!(
+            rename_frontmatter_name("---\nname: \"commit\"\n---\nname: commit\n", &rename)
+                .as_deref(),
+            Some("---\nname: save\n---\nname: commit\n")
+        );
+        assert!(rename_frontmatter_name("---\nname: other\n---\nBody\n", &rename).is_none());
+        assert!(rename_frontmatter_name("name: commit\n", &rename).is_none());
+    }
+}
@@ -1,13 +1,197 @@
+use super::command_renames::{apply_command_renames, carry_blacklist};
+use super::command_skill_name;
+use super::merge::{merge_markdown_variants, MarkdownMerge, MergeBases};
 use super::shared::{
-    collect_names, conflict_for_variants, list_files, list_if, log_action, read_markdown_variant,
-    select_markdown_winner, update_markdown_target, MarkdownVariant, TOOL_CENTRAL,
-    TOOL_OPENCODE_LEGACY,
+    collect_names, conflict_for_variants, contents_after, hash_bytes, hash_bytes_with,
+    legacy_state_version, list_files_recursive, list_if, log_action, persisted_hash,
+    preserve_markdown_conflict, read_markdown_variant, select_markdown_winner,
+    write_raw_if_changed, FileChange, HashMigration, HashScheme, MarkdownVariant, STATE_VERSION,
+    TOOL_CENTRAL, TOOL_OPENCODE_LEGACY,
 };
-use super::{ExecutionMode, LogMode, SyncConflict, SyncItemKind, SyncStats};
-use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_OPENCODE};
+use super::{CommandFormat, ExecutionMode, LogMode, SyncConflict, SyncItemKind, SyncStats};
+use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_GEMINI, TOOL_OPENCODE};
 use crate::history::HistoryRecorder;
-use std::collections::{HashMap, HashSet};
+use crate::tools::{command_format, provider_entries};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
 use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct CommandState {
+    #[serde(default = "legacy_state_version")]
+    version: u32,
+    #[serde(default)]
+    pub(super) commands: BTreeMap<String, CommandStateEntry>,
+}
+
+impl Default for CommandState {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            commands: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct CommandStateEntry {
+    #[serde(default)]
+    pub(super) tombstoned: bool,
+    /// Body hash of the last synced content, used to recognise renames.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) body_hash: Option<i64>,
+    /// Raw file hash per location as of the last sync that wrote it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) hashes: BTreeMap<String, i64>,
+    /// Body hash per location after the last sync, including copies it read
+    /// but did not write. A copy that moved off its entry was edited since.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) bodies: BTreeMap<String, i64>,
+    /// Modified copies left in place when the command was deleted elsewhere.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    preserved: BTreeMap<String, i64>,
+}
+
+/// Variant label for a selected command file outside every command directory.
+const TOOL_SELECTED: &str = "selected path";
+
+/// A command chosen by path for `relay sync command`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ScopedCommand {
+    name: String,
+    /// The selected file when it lives outside every command directory.
+    external: Option<PathBuf>,
+}
+
+/// Resolves command paths to command names. Files inside a command directory
+/// keep their name relative to it; anything else is named relative to the
+/// selected directory, or by its file name when a file was selected.
+pub(crate) fn discover_scoped_commands(
+    cfg: &Config,
+    inputs: &[PathBuf],
+) -> io::Result<Vec<ScopedCommand>> {
+    if inputs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "relay sync command requires at least one path",
+        ));
+    }
+
+    let mut roots = Vec::new();
+    for (_, _, dir) in command_targets(cfg) {
+        if let Ok(root) = fs::canonicalize(dir) {
+            roots.push(root);
+        }
+    }
+    if let Some(root) = cfg
+        .opencode_legacy_commands_dir
+        .as_deref()
+        .and_then(|dir| fs::canonicalize(dir).ok())
+    {
+        roots.push(root);
+    }
+
+    let mut selected: BTreeMap<String, Option<PathBuf>> = BTreeMap::new();
+    for input in inputs {
+        let invalid = |err: io::Error| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid command path {}: {err}", input.display()),
+            )
+        };
+        if fs::symlink_metadata(input)
+            .map_err(invalid)?
+            .file_type()
+            .is_symlink()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "directory and file symlinks are not supported as command inputs: {}",
+                    input.display()
+                ),
+            ));
+        }
+        let canonical = fs::canonicalize(input).map_err(invalid)?;
+        let metadata = fs::metadata(&canonical).map_err(invalid)?;
+        let root = roots.iter().find(|root| canonical.starts_with(root));
+
+        let mut found = Vec::new();
+        if metadata.is_file() {
+            if canonical.extension().and_then(|ext| ext.to_str()) != Some("md") {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("command file must be markdown (.md): {}", input.display()),
+                ));
+            }
+            let base = root.map_or_else(|| canonical.parent(), |root| Some(root.as_path()));
+            found.push((command_name(base, &canonical, input)?, canonical.clone()));
+        } else if metadata.is_dir() {
+            let base = root.map_or(canonical.as_path(), PathBuf::as_path);
+            for path in list_files_recursive(&canonical)?.into_values() {
+                if path.extension().and_then(|ext| ext.to_str()) == Some("md") {
+                    found.push((command_name(Some(base), &path, input)?, path));
+                }
+            }
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "command path is not a file or directory: {}",
+                    input.display()
+                ),
+            ));
+        }
+        if found.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "command path contains no markdown commands: {}",
+                    input.display()
+                ),
+            ));
+        }
+
+        for (name, path) in found {
+            let external = root.is_none().then_some(path);
+            match selected.get(&name) {
+                Some(existing) if *existing != external => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("selected paths resolve to the same command '{name}'"),
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    selected.insert(name, external);
+                }
+            }
+        }
+    }
+
+    Ok(selected
+        .into_iter()
+        .map(|(name, external)| ScopedCommand { name, external })
+        .collect())
+}
+
+fn command_name(base: Option<&Path>, path: &Path, input: &Path) -> io::Result<String> {
+    let relative = base.and_then(|base| path.strip_prefix(base).ok());
+    let segments = relative
+        .into_iter()
+        .flat_map(Path::components)
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>();
+    match segments {
+        Some(segments) if !segments.is_empty() => Ok(segments.join("/")),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("command path is not valid UTF-8: {}", input.display()),
+        )),
+    }
+}
 
 #[cfg(any(test, coverage))]
 pub(crate) fn sync_commands(cfg: &Config, log_mode: LogMode) -> io::Result<SyncStats> {
@@ -47,9 +231,87 @@ pub(crate) fn sync_commands_with_reserved_codex_skill_names(
     history: &mut Option<HistoryRecorder>,
     conflicts: &mut Vec<SyncConflict>,
     reserved_codex_skill_names: &HashSet<String>,
+) -> io::Result<SyncStats> {
+    sync_selected_commands(
+        cfg,
+        None,
+        log_mode,
+        mode,
+        history,
+        conflicts,
+        reserved_codex_skill_names,
+    )
+}
+
+/// Reconciles only the selected command names. Renames and deletions are not
+/// inferred, other commands' state and Codex wrappers are left alone, and a
+/// selected file outside every command directory competes as one more copy.
+pub(super) fn sync_scoped_commands_with_mode(
+    cfg: &Config,
+    selected: &[ScopedCommand],
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+    conflicts: &mut Vec<SyncConflict>,
+) -> io::Result<SyncStats> {
+    sync_selected_commands(
+        cfg,
+        Some(selected),
+        log_mode,
+        mode,
+        history,
+        conflicts,
+        &super::skills::codex_real_skill_names(cfg)?,
+    )
+}
+
+fn sync_selected_commands(
+    cfg: &Config,
+    scope: Option<&[ScopedCommand]>,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+    conflicts: &mut Vec<SyncConflict>,
+    reserved_codex_skill_names: &HashSet<String>,
 ) -> io::Result<SyncStats> {
     let mut stats = SyncStats::default();
 
+    let state_path = cfg.command_state_path()?;
+    let mut saved_state = load_command_state(&state_path)?;
+    let migrated = saved_state.version < STATE_VERSION;
+    migrate_command_state(cfg, &mut saved_state)?;
+    let mut previous_state = saved_state.clone();
+    let renames = if scope.is_none() {
+        apply_command_renames(
+            cfg,
+            &mut previous_state,
+            log_mode,
+            mode,
+            history.is_some(),
+            &mut stats,
+        )?
+    } else {
+        Vec::new()
+    };
+    let renamed_cfg;
+    let cfg = match carry_blacklist(cfg, &renames, mode)? {
+        Some(carried) => {
+            renamed_cfg = carried;
+            &renamed_cfg
+        }
+        None => cfg,
+    };
+    // A planned rename has not moved anything yet; leave both names alone
+    // rather than planning a deletion and a new command.
+    let planned_renames: HashSet<&str> = if mode == ExecutionMode::Plan {
+        renames
+            .iter()
+            .flat_map(|rename| [rename.from.as_str(), rename.to.as_str()])
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
     let claude_enabled = cfg.tool_enabled(TOOL_CLAUDE) && cfg.claude_dir.exists();
     let cursor_enabled = cfg.tool_enabled(TOOL_CURSOR) && cfg.cursor_dir.exists();
     let opencode_enabled = cfg.tool_enabled(TOOL_OPENCODE)
@@ -58,72 +320,285 @@ pub(crate) fn sync_commands_with_reserved_codex_skill_names(
             .parent()
             .is_some_and(|parent| parent.exists());
     let opencode_read_enabled = opencode_enabled && cfg.opencode_commands_dir.exists();
+    let gemini_enabled = cfg.tool_enabled(TOOL_GEMINI)
+        && cfg
+            .gemini_commands_dir
+            .parent()
+            .is_some_and(|parent| parent.exists());
     let codex_skills_enabled = super::skills::codex_skills_target_enabled(cfg);
 
-    let claude = list_if(claude_enabled, &cfg.claude_dir, list_files)?;
-    let cursor = list_if(cursor_enabled, &cfg.cursor_dir, list_files)?;
+    let claude = list_if(claude_enabled, &cfg.claude_dir, list_files_recursive)?;
+    let cursor = list_if(cursor_enabled, &cfg.cursor_dir, list_files_recursive)?;
     let opencode = list_if(
         opencode_read_enabled,
         &cfg.opencode_commands_dir,
-        list_files,
+        list_files_recursive,
     )?;
+    let gemini = if gemini_enabled && cfg.gemini_commands_dir.exists() {
+        command_format(TOOL_GEMINI).list(&cfg.gemini_commands_dir)?
+    } else {
+        HashMap::new()
+    };
     let legacy_opencode = match cfg.opencode_legacy_commands_dir.as_deref() {
-        Some(dir) if cfg.tool_enabled(TOOL_OPENCODE) && dir.exists() => list_files(dir)?,
+        Some(dir) if cfg.tool_enabled(TOOL_OPENCODE) && dir.exists() => list_files_recursive(dir)?,
         _ => HashMap::new(),
     };
     let central = if cfg.central_dir.exists() {
-        list_files(&cfg.central_dir)?
+        list_files_recursive(&cfg.central_dir)?
     } else {
         HashMap::new()
     };
+    let mut provider_sources: Vec<(&str, HashMap<String, PathBuf>)> = Vec::new();
+    let mut provider_readable: Vec<bool> = Vec::new();
+    for provider in provider_entries(cfg) {
+        let Some(dir) = provider.commands_dir else {
+            continue;
+        };
+        let readable = dir.parent().is_some_and(|parent| parent.exists()) && dir.exists();
+        provider_sources.push((provider.id, list_if(readable, dir, list_files_recursive)?));
+        provider_readable.push(readable);
+    }
+    let targets = command_targets(cfg);
+
+    let mut maps = vec![
+        &claude,
+        &cursor,
+        &gemini,
+        &opencode,
+        &legacy_opencode,
+        &central,
+    ];
+    maps.extend(provider_sources.iter().map(|(_, map)| map));
+
+    // Locations whose command directory exists, so a file missing there means
+    // the command was deleted rather than never synced. Legacy OpenCode is an
+    // import source only and never tracked.
+    let mut tracked: Vec<(&str, &HashMap<String, PathBuf>)> = Vec::new();
+    for (tool, readable, map) in [
+        (TOOL_CENTRAL, cfg.central_dir.exists(), &central),
+        (TOOL_CLAUDE, claude_enabled, &claude),
+        (TOOL_CURSOR, cursor_enabled, &cursor),
+        (
+            TOOL_GEMINI,
+            gemini_enabled && cfg.gemini_commands_dir.exists(),
+            &gemini,
+        ),
+        (TOOL_OPENCODE, opencode_read_enabled, &opencode),
+    ] {
+        if readable {
+            tracked.push((tool, map));
+        }
+    }
+    for ((tool, map), readable) in provider_sources.iter().zip(&provider_readable) {
+        if *readable {
+            tracked.push((*tool, map));
+        }
+    }
 
-    let names = collect_names(&[&claude, &cursor, &opencode, &legacy_opencode, &central]);
+    let selected: HashMap<String, PathBuf> = scope
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|command| Some((command.name.clone(), command.external.clone()?)))
+        .collect();
+
+    let listed = collect_names(&maps);
+    let mut wrapper_owners: HashMap<String, &String> = HashMap::new();
+    let (mut state, names) = match scope {
+        Some(scope) => {
+            let names: BTreeSet<String> =
+                scope.iter().map(|command| command.name.clone()).collect();
+            // Wrappers of commands outside the selection keep their owners.
+            for name in &listed {
+                if names.contains(name)
+                    || previous_state
+                        .commands
+                        .get(name)
+                        .is_some_and(|entry| entry.tombstoned)
+                {
+                    continue;
+                }
+                if let Some(skill_name) = command_skill_name(name) {
+                    wrapper_owners.entry(skill_name).or_insert(name);
+                }
+            }
+            (saved_state.clone(), names)
+        }
+        None => {
+            let mut names = listed;
+            names.extend(previous_state.commands.keys().cloned());
+            (CommandState::default(), names)
+        }
+    };
+    let mut active_names = names.clone();
+    let bases = MergeBases::new(cfg)?;
     for name in &names {
+        if planned_renames.contains(name.as_str()) {
+            continue;
+        }
         let blacklist_key = format!("commands/{name}");
+        // Selecting a command by path restores it even when tombstoned.
+        if let Some(entry) = previous_state
+            .commands
+            .get(name)
+            .filter(|_| scope.is_none())
+        {
+            let mut copies = Vec::new();
+            let mut deleted_in = Vec::new();
+            for (tool, map) in &tracked {
+                if *tool != TOOL_CENTRAL && cfg.is_blacklisted(&blacklist_key, tool) {
+                    continue;
+                }
+                match map.get(name) {
+                    Some(path) => {
+                        copies.push((*tool, path, persisted_hash(hash_bytes(&fs::read(path)?))))
+                    }
+                    None if entry.hashes.contains_key(*tool) => deleted_in.push(*tool),
+                    None => {}
+                }
+            }
+            if let Some(tombstone) = reconcile_tombstone(
+                name,
+                entry,
+                &copies,
+                &deleted_in,
+                log_mode,
+                mode,
+                history,
+                &mut stats,
+            )? {
+                state.commands.insert(name.clone(), tombstone);
+                active_names.remove(name);
+                continue;
+            }
+        }
         let mut variants: Vec<MarkdownVariant> = Vec::new();
+        let mut unreadable: Vec<&str> = Vec::new();
         for (tool, map) in [
             (TOOL_CENTRAL, &central),
             (TOOL_CLAUDE, &claude),
             (TOOL_CURSOR, &cursor),
+            (TOOL_GEMINI, &gemini),
             (TOOL_OPENCODE, &opencode),
             (TOOL_OPENCODE_LEGACY, &legacy_opencode),
-        ] {
-            if let Some(path) = map.get(name) {
+            (TOOL_SELECTED, &selected),
+        ]
+        .into_iter()
+        .chain(provider_sources.iter().map(|(tool, map)| (*tool, map)))
+        {
+            let Some(path) = map.get(name) else {
+                continue;
+            };
+            let format = command_format(tool);
+            if format == CommandFormat::Markdown {
                 variants.push(read_markdown_variant(tool, path)?);
+                continue;
+            }
+            // A converted file that fails to parse is left untouched rather
+            // than overwritten with the winner.
+            match format.read_variant(tool, name, path) {
+                Ok(variant) => variants.push(variant),
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    log_action(log_mode, &format!("warning: commands: skipping {err}"));
+                    unreadable.push(tool);
+                }
+                Err(err) => return Err(err),
             }
         }
-        let winner = select_markdown_winner(&variants);
-        if let Some(conflict) = conflict_for_variants(
-            name,
+        if variants.is_empty() {
+            continue;
+        }
+        let previous_bodies = previous_state.commands.get(name).map(|entry| &entry.bodies);
+        let changed = |variant: &MarkdownVariant| {
+            previous_bodies.and_then(|bodies| bodies.get(variant.tool))
+                != Some(&persisted_hash(variant.doc.body_hash))
+        };
+        let policy = cfg.conflict_policy.for_kind(SyncItemKind::Command);
+        let winner = select_markdown_winner(policy, &variants, changed);
+        let central_path = cfg.central_dir.join(name);
+        let mut base_paths = vec![central_path.as_path()];
+        base_paths.extend(
+            variants
+                .iter()
+                .filter(|variant| command_format(variant.tool) == CommandFormat::Markdown)
+                .map(|variant| variant.path.as_path()),
+        );
+        let merged_doc;
+        let source = match merge_markdown_variants(
+            cfg,
+            &bases,
             SyncItemKind::Command,
+            name,
             &variants,
-            winner.tool,
-            winner.doc.body_hash,
-        ) {
-            conflicts.push(conflict);
-            log_action(
-                log_mode,
-                &format!(
-                    "warning: commands '{name}' edited in multiple tools; last-write-wins chose {}",
-                    winner.tool
-                ),
-            );
-        }
-        let source = &winner.doc;
-
-        for (tool, enabled, base_dir) in [
-            (TOOL_CENTRAL, true, &cfg.central_dir),
-            (TOOL_CLAUDE, claude_enabled, &cfg.claude_dir),
-            (TOOL_CURSOR, cursor_enabled, &cfg.cursor_dir),
-            (TOOL_OPENCODE, opencode_enabled, &cfg.opencode_commands_dir),
-        ] {
-            if !enabled {
+            winner,
+            &base_paths,
+            log_mode,
+            mode,
+            conflicts,
+        )? {
+            MarkdownMerge::Merged(doc) => {
+                merged_doc = doc;
+                &merged_doc
+            }
+            MarkdownMerge::Conflicted => {
+                if let Some(conflict) = conflicts.last() {
+                    preserve_markdown_conflict(
+                        cfg,
+                        conflict,
+                        &variants,
+                        winner.doc.body_hash,
+                        log_mode,
+                        mode,
+                    )?;
+                }
+                &winner.doc
+            }
+            MarkdownMerge::NotAttempted => {
+                if let Some(conflict) = conflict_for_variants(
+                    name,
+                    SyncItemKind::Command,
+                    &variants,
+                    changed,
+                    winner.tool,
+                    winner.doc.body_hash,
+                ) {
+                    preserve_markdown_conflict(
+                        cfg,
+                        &conflict,
+                        &variants,
+                        winner.doc.body_hash,
+                        log_mode,
+                        mode,
+                    )?;
+                    conflicts.push(conflict);
+                    log_action(
+                        log_mode,
+                        &format!(
+                            "warning: commands '{name}' edited in multiple tools; conflict policy {} chose {}",
+                            policy.label(),
+                            winner.tool
+                        ),
+                    );
+                }
+                &winner.doc
+            }
+        };
+
+        let mut synced = CommandStateEntry {
+            body_hash: Some(persisted_hash(source.body_hash)),
+            ..CommandStateEntry::default()
+        };
+        for &(tool, enabled, base_dir) in &targets {
+            if !enabled || unreadable.contains(&tool) {
                 continue;
             }
             if tool != TOOL_CENTRAL && cfg.is_blacklisted(&blacklist_key, tool) {
                 continue;
             }
-            let target_path = base_dir.join(name);
+            let format = command_format(tool);
+            let Some(file_name) = format.file_name(name) else {
+                continue;
+            };
+            let target_path = base_dir.join(file_name);
             let existing = variants
                 .iter()
                 .find(|variant| {
@@ -131,58 +606,322 @@ pub(crate) fn sync_commands_with_reserved_codex_skill_names(
                 })
                 .map(|variant| &variant.doc);
             let label = format!("commands: {}", target_path.display());
-            let updated = update_markdown_target(
+            let change = format.update_target(
                 source,
                 existing,
                 &target_path,
-                true,
                 log_mode,
                 mode,
                 history,
                 &label,
             )?;
-            stats.updated += usize::from(updated);
+            // Targets keep some frontmatter (or TOML keys) of their own, so
+            // the written (or planned) file is parsed again for its body hash.
+            let mut body_hash = source.body_hash;
+            if let Some(written) = contents_after(&target_path, change.as_ref())? {
+                synced
+                    .hashes
+                    .insert(tool.to_string(), persisted_hash(hash_bytes(&written)));
+                if let Ok(doc) = String::from_utf8(written)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+                    .and_then(|raw| format.parse(raw, name, &target_path))
+                {
+                    body_hash = doc.body_hash;
+                }
+            }
+            stats.record(name, tool, change);
+            synced
+                .bodies
+                .insert(tool.to_string(), persisted_hash(body_hash));
         }
-
-        let codex_skill_allowed = super::codex_commands::command_skill_name(name)
-            .map(|skill_name| !cfg.is_blacklisted(&format!("skills/{skill_name}"), TOOL_CODEX))
-            .unwrap_or(false);
-        if codex_skills_enabled
-            && codex_skill_allowed
-            && !cfg.is_blacklisted(&blacklist_key, TOOL_CODEX)
+        for variant in &variants {
+            if variant.tool != TOOL_SELECTED {
+                synced
+                    .bodies
+                    .entry(variant.tool.to_string())
+                    .or_insert(persisted_hash(variant.doc.body_hash));
+            }
+        }
+        state.commands.insert(name.clone(), synced);
+
+        let wrapper = command_skill_name(name).filter(|skill_name| {
+            codex_skills_enabled
+                && !cfg.is_blacklisted(&format!("skills/{skill_name}"), TOOL_CODEX)
+                && !cfg.is_blacklisted(&blacklist_key, TOOL_CODEX)
+        });
+        let Some(skill_name) = wrapper else {
+            continue;
+        };
+        // `frontend/component.md` and `frontend-component.md` share a wrapper
+        // name; the first in sort order keeps it.
+        if let Some(owner) = wrapper_owners
+            .get(&skill_name)
+            .filter(|owner| owner.as_str() < name.as_str())
         {
-            let updated = super::codex_commands::sync_codex_command_skill_wrapper(
-                source,
-                &cfg.codex_skills_dir,
-                name,
+            log_action(
                 log_mode,
-                mode,
-                history,
-                reserved_codex_skill_names,
-            )?;
-            stats.updated += usize::from(updated);
+                &format!(
+                    "warning: commands '{name}' maps to codex skill '{skill_name}' already used by '{owner}'; skipping wrapper"
+                ),
+            );
+            continue;
+        }
+        wrapper_owners.insert(skill_name, name);
+        let changes = super::codex_commands::sync_codex_command_skill_wrapper(
+            source,
+            &cfg.codex_skills_dir,
+            name,
+            log_mode,
+            mode,
+            history,
+            reserved_codex_skill_names,
+        )?;
+        for change in changes {
+            stats.record(name, TOOL_CODEX, Some(change));
         }
     }
 
-    if codex_skills_enabled {
-        stats.updated += super::codex_commands::prune_stale_codex_command_skill_wrappers(
+    if codex_skills_enabled && scope.is_none() {
+        let pruned = super::codex_commands::prune_stale_codex_command_skill_wrappers(
             &cfg.codex_skills_dir,
-            &names,
+            &active_names
+                .iter()
+                .filter_map(|name| command_skill_name(name))
+                .collect(),
             log_mode,
             mode,
             history,
         )?;
+        for (skill_name, change) in pruned {
+            stats.record(&skill_name, TOOL_CODEX, Some(change));
+        }
+    }
+
+    // A migrated file is saved even when nothing changed, so the legacy hashes
+    // are not re-derived on every run.
+    if migrated || state != saved_state {
+        stats.record_state(save_command_state(&state_path, &state, mode, history)?);
     }
 
     Ok(stats)
 }
+
+/// Every location commands are written to, with whether it can be written now.
+pub(super) fn command_targets(cfg: &Config) -> Vec<(&str, bool, &PathBuf)> {
+    let parent_exists = |dir: &PathBuf| dir.parent().is_some_and(|parent| parent.exists());
+    let mut targets = vec![
+        (TOOL_CENTRAL, true, &cfg.central_dir),
+        (
+            TOOL_CLAUDE,
+            cfg.tool_enabled(TOOL_CLAUDE) && cfg.claude_dir.exists(),
+            &cfg.claude_dir,
+        ),
+        (
+            TOOL_CURSOR,
+            cfg.tool_enabled(TOOL_CURSOR) && cfg.cursor_dir.exists(),
+            &cfg.cursor_dir,
+        ),
+        (
+            TOOL_GEMINI,
+            cfg.tool_enabled(TOOL_GEMINI) && parent_exists(&cfg.gemini_commands_dir),
+            &cfg.gemini_commands_dir,
+        ),
+        (
+            TOOL_OPENCODE,
+            cfg.tool_enabled(TOOL_OPENCODE) && parent_exists(&cfg.opencode_commands_dir),
+            &cfg.opencode_commands_dir,
+        ),
+    ];
+    for provider in provider_entries(cfg) {
+        if let Some(dir) = provider.commands_dir {
+            targets.push((provider.id, parent_exists(dir), dir));
+        }
+    }
+    targets
+}
+
+/// Returns the tombstone to keep when `name` was deleted in some location
+/// since the last sync, or is still deleted. Copies that still match what
+/// Relay last wrote are removed; edited copies are left in place. A copy that
+/// differs from both is a recreation and lifts the tombstone.
+#[allow(clippy::too_many_arguments)]
+fn reconcile_tombstone(
+    name: &str,
+    entry: &CommandStateEntry,
+    copies: &[(&str, &PathBuf, i64)],
+    deleted_in: &[&str],
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+    stats: &mut SyncStats,
+) -> io::Result<Option<CommandStateEntry>> {
+    if entry.tombstoned {
+        let recreated = copies.iter().any(|(tool, _, hash)| {
+            entry.hashes.get(*tool) != Some(hash) && entry.preserved.get(*tool) != Some(hash)
+        });
+        if recreated {
+            log_action(
+                log_mode,
+                &format!("commands: '{name}' recreated; restoring"),
+            );
+            return Ok(None);
+        }
+    } else if deleted_in.is_empty() {
+        return Ok(None);
+    } else {
+        log_action(
+            log_mode,
+            &format!(
+                "commands: '{name}' deleted in {}; removing synced copies",
+                deleted_in.join(", ")
+            ),
+        );
+    }
+
+    let mut tombstone = CommandStateEntry {
+        tombstoned: true,
+        body_hash: entry.body_hash,
+        hashes: entry.hashes.clone(),
+        bodies: entry.bodies.clone(),
+        preserved: BTreeMap::new(),
+    };
+    for (tool, path, hash) in copies {
+        if entry.hashes.get(*tool) == Some(hash) {
+            let change = remove_command_target(path, log_mode, mode, history)?;
+            stats.record(name, tool, change);
+            continue;
+        }
+        if entry.preserved.get(*tool) != Some(hash) {
+            log_action(
+                log_mode,
+                &format!(
+                    "warning: commands '{name}' deleted but modified copy {} was preserved",
+                    path.display()
+                ),
+            );
+        }
+        tombstone.preserved.insert(tool.to_string(), *hash);
+    }
+    Ok(Some(tombstone))
+}
+
+fn remove_command_target(
+    path: &Path,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+) -> io::Result<Option<FileChange>> {
+    let change = match fs::read(path) {
+        Ok(raw) => FileChange::delete(path, Some(hash_bytes(&raw))),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if mode == ExecutionMode::Plan {
+        log_action(
+            log_mode,
+            &format!("commands: would remove {}", path.display()),
+        );
+        return Ok(Some(change));
+    }
+    let before = history
+        .as_ref()
+        .map(|recorder| recorder.capture_path(path))
+        .transpose()?;
+    fs::remove_file(path)?;
+    if let (Some(recorder), Some(before)) = (history.as_mut(), before) {
+        recorder.record_change(path, before, crate::history::EntityState::missing());
+    }
+    log_action(log_mode, &format!("commands: removed {}", path.display()));
+    Ok(Some(change))
+}
+
+fn load_command_state(path: &Path) -> io::Result<CommandState> {
+    if !path.exists() {
+        return Ok(CommandState::default());
+    }
+    let raw = fs::read_to_string(path)?;
+    toml::from_str(&raw).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid command state in {}: {err}", path.display()),
+        )
+    })
+}
+
+/// Re-derives the hashes of a state file written before `STATE_VERSION` 2
+/// from the command files currently on disk: raw file hashes for every
+/// location and body hashes for rename detection.
+fn migrate_command_state(cfg: &Config, state: &mut CommandState) -> io::Result<()> {
+    if state.version >= STATE_VERSION {
+        return Ok(());
+    }
+    let mut migration = HashMigration::default();
+    for (tool, _, dir) in command_targets(cfg) {
+        if !dir.exists() {
+            continue;
+        }
+        let format = command_format(tool);
+        for (name, path) in format.list(dir)? {
+            migration.add_bytes(&fs::read(&path)?);
+            // Unparsable files never contributed a body hash.
+            if let Ok(variant) = format.read_variant(tool, &name, &path) {
+                migration.add(
+                    hash_bytes_with(HashScheme::Legacy, variant.doc.body.as_bytes()),
+                    variant.doc.body_hash,
+                );
+            }
+        }
+    }
+    for entry in state.commands.values_mut() {
+        entry
+            .body_hash
+            .iter_mut()
+            .chain(entry.hashes.values_mut())
+            .chain(entry.bodies.values_mut())
+            .chain(entry.preserved.values_mut())
+            .for_each(|hash| migration.apply(hash));
+    }
+    state.version = STATE_VERSION;
+    Ok(())
+}
+
+fn save_command_state(
+    path: &Path,
+    state: &CommandState,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+) -> io::Result<Option<FileChange>> {
+    let raw = toml::to_string_pretty(state)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    write_raw_if_changed(path, raw.as_bytes(), mode, history)
+}
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sync::test_support::{doc, read_body, read_frontmatter, setup, write_plain};
+    use crate::sync::test_support::{
+        add_provider, doc, read_body, read_frontmatter, setup, write_plain,
+    };
     use std::collections::HashSet;
     use std::fs;
 
+    #[test]
+    fn sync_commands_includes_declared_providers() -> io::Result<()> {
+        let (tmp, mut cfg) = setup()?;
+        add_provider(&tmp, &mut cfg)?;
+        let provider_dir = tmp.path().join("windsurf/commands");
+        write_plain(&provider_dir.join("deploy.md"), &doc("deploy", "Ship it"))?;
+
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(read_body(&cfg.central_dir.join("deploy.md"))?, "Ship it");
+        assert_eq!(read_body(&cfg.claude_dir.join("deploy.md"))?, "Ship it");
+
+        write_plain(&cfg.central_dir.join("review.md"), &doc("review", "Review"))?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+        assert_eq!(read_body(&provider_dir.join("review.md"))?, "Review");
+        Ok(())
+    }
+
     #[test]
     fn sync_commands_last_write_wins_and_syncs_required_frontmatter() -> io::Result<()> {
         let (_tmp, cfg) = setup()?;
@@ -446,6 +1185,69 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sync_commands_renders_gemini_toml() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.central_dir.join("review.md"),
+            &doc("review", "Review $ARGUMENTS.\n"),
+        )?;
+
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(
+            fs::read_to_string(cfg.gemini_commands_dir.join("review.toml"))?,
+            "description = \"review description\"\nprompt = \"\"\"\nReview {{args}}.\n\"\"\"\n"
+        );
+        assert!(!cfg.gemini_commands_dir.join("review.md").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn sync_commands_gemini_edit_flows_back_to_markdown() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let claude = cfg.claude_dir.join("review.md");
+        let gemini = cfg.gemini_commands_dir.join("review.toml");
+        write_plain(
+            &claude,
+            "---\nname: review\ndescription: Old\nmodel: opus\n---\nOld",
+        )?;
+        write_plain(
+            &gemini,
+            "description = \"New\"\nprompt = \"Check {{args}}\"\nmodel = \"flash\"\n",
+        )?;
+        crate::sync::test_support::set_mtime(&gemini, 2_600_000_000)?;
+
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(read_body(&claude)?, "Check $ARGUMENTS");
+        let frontmatter = read_frontmatter(&claude)?.unwrap_or_default();
+        assert!(frontmatter.contains("description: New"));
+        assert!(frontmatter.contains("model: opus"));
+        assert_eq!(
+            read_body(&cfg.central_dir.join("review.md"))?,
+            "Check $ARGUMENTS"
+        );
+        let rendered = fs::read_to_string(&gemini)?;
+        assert!(rendered.contains("model = \"flash\""));
+        assert!(rendered.contains("Check {{args}}"));
+        Ok(())
+    }
+
+    #[test]
+    fn sync_commands_leaves_unparseable_gemini_command_alone() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let gemini = cfg.gemini_commands_dir.join("review.toml");
+        write_plain(&gemini, "prompt = ")?;
+        write_plain(&cfg.central_dir.join("review.md"), "Review body")?;
+
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(fs::read_to_string(&gemini)?, "prompt = ");
+        assert_eq!(read_body(&cfg.claude_dir.join("review.md"))?, "Review body");
+        Ok(())
+    }
+
     #[test]
     fn sync_commands_ignores_existing_codex_prompts() -> io::Result<()> {
         let (tmp, cfg) = setup()?;
@@ -545,8 +1347,8 @@ mod tests {
             vec![SyncConflict {
                 kind: SyncItemKind::Command,
                 name: "review.md".to_string(),
-                winner: TOOL_OPENCODE_LEGACY,
-                others: vec![TOOL_OPENCODE],
+                winner: TOOL_OPENCODE_LEGACY.to_string(),
+                others: vec![TOOL_OPENCODE.to_string()],
             }]
         );
         Ok(())
@@ -566,6 +1368,222 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sync_commands_mirrors_nested_commands() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        cfg.blacklist
+            .entry("commands/frontend/component.md".to_string())
+            .or_default()
+            .push(TOOL_CURSOR.to_string());
+        write_plain(
+            &cfg.claude_dir.join("frontend/component.md"),
+            &doc("component", "Build a component"),
+        )?;
+
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        for dir in [&cfg.central_dir, &cfg.opencode_commands_dir] {
+            assert_eq!(
+                read_body(&dir.join("frontend/component.md"))?,
+                "Build a component"
+            );
+        }
+        assert!(cfg
+            .gemini_commands_dir
+            .join("frontend/component.toml")
+            .exists());
+        assert!(!cfg.cursor_dir.join("frontend/component.md").exists());
+        assert_eq!(
+            read_body(&cfg.codex_skills_dir.join("frontend-component/SKILL.md"))?,
+            "Build a component"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sync_commands_skips_colliding_codex_wrapper_names() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(&cfg.central_dir.join("frontend-component.md"), "Flat")?;
+        write_plain(&cfg.central_dir.join("frontend/component.md"), "Nested")?;
+        write_plain(&cfg.central_dir.join("backend/api.md"), "Api")?;
+
+        let mut history = None;
+        let mut conflicts = Vec::new();
+        let reserved = HashSet::from(["backend-api".to_string()]);
+        sync_commands_with_reserved_codex_skill_names(
+            &cfg,
+            LogMode::Quiet,
+            ExecutionMode::Apply,
+            &mut history,
+            &mut conflicts,
+            &reserved,
+        )?;
+
+        assert_eq!(
+            read_body(&cfg.codex_skills_dir.join("frontend-component/SKILL.md"))?,
+            "Flat"
+        );
+        assert!(!cfg.codex_skills_dir.join("backend-api").exists());
+        assert_eq!(
+            read_body(&cfg.claude_dir.join("frontend/component.md"))?,
+            "Nested"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sync_commands_propagates_deletion_with_tombstone() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let claude = cfg.claude_dir.join("review.md");
+        write_plain(&claude, &doc("review", "Review body"))?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+        let gemini = cfg.gemini_commands_dir.join("review.toml");
+        let wrapper = cfg.codex_skills_dir.join("review");
+        assert!(gemini.exists());
+        assert!(wrapper.join("SKILL.md").exists());
+
+        fs::remove_file(&claude)?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        for path in [
+            &claude,
+            &cfg.central_dir.join("review.md"),
+            &cfg.cursor_dir.join("review.md"),
+            &cfg.opencode_commands_dir.join("review.md"),
+            &gemini,
+        ] {
+            assert!(!path.exists(), "{} was resurrected", path.display());
+        }
+        assert!(!wrapper.exists());
+        let state = load_command_state(&cfg.command_state_path()?)?;
+        assert!(state.commands["review.md"].tombstoned);
+        Ok(())
+    }
+
+    #[test]
+    fn no_change_sync_saves_migrated_legacy_command_state() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let state_path = cfg.command_state_path()?;
+        write_plain(&state_path, "")?;
+
+        let stats = sync_commands(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(stats.updated(), 0);
+        assert_eq!(load_command_state(&state_path)?.version, STATE_VERSION);
+        assert!(fs::read_to_string(&state_path)?.contains("version = 2"));
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_command_state_hashes_are_rederived_from_current_files() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let claude = cfg.claude_dir.join("review.md");
+        let central = cfg.central_dir.join("review.md");
+        write_plain(&claude, &doc("review", "Review body"))?;
+        write_plain(&central, &doc("review", "Review body"))?;
+        let raw = fs::read(&claude)?;
+        let body = read_markdown_variant(TOOL_CLAUDE, &claude)?.doc.body;
+        let legacy = |bytes: &[u8]| persisted_hash(hash_bytes_with(HashScheme::Legacy, bytes));
+        let state_path = cfg.command_state_path()?;
+        write_plain(
+            &state_path,
+            &format!(
+                "[commands.\"review.md\"]\nbody_hash = {}\n\n[commands.\"review.md\".hashes]\ncentral = {}\nclaude = {}\ncursor = 9\n",
+                legacy(body.as_bytes()),
+                legacy(&raw),
+                legacy(&raw)
+            ),
+        )?;
+
+        let mut state = load_command_state(&state_path)?;
+        assert_eq!(state.version, legacy_state_version());
+        migrate_command_state(&cfg, &mut state)?;
+
+        let entry = &state.commands["review.md"];
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(
+            entry.body_hash,
+            Some(persisted_hash(hash_bytes(body.as_bytes())))
+        );
+        let current = persisted_hash(hash_bytes(&raw));
+        assert_eq!(
+            entry.hashes,
+            BTreeMap::from([
+                (TOOL_CENTRAL.to_string(), current),
+                (TOOL_CLAUDE.to_string(), current),
+                (TOOL_CURSOR.to_string(), 9),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sync_commands_deletion_preserves_modified_copies() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let claude = cfg.claude_dir.join("review.md");
+        let cursor = cfg.cursor_dir.join("review.md");
+        write_plain(&claude, &doc("review", "Review body"))?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        write_plain(&cursor, &doc("review", "Local cursor tweak"))?;
+        fs::remove_file(&claude)?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(read_body(&cursor)?, "Local cursor tweak");
+        assert!(!claude.exists());
+        assert!(!cfg.central_dir.join("review.md").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn sync_commands_plan_reports_deletion_without_removing() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let claude = cfg.claude_dir.join("review.md");
+        write_plain(&claude, &doc("review", "Review body"))?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+        fs::remove_file(&claude)?;
+
+        let mut history = None;
+        let mut conflicts = Vec::new();
+        let stats = sync_commands_with_mode(
+            &cfg,
+            LogMode::Quiet,
+            ExecutionMode::Plan,
+            &mut history,
+            &mut conflicts,
+        )?;
+
+        assert!(stats.updated() > 0);
+        assert!(!claude.exists());
+        assert!(cfg.central_dir.join("review.md").exists());
+        assert!(!load_command_state(&cfg.command_state_path()?)?.commands["review.md"].tombstoned);
+        Ok(())
+    }
+
+    #[test]
+    fn sync_commands_recreated_command_lifts_tombstone() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let claude = cfg.claude_dir.join("review.md");
+        write_plain(&claude, &doc("review", "Review body"))?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+        fs::remove_file(&claude)?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        write_plain(
+            &cfg.central_dir.join("review.md"),
+            &doc("review", "Back again"),
+        )?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(read_body(&claude)?, "Back again");
+        assert!(cfg.codex_skills_dir.join("review/SKILL.md").exists());
+        let state = load_command_state(&cfg.command_state_path()?)?;
+        assert!(!state.commands["review.md"].tombstoned);
+        Ok(())
+    }
+
     #[test]
     fn sync_commands_collects_conflict_details() -> io::Result<()> {
         let (_tmp, cfg) = setup()?;
@@ -592,10 +1610,80 @@ mod tests {
             vec![SyncConflict {
                 kind: SyncItemKind::Command,
                 name: "review.md".to_string(),
-                winner: TOOL_CURSOR,
-                others: vec![TOOL_CLAUDE],
+                winner: TOOL_CURSOR.to_string(),
+                others: vec![TOOL_CLAUDE.to_string()],
             }]
         );
         Ok(())
     }
+
+    #[test]
+    fn discover_scoped_commands_names_by_location() -> io::Result<()> {
+        let (tmp, cfg) = setup()?;
+        let nested = cfg.claude_dir.join("frontend/component.md");
+        write_plain(&nested, "Component")?;
+        let drafts = tmp.path().join("drafts");
+        write_plain(&drafts.join("draft.md"), "Draft")?;
+        write_plain(&drafts.join("team/plan.md"), "Plan")?;
+        write_plain(&drafts.join("notes.txt"), "Not a command")?;
+
+        let selected = discover_scoped_commands(&cfg, &[nested, drafts.clone()])?;
+
+        let names: Vec<_> = selected
+            .iter()
+            .map(|command| (command.name.as_str(), command.external.is_some()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("draft.md", true),
+                ("frontend/component.md", false),
+                ("team/plan.md", true)
+            ]
+        );
+        let err = discover_scoped_commands(&cfg, &[drafts.join("notes.txt")]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+
+    #[test]
+    fn scoped_sync_leaves_unselected_commands_alone() -> io::Result<()> {
+        let (tmp, cfg) = setup()?;
+        write_plain(&cfg.claude_dir.join("review.md"), &doc("review", "Review"))?;
+        write_plain(&cfg.claude_dir.join("other.md"), &doc("other", "Other"))?;
+        sync_commands(&cfg, LogMode::Quiet)?;
+        fs::remove_file(cfg.cursor_dir.join("review.md"))?;
+        fs::remove_file(cfg.claude_dir.join("other.md"))?;
+        let draft = tmp.path().join("draft.md");
+        write_plain(&draft, &doc("draft", "Draft"))?;
+
+        let selected =
+            discover_scoped_commands(&cfg, &[cfg.central_dir.join("review.md"), draft.clone()])?;
+        let mut history = None;
+        let mut conflicts = Vec::new();
+        sync_scoped_commands_with_mode(
+            &cfg,
+            &selected,
+            LogMode::Quiet,
+            ExecutionMode::Apply,
+            &mut history,
+            &mut conflicts,
+        )?;
+
+        // The selected command is restored rather than treated as deleted.
+        assert_eq!(read_body(&cfg.cursor_dir.join("review.md"))?, "Review");
+        assert_eq!(read_body(&cfg.claude_dir.join("draft.md"))?, "Draft");
+        assert!(cfg.codex_skills_dir.join("draft/SKILL.md").exists());
+        assert_eq!(read_body(&draft)?, "Draft");
+        // The unselected deletion waits for the next full sync.
+        assert!(cfg.cursor_dir.join("other.md").exists());
+        assert!(cfg.codex_skills_dir.join("other/SKILL.md").exists());
+        let state = load_command_state(&cfg.command_state_path()?)?;
+        assert!(!state.commands["other.md"].tombstoned);
+        assert!(state.commands.contains_key("draft.md"));
+
+        sync_commands(&cfg, LogMode::Quiet)?;
+        assert!(!cfg.cursor_dir.join("other.md").exists());
+        Ok(())
+    }
 }
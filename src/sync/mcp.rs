@@ -0,0 +1,1314 @@
+use super::shared::{
+    conflict_for_variants, file_mtime_value, hash_bytes, legacy_state_version, list_visible_files,
+    log_action, persisted_hash, pick_winner, write_raw_if_changed, ChangeOp, ConflictVariant,
+    FileChange, HashMigration, STATE_VERSION, TOOL_CENTRAL,
+};
+use super::{ExecutionMode, LogMode, SyncConflict, SyncItemKind, SyncStats};
+use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_OPENCODE};
+use crate::history::HistoryRecorder;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// On-disk shape of each tool's MCP configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum McpFormat {
+    /// `mcpServers` in `~/.claude.json`.
+    Claude,
+    /// `[mcp_servers.<name>]` tables in Codex `config.toml`.
+    Codex,
+    /// `mcpServers` in `~/.cursor/mcp.json`.
+    Cursor,
+    /// `mcp` in OpenCode `opencode.json`.
+    Opencode,
+}
+
+impl McpFormat {
+    fn json_section(self) -> &'static str {
+        match self {
+            McpFormat::Opencode => "mcp",
+            _ => "mcpServers",
+        }
+    }
+}
+
+/// Tool-neutral server definition. This is also the format of the central
+/// `mcp/<name>.json` files.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct McpServer {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+}
+
+impl McpServer {
+    fn is_valid(&self) -> bool {
+        self.command.is_some() != self.url.is_some()
+    }
+
+    fn render(&self) -> io::Result<Vec<u8>> {
+        let mut out = serde_json::to_string_pretty(self).map_err(json_error)?;
+        out.push('\n');
+        Ok(out.into_bytes())
+    }
+
+    fn hash(&self) -> io::Result<u64> {
+        Ok(hash_bytes(&self.render()?))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct McpState {
+    #[serde(default = "legacy_state_version")]
+    version: u32,
+    #[serde(default)]
+    servers: BTreeMap<String, i64>,
+    /// Locations that held each server as of the last sync, used to tell a
+    /// deletion apart from a server that was never synced there.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    locations: BTreeMap<String, BTreeSet<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    tombstones: BTreeMap<String, McpTombstone>,
+}
+
+impl Default for McpState {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            servers: BTreeMap::new(),
+            locations: BTreeMap::new(),
+            tombstones: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct McpTombstone {
+    /// Hash of the definition that was deleted.
+    hash: i64,
+    /// Modified copies left in place when the server was deleted elsewhere.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    preserved: BTreeMap<String, i64>,
+}
+
+struct McpTarget<'a> {
+    tool: &'static str,
+    format: McpFormat,
+    path: &'a Path,
+}
+
+struct McpSource<'a> {
+    target: McpTarget<'a>,
+    raw: Option<String>,
+    servers: BTreeMap<String, McpServer>,
+    mtime: u128,
+}
+
+struct McpVariant {
+    tool: &'static str,
+    server: McpServer,
+    hash: u64,
+    mtime: u128,
+}
+
+impl ConflictVariant for McpVariant {
+    fn tool(&self) -> &str {
+        self.tool
+    }
+
+    fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    fn mtime(&self) -> u128 {
+        self.mtime
+    }
+}
+
+#[cfg(any(test, coverage))]
+pub(crate) fn sync_mcp(cfg: &Config, log_mode: LogMode) -> io::Result<SyncStats> {
+    let mut history = None;
+    let mut conflicts = Vec::new();
+    sync_mcp_with_mode(
+        cfg,
+        log_mode,
+        ExecutionMode::Apply,
+        &mut history,
+        &mut conflicts,
+    )
+}
+
+pub(crate) fn sync_mcp_with_mode(
+    cfg: &Config,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+    conflicts: &mut Vec<SyncConflict>,
+) -> io::Result<SyncStats> {
+    let mut stats = SyncStats::default();
+
+    let mut sources = Vec::new();
+    for target in mcp_targets(cfg) {
+        match read_source(target) {
+            Ok(source) => sources.push(source),
+            Err((path, err)) => log_action(
+                log_mode,
+                &format!("warning: mcp: skipping {}: {err}", path.display()),
+            ),
+        }
+    }
+    let central = read_central_servers(&cfg.central_mcp_dir)?;
+
+    let state_path = cfg.mcp_state_path()?;
+    let mut previous_state = load_mcp_state(&state_path)?;
+    let migrated = previous_state.version < STATE_VERSION;
+    migrate_mcp_state(
+        &mut previous_state,
+        central
+            .values()
+            .map(|(server, _)| server)
+            .chain(sources.iter().flat_map(|source| source.servers.values())),
+    )?;
+    let mut state = McpState::default();
+
+    let mut names: BTreeSet<String> = central.keys().cloned().collect();
+    for source in &sources {
+        names.extend(source.servers.keys().cloned());
+    }
+    names.extend(previous_state.tombstones.keys().cloned());
+
+    let mut pending: Vec<BTreeMap<String, Option<McpServer>>> =
+        sources.iter().map(|_| BTreeMap::new()).collect();
+    for name in &names {
+        if !is_valid_server_name(name) {
+            log_action(
+                log_mode,
+                &format!("warning: mcp: skipping server with unsupported name '{name}'"),
+            );
+            continue;
+        }
+        let blacklist_key = format!("mcp/{name}");
+        let tracked: Vec<&McpSource> = sources
+            .iter()
+            .filter(|source| !cfg.is_blacklisted(&blacklist_key, source.target.tool))
+            .collect();
+
+        let mut copies = Vec::new();
+        if let Some((server, _)) = central.get(name) {
+            copies.push((TOOL_CENTRAL, persisted_hash(server.hash()?)));
+        }
+        for source in &tracked {
+            if let Some(server) = source.servers.get(name) {
+                copies.push((source.target.tool, persisted_hash(server.hash()?)));
+            }
+        }
+        // A missing config file means the tool is absent (uninstalled or
+        // moved), not that each of its servers was deleted.
+        let deleted_in: Vec<&str> = previous_state
+            .locations
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(|tool| {
+                let present = if *tool == TOOL_CENTRAL {
+                    cfg.central_mcp_dir.exists()
+                } else {
+                    tracked
+                        .iter()
+                        .any(|source| source.target.tool == *tool && source.raw.is_some())
+                };
+                present && !copies.iter().any(|(copy, _)| copy == tool)
+            })
+            .collect();
+        if let Some(tombstone) = reconcile_tombstone(
+            name,
+            previous_state.tombstones.get(name),
+            previous_state.servers.get(name).copied(),
+            &copies,
+            &deleted_in,
+            log_mode,
+        ) {
+            if copies
+                .iter()
+                .any(|(tool, hash)| *tool == TOOL_CENTRAL && *hash == tombstone.hash)
+            {
+                let central_path = cfg.central_mcp_dir.join(format!("{name}.json"));
+                let change = remove_central_server(&central_path, log_mode, mode, history)?;
+                stats.record(name, TOOL_CENTRAL, change);
+            }
+            for (source, updates) in sources.iter().zip(pending.iter_mut()) {
+                if copies
+                    .iter()
+                    .any(|(tool, hash)| *tool == source.target.tool && *hash == tombstone.hash)
+                {
+                    updates.insert(name.clone(), None);
+                }
+            }
+            state.tombstones.insert(name.clone(), tombstone);
+            continue;
+        }
+
+        let mut variants = Vec::new();
+        if let Some((server, mtime)) = central.get(name) {
+            variants.push(McpVariant {
+                tool: TOOL_CENTRAL,
+                server: server.clone(),
+                hash: server.hash()?,
+                mtime: *mtime,
+            });
+        }
+        for source in &sources {
+            if let Some(server) = source.servers.get(name) {
+                variants.push(McpVariant {
+                    tool: source.target.tool,
+                    server: server.clone(),
+                    hash: server.hash()?,
+                    mtime: source.mtime,
+                });
+            }
+        }
+
+        // Several servers share one tool file, so the file mtime alone cannot
+        // say which copy changed. Copies that moved off the last synced
+        // definition are the edits.
+        let last_synced = previous_state.servers.get(name).copied();
+        let changed = |variant: &McpVariant| Some(persisted_hash(variant.hash)) != last_synced;
+        let policy = cfg.conflict_policy.for_kind(SyncItemKind::Mcp);
+        let Some(winner) = pick_winner(policy, &variants, changed) else {
+            continue;
+        };
+        if let Some(conflict) = conflict_for_variants(
+            name,
+            SyncItemKind::Mcp,
+            &variants,
+            changed,
+            winner.tool,
+            winner.hash,
+        ) {
+            conflicts.push(conflict);
+            log_action(
+                log_mode,
+                &format!(
+                    "warning: mcp server '{name}' edited in multiple tools; conflict policy {} chose {}",
+                    policy.label(),
+                    winner.tool
+                ),
+            );
+        }
+        state
+            .servers
+            .insert(name.clone(), persisted_hash(winner.hash));
+        state.locations.insert(
+            name.clone(),
+            std::iter::once(TOOL_CENTRAL)
+                .chain(tracked.iter().map(|source| source.target.tool))
+                .map(str::to_string)
+                .collect(),
+        );
+
+        let central_path = cfg.central_mcp_dir.join(format!("{name}.json"));
+        let change = write_raw_if_changed(&central_path, &winner.server.render()?, mode, history)?;
+        if stats.record(name, TOOL_CENTRAL, change) {
+            log_action(
+                log_mode,
+                &format!("mcp: {} {}", action_label(mode), central_path.display()),
+            );
+        }
+
+        for (source, updates) in sources.iter().zip(pending.iter_mut()) {
+            if cfg.is_blacklisted(&blacklist_key, source.target.tool) {
+                continue;
+            }
+            if source.servers.get(name) != Some(&winner.server) {
+                updates.insert(name.clone(), Some(winner.server.clone()));
+            }
+        }
+    }
+
+    for (source, updates) in sources.iter().zip(pending.iter()) {
+        if updates.is_empty() {
+            continue;
+        }
+        let contents = render_servers(source.target.format, source.raw.as_deref(), updates)?;
+        if let Some(change) = write_raw_if_changed(source.target.path, &contents, mode, history)? {
+            for (name, update) in updates {
+                // The file changes as a whole; the op says what happened to
+                // this server within it.
+                let op = match update {
+                    None => ChangeOp::Delete,
+                    Some(_) if source.servers.contains_key(name) => ChangeOp::Update,
+                    Some(_) => ChangeOp::Create,
+                };
+                stats.record(
+                    name,
+                    source.target.tool,
+                    Some(FileChange {
+                        op,
+                        ..change.clone()
+                    }),
+                );
+                log_action(
+                    log_mode,
+                    &format!(
+                        "mcp: {} {name} in {}",
+                        if update.is_some() {
+                            action_label(mode)
+                        } else {
+                            removal_label(mode)
+                        },
+                        source.target.path.display()
+                    ),
+                );
+            }
+        }
+    }
+
+    if migrated || state != previous_state {
+        stats.record_state(save_mcp_state(&state_path, &state, mode, history)?);
+    }
+
+    Ok(stats)
+}
+
+/// Returns the tool config path and its contents with `name` removed from the
+/// MCP section, or `None` when the tool does not define that server.
+pub(crate) fn mcp_server_removal(
+    cfg: &Config,
+    name: &str,
+    tool: &str,
+) -> io::Result<Option<(PathBuf, Vec<u8>)>> {
+    let Some(target) = all_mcp_targets(cfg)
+        .into_iter()
+        .find(|target| target.tool == tool)
+    else {
+        return Ok(None);
+    };
+    if !target.path.exists() {
+        return Ok(None);
+    }
+    let raw = fs::read_to_string(target.path)?;
+    let servers = parse_servers(target.format, &raw)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", target.path.display())))?;
+    if !servers.contains_key(name) {
+        return Ok(None);
+    }
+    let updates = BTreeMap::from([(name.to_string(), None)]);
+    let contents = render_servers(target.format, Some(&raw), &updates)?;
+    Ok(Some((target.path.to_path_buf(), contents)))
+}
+
+fn all_mcp_targets(cfg: &Config) -> Vec<McpTarget<'_>> {
+    vec![
+        McpTarget {
+            tool: TOOL_CLAUDE,
+            format: McpFormat::Claude,
+            path: &cfg.claude_mcp_file,
+        },
+        McpTarget {
+            tool: TOOL_CODEX,
+            format: McpFormat::Codex,
+            path: &cfg.codex_config_file,
+        },
+        McpTarget {
+            tool: TOOL_CURSOR,
+            format: McpFormat::Cursor,
+            path: &cfg.cursor_mcp_file,
+        },
+        McpTarget {
+            tool: TOOL_OPENCODE,
+            format: McpFormat::Opencode,
+            path: &cfg.opencode_config_file,
+        },
+    ]
+}
+
+fn mcp_targets(cfg: &Config) -> Vec<McpTarget<'_>> {
+    all_mcp_targets(cfg)
+        .into_iter()
+        .filter(|target| {
+            cfg.tool_enabled(target.tool)
+                && target.path.parent().is_some_and(|parent| parent.exists())
+        })
+        .collect()
+}
+
+fn read_source(target: McpTarget<'_>) -> Result<McpSource<'_>, (PathBuf, io::Error)> {
+    let fail = |err| (target.path.to_path_buf(), err);
+    if !target.path.exists() {
+        return Ok(McpSource {
+            target,
+            raw: None,
+            servers: BTreeMap::new(),
+            mtime: 0,
+        });
+    }
+    let raw = fs::read_to_string(target.path).map_err(fail)?;
+    let servers = parse_servers(target.format, &raw).map_err(fail)?;
+    let mtime = file_mtime_value(target.path);
+    Ok(McpSource {
+        target,
+        raw: Some(raw),
+        servers,
+        mtime,
+    })
+}
+
+fn read_central_servers(dir: &Path) -> io::Result<BTreeMap<String, (McpServer, u128)>> {
+    let mut servers = BTreeMap::new();
+    if !dir.exists() {
+        return Ok(servers);
+    }
+    for (file_name, path) in list_visible_files(dir)? {
+        let Some(name) = file_name.strip_suffix(".json") else {
+            continue;
+        };
+        let raw = fs::read_to_string(&path)?;
+        let server: McpServer = serde_json::from_str(&raw).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid mcp server in {}: {err}", path.display()),
+            )
+        })?;
+        if !server.is_valid() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "invalid mcp server in {}: expected exactly one of `command` or `url`",
+                    path.display()
+                ),
+            ));
+        }
+        servers.insert(name.to_string(), (server, file_mtime_value(&path)));
+    }
+    Ok(servers)
+}
+
+fn parse_servers(format: McpFormat, raw: &str) -> io::Result<BTreeMap<String, McpServer>> {
+    let section = match format {
+        McpFormat::Codex => {
+            let doc: toml::Table = toml::from_str(raw)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            match doc.get("mcp_servers") {
+                Some(section) => serde_json::to_value(section).map_err(json_error)?,
+                None => return Ok(BTreeMap::new()),
+            }
+        }
+        _ => {
+            if raw.trim().is_empty() {
+                return Ok(BTreeMap::new());
+            }
+            let mut doc: Value = serde_json::from_str(raw).map_err(json_error)?;
+            match doc.get_mut(format.json_section()) {
+                Some(section) => section.take(),
+                None => return Ok(BTreeMap::new()),
+            }
+        }
+    };
+    let Value::Object(section) = section else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "mcp server section is not a table",
+        ));
+    };
+    let mut servers = BTreeMap::new();
+    for (name, entry) in section {
+        match entry
+            .as_object()
+            .and_then(|entry| server_from_entry(format, entry))
+        {
+            Some(server) => {
+                servers.insert(name, server);
+            }
+            None => {
+                crate::logging::debug(&format!("mcp: ignoring unsupported server '{name}'"));
+            }
+        }
+    }
+    Ok(servers)
+}
+
+fn server_from_entry(format: McpFormat, entry: &Map<String, Value>) -> Option<McpServer> {
+    let mut server = McpServer {
+        url: optional_string(entry, "url")?,
+        ..McpServer::default()
+    };
+    match format {
+        McpFormat::Opencode => {
+            let mut command = optional_strings(entry, "command")?.into_iter();
+            server.command = command.next();
+            server.args = command.collect();
+            server.env = optional_string_map(entry, "environment")?;
+            server.headers = optional_string_map(entry, "headers")?;
+        }
+        McpFormat::Claude | McpFormat::Codex | McpFormat::Cursor => {
+            server.command = optional_string(entry, "command")?;
+            server.args = optional_strings(entry, "args")?;
+            server.env = optional_string_map(entry, "env")?;
+            let headers_key = if format == McpFormat::Codex {
+                "http_headers"
+            } else {
+                "headers"
+            };
+            server.headers = optional_string_map(entry, headers_key)?;
+        }
+    }
+    server.is_valid().then_some(server)
+}
+
+/// Field values for `server` in the tool's own shape; `None` removes the key.
+/// Keys not listed here are tool-specific and left untouched.
+fn entry_fields(
+    format: McpFormat,
+    server: &McpServer,
+    existing: Option<&Map<String, Value>>,
+) -> Vec<(&'static str, Option<Value>)> {
+    let string = |value: &Option<String>| value.clone().map(Value::String);
+    let strings = |values: &[String]| {
+        (!values.is_empty())
+            .then(|| Value::Array(values.iter().cloned().map(Value::String).collect()))
+    };
+    let map = |values: &BTreeMap<String, String>| {
+        (!values.is_empty()).then(|| {
+            Value::Object(
+                values
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+                    .collect(),
+            )
+        })
+    };
+    match format {
+        McpFormat::Claude | McpFormat::Cursor => {
+            let mut fields = Vec::new();
+            if format == McpFormat::Claude {
+                let existing_type = existing
+                    .and_then(|entry| entry.get("type"))
+                    .and_then(Value::as_str)
+                    .filter(|kind| matches!(*kind, "http" | "sse"));
+                let kind = match (&server.command, existing_type) {
+                    (Some(_), _) => "stdio",
+                    (None, Some(kind)) => kind,
+                    (None, None) => "http",
+                };
+                fields.push(("type", Some(Value::String(kind.to_string()))));
+            }
+            fields.extend([
+                ("command", string(&server.command)),
+                ("args", strings(&server.args)),
+                ("env", map(&server.env)),
+                ("url", string(&server.url)),
+                ("headers", map(&server.headers)),
+            ]);
+            fields
+        }
+        McpFormat::Codex => vec![
+            ("command", string(&server.command)),
+            ("args", strings(&server.args)),
+            ("env", map(&server.env)),
+            ("url", string(&server.url)),
+            ("http_headers", map(&server.headers)),
+        ],
+        McpFormat::Opencode => {
+            let kind = if server.command.is_some() {
+                "local"
+            } else {
+                "remote"
+            };
+            let command = server.command.as_ref().map(|command| {
+                let mut parts = vec![command.clone()];
+                parts.extend(server.args.iter().cloned());
+                parts
+            });
+            vec![
+                ("type", Some(Value::String(kind.to_string()))),
+                ("command", command.as_deref().and_then(strings)),
+                ("environment", map(&server.env)),
+                ("url", string(&server.url)),
+                ("headers", map(&server.headers)),
+            ]
+        }
+    }
+}
+
+fn render_servers(
+    format: McpFormat,
+    raw: Option<&str>,
+    updates: &BTreeMap<String, Option<McpServer>>,
+) -> io::Result<Vec<u8>> {
+    match format {
+        McpFormat::Codex => render_codex_servers(raw, updates),
+        _ => render_json_servers(format, raw, updates),
+    }
+}
+
+fn render_json_servers(
+    format: McpFormat,
+    raw: Option<&str>,
+    updates: &BTreeMap<String, Option<McpServer>>,
+) -> io::Result<Vec<u8>> {
+    let mut doc = match raw {
+        Some(raw) if !raw.trim().is_empty() => serde_json::from_str(raw).map_err(json_error)?,
+        _ => Value::Object(Map::new()),
+    };
+    let root = doc.as_object_mut().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "expected a JSON object at the top level",
+        )
+    })?;
+    let section = root
+        .entry(format.json_section())
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "mcp server section is not an object",
+            )
+        })?;
+    for (name, server) in updates {
+        let Some(server) = server else {
+            section.shift_remove(name);
+            continue;
+        };
+        let mut entry = section
+            .get(name)
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default();
+        for (key, value) in entry_fields(format, server, Some(&entry)) {
+            match value {
+                Some(value) => {
+                    entry.insert(key.to_string(), value);
+                }
+                None => {
+                    entry.shift_remove(key);
+                }
+            }
+        }
+        section.insert(name.clone(), Value::Object(entry));
+    }
+    let mut out = serde_json::to_string_pretty(&doc).map_err(json_error)?;
+    if raw.is_none_or(|raw| raw.ends_with('\n')) {
+        out.push('\n');
+    }
+    Ok(out.into_bytes())
+}
+
+fn render_codex_servers(
+    raw: Option<&str>,
+    updates: &BTreeMap<String, Option<McpServer>>,
+) -> io::Result<Vec<u8>> {
+    let mut doc: toml_edit::DocumentMut = raw
+        .unwrap_or_default()
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    if !doc.contains_key("mcp_servers") {
+        let mut table = toml_edit::Table::new();
+        table.set_implicit(true);
+        doc.insert("mcp_servers", toml_edit::Item::Table(table));
+    }
+    let section = doc["mcp_servers"].as_table_like_mut().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "`mcp_servers` in Codex config is not a table",
+        )
+    })?;
+    for (name, server) in updates {
+        let Some(server) = server else {
+            section.remove(name);
+            continue;
+        };
+        if !section
+            .get(name)
+            .is_some_and(toml_edit::Item::is_table_like)
+        {
+            section.insert(name, toml_edit::Item::Table(toml_edit::Table::new()));
+        }
+        let entry = section
+            .get_mut(name)
+            .and_then(toml_edit::Item::as_table_like_mut)
+            .expect("server entry is a table");
+        for (key, value) in entry_fields(McpFormat::Codex, server, None) {
+            match value {
+                Some(value) => {
+                    entry.insert(key, toml_edit::Item::Value(json_to_toml(&value)));
+                }
+                None => {
+                    entry.remove(key);
+                }
+            }
+        }
+    }
+    Ok(doc.to_string().into_bytes())
+}
+
+/// Converts the string, array, and object values used by server definitions.
+fn json_to_toml(value: &Value) -> toml_edit::Value {
+    match value {
+        Value::Array(items) => toml_edit::Value::Array(items.iter().map(json_to_toml).collect()),
+        Value::Object(entries) => toml_edit::Value::InlineTable(
+            entries
+                .iter()
+                .map(|(key, value)| (key.as_str(), json_to_toml(value)))
+                .collect(),
+        ),
+        Value::Bool(value) => toml_edit::Value::from(*value),
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => toml_edit::Value::from(value),
+            None => toml_edit::Value::from(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(value) => toml_edit::Value::from(value.as_str()),
+        Value::Null => toml_edit::Value::from(""),
+    }
+}
+
+fn optional_string(entry: &Map<String, Value>, key: &str) -> Option<Option<String>> {
+    match entry.get(key) {
+        None => Some(None),
+        Some(value) => value.as_str().map(|value| Some(value.to_string())),
+    }
+}
+
+fn optional_strings(entry: &Map<String, Value>, key: &str) -> Option<Vec<String>> {
+    match entry.get(key) {
+        None => Some(Vec::new()),
+        Some(value) => value
+            .as_array()?
+            .iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect(),
+    }
+}
+
+fn optional_string_map(entry: &Map<String, Value>, key: &str) -> Option<BTreeMap<String, String>> {
+    match entry.get(key) {
+        None => Some(BTreeMap::new()),
+        Some(value) => value
+            .as_object()?
+            .iter()
+            .map(|(key, value)| value.as_str().map(|value| (key.clone(), value.to_string())))
+            .collect(),
+    }
+}
+
+fn is_valid_server_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\'])
+        && !name.chars().any(char::is_control)
+}
+
+fn action_label(mode: ExecutionMode) -> &'static str {
+    if mode == ExecutionMode::Plan {
+        "would update"
+    } else {
+        "updated"
+    }
+}
+
+fn removal_label(mode: ExecutionMode) -> &'static str {
+    if mode == ExecutionMode::Plan {
+        "would remove"
+    } else {
+        "removed"
+    }
+}
+
+/// Returns the tombstone to keep when `name` was deleted in some location
+/// since the last sync, or is still deleted. Copies matching the tombstone
+/// hash are removed by the caller; edited copies are preserved. A copy that
+/// differs from both is a recreation and lifts the tombstone.
+fn reconcile_tombstone(
+    name: &str,
+    previous: Option<&McpTombstone>,
+    last_synced: Option<i64>,
+    copies: &[(&'static str, i64)],
+    deleted_in: &[&str],
+    log_mode: LogMode,
+) -> Option<McpTombstone> {
+    let hash = match previous {
+        Some(tombstone) => {
+            let recreated = copies.iter().any(|(tool, hash)| {
+                *hash != tombstone.hash && tombstone.preserved.get(*tool) != Some(hash)
+            });
+            if recreated {
+                log_action(log_mode, &format!("mcp: '{name}' recreated; restoring"));
+                return None;
+            }
+            tombstone.hash
+        }
+        None if deleted_in.is_empty() => return None,
+        None => {
+            let hash = last_synced?;
+            log_action(
+                log_mode,
+                &format!(
+                    "mcp: '{name}' deleted in {}; removing synced copies",
+                    deleted_in.join(", ")
+                ),
+            );
+            hash
+        }
+    };
+
+    let mut tombstone = McpTombstone {
+        hash,
+        preserved: BTreeMap::new(),
+    };
+    for (tool, copy_hash) in copies {
+        if *copy_hash == hash {
+            continue;
+        }
+        if previous.and_then(|previous| previous.preserved.get(*tool)) != Some(copy_hash) {
+            log_action(
+                log_mode,
+                &format!("warning: mcp server '{name}' deleted but modified copy in {tool} was preserved"),
+            );
+        }
+        tombstone.preserved.insert(tool.to_string(), *copy_hash);
+    }
+    Some(tombstone)
+}
+
+fn remove_central_server(
+    path: &Path,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+) -> io::Result<Option<FileChange>> {
+    let change = match fs::read(path) {
+        Ok(raw) => FileChange::delete(path, Some(hash_bytes(&raw))),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if mode == ExecutionMode::Apply {
+        let before = history
+            .as_ref()
+            .map(|recorder| recorder.capture_path(path))
+            .transpose()?;
+        fs::remove_file(path)?;
+        if let (Some(recorder), Some(before)) = (history.as_mut(), before) {
+            recorder.record_change(path, before, crate::history::EntityState::missing());
+        }
+    }
+    log_action(
+        log_mode,
+        &format!("mcp: {} {}", removal_label(mode), path.display()),
+    );
+    Ok(Some(change))
+}
+
+fn load_mcp_state(path: &Path) -> io::Result<McpState> {
+    if !path.exists() {
+        return Ok(McpState::default());
+    }
+    let raw = fs::read_to_string(path)?;
+    toml::from_str(&raw).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid mcp state in {}: {err}", path.display()),
+        )
+    })
+}
+
+/// Re-derives the hashes of a state file written before `STATE_VERSION` 2
+/// from the server definitions currently configured.
+fn migrate_mcp_state<'a>(
+    state: &mut McpState,
+    servers: impl Iterator<Item = &'a McpServer>,
+) -> io::Result<()> {
+    if state.version >= STATE_VERSION {
+        return Ok(());
+    }
+    let mut migration = HashMigration::default();
+    for server in servers {
+        migration.add_bytes(&server.render()?);
+    }
+    state
+        .servers
+        .values_mut()
+        .for_each(|hash| migration.apply(hash));
+    state.version = STATE_VERSION;
+    Ok(())
+}
+
+fn save_mcp_state(
+    path: &Path,
+    state: &McpState,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+) -> io::Result<Option<FileChange>> {
+    let raw = toml::to_string_pretty(state)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    write_raw_if_changed(path, raw.as_bytes(), mode, history)
+}
+
+fn json_error(err: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::test_support::{set_mtime, setup, write_plain};
+
+    fn read_json(path: &Path) -> io::Result<Value> {
+        serde_json::from_str(&fs::read_to_string(path)?).map_err(json_error)
+    }
+
+    #[test]
+    fn no_change_sync_saves_migrated_legacy_mcp_state() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let state_path = cfg.mcp_state_path()?;
+        write_plain(&state_path, "")?;
+
+        let stats = sync_mcp(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(stats.updated(), 0);
+        assert_eq!(load_mcp_state(&state_path)?.version, STATE_VERSION);
+        assert!(fs::read_to_string(&state_path)?.contains("version = 2"));
+        Ok(())
+    }
+
+    #[test]
+    fn sync_mcp_mirrors_claude_server_into_every_tool_shape() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.claude_mcp_file,
+            r#"{"numStartups": 3, "mcpServers": {"docs": {"type": "stdio", "command": "npx", "args": ["-y", "docs-mcp"], "env": {"TOKEN": "x"}}}}"#,
+        )?;
+
+        let stats = sync_mcp(&cfg, LogMode::Quiet)?;
+        assert_eq!(stats.updated(), 4);
+
+        let central = read_json(&cfg.central_mcp_dir.join("docs.json"))?;
+        assert_eq!(central["command"], "npx");
+        assert_eq!(central["args"], serde_json::json!(["-y", "docs-mcp"]));
+
+        let cursor = read_json(&cfg.cursor_mcp_file)?;
+        assert_eq!(cursor["mcpServers"]["docs"]["env"]["TOKEN"], "x");
+        assert!(cursor["mcpServers"]["docs"].get("type").is_none());
+
+        let opencode = read_json(&cfg.opencode_config_file)?;
+        assert_eq!(
+            opencode["mcp"]["docs"],
+            serde_json::json!({
+                "type": "local",
+                "command": ["npx", "-y", "docs-mcp"],
+                "environment": {"TOKEN": "x"}
+            })
+        );
+
+        let codex: toml::Table = toml::from_str(&fs::read_to_string(&cfg.codex_config_file)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        assert_eq!(
+            codex["mcp_servers"]["docs"]["command"].as_str(),
+            Some("npx")
+        );
+
+        let claude = read_json(&cfg.claude_mcp_file)?;
+        assert_eq!(claude["numStartups"], 3);
+
+        assert_eq!(sync_mcp(&cfg, LogMode::Quiet)?.updated(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn sync_mcp_edits_only_the_codex_mcp_tables() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.codex_config_file,
+            "# model settings\nmodel = \"o3\"\n\n[mcp_servers.search]\ncommand = \"old\"\nstartup_timeout_sec = 20 # keep\n",
+        )?;
+        write_plain(
+            &cfg.cursor_mcp_file,
+            r#"{"mcpServers": {"search": {"url": "https://search.example/mcp", "headers": {"X-Key": "k"}}}}"#,
+        )?;
+        set_mtime(&cfg.codex_config_file, 100)?;
+        set_mtime(&cfg.cursor_mcp_file, 200)?;
+
+        sync_mcp(&cfg, LogMode::Quiet)?;
+
+        let codex = fs::read_to_string(&cfg.codex_config_file)?;
+        assert!(codex.starts_with("# model settings\nmodel = \"o3\"\n"));
+        assert!(codex.contains("startup_timeout_sec = 20 # keep"));
+        assert!(codex.contains("url = \"https://search.example/mcp\""));
+        assert!(codex.contains("http_headers = { X-Key = \"k\" }"));
+        assert!(!codex.contains("command"));
+        Ok(())
+    }
+
+    #[test]
+    fn sync_mcp_keeps_separate_edits_from_different_files() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.claude_mcp_file,
+            r#"{"mcpServers": {"a": {"command": "a-v1"}, "b": {"command": "b-v1"}}}"#,
+        )?;
+        sync_mcp(&cfg, LogMode::Quiet)?;
+
+        write_plain(
+            &cfg.cursor_mcp_file,
+            r#"{"mcpServers": {"a": {"command": "a-v1"}, "b": {"command": "b-v2"}}}"#,
+        )?;
+        write_plain(
+            &cfg.claude_mcp_file,
+            r#"{"mcpServers": {"a": {"command": "a-v2"}, "b": {"command": "b-v1"}}}"#,
+        )?;
+        set_mtime(&cfg.cursor_mcp_file, 100)?;
+        set_mtime(&cfg.claude_mcp_file, 200)?;
+
+        sync_mcp(&cfg, LogMode::Quiet)?;
+
+        for path in [&cfg.claude_mcp_file, &cfg.cursor_mcp_file] {
+            let doc = read_json(path)?;
+            assert_eq!(doc["mcpServers"]["a"]["command"], "a-v2");
+            assert_eq!(doc["mcpServers"]["b"]["command"], "b-v2");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sync_mcp_plan_and_blacklist_skip_writes() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        write_plain(
+            &cfg.central_mcp_dir.join("docs.json"),
+            "{\n  \"url\": \"https://docs.example/mcp\"\n}\n",
+        )?;
+        cfg.blacklist
+            .entry("mcp/docs".to_string())
+            .or_default()
+            .push(TOOL_CURSOR.to_string());
+
+        let mut history = None;
+        let mut conflicts = Vec::new();
+        let planned = sync_mcp_with_mode(
+            &cfg,
+            LogMode::Quiet,
+            ExecutionMode::Plan,
+            &mut history,
+            &mut conflicts,
+        )?;
+        assert_eq!(planned.updated(), 3);
+        assert!(!cfg.claude_mcp_file.exists());
+        assert!(!cfg.mcp_state_path()?.exists());
+
+        sync_mcp(&cfg, LogMode::Quiet)?;
+        assert!(!cfg.cursor_mcp_file.exists());
+        let claude = read_json(&cfg.claude_mcp_file)?;
+        assert_eq!(claude["mcpServers"]["docs"]["type"], "http");
+        Ok(())
+    }
+
+    #[test]
+    fn sync_mcp_skips_unparseable_tool_files() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(&cfg.cursor_mcp_file, "{ not json")?;
+        write_plain(
+            &cfg.claude_mcp_file,
+            r#"{"mcpServers": {"docs": {"command": "docs"}}}"#,
+        )?;
+
+        sync_mcp(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(fs::read_to_string(&cfg.cursor_mcp_file)?, "{ not json");
+        assert!(cfg.central_mcp_dir.join("docs.json").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn sync_mcp_reports_conflicting_edits() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.claude_mcp_file,
+            r#"{"mcpServers": {"docs": {"command": "claude"}}}"#,
+        )?;
+        write_plain(
+            &cfg.cursor_mcp_file,
+            r#"{"mcpServers": {"docs": {"command": "cursor"}}}"#,
+        )?;
+        set_mtime(&cfg.claude_mcp_file, 100)?;
+        set_mtime(&cfg.cursor_mcp_file, 101)?;
+
+        let mut history = None;
+        let mut conflicts = Vec::new();
+        sync_mcp_with_mode(
+            &cfg,
+            LogMode::Quiet,
+            ExecutionMode::Plan,
+            &mut history,
+            &mut conflicts,
+        )?;
+
+        assert_eq!(
+            conflicts,
+            vec![SyncConflict {
+                kind: SyncItemKind::Mcp,
+                name: "docs".to_string(),
+                winner: TOOL_CURSOR.to_string(),
+                others: vec![TOOL_CLAUDE.to_string()],
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sync_mcp_propagates_deletion_with_tombstone() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.claude_mcp_file,
+            r#"{"mcpServers": {"docs": {"command": "docs"}, "keep": {"command": "keep"}}}"#,
+        )?;
+        sync_mcp(&cfg, LogMode::Quiet)?;
+
+        write_plain(
+            &cfg.cursor_mcp_file,
+            r#"{"mcpServers": {"keep": {"command": "keep"}}}"#,
+        )?;
+        let stats = sync_mcp(&cfg, LogMode::Quiet)?;
+
+        assert!(stats
+            .actions
+            .iter()
+            .all(|action| action.change.op == ChangeOp::Delete));
+        assert!(!cfg.central_mcp_dir.join("docs.json").exists());
+        for path in [
+            &cfg.claude_mcp_file,
+            &cfg.cursor_mcp_file,
+            &cfg.opencode_config_file,
+        ] {
+            let doc = read_json(path)?;
+            let section = &doc[if path == &cfg.opencode_config_file {
+                "mcp"
+            } else {
+                "mcpServers"
+            }];
+            assert!(section.get("docs").is_none());
+            assert!(section.get("keep").is_some());
+        }
+        assert!(!fs::read_to_string(&cfg.codex_config_file)?.contains("mcp_servers.docs"));
+        let state = load_mcp_state(&cfg.mcp_state_path()?)?;
+        assert!(state.tombstones.contains_key("docs"));
+        assert!(!state.servers.contains_key("docs"));
+
+        assert_eq!(sync_mcp(&cfg, LogMode::Quiet)?.updated(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn sync_mcp_missing_config_file_is_not_a_deletion() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.claude_mcp_file,
+            r#"{"mcpServers": {"docs": {"command": "docs"}}}"#,
+        )?;
+        sync_mcp(&cfg, LogMode::Quiet)?;
+
+        fs::remove_file(&cfg.cursor_mcp_file)?;
+        fs::remove_file(&cfg.codex_config_file)?;
+        sync_mcp(&cfg, LogMode::Quiet)?;
+
+        assert!(cfg.central_mcp_dir.join("docs.json").exists());
+        for path in [&cfg.claude_mcp_file, &cfg.cursor_mcp_file] {
+            assert_eq!(read_json(path)?["mcpServers"]["docs"]["command"], "docs");
+        }
+        assert_eq!(
+            read_json(&cfg.opencode_config_file)?["mcp"]["docs"]["command"][0],
+            "docs"
+        );
+        assert!(fs::read_to_string(&cfg.codex_config_file)?.contains("[mcp_servers.docs]"));
+        let state = load_mcp_state(&cfg.mcp_state_path()?)?;
+        assert!(state.tombstones.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn sync_mcp_deletion_preserves_modified_copies() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.claude_mcp_file,
+            r#"{"mcpServers": {"docs": {"command": "docs"}}}"#,
+        )?;
+        sync_mcp(&cfg, LogMode::Quiet)?;
+
+        write_plain(&cfg.claude_mcp_file, r#"{"mcpServers": {}}"#)?;
+        write_plain(
+            &cfg.cursor_mcp_file,
+            r#"{"mcpServers": {"docs": {"command": "docs-edited"}}}"#,
+        )?;
+        sync_mcp(&cfg, LogMode::Quiet)?;
+
+        let cursor = read_json(&cfg.cursor_mcp_file)?;
+        assert_eq!(cursor["mcpServers"]["docs"]["command"], "docs-edited");
+        assert!(read_json(&cfg.claude_mcp_file)?["mcpServers"]
+            .get("docs")
+            .is_none());
+        assert!(!cfg.central_mcp_dir.join("docs.json").exists());
+        let state = load_mcp_state(&cfg.mcp_state_path()?)?;
+        assert!(state.tombstones["docs"].preserved.contains_key(TOOL_CURSOR));
+
+        assert_eq!(sync_mcp(&cfg, LogMode::Quiet)?.updated(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn sync_mcp_recreated_server_lifts_tombstone() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.claude_mcp_file,
+            r#"{"mcpServers": {"docs": {"command": "docs"}}}"#,
+        )?;
+        sync_mcp(&cfg, LogMode::Quiet)?;
+        write_plain(&cfg.claude_mcp_file, r#"{"mcpServers": {}}"#)?;
+        sync_mcp(&cfg, LogMode::Quiet)?;
+        assert!(!cfg.central_mcp_dir.join("docs.json").exists());
+
+        write_plain(
+            &cfg.claude_mcp_file,
+            r#"{"mcpServers": {"docs": {"command": "docs-v2"}}}"#,
+        )?;
+        sync_mcp(&cfg, LogMode::Quiet)?;
+
+        let central = read_json(&cfg.central_mcp_dir.join("docs.json"))?;
+        assert_eq!(central["command"], "docs-v2");
+        assert_eq!(
+            read_json(&cfg.cursor_mcp_file)?["mcpServers"]["docs"]["command"],
+            "docs-v2"
+        );
+        let state = load_mcp_state(&cfg.mcp_state_path()?)?;
+        assert!(!state.tombstones.contains_key("docs"));
+        Ok(())
+    }
+
+    #[test]
+    fn mcp_server_removal_drops_only_the_named_entry() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(
+            &cfg.opencode_config_file,
+            r#"{"theme": "dark", "mcp": {"docs": {"type": "remote", "url": "https://d"}, "keep": {"type": "local", "command": ["k"]}}}"#,
+        )?;
+
+        let (path, contents) = mcp_server_removal(&cfg, "docs", TOOL_OPENCODE)?.unwrap();
+        assert_eq!(path, cfg.opencode_config_file);
+        let doc: Value = serde_json::from_slice(&contents).map_err(json_error)?;
+        assert_eq!(doc["theme"], "dark");
+        assert!(doc["mcp"].get("docs").is_none());
+        assert!(doc["mcp"].get("keep").is_some());
+
+        assert!(mcp_server_removal(&cfg, "missing", TOOL_OPENCODE)?.is_none());
+        assert!(mcp_server_removal(&cfg, "docs", TOOL_CLAUDE)?.is_none());
+        Ok(())
+    }
+}
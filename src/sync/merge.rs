@@ -0,0 +1,437 @@
+use super::shared::{log_action, merge_frontmatter, parse_markdown, MarkdownDoc, MarkdownVariant};
+use super::{ExecutionMode, LogMode, SyncConflict, SyncItemKind};
+use crate::atomic::write_atomic;
+use crate::config::Config;
+use crate::history::HistoryStore;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Larger inputs skip the merge and fall back to last-write-wins; the LCS
+/// table is quadratic in the number of lines.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Last synced contents from history, loaded on first use and only when
+/// `three_way_merge` is enabled.
+pub(crate) struct MergeBases {
+    store: Option<HistoryStore>,
+    latest: OnceCell<HashMap<PathBuf, (usize, String)>>,
+}
+
+impl MergeBases {
+    pub(crate) fn new(cfg: &Config) -> io::Result<Self> {
+        let store = if cfg.three_way_merge {
+            Some(HistoryStore::from_config(cfg)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            store,
+            latest: OnceCell::new(),
+        })
+    }
+
+    /// Newest contents relay itself wrote to any of `paths`. Edits made in a
+    /// tool since then are exactly what a merge has to reconcile.
+    pub(crate) fn base_for<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a Path>,
+    ) -> io::Result<Option<String>> {
+        let Some(store) = self.store.as_ref() else {
+            return Ok(None);
+        };
+        if self.latest.get().is_none() {
+            let _ = self.latest.set(store.last_written_files()?);
+        }
+        let Some(latest) = self.latest.get() else {
+            return Ok(None);
+        };
+        let Some((_, blob)) = paths
+            .into_iter()
+            .filter_map(|path| latest.get(path))
+            .max_by_key(|(seq, _)| *seq)
+        else {
+            return Ok(None);
+        };
+        Ok(String::from_utf8(store.blob_contents(blob)?).ok())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Reconciled {
+    /// Every edit applied cleanly.
+    Merged(String),
+    /// Edits overlap; the text carries conflict markers.
+    Conflicted(String),
+}
+
+/// Merges every side that changed since `base`. `sides` are `(label, text)`
+/// pairs, winner first. Returns `None` when nothing diverged from the base or
+/// the inputs are too large to diff.
+pub(crate) fn reconcile(base: &str, sides: &[(&str, &str)]) -> Option<Reconciled> {
+    let mut changed: Vec<(&str, &str)> = Vec::new();
+    for &(label, text) in sides {
+        if text != base && !changed.iter().any(|(_, seen)| *seen == text) {
+            changed.push((label, text));
+        }
+    }
+    let ((first_label, first), rest) = changed.split_first()?;
+    let mut merged = (*first).to_string();
+    let mut merged_label = (*first_label).to_string();
+    for (label, text) in rest {
+        let result = merge3(base, &merged, text, &merged_label, label)?;
+        if result.conflicts > 0 {
+            return Some(Reconciled::Conflicted(result.text));
+        }
+        merged = result.text;
+        merged_label = format!("{merged_label}+{label}");
+    }
+    Some(Reconciled::Merged(merged))
+}
+
+pub(crate) enum MarkdownMerge {
+    /// No base, merge disabled, or nothing to reconcile.
+    NotAttempted,
+    Merged(MarkdownDoc),
+    /// Overlapping edits; the conflict is recorded and a sidecar written.
+    Conflicted,
+}
+
+/// Reconciles markdown bodies against the last synced body from `base_paths`.
+/// A clean merge keeps the winner's frontmatter. Overlapping edits leave the
+/// live files to last-write-wins and write the marked-up body to a sidecar.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn merge_markdown_variants(
+    cfg: &Config,
+    bases: &MergeBases,
+    kind: SyncItemKind,
+    name: &str,
+    variants: &[MarkdownVariant],
+    winner: &MarkdownVariant,
+    base_paths: &[&Path],
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    conflicts: &mut Vec<SyncConflict>,
+) -> io::Result<MarkdownMerge> {
+    if variants
+        .iter()
+        .all(|variant| variant.doc.body_hash == winner.doc.body_hash)
+    {
+        return Ok(MarkdownMerge::NotAttempted);
+    }
+    let Some(base) = bases.base_for(base_paths.iter().copied())? else {
+        return Ok(MarkdownMerge::NotAttempted);
+    };
+    let base_body = parse_markdown(base).body;
+    let mut sides = vec![(winner.tool, winner.doc.body.as_str())];
+    sides.extend(
+        variants
+            .iter()
+            .map(|variant| (variant.tool, variant.doc.body.as_str())),
+    );
+    match reconcile(&base_body, &sides) {
+        None => Ok(MarkdownMerge::NotAttempted),
+        Some(Reconciled::Merged(body)) => {
+            if body != winner.doc.body {
+                log_action(
+                    log_mode,
+                    &format!("{}: merged edits to '{name}'", kind.store_dir()),
+                );
+            }
+            let raw = merge_frontmatter(winner.doc.frontmatter.as_deref(), &body);
+            Ok(MarkdownMerge::Merged(parse_markdown(raw)))
+        }
+        Some(Reconciled::Conflicted(marked)) => {
+            let others = variants
+                .iter()
+                .filter(|variant| variant.doc.body_hash != winner.doc.body_hash)
+                .map(|variant| variant.tool);
+            record_conflict(
+                cfg,
+                kind,
+                name,
+                winner.tool,
+                others,
+                &marked,
+                log_mode,
+                mode,
+                conflicts,
+            )?;
+            Ok(MarkdownMerge::Conflicted)
+        }
+    }
+}
+
+/// Records an overlapping merge as a conflict and writes the marked-up text to
+/// `<conflicts dir>/<kind>/<name>.conflict` so the live files stay usable.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn record_conflict<'a>(
+    cfg: &Config,
+    kind: SyncItemKind,
+    name: &str,
+    winner: &str,
+    others: impl Iterator<Item = &'a str>,
+    marked: &str,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    conflicts: &mut Vec<SyncConflict>,
+) -> io::Result<()> {
+    let mut other_tools: Vec<String> = Vec::new();
+    for tool in others {
+        if tool != winner && !other_tools.iter().any(|other| other == tool) {
+            other_tools.push(tool.to_string());
+        }
+    }
+    conflicts.push(SyncConflict {
+        kind,
+        name: name.to_string(),
+        winner: winner.to_string(),
+        others: other_tools,
+    });
+    let sidecar = crate::conflicts::sidecar_path(cfg, kind.store_dir(), name)?;
+    let verb = if mode == ExecutionMode::Plan {
+        "would write"
+    } else {
+        if let Some(parent) = sidecar.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomic(&sidecar, marked.as_bytes())?;
+        "wrote"
+    };
+    log_action(
+        log_mode,
+        &format!(
+            "warning: {} '{name}' has overlapping edits; kept {winner} and {verb} conflict markers to {}",
+            kind.store_dir(),
+            sidecar.display()
+        ),
+    );
+    Ok(())
+}
+
+struct MergeText {
+    text: String,
+    conflicts: usize,
+}
+
+/// A replacement of `base[start..end]` by `lines`.
+struct Hunk<'a> {
+    start: usize,
+    end: usize,
+    lines: Vec<&'a str>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Ours,
+    Theirs,
+}
+
+fn merge3(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    ours_label: &str,
+    theirs_label: &str,
+) -> Option<MergeText> {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let mut hunks: Vec<(Side, Hunk)> = Vec::new();
+    hunks.extend(
+        diff_hunks(&base_lines, &ours_lines)?
+            .into_iter()
+            .map(|hunk| (Side::Ours, hunk)),
+    );
+    hunks.extend(
+        diff_hunks(&base_lines, &theirs_lines)?
+            .into_iter()
+            .map(|hunk| (Side::Theirs, hunk)),
+    );
+    hunks.sort_by_key(|(_, hunk)| (hunk.start, hunk.end));
+
+    let mut out = String::new();
+    let mut conflicts = 0;
+    let mut pos = 0;
+    let mut next = 0;
+    while next < hunks.len() {
+        // Hunks that overlap or touch form one region; touching edits from
+        // both sides are treated as a conflict, as diff3 does.
+        let start = hunks[next].1.start;
+        let mut end = hunks[next].1.end;
+        let mut last = next + 1;
+        while last < hunks.len() && hunks[last].1.start <= end {
+            end = end.max(hunks[last].1.end);
+            last += 1;
+        }
+        let region = &hunks[next..last];
+        out.push_str(&base_lines[pos..start].concat());
+
+        let has = |side: Side| region.iter().any(|(hunk_side, _)| *hunk_side == side);
+        let ours_text = apply_region(&base_lines, start, end, region, Side::Ours);
+        let theirs_text = apply_region(&base_lines, start, end, region, Side::Theirs);
+        if !has(Side::Theirs) || ours_text == theirs_text {
+            out.push_str(&ours_text);
+        } else if !has(Side::Ours) {
+            out.push_str(&theirs_text);
+        } else {
+            conflicts += 1;
+            out.push_str(&format!("<<<<<<< {ours_label}\n"));
+            push_section(&mut out, &ours_text);
+            out.push_str("||||||| base\n");
+            push_section(&mut out, &base_lines[start..end].concat());
+            out.push_str("=======\n");
+            push_section(&mut out, &theirs_text);
+            out.push_str(&format!(">>>>>>> {theirs_label}\n"));
+        }
+        pos = end;
+        next = last;
+    }
+    out.push_str(&base_lines[pos..].concat());
+    Some(MergeText {
+        text: out,
+        conflicts,
+    })
+}
+
+fn apply_region(
+    base: &[&str],
+    start: usize,
+    end: usize,
+    region: &[(Side, Hunk)],
+    side: Side,
+) -> String {
+    let mut out = String::new();
+    let mut pos = start;
+    for (_, hunk) in region.iter().filter(|(hunk_side, _)| *hunk_side == side) {
+        out.push_str(&base[pos..hunk.start].concat());
+        out.push_str(&hunk.lines.concat());
+        pos = hunk.end;
+    }
+    out.push_str(&base[pos..end].concat());
+    out
+}
+
+fn push_section(out: &mut String, text: &str) {
+    out.push_str(text);
+    if !text.is_empty() && !text.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Line hunks turning `base` into `side`, from a longest-common-subsequence
+/// alignment.
+fn diff_hunks<'a>(base: &[&str], side: &[&'a str]) -> Option<Vec<Hunk<'a>>> {
+    let (n, m) = (base.len(), side.len());
+    if (n + 1).saturating_mul(m + 1) > MAX_DIFF_CELLS {
+        return None;
+    }
+    let width = m + 1;
+    let mut lcs = vec![0u32; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * width + j] = if base[i] == side[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let (mut hunk_i, mut hunk_j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && base[i] == side[j] {
+            if hunk_i < i || hunk_j < j {
+                hunks.push(Hunk {
+                    start: hunk_i,
+                    end: i,
+                    lines: side[hunk_j..j].to_vec(),
+                });
+            }
+            i += 1;
+            j += 1;
+            hunk_i = i;
+            hunk_j = j;
+        } else if j < m && (i == n || lcs[i * width + j + 1] >= lcs[(i + 1) * width + j]) {
+            j += 1;
+        } else {
+            i += 1;
+        }
+    }
+    if hunk_i < n || hunk_j < m {
+        hunks.push(Hunk {
+            start: hunk_i,
+            end: n,
+            lines: side[hunk_j..].to_vec(),
+        });
+    }
+    Some(hunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconcile_combines_non_overlapping_edits() {
+        let base = "one\ntwo\nthree\nfour\n";
+        let claude = "ONE\ntwo\nthree\nfour\n";
+        let opencode = "one\ntwo\nthree\nFOUR\n";
+
+        let merged = reconcile(base, &[("claude", claude), ("opencode", opencode)]);
+
+        assert_eq!(
+            merged,
+            Some(Reconciled::Merged("ONE\ntwo\nthree\nFOUR\n".to_string()))
+        );
+    }
+
+    #[test]
+    fn reconcile_takes_the_only_changed_side() {
+        let base = "a\nb\n";
+        let merged = reconcile(base, &[("central", base), ("claude", "a\nb\nc\n")]);
+        assert_eq!(merged, Some(Reconciled::Merged("a\nb\nc\n".to_string())));
+        assert_eq!(reconcile(base, &[("central", base)]), None);
+    }
+
+    #[test]
+    fn reconcile_marks_overlapping_edits() {
+        let base = "keep\nshared\ntail";
+        let merged = reconcile(
+            base,
+            &[
+                ("claude", "keep\nclaude\ntail"),
+                ("cursor", "keep\ncursor\ntail"),
+            ],
+        );
+
+        assert_eq!(
+            merged,
+            Some(Reconciled::Conflicted(
+                "keep\n<<<<<<< claude\nclaude\n||||||| base\nshared\n=======\ncursor\n>>>>>>> cursor\ntail"
+                    .to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn reconcile_accepts_identical_edits_and_insertions() {
+        let base = "a\nb\nc\n";
+        let merged = reconcile(
+            base,
+            &[
+                ("claude", "a\nx\nb\nc\n"),
+                ("codex", "a\nx\nb\nc\n"),
+                ("opencode", "a\nb\nc\nend\n"),
+            ],
+        );
+        assert_eq!(
+            merged,
+            Some(Reconciled::Merged("a\nx\nb\nc\nend\n".to_string()))
+        );
+    }
+}
@@ -1,15 +1,26 @@
-use crate::config::Config;
+use crate::config::{Config, ConflictPolicy};
 use crate::history::HistoryRecorder;
+use std::fmt;
 use std::io;
 
 mod agents;
 mod codex_commands;
+mod command_format;
+mod command_renames;
 mod commands;
+mod mcp;
+mod merge;
 mod rules;
 mod shared;
 mod skills;
+mod subagents;
 
-pub(crate) use skills::{discover_scoped_skills, ScopedSkill};
+pub(crate) use codex_commands::command_skill_name;
+pub(crate) use command_format::CommandFormat;
+pub(crate) use commands::{discover_scoped_commands, ScopedCommand};
+pub(crate) use mcp::mcp_server_removal;
+pub(crate) use shared::{ChangeOp, FileChange, PlannedContent, PlannedFile};
+pub(crate) use skills::{discover_scoped_skills, ScopedSkill, SkillDiagnostics};
 
 #[cfg(test)]
 pub(crate) mod test_support;
@@ -32,41 +43,218 @@ pub(crate) enum SyncItemKind {
     Skill,
     Agent,
     Rule,
+    Mcp,
+    Subagent,
+}
+
+impl SyncItemKind {
+    pub(crate) const ALL: [Self; 6] = [
+        Self::Command,
+        Self::Skill,
+        Self::Agent,
+        Self::Rule,
+        Self::Mcp,
+        Self::Subagent,
+    ];
+
+    pub(crate) fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.id() == id)
+    }
+
+    /// Singular name used in machine-readable output.
+    pub(crate) fn id(self) -> &'static str {
+        match self {
+            Self::Command => "command",
+            Self::Skill => "skill",
+            Self::Agent => "agent",
+            Self::Rule => "rule",
+            Self::Mcp => "mcp",
+            Self::Subagent => "subagent",
+        }
+    }
+
+    /// Directory name used for this kind in the central store.
+    pub(crate) fn store_dir(self) -> &'static str {
+        match self {
+            Self::Command => "commands",
+            Self::Skill => "skills",
+            Self::Agent => "agents",
+            Self::Rule => "rules",
+            Self::Mcp => "mcp",
+            Self::Subagent => "subagents",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct SyncConflict {
     pub kind: SyncItemKind,
     pub name: String,
-    pub winner: &'static str,
-    pub others: Vec<&'static str>,
+    pub winner: String,
+    pub others: Vec<String>,
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+/// Returned when a `fail` conflict policy stops an applied sync before it
+/// writes anything.
+#[derive(Debug)]
+pub(crate) struct ConflictPolicyAbort {
+    pub conflicts: Vec<SyncConflict>,
+}
+
+impl fmt::Display for ConflictPolicyAbort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items: Vec<String> = self
+            .conflicts
+            .iter()
+            .map(|conflict| format!("{}/{}", conflict.kind.store_dir(), conflict.name))
+            .collect();
+        write!(
+            f,
+            "sync aborted by conflict policy `fail`: {}",
+            items.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ConflictPolicyAbort {}
+
+pub(crate) fn is_conflict_policy_abort(err: &io::Error) -> bool {
+    err.get_ref()
+        .is_some_and(|inner| inner.is::<ConflictPolicyAbort>())
+}
+
+/// Returned when `--fail-on-conflict` or a scoped sync's canonical check
+/// stops the run before it writes anything.
+#[derive(Debug)]
+pub(crate) struct ConflictAbort {
+    pub message: String,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+impl fmt::Display for ConflictAbort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ConflictAbort {}
+
+/// The conflicts behind an aborted sync, whichever check stopped it.
+#[cfg_attr(any(test, coverage), allow(dead_code))]
+pub(crate) fn aborted_conflicts(err: &io::Error) -> Option<&[SyncConflict]> {
+    let inner = err.get_ref()?;
+    if let Some(abort) = inner.downcast_ref::<ConflictPolicyAbort>() {
+        return Some(&abort.conflicts);
+    }
+    inner
+        .downcast_ref::<ConflictAbort>()
+        .map(|abort| abort.conflicts.as_slice())
+}
+
+/// One write or removal sync made, or would make in plan mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SyncAction {
+    pub name: String,
+    pub tool: String,
+    pub change: FileChange,
+}
+
+#[derive(Debug, Default, Clone)]
 pub(crate) struct SyncStats {
-    pub updated: usize,
+    pub actions: Vec<SyncAction>,
+    /// Relay's own state files the sync saved, or would save in plan mode.
+    /// They are not items, so they never count as updates.
+    pub state: Vec<FileChange>,
 }
 
 impl SyncStats {
+    pub(crate) fn updated(&self) -> usize {
+        self.actions.len()
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
-        self.updated == 0
+        self.actions.is_empty()
+    }
+
+    /// Records `change` against `name` in `tool`; returns whether there was one.
+    pub(crate) fn record(&mut self, name: &str, tool: &str, change: Option<FileChange>) -> bool {
+        let Some(change) = change else {
+            return false;
+        };
+        self.actions.push(SyncAction {
+            name: name.to_string(),
+            tool: tool.to_string(),
+            change,
+        });
+        true
+    }
+
+    pub(crate) fn record_state(&mut self, change: Option<FileChange>) {
+        self.state.extend(change);
+    }
+
+    pub(crate) fn extend(&mut self, other: SyncStats) {
+        self.actions.extend(other.actions);
+        self.state.extend(other.state);
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone)]
 pub(crate) struct SyncReport {
     pub commands: SyncStats,
     pub skills: SyncStats,
     pub agents: SyncStats,
     pub rules: SyncStats,
+    pub mcp: SyncStats,
+    pub subagents: SyncStats,
 }
 
 impl SyncReport {
+    /// Per-kind stats in the order summaries list them.
+    pub(crate) fn by_kind(&self) -> [(SyncItemKind, &SyncStats); 6] {
+        [
+            (SyncItemKind::Command, &self.commands),
+            (SyncItemKind::Skill, &self.skills),
+            (SyncItemKind::Agent, &self.agents),
+            (SyncItemKind::Rule, &self.rules),
+            (SyncItemKind::Mcp, &self.mcp),
+            (SyncItemKind::Subagent, &self.subagents),
+        ]
+    }
+
+    /// One action per path, in report order. Several records can share a
+    /// file (every MCP server in a tool config); the last one describes what
+    /// would end up on disk.
+    pub(crate) fn targets(&self) -> Vec<(SyncItemKind, &SyncAction)> {
+        let mut targets: Vec<(SyncItemKind, &SyncAction)> = Vec::new();
+        for (kind, stats) in self.by_kind() {
+            for action in &stats.actions {
+                match targets
+                    .iter_mut()
+                    .find(|(_, seen)| seen.change.path == action.change.path)
+                {
+                    Some(target) => *target = (kind, action),
+                    None => targets.push((kind, action)),
+                }
+            }
+        }
+        targets
+    }
+
+    /// Every state-file change, in report order.
+    pub(crate) fn state_changes(&self) -> impl Iterator<Item = &FileChange> {
+        self.by_kind()
+            .into_iter()
+            .flat_map(|(_, stats)| &stats.state)
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         self.commands.is_empty()
             && self.skills.is_empty()
             && self.agents.is_empty()
             && self.rules.is_empty()
+            && self.mcp.is_empty()
+            && self.subagents.is_empty()
     }
 }
 
@@ -88,7 +276,7 @@ pub(crate) fn sync_all(cfg: &Config, log_mode: LogMode) -> io::Result<SyncReport
     Ok(sync_all_with_mode(cfg, log_mode, ExecutionMode::Apply, "sync")?.report)
 }
 
-pub(crate) fn skill_diagnostics(cfg: &Config) -> io::Result<Vec<String>> {
+pub(crate) fn skill_diagnostics(cfg: &Config) -> io::Result<SkillDiagnostics> {
     skills::diagnostics(cfg)
 }
 
@@ -98,6 +286,17 @@ pub(crate) fn sync_all_with_mode(
     mode: ExecutionMode,
     origin: &str,
 ) -> io::Result<SyncOutcome> {
+    if mode == ExecutionMode::Apply && cfg.conflict_policy.any_fail() {
+        let preflight = sync_all_with_mode(cfg, LogMode::Quiet, ExecutionMode::Plan, origin)?;
+        let blocked: Vec<SyncConflict> = preflight
+            .conflicts
+            .into_iter()
+            .filter(|conflict| cfg.conflict_policy.for_kind(conflict.kind) == &ConflictPolicy::Fail)
+            .collect();
+        if !blocked.is_empty() {
+            return Err(io::Error::other(ConflictPolicyAbort { conflicts: blocked }));
+        }
+    }
     let mut history = if mode == ExecutionMode::Apply {
         Some(HistoryRecorder::new(cfg, origin)?)
     } else {
@@ -115,15 +314,23 @@ pub(crate) fn sync_all_with_mode(
         &skill_outcome.codex_real_skill_names,
     )?;
     let agents = agents::sync_agents_with_mode(cfg, log_mode, mode, &mut history, &mut conflicts)?;
+    let subagents =
+        subagents::sync_subagents_with_mode(cfg, log_mode, mode, &mut history, &mut conflicts)?;
     let rules = rules::sync_rules_with_mode(cfg, log_mode, mode, &mut history, &mut conflicts)?;
+    let mcp = mcp::sync_mcp_with_mode(cfg, log_mode, mode, &mut history, &mut conflicts)?;
     let report = SyncReport {
         commands,
         skills: skill_outcome.stats,
         agents,
         rules,
+        mcp,
+        subagents,
     };
     let history_event_id = match history {
-        Some(recorder) => recorder.finish()?,
+        Some(mut recorder) => {
+            recorder.describe_sync(cfg, &report, &conflicts);
+            recorder.finish()?
+        }
         None => None,
     };
     Ok(SyncOutcome {
@@ -155,26 +362,75 @@ pub(crate) fn sync_scoped_skills_with_mode(
         &mut conflicts,
     ) {
         Ok(outcome) => outcome,
-        Err(sync_err) => {
-            if let Some(recorder) = history.take() {
-                if let Err(rollback_err) = recorder.rollback_pending() {
-                    return Err(io::Error::new(
-                        sync_err.kind(),
-                        format!(
-                            "scoped sync failed ({sync_err}) and failed to revert earlier writes ({rollback_err})"
-                        ),
-                    ));
-                }
-            }
-            return Err(sync_err);
-        }
+        Err(sync_err) => return Err(revert_scoped_sync(history, sync_err)),
     };
     let report = SyncReport {
         skills: skill_outcome.stats,
         ..SyncReport::default()
     };
     let history_event_id = match history {
-        Some(recorder) => recorder.finish()?,
+        Some(mut recorder) => {
+            recorder.describe_sync(cfg, &report, &conflicts);
+            recorder.finish()?
+        }
+        None => None,
+    };
+    Ok(SyncOutcome {
+        report,
+        conflicts,
+        history_event_id,
+    })
+}
+
+pub(crate) fn sync_scoped_commands_with_mode(
+    cfg: &Config,
+    selected: &[ScopedCommand],
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    origin: &str,
+) -> io::Result<SyncOutcome> {
+    if mode == ExecutionMode::Apply
+        && cfg.conflict_policy.for_kind(SyncItemKind::Command) == &ConflictPolicy::Fail
+    {
+        let preflight = sync_scoped_commands_with_mode(
+            cfg,
+            selected,
+            LogMode::Quiet,
+            ExecutionMode::Plan,
+            origin,
+        )?;
+        if !preflight.conflicts.is_empty() {
+            return Err(io::Error::other(ConflictPolicyAbort {
+                conflicts: preflight.conflicts,
+            }));
+        }
+    }
+    let mut history = if mode == ExecutionMode::Apply {
+        Some(HistoryRecorder::new(cfg, origin)?)
+    } else {
+        None
+    };
+    let mut conflicts = Vec::new();
+    let commands = match commands::sync_scoped_commands_with_mode(
+        cfg,
+        selected,
+        log_mode,
+        mode,
+        &mut history,
+        &mut conflicts,
+    ) {
+        Ok(stats) => stats,
+        Err(sync_err) => return Err(revert_scoped_sync(history, sync_err)),
+    };
+    let report = SyncReport {
+        commands,
+        ..SyncReport::default()
+    };
+    let history_event_id = match history {
+        Some(mut recorder) => {
+            recorder.describe_sync(cfg, &report, &conflicts);
+            recorder.finish()?
+        }
         None => None,
     };
     Ok(SyncOutcome {
@@ -184,6 +440,21 @@ pub(crate) fn sync_scoped_skills_with_mode(
     })
 }
 
+/// Reverts the writes a failed scoped sync already made.
+fn revert_scoped_sync(history: Option<HistoryRecorder>, sync_err: io::Error) -> io::Error {
+    if let Some(recorder) = history {
+        if let Err(rollback_err) = recorder.rollback_pending() {
+            return io::Error::new(
+                sync_err.kind(),
+                format!(
+                    "scoped sync failed ({sync_err}) and failed to revert earlier writes ({rollback_err})"
+                ),
+            );
+        }
+    }
+    sync_err
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -198,7 +469,7 @@ mod tests {
         write_plain(&cfg.claude_dir.join("demo.md"), "hello")?;
 
         let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
-        assert!(outcome.report.commands.updated > 0);
+        assert!(outcome.report.commands.updated() > 0);
         assert!(outcome.conflicts.is_empty());
         assert!(outcome.history_event_id.is_some());
 
@@ -209,10 +480,345 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn plan_content_matches_what_apply_writes() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(&cfg.claude_dir.join("review.md"), "Review the diff\n")?;
+        write_skill(
+            &cfg.central_skills_dir,
+            "notes",
+            &doc("notes", "Take notes"),
+        )?;
+        write_plain(&cfg.central_skills_dir.join("notes/extra.md"), "extra\n")?;
+        write_skill(&cfg.claude_skills_dir, "draft", &doc("draft", "Imported"))?;
+        write_plain(&cfg.codex_agents_file, "codex agents\n")?;
+
+        let plan = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Plan, "sync")?;
+        let planned: Vec<&FileChange> = plan
+            .report
+            .by_kind()
+            .into_iter()
+            .flat_map(|(_, stats)| stats.actions.iter().map(|action| &action.change))
+            .filter(|change| change.op != shared::ChangeOp::Delete)
+            .collect();
+        assert!(!planned.is_empty());
+        assert!(planned.iter().all(|change| change.content.is_some()));
+
+        let applied = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+        assert!(applied.report.by_kind().into_iter().all(|(_, stats)| stats
+            .actions
+            .iter()
+            .all(|action| action.change.content.is_none())));
+        for change in planned {
+            assert_eq!(
+                PlannedContent::read(&change.path)?.as_ref(),
+                change.content.as_ref(),
+                "{}",
+                change.path.display()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn three_way_merge_combines_command_edits_from_two_tools() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        cfg.three_way_merge = true;
+        write_plain(&cfg.central_dir.join("review.md"), "one\ntwo\nthree\n")?;
+        sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        let claude = cfg.claude_dir.join("review.md");
+        let cursor = cfg.cursor_dir.join("review.md");
+        write_plain(&claude, "ONE\ntwo\nthree\n")?;
+        write_plain(&cursor, "one\ntwo\nTHREE\n")?;
+        sync::test_support::set_mtime(&claude, 100)?;
+        sync::test_support::set_mtime(&cursor, 101)?;
+
+        let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        assert!(outcome.conflicts.is_empty());
+        for path in [&claude, &cursor, &cfg.central_dir.join("review.md")] {
+            assert_eq!(fs::read_to_string(path)?, "ONE\ntwo\nTHREE\n");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn three_way_merge_writes_sidecar_for_overlapping_rule_edits() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        cfg.three_way_merge = true;
+        write_plain(&cfg.codex_rules_file, "rule(\"a\")\n")?;
+        sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        let central = cfg.central_rules_dir.join("codex/default.rules");
+        write_plain(&cfg.codex_rules_file, "rule(\"codex\")\n")?;
+        write_plain(&central, "rule(\"central\")\n")?;
+        sync::test_support::set_mtime(&cfg.codex_rules_file, 100)?;
+        sync::test_support::set_mtime(&central, 101)?;
+
+        let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        assert_eq!(
+            outcome.conflicts,
+            vec![SyncConflict {
+                kind: SyncItemKind::Rule,
+                name: "codex/default.rules".to_string(),
+                winner: "central".to_string(),
+                others: vec![config::TOOL_CODEX.to_string()],
+            }]
+        );
+        assert_eq!(
+            fs::read_to_string(&cfg.codex_rules_file)?,
+            "rule(\"central\")\n"
+        );
+        let sidecar = cfg
+            .conflicts_dir()?
+            .join("rules/codex/default.rules.conflict");
+        assert_eq!(
+            fs::read_to_string(sidecar)?,
+            "<<<<<<< central\nrule(\"central\")\n||||||| base\nrule(\"a\")\n=======\nrule(\"codex\")\n>>>>>>> codex\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn three_way_merge_without_history_keeps_last_write_wins() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        cfg.three_way_merge = true;
+        let claude = cfg.claude_dir.join("review.md");
+        let cursor = cfg.cursor_dir.join("review.md");
+        write_plain(&claude, "claude\n")?;
+        write_plain(&cursor, "cursor\n")?;
+        sync::test_support::set_mtime(&claude, 100)?;
+        sync::test_support::set_mtime(&cursor, 101)?;
+
+        let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        assert_eq!(outcome.conflicts.len(), 1);
+        assert_eq!(fs::read_to_string(&claude)?, "cursor\n");
+        assert!(!cfg.conflicts_dir()?.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn preserve_conflicts_saves_losing_copies_before_overwrite() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        cfg.preserve_conflicts = true;
+        let claude = cfg.claude_dir.join("review.md");
+        let cursor = cfg.cursor_dir.join("review.md");
+        write_plain(&claude, "claude\n")?;
+        write_plain(&cursor, "cursor\n")?;
+        sync::test_support::set_mtime(&claude, 100)?;
+        sync::test_support::set_mtime(&cursor, 101)?;
+        let claude_skill = write_skill(&cfg.claude_skills_dir, "plan", &doc("claude", "Old"))?;
+        let codex_skill = write_skill(&cfg.codex_skills_dir, "plan", &doc("codex", "New"))?;
+        sync::test_support::set_mtime(&claude_skill.join("SKILL.md"), 100)?;
+        sync::test_support::set_mtime(&codex_skill.join("SKILL.md"), 101)?;
+
+        let plan = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Plan, "sync")?;
+        assert_eq!(plan.conflicts.len(), 2);
+        assert!(!cfg.conflicts_dir()?.exists());
+
+        sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        assert_eq!(fs::read_to_string(&claude)?, "cursor\n");
+        let (dir, manifest) = crate::conflicts::find_conflict(&cfg, "review.md")?;
+        assert_eq!(manifest.winner(), Some(config::TOOL_CURSOR));
+        let saved = &manifest.variants[0];
+        assert_eq!(saved.tool, config::TOOL_CLAUDE);
+        assert_eq!(saved.source, claude);
+        assert_eq!(fs::read_to_string(dir.join(&saved.file))?, "claude\n");
+
+        let (dir, manifest) = crate::conflicts::find_conflict(&cfg, "skills/plan")?;
+        let saved = &manifest.variants[0];
+        assert!(saved.dir);
+        assert!(fs::read_to_string(dir.join(&saved.file).join("SKILL.md"))?.contains("Old"));
+
+        crate::conflicts::resolve_conflict(&cfg, "review.md", config::TOOL_CLAUDE)?;
+        assert_eq!(fs::read_to_string(&claude)?, "claude\n");
+        Ok(())
+    }
+
+    #[test]
+    fn conflict_policy_priority_and_central_override_newest() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        cfg.conflict_policy.agents = Some(ConflictPolicy::Priority(vec![
+            config::TOOL_CLAUDE.to_string()
+        ]));
+        cfg.conflict_policy.rules = Some(ConflictPolicy::Central);
+        write_plain(&cfg.claude_agents_file, "claude agents\n")?;
+        write_plain(&cfg.codex_agents_file, "codex agents\n")?;
+        sync::test_support::set_mtime(&cfg.claude_agents_file, 100)?;
+        sync::test_support::set_mtime(&cfg.codex_agents_file, 101)?;
+        let central_rules = cfg.central_rules_dir.join("codex/default.rules");
+        write_plain(&central_rules, "rule(\"central\")")?;
+        write_plain(&cfg.codex_rules_file, "rule(\"codex\")")?;
+        sync::test_support::set_mtime(&central_rules, 100)?;
+        sync::test_support::set_mtime(&cfg.codex_rules_file, 101)?;
+
+        let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        assert_eq!(
+            fs::read_to_string(&cfg.codex_agents_file)?,
+            "claude agents\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&cfg.codex_rules_file)?,
+            "rule(\"central\")"
+        );
+        assert!(outcome.conflicts.contains(&SyncConflict {
+            kind: SyncItemKind::Agent,
+            name: "AGENTS.md".to_string(),
+            winner: config::TOOL_CLAUDE.to_string(),
+            others: vec![config::TOOL_CODEX.to_string()],
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn agent_and_rule_policies_apply_to_sequential_edits() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        cfg.conflict_policy.agents = Some(ConflictPolicy::Priority(vec![
+            config::TOOL_CLAUDE.to_string()
+        ]));
+        cfg.conflict_policy.rules = Some(ConflictPolicy::Central);
+        let central_rules = cfg.central_rules_dir.join("codex/default.rules");
+        write_plain(&cfg.claude_agents_file, "agents\n")?;
+        write_plain(&central_rules, "rule(\"base\")")?;
+        sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        write_plain(&cfg.claude_agents_file, "claude agents\n")?;
+        write_plain(&cfg.codex_agents_file, "codex agents\n")?;
+        sync::test_support::set_mtime(&cfg.claude_agents_file, 100)?;
+        sync::test_support::set_mtime(&cfg.codex_agents_file, 900)?;
+        write_plain(&central_rules, "rule(\"central\")")?;
+        write_plain(&cfg.codex_rules_file, "rule(\"codex\")")?;
+        sync::test_support::set_mtime(&central_rules, 100)?;
+        sync::test_support::set_mtime(&cfg.codex_rules_file, 900)?;
+
+        let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        assert_eq!(
+            fs::read_to_string(&cfg.codex_agents_file)?,
+            "claude agents\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&cfg.codex_rules_file)?,
+            "rule(\"central\")"
+        );
+        assert_eq!(outcome.conflicts.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn conflict_policy_only_applies_to_competing_edits() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        cfg.conflict_policy.rules = Some(ConflictPolicy::Central);
+        let central_rules = cfg.central_rules_dir.join("codex/default.rules");
+        write_plain(&central_rules, "rule(\"central\")")?;
+        sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+        write_plain(&cfg.codex_rules_file, "rule(\"codex\")")?;
+
+        let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        assert_eq!(fs::read_to_string(&central_rules)?, "rule(\"codex\")");
+        assert!(outcome.conflicts.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn conflict_policy_applies_to_edits_made_apart_since_the_last_sync() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        cfg.conflict_policy.commands = Some(ConflictPolicy::Priority(vec![
+            config::TOOL_CLAUDE.to_string()
+        ]));
+        let claude = cfg.claude_dir.join("review.md");
+        let cursor = cfg.cursor_dir.join("review.md");
+        write_plain(&claude, "base\n")?;
+        sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+        write_plain(&claude, "claude\n")?;
+        write_plain(&cursor, "cursor\n")?;
+        sync::test_support::set_mtime(&claude, 100)?;
+        sync::test_support::set_mtime(&cursor, 500)?;
+
+        let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        // Both copies changed since the last sync, minutes apart; priority
+        // still decides over the newer Cursor edit.
+        assert_eq!(
+            fs::read_to_string(cfg.central_dir.join("review.md"))?,
+            "claude\n"
+        );
+        assert_eq!(fs::read_to_string(&cursor)?, "claude\n");
+        assert_eq!(
+            outcome.conflicts,
+            vec![SyncConflict {
+                kind: SyncItemKind::Command,
+                name: "review.md".to_string(),
+                winner: config::TOOL_CLAUDE.to_string(),
+                others: vec![config::TOOL_CURSOR.to_string()],
+            }]
+        );
+
+        write_plain(&cursor, "cursor again\n")?;
+        let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+        assert_eq!(fs::read_to_string(&claude)?, "cursor again\n");
+        assert!(outcome.conflicts.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn conflict_policy_fail_aborts_before_writing() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        cfg.conflict_policy.commands = Some(ConflictPolicy::Fail);
+        let claude = cfg.claude_dir.join("review.md");
+        let cursor = cfg.cursor_dir.join("review.md");
+        write_plain(&claude, "claude\n")?;
+        write_plain(&cursor, "cursor\n")?;
+        sync::test_support::set_mtime(&claude, 100)?;
+        sync::test_support::set_mtime(&cursor, 101)?;
+
+        let plan = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Plan, "sync")?;
+        assert_eq!(plan.conflicts.len(), 1);
+
+        let err =
+            sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync").unwrap_err();
+        assert!(is_conflict_policy_abort(&err));
+        assert!(err.to_string().contains("commands/review.md"));
+        assert_eq!(fs::read_to_string(&claude)?, "claude\n");
+        assert!(!cfg.central_dir.join("review.md").exists());
+        assert!(HistoryStore::from_config(&cfg)?.list_recent(5)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn conflict_policy_newest_lets_skill_adapter_beat_canonical() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        let claude_skill = write_skill(&cfg.claude_skills_dir, "plan", &doc("plan", "Initial"))?;
+        sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+        let canonical = cfg.central_skills_dir.join("plan/SKILL.md");
+        write_plain(&canonical, &doc("plan", "Canonical edit"))?;
+        write_plain(&claude_skill.join("SKILL.md"), &doc("plan", "Claude edit"))?;
+        sync::test_support::set_mtime(&canonical, 100)?;
+        sync::test_support::set_mtime(&claude_skill.join("SKILL.md"), 200)?;
+        cfg.conflict_policy.skills = Some(ConflictPolicy::Newest);
+
+        let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+
+        assert!(fs::read_to_string(&canonical)?.contains("Claude edit"));
+        assert!(outcome.conflicts.contains(&SyncConflict {
+            kind: SyncItemKind::Skill,
+            name: "plan".to_string(),
+            winner: config::TOOL_CLAUDE.to_string(),
+            others: vec![shared::TOOL_CENTRAL.to_string()],
+        }));
+        Ok(())
+    }
+
     #[test]
     fn skill_diagnostics_are_available_for_status() -> io::Result<()> {
         let (_tmp, cfg) = setup()?;
-        let lines = skill_diagnostics(&cfg)?;
+        let lines = skill_diagnostics(&cfg)?.lines();
         assert!(lines[0].starts_with("skills: canonical="));
         Ok(())
     }
@@ -251,10 +857,10 @@ mod tests {
             "sync:scoped",
         )?;
 
-        assert_eq!(scoped.report.commands.updated, 0);
-        assert_eq!(scoped.report.agents.updated, 0);
-        assert_eq!(scoped.report.rules.updated, 0);
-        assert!(scoped.report.skills.updated > 0);
+        assert_eq!(scoped.report.commands.updated(), 0);
+        assert_eq!(scoped.report.agents.updated(), 0);
+        assert_eq!(scoped.report.rules.updated(), 0);
+        assert!(scoped.report.skills.updated() > 0);
         assert!(!cfg.central_dir.join("pending.md").exists());
         assert!(!cfg.central_agents_dir.join("codex/AGENTS.md").exists());
         assert!(!cfg.central_rules_dir.join("codex/default.rules").exists());
@@ -266,9 +872,9 @@ mod tests {
         assert_eq!(state_after["skills"]["unrelated"], unrelated_state_before);
 
         let full = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
-        assert!(full.report.commands.updated > 0);
-        assert!(full.report.agents.updated > 0);
-        assert!(full.report.rules.updated > 0);
+        assert!(full.report.commands.updated() > 0);
+        assert!(full.report.agents.updated() > 0);
+        assert!(full.report.rules.updated() > 0);
         assert!(!cfg.claude_skills_dir.join("unrelated/SKILL.md").exists());
         Ok(())
     }
@@ -399,7 +1005,7 @@ mod tests {
         )?;
 
         assert_eq!(outcome.conflicts.len(), 1);
-        assert_eq!(outcome.report.skills.updated, 0);
+        assert_eq!(outcome.report.skills.updated(), 0);
         assert_eq!(outcome.history_event_id, None);
         assert!(!cfg.central_skills_dir.join("valid").exists());
         for root in [
@@ -460,6 +1066,36 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn scoped_command_sync_records_one_event_for_selected_writes() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        write_plain(&cfg.claude_dir.join("other.md"), &doc("other", "Other"))?;
+        let source = tempfile::TempDir::new()?;
+        let draft = source.path().join("draft.md");
+        write_plain(&draft, &doc("draft", "Draft"))?;
+        let selected = discover_scoped_commands(&cfg, &[draft])?;
+
+        let outcome = sync_scoped_commands_with_mode(
+            &cfg,
+            &selected,
+            LogMode::Quiet,
+            ExecutionMode::Apply,
+            "sync:scoped",
+        )?;
+
+        assert!(outcome.report.commands.updated() > 0);
+        assert_eq!(outcome.report.skills.updated(), 0);
+        assert!(cfg.cursor_dir.join("draft.md").exists());
+        assert!(!cfg.central_dir.join("other.md").exists());
+        let event_id = outcome.history_event_id.expect("scoped history event");
+        let store = HistoryStore::from_config(&cfg)?;
+        assert_eq!(store.list_recent(5)?[0].origin, "sync:scoped");
+        store.rollback(&event_id, false)?;
+        assert!(!cfg.cursor_dir.join("draft.md").exists());
+        assert!(!cfg.codex_skills_dir.join("draft/SKILL.md").exists());
+        Ok(())
+    }
+
     #[test]
     fn scoped_mid_assembly_failures_clean_temps_and_preserve_transaction_state() -> io::Result<()> {
         let _env = crate::ENV_LOCK
@@ -815,7 +1451,7 @@ mod tests {
             ExecutionMode::Apply,
             "sync:scoped",
         )?;
-        assert!(first.report.skills.updated > 0);
+        assert!(first.report.skills.updated() > 0);
 
         let state_path = cfg.skill_state_path()?;
         let state_before = fs::read(&state_path)?;
@@ -846,7 +1482,7 @@ mod tests {
             "sync:scoped",
         )?;
 
-        assert_eq!(second.report.skills.updated, 0);
+        assert_eq!(second.report.skills.updated(), 0);
         assert_eq!(second.history_event_id, None);
         assert_eq!(fs::read(&state_path)?, state_before);
         assert_eq!(fs::metadata(&state_path)?.modified()?, state_mtime);
@@ -1139,7 +1775,7 @@ mod tests {
 
         let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
 
-        assert!(outcome.report.skills.updated > 0);
+        assert!(outcome.report.skills.updated() > 0);
         let skill = fs::read_to_string(cfg.codex_skills_dir.join("map/SKILL.md"))?;
         assert!(skill.contains("Central skill body."));
         assert!(!skill.contains("Map command body."));
@@ -1166,7 +1802,7 @@ mod tests {
 
         let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
 
-        assert!(outcome.report.skills.updated > 0);
+        assert!(outcome.report.skills.updated() > 0);
         let skill = fs::read_to_string(cfg.codex_skills_dir.join("map/SKILL.md"))?;
         assert!(skill.contains("Central skill body."));
         assert!(!skill.contains("Map command body."));
@@ -1180,6 +1816,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn scoped_command_sync_preserves_real_codex_skill_when_name_collides() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let command = cfg.central_dir.join("map.md");
+        write_plain(&command, "Map command body.")?;
+        write_skill(
+            &cfg.central_skills_dir,
+            "map",
+            &doc("central", "Central skill body."),
+        )?;
+
+        let selected = discover_scoped_commands(&cfg, &[command])?;
+        let outcome = sync_scoped_commands_with_mode(
+            &cfg,
+            &selected,
+            LogMode::Quiet,
+            ExecutionMode::Apply,
+            "sync:scoped",
+        )?;
+
+        assert!(outcome.report.commands.updated() > 0);
+        assert!(!cfg.codex_skills_dir.join("map").exists());
+        sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
+        let skill = fs::read_to_string(cfg.codex_skills_dir.join("map/SKILL.md"))?;
+        assert!(skill.contains("Central skill body."));
+        Ok(())
+    }
+
     #[test]
     fn sync_plan_projects_imported_skill_names_before_command_wrappers() -> io::Result<()> {
         fn setup_collision() -> io::Result<(tempfile::TempDir, Config)> {
@@ -1200,10 +1864,10 @@ mod tests {
         let (_apply_tmp, apply_cfg) = setup_collision()?;
         let apply = sync_all_with_mode(&apply_cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
 
-        assert_eq!(plan.report.skills.updated, 3);
-        assert_eq!(apply.report.skills.updated, 3);
-        assert_eq!(plan.report.commands.updated, 3);
-        assert_eq!(apply.report.commands.updated, 3);
+        assert_eq!(plan.report.skills.updated(), 3);
+        assert_eq!(apply.report.skills.updated(), 3);
+        assert_eq!(plan.report.commands.updated(), 4);
+        assert_eq!(apply.report.commands.updated(), 4);
         assert!(
             fs::read_to_string(apply_cfg.central_skills_dir.join("review/SKILL.md"))?
                 .contains("Real skill body.")
@@ -1229,8 +1893,8 @@ mod tests {
 
         let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Apply, "sync")?;
 
-        assert!(outcome.report.commands.updated > 0);
-        assert_eq!(outcome.report.skills.updated, 0);
+        assert!(outcome.report.commands.updated() > 0);
+        assert_eq!(outcome.report.skills.updated(), 0);
         assert!(cfg.codex_skills_dir.join("map/SKILL.md").exists());
         assert!(!cfg.central_skills_dir.join("map/SKILL.md").exists());
         assert!(!cfg.claude_skills_dir.join("map/SKILL.md").exists());
@@ -1276,26 +1940,26 @@ mod tests {
         assert!(outcome.conflicts.contains(&SyncConflict {
             kind: SyncItemKind::Command,
             name: "review.md".to_string(),
-            winner: config::TOOL_CURSOR,
-            others: vec![config::TOOL_CLAUDE],
+            winner: config::TOOL_CURSOR.to_string(),
+            others: vec![config::TOOL_CLAUDE.to_string()],
         }));
         assert!(outcome.conflicts.contains(&SyncConflict {
             kind: SyncItemKind::Skill,
             name: "plan".to_string(),
-            winner: config::TOOL_CODEX,
-            others: vec![config::TOOL_CLAUDE],
+            winner: config::TOOL_CODEX.to_string(),
+            others: vec![config::TOOL_CLAUDE.to_string()],
         }));
         assert!(outcome.conflicts.contains(&SyncConflict {
             kind: SyncItemKind::Agent,
             name: "AGENTS.md".to_string(),
-            winner: config::TOOL_OPENCODE,
-            others: vec![config::TOOL_CODEX],
+            winner: config::TOOL_OPENCODE.to_string(),
+            others: vec![config::TOOL_CODEX.to_string()],
         }));
         assert!(outcome.conflicts.contains(&SyncConflict {
             kind: SyncItemKind::Rule,
             name: "codex/default.rules".to_string(),
-            winner: "central",
-            others: vec![config::TOOL_CODEX],
+            winner: "central".to_string(),
+            others: vec![config::TOOL_CODEX.to_string()],
         }));
         Ok(())
     }
@@ -1313,7 +1977,7 @@ mod tests {
         let outcome = sync_all_with_mode(&cfg, LogMode::Quiet, ExecutionMode::Plan, "sync")?;
 
         assert!(outcome.has_conflicts());
-        assert!(outcome.report.commands.updated > 0);
+        assert!(outcome.report.commands.updated() > 0);
         assert_eq!(outcome.history_event_id, None);
         assert!(!cfg.central_dir.exists());
         assert!(!cfg.central_dir.join("demo.md").exists());
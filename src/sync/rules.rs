@@ -1,13 +1,16 @@
+use super::merge::{reconcile, record_conflict, MergeBases, Reconciled};
 use super::shared::{
-    conflict_for_variants, file_mtime_value, hash_bytes, log_action, tool_order,
-    write_raw_if_changed, TOOL_CENTRAL,
+    conflict_for_variants, file_mtime_value, hash_bytes, log_action, pick_winner,
+    preserve_conflict, write_raw_if_changed, SyncedHashes, TOOL_CENTRAL,
 };
 use super::{ExecutionMode, LogMode, SyncConflict, SyncItemKind, SyncStats};
-use crate::blacklist::CODEX_RULES_BLACKLIST_KEY;
 use crate::config::{Config, TOOL_CODEX};
+use crate::conflicts::{LosingCopy, LosingVariant};
 use crate::history::HistoryRecorder;
+use crate::tools::provider_entries;
 use std::fs;
 use std::io;
+use std::path::Path;
 
 #[cfg(any(test, coverage))]
 pub(crate) fn sync_rules(cfg: &Config, log_mode: LogMode) -> io::Result<SyncStats> {
@@ -31,67 +34,189 @@ pub(crate) fn sync_rules_with_mode(
 ) -> io::Result<SyncStats> {
     let mut stats = SyncStats::default();
 
-    let codex_enabled = cfg.tool_enabled(TOOL_CODEX)
-        && cfg
-            .codex_rules_file
-            .parent()
-            .is_some_and(|parent| parent.exists());
-    let central_path = cfg.central_rules_dir.join("codex/default.rules");
+    // Rules files are tool-specific (Codex uses Starlark), so each one only
+    // syncs with its own central copy at `rules/<tool>/<file name>`.
+    let mut pairs: Vec<(&str, bool, &Path, String)> = vec![(
+        TOOL_CODEX,
+        cfg.tool_enabled(TOOL_CODEX),
+        cfg.codex_rules_file.as_path(),
+        "codex/default.rules".to_string(),
+    )];
+    for provider in provider_entries(cfg) {
+        if let Some(file) = provider.rules_file {
+            if let Some(central_name) = provider.central_file_name(file) {
+                pairs.push((provider.id, true, file.as_path(), central_name));
+            }
+        }
+    }
 
-    let mut variants: Vec<RuleVariant> = Vec::new();
-    if codex_enabled && cfg.codex_rules_file.exists() {
-        let contents = fs::read(&cfg.codex_rules_file)?;
-        variants.push(RuleVariant {
-            tool: TOOL_CODEX,
-            path: cfg.codex_rules_file.clone(),
-            mtime: file_mtime_value(&cfg.codex_rules_file),
-            hash: hash_bytes(&contents),
-        });
+    let bases = MergeBases::new(cfg)?;
+    let state_path = cfg.synced_state_path(SyncItemKind::Rule)?;
+    let previous_state = SyncedHashes::load(&state_path)?;
+    let mut state = SyncedHashes::default();
+    for (tool, enabled, tool_path, central_name) in pairs {
+        let enabled = enabled && tool_path.parent().is_some_and(|parent| parent.exists());
+        stats.extend(sync_rule_file(
+            cfg,
+            tool,
+            enabled,
+            tool_path,
+            &central_name,
+            &bases,
+            &previous_state,
+            &mut state,
+            log_mode,
+            mode,
+            history,
+            conflicts,
+        )?);
     }
-    if central_path.exists() {
-        let contents = fs::read(&central_path)?;
-        variants.push(RuleVariant {
-            tool: TOOL_CENTRAL,
-            path: central_path.clone(),
-            mtime: file_mtime_value(&central_path),
-            hash: hash_bytes(&contents),
-        });
+    stats.record_state(state.save_if_changed(&previous_state, &state_path, mode, history)?);
+
+    Ok(stats)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sync_rule_file(
+    cfg: &Config,
+    tool: &str,
+    enabled: bool,
+    tool_path: &Path,
+    central_name: &str,
+    bases: &MergeBases,
+    previous_state: &SyncedHashes,
+    state: &mut SyncedHashes,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+    conflicts: &mut Vec<SyncConflict>,
+) -> io::Result<SyncStats> {
+    let mut stats = SyncStats::default();
+    let central_path = cfg.central_rules_dir.join(central_name);
+
+    let mut variants: Vec<RuleVariant> = Vec::new();
+    for (variant_tool, path, readable) in [
+        (tool, tool_path, enabled),
+        (TOOL_CENTRAL, central_path.as_path(), true),
+    ] {
+        if readable && path.exists() {
+            let contents = fs::read(path)?;
+            variants.push(RuleVariant {
+                tool: variant_tool,
+                path,
+                mtime: file_mtime_value(path),
+                hash: hash_bytes(&contents),
+                contents,
+            });
+        }
     }
-    let Some(winner) = variants
-        .iter()
-        .max_by_key(|variant| (variant.mtime, tool_order(variant.tool)))
-    else {
+    let policy = cfg.conflict_policy.for_kind(SyncItemKind::Rule);
+    let changed = |variant: &RuleVariant| previous_state.changed(variant.path, variant.hash);
+    let Some(winner) = pick_winner(policy, &variants, changed) else {
         return Ok(stats);
     };
-    if let Some(conflict) = conflict_for_variants(
-        "codex/default.rules",
-        SyncItemKind::Rule,
-        &variants,
-        winner.tool,
-        winner.hash,
-    ) {
-        conflicts.push(conflict);
-        log_action(
-            log_mode,
-            &format!(
-                "warning: rules edited in multiple tools; last-write-wins chose {}",
-                winner.tool
-            ),
-        );
+    let mut winner_contents = winner.contents.clone();
+    let mut reconciled = None;
+    if variants.iter().any(|variant| variant.hash != winner.hash) {
+        if let Some(base) = bases.base_for([tool_path, central_path.as_path()])? {
+            let sides: Option<Vec<(&str, &str)>> = std::iter::once(winner)
+                .chain(&variants)
+                .map(|variant| {
+                    std::str::from_utf8(&variant.contents)
+                        .ok()
+                        .map(|text| (variant.tool, text))
+                })
+                .collect();
+            reconciled = sides.and_then(|sides| reconcile(&base, &sides));
+        }
     }
-    let winner_contents = fs::read(&winner.path)?;
-    for (tool, enabled, path) in [
-        (TOOL_CODEX, codex_enabled, &cfg.codex_rules_file),
-        (TOOL_CENTRAL, true, &central_path),
-    ] {
-        if !enabled {
-            continue;
+    let preserve = |conflict: &SyncConflict| {
+        let losers: Vec<LosingVariant<'_>> = variants
+            .iter()
+            .filter(|variant| {
+                variant.hash != winner.hash
+                    && conflict.others.iter().any(|tool| tool == variant.tool)
+            })
+            .map(|variant| LosingVariant {
+                tool: variant.tool,
+                source: if variant.tool == TOOL_CENTRAL {
+                    central_path.as_path()
+                } else {
+                    tool_path
+                },
+                copy: LosingCopy::Bytes(&variant.contents),
+            })
+            .collect();
+        preserve_conflict(cfg, conflict, &losers, log_mode, mode)
+    };
+    match reconciled {
+        Some(Reconciled::Merged(text)) => {
+            if text.as_bytes() != winner_contents {
+                log_action(
+                    log_mode,
+                    &format!("rules: merged edits to '{central_name}'"),
+                );
+            }
+            winner_contents = text.into_bytes();
+        }
+        Some(Reconciled::Conflicted(marked)) => {
+            let others = variants
+                .iter()
+                .filter(|variant| variant.hash != winner.hash)
+                .map(|variant| variant.tool);
+            record_conflict(
+                cfg,
+                SyncItemKind::Rule,
+                central_name,
+                winner.tool,
+                others,
+                &marked,
+                log_mode,
+                mode,
+                conflicts,
+            )?;
+            if let Some(conflict) = conflicts.last() {
+                preserve(conflict)?;
+            }
+        }
+        None => {
+            if let Some(conflict) = conflict_for_variants(
+                central_name,
+                SyncItemKind::Rule,
+                &variants,
+                changed,
+                winner.tool,
+                winner.hash,
+            ) {
+                preserve(&conflict)?;
+                conflicts.push(conflict);
+                log_action(
+                    log_mode,
+                    &format!(
+                        "warning: rules edited in multiple tools; conflict policy {} chose {}",
+                        policy.label(),
+                        winner.tool
+                    ),
+                );
+            }
         }
-        if tool != TOOL_CENTRAL && cfg.is_blacklisted(CODEX_RULES_BLACKLIST_KEY, tool) {
+    }
+    let blacklist_key = format!("rules/{central_name}");
+    for (target_tool, target_enabled, path) in [
+        (tool, enabled, tool_path),
+        (TOOL_CENTRAL, true, central_path.as_path()),
+    ] {
+        if !target_enabled
+            || (target_tool != TOOL_CENTRAL && cfg.is_blacklisted(&blacklist_key, target_tool))
+        {
+            if let Some(variant) = variants.iter().find(|variant| variant.path == path) {
+                state.insert(path, variant.hash);
+            }
             continue;
         }
-        if write_raw_if_changed(path, &winner_contents, mode, history)? {
-            stats.updated += 1;
+        state.insert(path, hash_bytes(&winner_contents));
+        let change = write_raw_if_changed(path, &winner_contents, mode, history)?;
+        if stats.record(central_name, target_tool, change) {
             let action = if mode == ExecutionMode::Plan {
                 "would update"
             } else {
@@ -104,15 +229,16 @@ pub(crate) fn sync_rules_with_mode(
     Ok(stats)
 }
 
-struct RuleVariant {
-    tool: &'static str,
-    path: std::path::PathBuf,
+struct RuleVariant<'a> {
+    tool: &'a str,
+    path: &'a Path,
+    contents: Vec<u8>,
     mtime: u128,
     hash: u64,
 }
 
-impl super::shared::ConflictVariant for RuleVariant {
-    fn tool(&self) -> &'static str {
+impl super::shared::ConflictVariant for RuleVariant<'_> {
+    fn tool(&self) -> &str {
         self.tool
     }
 
@@ -128,7 +254,7 @@ impl super::shared::ConflictVariant for RuleVariant {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sync::test_support::{setup, write_plain};
+    use crate::sync::test_support::{add_provider, setup, write_plain};
 
     #[test]
     fn sync_rules_mirrors_codex_rules() -> io::Result<()> {
@@ -142,6 +268,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sync_rules_keeps_provider_rules_separate_from_codex() -> io::Result<()> {
+        let (tmp, mut cfg) = setup()?;
+        add_provider(&tmp, &mut cfg)?;
+        let provider_rules = tmp.path().join("windsurf/rules.md");
+        write_plain(&provider_rules, "- be brief")?;
+        write_plain(&cfg.codex_rules_file, "rule(\"x\")")?;
+
+        sync_rules(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(
+            fs::read_to_string(cfg.central_rules_dir.join("windsurf/rules.md"))?,
+            "- be brief"
+        );
+        assert_eq!(fs::read_to_string(&cfg.codex_rules_file)?, "rule(\"x\")");
+        Ok(())
+    }
+
     #[test]
     fn sync_rules_blacklist_skips_codex() -> io::Result<()> {
         let (_tmp, mut cfg) = setup()?;
@@ -201,8 +345,8 @@ mod tests {
             vec![SyncConflict {
                 kind: SyncItemKind::Rule,
                 name: "codex/default.rules".to_string(),
-                winner: TOOL_CENTRAL,
-                others: vec![TOOL_CODEX],
+                winner: TOOL_CENTRAL.to_string(),
+                others: vec![TOOL_CODEX.to_string()],
             }]
         );
         Ok(())
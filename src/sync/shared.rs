@@ -1,8 +1,12 @@
 use super::{ExecutionMode, LogMode, SyncConflict, SyncItemKind};
 use crate::atomic::write_atomic;
+use crate::config::{Config, ConflictPolicy};
+use crate::conflicts::{preserve_losers, LosingCopy, LosingVariant};
 use crate::history::HistoryRecorder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::hash_map::DefaultHasher;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::io;
@@ -11,7 +15,6 @@ use std::time::UNIX_EPOCH;
 
 pub(crate) const TOOL_CENTRAL: &str = "central";
 pub(crate) const TOOL_OPENCODE_LEGACY: &str = "opencode (legacy path)";
-pub(crate) const CONFLICT_WINDOW_NS: u128 = 2_000_000_000;
 
 pub(crate) struct MarkdownDoc {
     pub(crate) raw: String,
@@ -26,42 +29,167 @@ pub(crate) struct RequiredFrontmatter {
     pub(crate) description: String,
 }
 
-pub(crate) struct MarkdownVariant {
-    pub(crate) tool: &'static str,
+pub(crate) struct MarkdownVariant<'a> {
+    pub(crate) tool: &'a str,
     pub(crate) path: PathBuf,
     pub(crate) doc: MarkdownDoc,
     pub(crate) mtime: u128,
 }
 
 pub(crate) trait ConflictVariant {
-    fn tool(&self) -> &'static str;
+    fn tool(&self) -> &str;
     fn hash(&self) -> u64;
     fn mtime(&self) -> u128;
 }
 
-enum TargetAction {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChangeOp {
     Create,
     Update,
+    Delete,
 }
 
-impl TargetAction {
-    fn plan_verb(&self) -> &'static str {
+impl ChangeOp {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+
+    fn plan_verb(self) -> &'static str {
         match self {
             Self::Create => "would create",
             Self::Update => "would update",
+            Self::Delete => "would remove",
         }
     }
 
-    fn done_verb(&self) -> &'static str {
+    fn done_verb(self) -> &'static str {
         match self {
             Self::Create => "created",
             Self::Update => "updated",
+            Self::Delete => "removed",
+        }
+    }
+}
+
+/// A path sync wrote or removed, or would in plan mode. Hashes identify the
+/// content on either side and are `None` where nothing exists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FileChange {
+    pub(crate) path: PathBuf,
+    pub(crate) op: ChangeOp,
+    pub(crate) before: Option<u64>,
+    pub(crate) after: Option<u64>,
+    /// What a planned write would leave at `path`; only kept in plan mode so
+    /// the plan can be reviewed before anything is written.
+    pub(crate) content: Option<PlannedContent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PlannedContent {
+    File(Vec<u8>),
+    /// Regular files of a directory target, sorted by relative path.
+    Dir(Vec<PlannedFile>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PlannedFile {
+    pub(crate) rel: String,
+    pub(crate) mode: u32,
+    pub(crate) bytes: Vec<u8>,
+}
+
+impl PlannedContent {
+    /// Reads what currently sits at `path`, or `None` when nothing does.
+    pub(crate) fn read(path: &Path) -> io::Result<Option<Self>> {
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if !metadata.is_dir() {
+            return fs::read(path).map(|bytes| Some(Self::File(bytes)));
+        }
+        let mut files = Vec::new();
+        read_dir_files(path, path, &mut files)?;
+        files.sort_by(|a, b| a.rel.cmp(&b.rel));
+        Ok(Some(Self::Dir(files)))
+    }
+}
+
+fn read_dir_files(root: &Path, dir: &Path, files: &mut Vec<PlannedFile>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            read_dir_files(root, &path, files)?;
+        } else if file_type.is_file() {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            files.push(PlannedFile {
+                rel: relative.to_string_lossy().into_owned(),
+                mode: file_mode(&entry.metadata()?),
+                bytes: fs::read(&path)?,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub(crate) fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_mode(_metadata: &fs::Metadata) -> u32 {
+    0
+}
+
+impl FileChange {
+    pub(crate) fn write(path: &Path, before: Option<u64>, after: u64) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            op: if before.is_some() {
+                ChangeOp::Update
+            } else {
+                ChangeOp::Create
+            },
+            before,
+            after: Some(after),
+            content: None,
+        }
+    }
+
+    /// Keeps `content` for plan review; applied changes never carry it.
+    pub(crate) fn planned(
+        mut self,
+        mode: ExecutionMode,
+        content: impl FnOnce() -> io::Result<Option<PlannedContent>>,
+    ) -> io::Result<Self> {
+        if mode == ExecutionMode::Plan {
+            self.content = content()?;
+        }
+        Ok(self)
+    }
+
+    pub(crate) fn delete(path: &Path, before: Option<u64>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            op: ChangeOp::Delete,
+            before,
+            after: None,
+            content: None,
         }
     }
 }
 
-impl ConflictVariant for MarkdownVariant {
-    fn tool(&self) -> &'static str {
+impl ConflictVariant for MarkdownVariant<'_> {
+    fn tool(&self) -> &str {
         self.tool
     }
 
@@ -110,10 +238,44 @@ pub(crate) fn list_files(dir: &Path) -> io::Result<HashMap<String, PathBuf>> {
     Ok(list_visible_files(dir)?.into_iter().collect())
 }
 
-pub(crate) fn read_markdown_variant(
-    tool: &'static str,
+/// Lists files below `dir` keyed by their `/`-separated path relative to it,
+/// so `frontend/component.md` stays distinct from a top-level `component.md`.
+/// Hidden entries are skipped and symlinked directories are not followed.
+pub(crate) fn list_files_recursive(dir: &Path) -> io::Result<HashMap<String, PathBuf>> {
+    let mut out = HashMap::new();
+    collect_files_recursive(dir, "", &mut out)?;
+    Ok(out)
+}
+
+fn collect_files_recursive(
+    dir: &Path,
+    prefix: &str,
+    out: &mut HashMap<String, PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_symlink = entry.file_type()?.is_symlink();
+        let Some((name, path, meta)) = read_visible_entry(entry, true)? else {
+            continue;
+        };
+        let key = if prefix.is_empty() {
+            name
+        } else {
+            format!("{prefix}/{name}")
+        };
+        if meta.is_file() {
+            out.insert(key, path);
+        } else if meta.is_dir() && !is_symlink {
+            collect_files_recursive(&path, &key, out)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn read_markdown_variant<'a>(
+    tool: &'a str,
     path: &Path,
-) -> io::Result<MarkdownVariant> {
+) -> io::Result<MarkdownVariant<'a>> {
     let doc = read_markdown(path)?;
     let mtime = file_mtime_value(path);
     Ok(MarkdownVariant {
@@ -124,52 +286,168 @@ pub(crate) fn read_markdown_variant(
     })
 }
 
-pub(crate) fn select_markdown_winner(variants: &[MarkdownVariant]) -> &MarkdownVariant {
-    variants
+pub(crate) fn select_markdown_winner<'a, 'b>(
+    policy: &ConflictPolicy,
+    variants: &'a [MarkdownVariant<'b>],
+    changed: impl Fn(&MarkdownVariant<'b>) -> bool,
+) -> &'a MarkdownVariant<'b> {
+    pick_winner(policy, variants, changed).expect("winner available")
+}
+
+/// Picks the copy to propagate. `changed` marks copies that moved off what
+/// the last sync left in place. When changed copies differ they compete and
+/// `policy` decides; a single edit wins outright. With nothing changed the
+/// central copy, which every sync writes, is kept.
+pub(crate) fn pick_winner<'a, T: ConflictVariant>(
+    policy: &ConflictPolicy,
+    variants: &'a [T],
+    changed: impl Fn(&T) -> bool,
+) -> Option<&'a T> {
+    let edited: Vec<&T> = variants.iter().filter(|variant| changed(variant)).collect();
+    if variants_conflict(&edited) {
+        return pick_by_policy(policy, &edited);
+    }
+    if !edited.is_empty() {
+        return newest_variant(&edited);
+    }
+    let all: Vec<&T> = variants.iter().collect();
+    pick_by_policy(&ConflictPolicy::Central, &all)
+}
+
+/// Picks among copies already known to compete. `fail` still names the newest
+/// copy; aborting is left to the caller.
+pub(crate) fn pick_by_policy<'a, T: ConflictVariant>(
+    policy: &ConflictPolicy,
+    candidates: &[&'a T],
+) -> Option<&'a T> {
+    let preferred = match policy {
+        ConflictPolicy::Newest | ConflictPolicy::Fail => None,
+        ConflictPolicy::Central => newest_variant(
+            &candidates
+                .iter()
+                .copied()
+                .filter(|variant| variant.tool() == TOOL_CENTRAL)
+                .collect::<Vec<_>>(),
+        ),
+        ConflictPolicy::Priority(tools) => tools.iter().find_map(|tool| {
+            newest_variant(
+                &candidates
+                    .iter()
+                    .copied()
+                    .filter(|variant| variant.tool() == tool)
+                    .collect::<Vec<_>>(),
+            )
+        }),
+    };
+    preferred.or_else(|| newest_variant(candidates))
+}
+
+fn newest_variant<'a, T: ConflictVariant>(candidates: &[&'a T]) -> Option<&'a T> {
+    candidates
         .iter()
-        .max_by_key(|variant| (variant.mtime, tool_order(variant.tool)))
-        .expect("winner available")
+        .copied()
+        .max_by_key(|variant| (variant.mtime(), tool_order(variant.tool())))
+}
+
+/// Two or more changed copies with different contents.
+fn variants_conflict<T: ConflictVariant>(edited: &[&T]) -> bool {
+    edited
+        .iter()
+        .map(|variant| variant.hash())
+        .collect::<HashSet<_>>()
+        .len()
+        >= 2
 }
 
 pub(crate) fn conflict_for_variants<T: ConflictVariant>(
     name: &str,
     item_kind: SyncItemKind,
     variants: &[T],
-    winner: &'static str,
+    changed: impl Fn(&T) -> bool,
+    winner: &str,
     winner_hash: u64,
 ) -> Option<SyncConflict> {
-    if variants.len() < 2 {
-        return None;
-    }
-    let mut min = u128::MAX;
-    let mut max = 0u128;
-    let mut hashes = HashSet::new();
-    for variant in variants {
-        min = min.min(variant.mtime());
-        max = max.max(variant.mtime());
-        hashes.insert(variant.hash());
-    }
-    if hashes.len() < 2 || max.saturating_sub(min) > CONFLICT_WINDOW_NS {
+    let edited: Vec<&T> = variants.iter().filter(|variant| changed(variant)).collect();
+    if !variants_conflict(&edited) {
         return None;
     }
 
     let mut others = Vec::new();
-    for variant in variants {
+    for variant in edited {
         if variant.tool() != winner
             && variant.hash() != winner_hash
-            && !others.contains(&variant.tool())
+            && !others.iter().any(|other| other == variant.tool())
         {
-            others.push(variant.tool());
+            others.push(variant.tool().to_string());
         }
     }
     Some(SyncConflict {
         kind: item_kind,
         name: name.to_string(),
-        winner,
+        winner: winner.to_string(),
         others,
     })
 }
 
+/// Saves the losers of `conflict` when `preserve_conflicts` is on. Callers run
+/// this before writing any target, so the copies are the tools' own edits.
+pub(crate) fn preserve_conflict(
+    cfg: &Config,
+    conflict: &SyncConflict,
+    losers: &[LosingVariant<'_>],
+    log_mode: LogMode,
+    mode: ExecutionMode,
+) -> io::Result<()> {
+    if !cfg.preserve_conflicts || mode == ExecutionMode::Plan {
+        return Ok(());
+    }
+    if let Some(dir) = preserve_losers(cfg, conflict, losers)? {
+        log_action(
+            log_mode,
+            &format!(
+                "{} '{}': saved losing copies to {}",
+                conflict.kind.store_dir(),
+                conflict.name,
+                dir.display()
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// Markdown variants are saved byte for byte from disk, so formats converted
+/// at the tool boundary (Gemini TOML) restore exactly as the tool wrote them.
+pub(crate) fn preserve_markdown_conflict(
+    cfg: &Config,
+    conflict: &SyncConflict,
+    variants: &[MarkdownVariant],
+    winner_hash: u64,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+) -> io::Result<()> {
+    if !cfg.preserve_conflicts || mode == ExecutionMode::Plan {
+        return Ok(());
+    }
+    let mut copies = Vec::new();
+    for tool in &conflict.others {
+        if let Some(variant) = variants
+            .iter()
+            .find(|variant| variant.tool == *tool && variant.doc.body_hash != winner_hash)
+        {
+            copies.push((variant, fs::read(&variant.path)?));
+        }
+    }
+    let losers: Vec<LosingVariant<'_>> = copies
+        .iter()
+        .map(|(variant, bytes)| LosingVariant {
+            tool: variant.tool,
+            source: &variant.path,
+            copy: LosingCopy::Bytes(bytes),
+        })
+        .collect();
+    preserve_conflict(cfg, conflict, &losers, log_mode, mode)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn update_markdown_target(
     source: &MarkdownDoc,
@@ -180,22 +458,50 @@ pub(crate) fn update_markdown_target(
     mode: ExecutionMode,
     history: &mut Option<HistoryRecorder>,
     label: &str,
-) -> io::Result<bool> {
+) -> io::Result<Option<FileChange>> {
     let frontmatter =
         select_frontmatter_for_target(source, existing, preserve_frontmatter, log_mode, label);
     let merged = merge_frontmatter(frontmatter.as_deref(), &source.body);
-    let action = match existing {
-        Some(existing_doc) if existing_doc.raw == merged => return Ok(false),
-        Some(_) => TargetAction::Update,
-        None => TargetAction::Create,
-    };
+    write_text_target(
+        existing.map(|doc| doc.raw.as_str()),
+        &merged,
+        target_path,
+        log_mode,
+        mode,
+        history,
+        label,
+    )
+}
+
+/// Writes `contents` unless the target already holds exactly that text,
+/// logging the create/update the same way for every kind.
+pub(crate) fn write_text_target(
+    existing_raw: Option<&str>,
+    contents: &str,
+    target_path: &Path,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+    label: &str,
+) -> io::Result<Option<FileChange>> {
+    if existing_raw == Some(contents) {
+        return Ok(None);
+    }
+    let change = FileChange::write(
+        target_path,
+        existing_raw.map(|raw| hash_bytes(raw.as_bytes())),
+        hash_bytes(contents.as_bytes()),
+    )
+    .planned(mode, || {
+        Ok(Some(PlannedContent::File(contents.as_bytes().to_vec())))
+    })?;
     if mode == ExecutionMode::Plan {
-        log_action(log_mode, &format!("{label}: {}", action.plan_verb()));
-        return Ok(true);
+        log_action(log_mode, &format!("{label}: {}", change.op.plan_verb()));
+        return Ok(Some(change));
     }
-    write_file(target_path, merged.as_bytes(), mode, history)?;
-    log_action(log_mode, &format!("{label}: {}", action.done_verb()));
-    Ok(true)
+    write_file(target_path, contents.as_bytes(), mode, history)?;
+    log_action(log_mode, &format!("{label}: {}", change.op.done_verb()));
+    Ok(Some(change))
 }
 
 pub(crate) fn write_file(
@@ -228,12 +534,40 @@ pub(crate) fn write_raw_if_changed(
     contents: &[u8],
     mode: ExecutionMode,
     history: &mut Option<HistoryRecorder>,
-) -> io::Result<bool> {
-    if fs::read(path).ok().as_deref() == Some(contents) {
-        return Ok(false);
+) -> io::Result<Option<FileChange>> {
+    let existing = fs::read(path).ok();
+    if existing.as_deref() == Some(contents) {
+        return Ok(None);
     }
     write_file(path, contents, mode, history)?;
-    Ok(true)
+    FileChange::write(
+        path,
+        existing.as_deref().map(hash_bytes),
+        hash_bytes(contents),
+    )
+    .planned(mode, || Ok(Some(PlannedContent::File(contents.to_vec()))))
+    .map(Some)
+}
+
+/// What `path` holds once `change` is made: the planned bytes when the sync
+/// only planned it, otherwise whatever is on disk.
+pub(crate) fn contents_after(
+    path: &Path,
+    change: Option<&FileChange>,
+) -> io::Result<Option<Vec<u8>>> {
+    match change {
+        Some(change) if change.op == ChangeOp::Delete => return Ok(None),
+        Some(FileChange {
+            content: Some(PlannedContent::File(bytes)),
+            ..
+        }) => return Ok(Some(bytes.clone())),
+        _ => {}
+    }
+    match fs::read(path) {
+        Ok(raw) => Ok(Some(raw)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
 }
 
 pub(crate) fn merge_frontmatter(frontmatter: Option<&str>, body: &str) -> String {
@@ -243,13 +577,13 @@ pub(crate) fn merge_frontmatter(frontmatter: Option<&str>, body: &str) -> String
     }
 }
 
-pub(crate) fn required_frontmatter_hash(doc: &MarkdownDoc) -> Option<u64> {
+pub(crate) fn required_frontmatter_hash(scheme: HashScheme, doc: &MarkdownDoc) -> Option<u64> {
     parse_required_frontmatter(doc.frontmatter.as_deref()).map(|required| {
         let serialized = format!(
             "name:{}\ndescription:{}",
             required.name, required.description
         );
-        hash_bytes(serialized.as_bytes())
+        hash_bytes_with(scheme, serialized.as_bytes())
     })
 }
 
@@ -303,15 +637,18 @@ pub(crate) fn select_frontmatter_for_target(
 }
 
 pub(crate) fn read_markdown(path: &Path) -> io::Result<MarkdownDoc> {
-    let raw = fs::read_to_string(path)?;
+    fs::read_to_string(path).map(parse_markdown)
+}
+
+pub(crate) fn parse_markdown(raw: String) -> MarkdownDoc {
     let (frontmatter, body) = split_frontmatter(&raw);
     let body_hash = hash_bytes(body.as_bytes());
-    Ok(MarkdownDoc {
+    MarkdownDoc {
         raw,
         frontmatter,
         body,
         body_hash,
-    })
+    }
 }
 
 fn split_frontmatter(contents: &str) -> (Option<String>, String) {
@@ -440,12 +777,213 @@ fn strip_line_end(line: &str) -> &str {
     line.trim_end_matches(['\n', '\r'])
 }
 
+/// Version written into `skills-state.toml`, `commands-state.toml`,
+/// `mcp-state.toml` and the `SyncedHashes` files. Files without one predate
+/// `HashScheme::Sha256`.
+pub(crate) const STATE_VERSION: u32 = 2;
+
+pub(crate) fn legacy_state_version() -> u32 {
+    1
+}
+
+/// The variant hash each file held after the last sync, for kinds whose
+/// copies carry no other state (agents, rules, subagents). A copy whose hash
+/// moved off its entry was edited since, which is what makes two copies
+/// compete under the conflict policy.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SyncedHashes {
+    #[serde(default = "legacy_state_version")]
+    version: u32,
+    #[serde(default)]
+    files: BTreeMap<String, i64>,
+}
+
+impl Default for SyncedHashes {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+impl SyncedHashes {
+    pub(crate) fn load(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let raw = fs::read_to_string(path)?;
+        let state: Self = toml::from_str(&raw).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid sync state in {}: {err}", path.display()),
+            )
+        })?;
+        // Older hashes cannot be compared; every copy counts as changed once.
+        if state.version < STATE_VERSION {
+            return Ok(Self::default());
+        }
+        Ok(state)
+    }
+
+    pub(crate) fn changed(&self, path: &Path, hash: u64) -> bool {
+        self.files.get(&*path.to_string_lossy()) != Some(&persisted_hash(hash))
+    }
+
+    pub(crate) fn insert(&mut self, path: &Path, hash: u64) {
+        self.files
+            .insert(path.to_string_lossy().into_owned(), persisted_hash(hash));
+    }
+
+    /// Records what a markdown target holds once its write lands. Targets
+    /// keep some frontmatter of their own, so the written (or planned) file
+    /// is parsed again rather than trusting the source hash.
+    pub(crate) fn insert_written_markdown(
+        &mut self,
+        path: &Path,
+        change: Option<&FileChange>,
+    ) -> io::Result<()> {
+        if let Some(raw) = contents_after(path, change)? {
+            let doc = parse_markdown(String::from_utf8_lossy(&raw).into_owned());
+            self.insert(path, doc.body_hash);
+        }
+        Ok(())
+    }
+
+    /// Saves the state when anything moved; a plan returns the write instead.
+    pub(crate) fn save_if_changed(
+        &self,
+        previous: &Self,
+        path: &Path,
+        mode: ExecutionMode,
+        history: &mut Option<HistoryRecorder>,
+    ) -> io::Result<Option<FileChange>> {
+        if self == previous {
+            return Ok(None);
+        }
+        let raw = toml::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        write_raw_if_changed(path, raw.as_bytes(), mode, history)
+    }
+}
+
+/// How content hashes are derived. Hashes end up in state files, so the
+/// scheme must not change between builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HashScheme {
+    /// `DefaultHasher`, whose algorithm may change with any Rust release.
+    /// Only used to migrate state files written before `STATE_VERSION` 2.
+    Legacy,
+    /// The first 8 bytes of a SHA-256 digest, big-endian. Every field is
+    /// length-prefixed or fixed-width, so the input is unambiguous.
+    Sha256,
+}
+
+/// Feeds content into a `HashScheme`. `Legacy` goes through the `Hash`
+/// impls exactly as the code that wrote old state files did.
+pub(crate) enum ContentHasher {
+    Legacy(DefaultHasher),
+    Sha256(Sha256),
+}
+
+impl ContentHasher {
+    pub(crate) fn new(scheme: HashScheme) -> Self {
+        match scheme {
+            HashScheme::Legacy => Self::Legacy(DefaultHasher::new()),
+            HashScheme::Sha256 => Self::Sha256(Sha256::new()),
+        }
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Legacy(hasher) => bytes.hash(hasher),
+            Self::Sha256(digest) => {
+                digest.update((bytes.len() as u64).to_be_bytes());
+                digest.update(bytes);
+            }
+        }
+    }
+
+    pub(crate) fn str(&mut self, value: &str) {
+        match self {
+            Self::Legacy(hasher) => value.hash(hasher),
+            Self::Sha256(_) => self.bytes(value.as_bytes()),
+        }
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        match self {
+            Self::Legacy(hasher) => value.hash(hasher),
+            Self::Sha256(digest) => digest.update(value.to_be_bytes()),
+        }
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        match self {
+            Self::Legacy(hasher) => value.hash(hasher),
+            Self::Sha256(digest) => digest.update(value.to_be_bytes()),
+        }
+    }
+
+    pub(crate) fn finish(self) -> u64 {
+        match self {
+            Self::Legacy(hasher) => hasher.finish(),
+            Self::Sha256(digest) => {
+                let digest = digest.finalize();
+                let mut head = [0u8; 8];
+                head.copy_from_slice(&digest[..8]);
+                u64::from_be_bytes(head)
+            }
+        }
+    }
+}
+
 pub(crate) fn hash_bytes(bytes: &[u8]) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    bytes.hash(&mut hasher);
+    hash_bytes_with(HashScheme::Sha256, bytes)
+}
+
+pub(crate) fn hash_bytes_with(scheme: HashScheme, bytes: &[u8]) -> u64 {
+    let mut hasher = ContentHasher::new(scheme);
+    hasher.bytes(bytes);
     hasher.finish()
 }
 
+/// Rewrites hashes persisted under `HashScheme::Legacy` to the current
+/// scheme. Hashes identify content, not paths, so every candidate content
+/// still on disk is added and any stored hash that matches one is
+/// translated. The rest describe content that has changed since, and stay
+/// as they are: they did not match the current content before the
+/// migration and still do not.
+#[derive(Debug, Default)]
+pub(crate) struct HashMigration {
+    hashes: HashMap<i64, i64>,
+}
+
+impl HashMigration {
+    pub(crate) fn add(&mut self, legacy: u64, current: u64) {
+        self.hashes
+            .insert(persisted_hash(legacy), persisted_hash(current));
+    }
+
+    pub(crate) fn add_bytes(&mut self, bytes: &[u8]) {
+        self.add(
+            hash_bytes_with(HashScheme::Legacy, bytes),
+            hash_bytes(bytes),
+        );
+    }
+
+    pub(crate) fn apply(&self, hash: &mut i64) {
+        if let Some(current) = self.hashes.get(hash) {
+            *hash = *current;
+        }
+    }
+}
+
+/// TOML integers are signed, so state files store hashes bit-for-bit as `i64`.
+pub(crate) fn persisted_hash(hash: u64) -> i64 {
+    i64::from_ne_bytes(hash.to_ne_bytes())
+}
+
 pub(crate) fn file_mtime_value(path: &Path) -> u128 {
     fs::metadata(path)
         .ok()
@@ -463,9 +1001,10 @@ pub(crate) fn file_mtime_value_from_meta(meta: &fs::Metadata) -> u128 {
 
 pub(crate) fn tool_order(tool: &str) -> u8 {
     match tool {
-        crate::config::TOOL_CURSOR => 4,
-        crate::config::TOOL_CLAUDE => 3,
-        crate::config::TOOL_CODEX => 2,
+        crate::config::TOOL_CURSOR => 5,
+        crate::config::TOOL_CLAUDE => 4,
+        crate::config::TOOL_CODEX => 3,
+        crate::config::TOOL_GEMINI => 2,
         crate::config::TOOL_OPENCODE => 1,
         _ => 0,
     }
@@ -561,6 +1100,31 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn list_files_recursive_keys_nested_files_by_relative_path() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join("commands");
+        for name in [
+            "review.md",
+            "frontend/component.md",
+            "frontend/.draft.md",
+            ".git/HEAD",
+        ] {
+            write_plain(&dir.join(name), "body")?;
+        }
+
+        let list = list_files_recursive(&dir)?;
+
+        let mut keys: Vec<_> = list.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec!["frontend/component.md", "review.md"]);
+        assert_eq!(
+            list["frontend/component.md"],
+            dir.join("frontend/component.md")
+        );
+        Ok(())
+    }
+
     #[test]
     fn update_markdown_target_respects_source_frontmatter_when_requested() -> io::Result<()> {
         let tmp = TempDir::new()?;
@@ -618,7 +1182,7 @@ mod tests {
             &mut history,
             "update",
         )?;
-        assert!(changed);
+        assert!(changed.is_some());
         let updated = fs::read_to_string(&target_path)?;
         assert!(updated.contains("name: source"));
         assert!(updated.contains("description: source desc"));
@@ -652,7 +1216,7 @@ mod tests {
             &mut history,
             "update",
         )?;
-        assert!(changed);
+        assert!(changed.is_some());
         let updated = fs::read_to_string(&target_path)?;
         assert!(updated.contains("name: target"));
         assert!(updated.contains("description: target desc"));
@@ -680,7 +1244,7 @@ mod tests {
             "create",
         )?;
 
-        assert!(changed);
+        assert!(changed.is_some());
         let created = fs::read_to_string(&target_path)?;
         assert!(created.contains("name: source"));
         assert!(created.ends_with("Body"));
@@ -707,7 +1271,7 @@ mod tests {
             "create",
         )?;
 
-        assert!(changed);
+        assert!(changed.is_some());
         assert!(!target_path.exists());
         Ok(())
     }
@@ -735,7 +1299,7 @@ mod tests {
             "update",
         )?;
 
-        assert!(changed);
+        assert!(changed.is_some());
         assert_eq!(fs::read_to_string(&target_path)?, original);
         Ok(())
     }
@@ -826,7 +1390,7 @@ mod tests {
         write_plain(&path, "same")?;
         let mut history = None;
         let changed = write_raw_if_changed(&path, b"same", ExecutionMode::Apply, &mut history)?;
-        assert!(!changed);
+        assert!(changed.is_none());
         Ok(())
     }
 
@@ -839,7 +1403,7 @@ mod tests {
 
         let changed = write_raw_if_changed(&path, b"new", ExecutionMode::Plan, &mut history)?;
 
-        assert!(changed);
+        assert!(changed.is_some());
         assert_eq!(fs::read_to_string(&path)?, "old");
         assert_no_relay_temp_files(tmp.path())?;
         Ok(())
@@ -861,10 +1425,36 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn hash_bytes_is_truncated_sha256_and_legacy_matches_default_hasher() {
+        // SHA-256 of the length prefix (3, big-endian u64) followed by "abc".
+        let mut digest = Sha256::new();
+        digest.update(3u64.to_be_bytes());
+        digest.update(b"abc");
+        let expected = u64::from_be_bytes(digest.finalize()[..8].try_into().unwrap());
+        assert_eq!(hash_bytes(b"abc"), expected);
+
+        let mut hasher = DefaultHasher::new();
+        b"abc"[..].hash(&mut hasher);
+        assert_eq!(hash_bytes_with(HashScheme::Legacy, b"abc"), hasher.finish());
+    }
+
+    #[test]
+    fn hash_migration_translates_only_known_legacy_hashes() {
+        let mut migration = HashMigration::default();
+        migration.add_bytes(b"synced");
+        let mut known = persisted_hash(hash_bytes_with(HashScheme::Legacy, b"synced"));
+        let mut edited = 7;
+        migration.apply(&mut known);
+        migration.apply(&mut edited);
+        assert_eq!(known, persisted_hash(hash_bytes(b"synced")));
+        assert_eq!(edited, 7);
+    }
+
     #[test]
     fn misc_helpers_cover_branches() {
         assert_eq!(file_mtime_value(Path::new("/nope")), 0);
-        assert_eq!(tool_order(crate::config::TOOL_CURSOR), 4);
+        assert_eq!(tool_order(crate::config::TOOL_CURSOR), 5);
         assert_eq!(tool_order("unknown"), 0);
         log_action(LogMode::Actions, "hello");
         log_action(LogMode::Quiet, "quiet");
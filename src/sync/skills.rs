@@ -1,22 +1,24 @@
 use super::shared::{
-    collect_names, conflict_for_variants, file_mtime_value_from_meta, hash_bytes, log_action,
-    parse_required_frontmatter, read_markdown, read_visible_entry, required_frontmatter_hash,
-    select_frontmatter_for_target, tool_order, write_file, write_raw_if_changed, TOOL_CENTRAL,
+    collect_names, conflict_for_variants, file_mode, file_mtime_value_from_meta, hash_bytes_with,
+    legacy_state_version, log_action, parse_required_frontmatter, persisted_hash, pick_by_policy,
+    pick_winner, preserve_conflict, read_markdown, read_visible_entry, required_frontmatter_hash,
+    select_frontmatter_for_target, write_file, write_raw_if_changed, ContentHasher, FileChange,
+    HashMigration, HashScheme, PlannedContent, PlannedFile, STATE_VERSION, TOOL_CENTRAL,
 };
 use super::{ExecutionMode, LogMode as SyncLogMode, SyncConflict, SyncItemKind, SyncStats};
-use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_OPENCODE};
+use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_GEMINI, TOOL_OPENCODE};
+use crate::conflicts::{LosingCopy, LosingVariant};
 use crate::history::HistoryRecorder;
 use crate::markers::is_relay_generated_command_skill;
+use crate::tools::provider_entries;
 use serde::{Deserialize, Serialize};
 #[cfg(test)]
 use std::cell::Cell;
-use std::collections::hash_map::DefaultHasher;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::ffi::CString;
 use std::fs;
-use std::hash::{Hash, Hasher};
 use std::io;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::os::unix::ffi::OsStrExt;
@@ -43,18 +45,29 @@ enum PackagePolicy {
     CompleteStrict,
 }
 
-struct SkillVariant {
-    tool: &'static str,
+struct SkillVariant<'a> {
+    tool: &'a str,
     path: PathBuf,
     digest: DirDigest,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct SkillState {
+    #[serde(default = "legacy_state_version")]
+    version: u32,
     #[serde(default)]
     skills: BTreeMap<String, SkillStateEntry>,
 }
 
+impl Default for SkillState {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            skills: BTreeMap::new(),
+        }
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 struct SkillStateEntry {
     canonical_hash: Option<i64>,
@@ -83,6 +96,14 @@ impl SkillStateEntry {
         }
     }
 
+    /// A planned copy has nothing on disk to digest yet, so it is owned by
+    /// its legacy hash alone until it is next written.
+    fn forget_complete_hashes(&mut self, labels: &[&str]) {
+        for label in labels {
+            self.adapter_complete_hashes.remove(*label);
+        }
+    }
+
     fn clear_adapter_ownership(&mut self, labels: &[&str]) {
         for label in labels {
             self.adapter_hashes.remove(*label);
@@ -91,20 +112,16 @@ impl SkillStateEntry {
     }
 }
 
-fn persisted_hash(hash: u64) -> i64 {
-    i64::from_ne_bytes(hash.to_ne_bytes())
-}
-
-struct SkillLocation {
-    label: &'static str,
-    labels: Vec<&'static str>,
+struct SkillLocation<'a> {
+    label: &'a str,
+    labels: Vec<&'a str>,
     path: PathBuf,
     adapter: bool,
     import_managed: bool,
 }
 
-impl SkillLocation {
-    fn allowed_adapter_label(&self, cfg: &Config, skill_name: &str) -> Option<&'static str> {
+impl<'a> SkillLocation<'a> {
+    fn allowed_adapter_label(&self, cfg: &Config, skill_name: &str) -> Option<&'a str> {
         self.labels
             .iter()
             .copied()
@@ -335,7 +352,7 @@ fn portable_collision_description(existing_name: &str, selected_name: &str) -> &
 fn validate_scoped_destination_names(
     cfg: &Config,
     selected: &[ScopedSkill],
-    locations: &[SkillLocation],
+    locations: &[SkillLocation<'_>],
 ) -> io::Result<()> {
     for skill in selected {
         let portable_selected_name = portable_skill_destination_name(&skill.name);
@@ -415,20 +432,60 @@ fn discover_skill_roots(dir: &Path, roots: &mut Vec<PathBuf>) -> io::Result<()>
     Ok(())
 }
 
-pub(crate) fn diagnostics(cfg: &Config) -> io::Result<Vec<String>> {
+/// Counts behind `relay status` for the canonical store and each location.
+#[derive(Debug, Clone)]
+pub(crate) struct SkillDiagnostics {
+    pub canonical: PathBuf,
+    pub count: usize,
+    pub tombstones: usize,
+    pub locations: Vec<SkillLocationDiagnostics>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SkillLocationDiagnostics {
+    pub label: String,
+    pub role: &'static str,
+    pub path: PathBuf,
+    pub count: usize,
+    pub owned: usize,
+    pub divergent: usize,
+    pub collisions: usize,
+}
+
+impl SkillDiagnostics {
+    pub(crate) fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "skills: canonical={} count={} tombstones={}",
+            self.canonical.display(),
+            self.count,
+            self.tombstones
+        )];
+        lines.extend(self.locations.iter().map(|location| {
+            format!(
+                "skills: {} role={} path={} count={} owned={} divergent={} collisions={}",
+                location.label,
+                location.role,
+                location.path.display(),
+                location.count,
+                location.owned,
+                location.divergent,
+                location.collisions
+            )
+        }));
+        lines
+    }
+}
+
+pub(crate) fn diagnostics(cfg: &Config) -> io::Result<SkillDiagnostics> {
     let canonical = list_skills_if_exists(&cfg.central_skills_dir, true)?;
-    let state = load_skill_state(&cfg.skill_state_path()?)?;
+    let mut state = load_skill_state(&cfg.skill_state_path()?)?;
+    migrate_skill_state(cfg, &mut state)?;
     let tombstones = state
         .skills
         .values()
         .filter(|entry| entry.tombstoned)
         .count();
-    let mut lines = vec![format!(
-        "skills: canonical={} count={} tombstones={}",
-        cfg.central_skills_dir.display(),
-        canonical.len(),
-        tombstones
-    )];
+    let mut locations = Vec::new();
     for location in skill_locations(cfg)? {
         let role = if location.adapter {
             "adapter"
@@ -468,23 +525,27 @@ pub(crate) fn diagnostics(cfg: &Config) -> io::Result<Vec<String>> {
                 }
             }
         }
-        lines.push(format!(
-            "skills: {} role={} path={} count={} owned={} divergent={} collisions={}",
-            location.label,
+        locations.push(SkillLocationDiagnostics {
+            label: location.label.to_string(),
             role,
-            location.path.display(),
-            found.len(),
+            path: location.path,
+            count: found.len(),
             owned,
             divergent,
-            collisions
-        ));
+            collisions,
+        });
     }
-    Ok(lines)
+    Ok(SkillDiagnostics {
+        canonical: cfg.central_skills_dir.clone(),
+        count: canonical.len(),
+        tombstones,
+        locations,
+    })
 }
 
-fn skill_locations(cfg: &Config) -> io::Result<Vec<SkillLocation>> {
+fn skill_locations<'a>(cfg: &'a Config) -> io::Result<Vec<SkillLocation<'a>>> {
     let mut out: Vec<SkillLocation> = Vec::new();
-    let mut push = |label: &'static str, path: PathBuf, adapter: bool, import_managed: bool| {
+    let mut push = |label: &'a str, path: PathBuf, adapter: bool, import_managed: bool| {
         if path == cfg.central_skills_dir {
             return;
         }
@@ -512,6 +573,11 @@ fn skill_locations(cfg: &Config) -> io::Result<Vec<SkillLocation>> {
             cfg.tool_enabled(TOOL_CODEX),
             &cfg.codex_skills_dir,
         ),
+        (
+            TOOL_GEMINI,
+            cfg.tool_enabled(TOOL_GEMINI),
+            &cfg.gemini_skills_dir,
+        ),
         (
             TOOL_OPENCODE,
             cfg.tool_enabled(TOOL_OPENCODE),
@@ -523,6 +589,11 @@ fn skill_locations(cfg: &Config) -> io::Result<Vec<SkillLocation>> {
             push(label, path.clone(), !legacy_native, !legacy_native);
         }
     }
+    for provider in provider_entries(cfg) {
+        if let Some(path) = provider.skills_dir {
+            push(provider.id, path.clone(), true, true);
+        }
+    }
     for path in cfg.legacy_skill_import_dirs()? {
         push("migration", path, false, false);
     }
@@ -549,15 +620,58 @@ fn load_skill_state(path: &Path) -> io::Result<SkillState> {
     })
 }
 
+/// Re-derives the hashes of a state file written before `STATE_VERSION` 2
+/// from the skill directories currently on disk.
+fn migrate_skill_state(cfg: &Config, state: &mut SkillState) -> io::Result<()> {
+    if state.version >= STATE_VERSION {
+        return Ok(());
+    }
+    let mut dirs: Vec<PathBuf> = list_skills_if_exists(&cfg.central_skills_dir, true)?
+        .into_values()
+        .collect();
+    for location in skill_locations(cfg)? {
+        dirs.extend(list_skills_if_exists(&location.path, location.import_managed)?.into_values());
+    }
+    let mut migration = HashMigration::default();
+    for dir in dirs {
+        for (policy, raw_skill_file) in [
+            (PackagePolicy::LegacyVisible, false),
+            (PackagePolicy::CompleteStrict, false),
+            (PackagePolicy::CompleteStrict, true),
+        ] {
+            // A package the strict digest rejects never had its hash recorded.
+            let digest =
+                |scheme| digest_skill_dir_with_policy(&dir, policy, raw_skill_file, scheme);
+            if let (Ok(legacy), Ok(current)) =
+                (digest(HashScheme::Legacy), digest(HashScheme::Sha256))
+            {
+                migration.add(legacy.body_hash, current.body_hash);
+            }
+        }
+    }
+    for entry in state.skills.values_mut() {
+        entry
+            .canonical_hash
+            .iter_mut()
+            .chain(entry.adapter_hashes.values_mut())
+            .chain(entry.adapter_complete_hashes.values_mut())
+            .for_each(|hash| migration.apply(hash));
+    }
+    state.version = STATE_VERSION;
+    Ok(())
+}
+
 fn save_skill_state(
     path: &Path,
     state: &SkillState,
+    mode: ExecutionMode,
     history: &mut Option<HistoryRecorder>,
-) -> io::Result<()> {
+) -> io::Result<Option<FileChange>> {
     #[cfg(test)]
-    if env::var_os("RELAY_TEST_FAIL_SAVE_SKILL_STATE")
-        .as_deref()
-        .is_some_and(|fault_path| fault_path == path.as_os_str())
+    if mode == ExecutionMode::Apply
+        && env::var_os("RELAY_TEST_FAIL_SAVE_SKILL_STATE")
+            .as_deref()
+            .is_some_and(|fault_path| fault_path == path.as_os_str())
     {
         return Err(io::Error::new(
             io::ErrorKind::PermissionDenied,
@@ -566,24 +680,26 @@ fn save_skill_state(
     }
     let raw = toml::to_string_pretty(state)
         .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
-    write_raw_if_changed(path, raw.as_bytes(), ExecutionMode::Apply, history).map(|_| ())
+    write_raw_if_changed(path, raw.as_bytes(), mode, history)
 }
 
 fn remove_skill_target(
     path: &Path,
+    digest: DirDigest,
     log_mode: SyncLogMode,
     mode: ExecutionMode,
     history: &mut Option<HistoryRecorder>,
-) -> io::Result<bool> {
+) -> io::Result<Option<FileChange>> {
     if !path.exists() {
-        return Ok(false);
+        return Ok(None);
     }
+    let change = FileChange::delete(path, Some(digest.body_hash));
     if mode == ExecutionMode::Plan {
         log_action(
             log_mode,
             &format!("skills: would remove {}", path.display()),
         );
-        return Ok(true);
+        return Ok(Some(change));
     }
     let before = history
         .as_ref()
@@ -594,11 +710,11 @@ fn remove_skill_target(
         recorder.record_change(path, before, crate::history::EntityState::missing());
     }
     log_action(log_mode, &format!("skills: removed {}", path.display()));
-    Ok(true)
+    Ok(Some(change))
 }
 
-impl super::shared::ConflictVariant for SkillVariant {
-    fn tool(&self) -> &'static str {
+impl super::shared::ConflictVariant for SkillVariant<'_> {
+    fn tool(&self) -> &str {
         self.tool
     }
 
@@ -655,6 +771,7 @@ pub(crate) fn sync_scoped_skills_with_mode(
     let mut codex_real_skill_names = HashSet::new();
     let state_path = cfg.skill_state_path()?;
     let mut state = load_skill_state(&state_path)?;
+    migrate_skill_state(cfg, &mut state)?;
 
     for skill in prepared {
         let entry = state.skills.entry(skill.name.clone()).or_default();
@@ -664,7 +781,7 @@ pub(crate) fn sync_scoped_skills_with_mode(
         }
 
         if skill.canonical_digest.is_none() {
-            stats.updated += usize::from(sync_complete_skill_target(
+            let change = sync_complete_skill_target(
                 &skill.selected_path,
                 skill.selected_digest,
                 None,
@@ -672,7 +789,8 @@ pub(crate) fn sync_scoped_skills_with_mode(
                 log_mode,
                 mode,
                 history,
-            )?);
+            )?;
+            stats.record(&skill.name, TOOL_CENTRAL, change);
             let action = if mode == ExecutionMode::Plan {
                 "would import"
             } else {
@@ -695,16 +813,28 @@ pub(crate) fn sync_scoped_skills_with_mode(
             codex_real_skill_names.insert(skill.name.clone());
         }
 
+        let source = if skill.canonical_digest.is_none() && mode == ExecutionMode::Plan {
+            &skill.selected_path
+        } else {
+            &skill.canonical_path
+        };
         for adapter in skill.adapters {
-            stats.updated += usize::from(sync_complete_skill_target(
-                &skill.canonical_path,
+            let change = sync_complete_skill_target(
+                source,
                 skill.selected_digest,
                 adapter.existing_digest,
                 &adapter.path,
                 log_mode,
                 mode,
                 history,
-            )?);
+            )?;
+            let updated = adapter
+                .labels
+                .first()
+                .is_some_and(|&tool| stats.record(&skill.name, tool, change));
+            if updated && mode == ExecutionMode::Plan {
+                entry.forget_complete_hashes(&adapter.labels);
+            }
             let complete_hash = (mode == ExecutionMode::Apply)
                 .then(|| digest_complete_skill_identity(&adapter.path))
                 .transpose()?
@@ -717,36 +847,34 @@ pub(crate) fn sync_scoped_skills_with_mode(
         }
     }
 
-    if mode == ExecutionMode::Apply {
-        save_skill_state(&state_path, &state, history)?;
-    }
+    stats.record_state(save_skill_state(&state_path, &state, mode, history)?);
     Ok(SkillSyncOutcome {
         stats,
         codex_real_skill_names,
     })
 }
 
-struct PreparedScopedSkill {
+struct PreparedScopedSkill<'a> {
     name: String,
     selected_path: PathBuf,
     selected_digest: DirDigest,
     state_digest: DirDigest,
     canonical_path: PathBuf,
     canonical_digest: Option<DirDigest>,
-    adapters: Vec<PreparedAdapter>,
+    adapters: Vec<PreparedAdapter<'a>>,
 }
 
-struct PreparedAdapter {
-    labels: Vec<&'static str>,
+struct PreparedAdapter<'a> {
+    labels: Vec<&'a str>,
     path: PathBuf,
     existing_digest: Option<DirDigest>,
 }
 
-fn prepare_scoped_skills(
-    cfg: &Config,
+fn prepare_scoped_skills<'a>(
+    cfg: &'a Config,
     selected: &[ScopedSkill],
     conflicts: &mut Vec<SyncConflict>,
-) -> io::Result<Vec<PreparedScopedSkill>> {
+) -> io::Result<Vec<PreparedScopedSkill<'a>>> {
     let locations = skill_locations(cfg)?;
     validate_scoped_destination_names(cfg, selected, &locations)?;
     let mut prepared = Vec::with_capacity(selected.len());
@@ -762,8 +890,8 @@ fn prepare_scoped_skills(
             conflicts.push(SyncConflict {
                 kind: SyncItemKind::Skill,
                 name: refreshed.name.clone(),
-                winner: TOOL_CENTRAL,
-                others: vec![TOOL_SELECTED],
+                winner: TOOL_CENTRAL.to_string(),
+                others: vec![TOOL_SELECTED.to_string()],
             });
             log_action(
                 SyncLogMode::Quiet,
@@ -904,6 +1032,7 @@ fn sync_all_skills_with_mode(
 
     let state_path = cfg.skill_state_path()?;
     let mut state = load_skill_state(&state_path)?;
+    migrate_skill_state(cfg, &mut state)?;
     let central = list_skills_if_exists(&cfg.central_skills_dir, true)?;
     let locations = skill_locations(cfg)?;
     let mut location_maps = Vec::new();
@@ -966,8 +1095,8 @@ fn sync_all_skills_with_mode(
                 let owned =
                     adapter_matches_recorded_ownership(entry, &location.labels, path, digest);
                 if owned {
-                    stats.updated +=
-                        usize::from(remove_skill_target(path, log_mode, mode, history)?);
+                    let change = remove_skill_target(path, digest, log_mode, mode, history)?;
+                    stats.record(&name, location.label, change);
                     entry.clear_adapter_ownership(&location.labels);
                 } else {
                     log_action(log_mode, &format!(
@@ -1006,63 +1135,81 @@ fn sync_all_skills_with_mode(
                         && adapter_changed_since_sync(entry, source)
                 })
                 .collect();
-            if canonical_changed {
-                for source in changed_adapters
+            let policy = cfg.conflict_policy.for_kind(SyncItemKind::Skill);
+            let contenders: Vec<&SkillVariant> = if canonical_changed {
+                changed_adapters
                     .iter()
+                    .copied()
                     .filter(|source| source.digest.body_hash != current_digest.body_hash)
-                {
-                    conflicts.push(SyncConflict {
-                        kind: SyncItemKind::Skill,
-                        name: name.clone(),
-                        winner: TOOL_CENTRAL,
-                        others: vec![source.tool],
-                    });
-                    log_action(log_mode, &format!(
-                        "warning: skills '{name}' changed in canonical store and {}; canonical store won",
-                        source.tool
-                    ));
+                    .chain(std::iter::once(current))
+                    .collect()
+            } else {
+                changed_adapters
+            };
+            match pick_by_policy(policy, &contenders) {
+                Some(winner) if winner.tool == TOOL_CENTRAL => {
+                    for source in contenders
+                        .iter()
+                        .filter(|source| source.tool != TOOL_CENTRAL)
+                    {
+                        let conflict = SyncConflict {
+                            kind: SyncItemKind::Skill,
+                            name: name.clone(),
+                            winner: TOOL_CENTRAL.to_string(),
+                            others: vec![source.tool.to_string()],
+                        };
+                        preserve_skill_conflict(cfg, &conflict, &contenders, log_mode, mode)?;
+                        conflicts.push(conflict);
+                        log_action(log_mode, &format!(
+                            "warning: skills '{name}' changed in canonical store and {}; canonical store won",
+                            source.tool
+                        ));
+                    }
                 }
-            } else if let Some(winner) = changed_adapters
-                .iter()
-                .max_by_key(|source| (source.digest.mtime, tool_order(source.tool)))
-            {
-                let others: Vec<&'static str> = changed_adapters
-                    .iter()
-                    .filter(|source| {
-                        source.tool != winner.tool
-                            && source.digest.body_hash != winner.digest.body_hash
-                    })
-                    .map(|source| source.tool)
-                    .collect();
-                if !others.is_empty() {
-                    conflicts.push(SyncConflict {
-                        kind: SyncItemKind::Skill,
-                        name: name.clone(),
-                        winner: winner.tool,
-                        others,
-                    });
-                    log_action(
+                Some(winner) => {
+                    let others: Vec<String> = contenders
+                        .iter()
+                        .filter(|source| {
+                            source.tool != winner.tool
+                                && source.digest.body_hash != winner.digest.body_hash
+                        })
+                        .map(|source| source.tool.to_string())
+                        .collect();
+                    if !others.is_empty() {
+                        let conflict = SyncConflict {
+                            kind: SyncItemKind::Skill,
+                            name: name.clone(),
+                            winner: winner.tool.to_string(),
+                            others,
+                        };
+                        preserve_skill_conflict(cfg, &conflict, &contenders, log_mode, mode)?;
+                        conflicts.push(conflict);
+                        log_action(
+                            log_mode,
+                            &format!(
+                                "warning: skills '{name}' changed in several places; conflict policy {} chose {}",
+                                policy.label(),
+                                winner.tool
+                            ),
+                        );
+                    }
+                    let change = sync_skill_target(
+                        &winner.path,
+                        winner.digest,
+                        Some(current_digest),
+                        &central_path,
                         log_mode,
-                        &format!(
-                            "warning: skills '{name}' changed in multiple adapters; newest adapter {} won",
-                            winner.tool
-                        ),
-                    );
+                        mode,
+                        history,
+                    )?;
+                    stats.record(&name, TOOL_CENTRAL, change);
+                    canonical = Some(SkillVariant {
+                        tool: TOOL_CENTRAL,
+                        path: central_path.clone(),
+                        digest: winner.digest,
+                    });
                 }
-                stats.updated += usize::from(sync_skill_target(
-                    &winner.path,
-                    winner.digest,
-                    Some(current_digest),
-                    &central_path,
-                    log_mode,
-                    mode,
-                    history,
-                )?);
-                canonical = Some(SkillVariant {
-                    tool: TOOL_CENTRAL,
-                    path: central_path.clone(),
-                    digest: winner.digest,
-                });
+                None => {}
             }
             for source in sources.iter().filter(|source| {
                 locations
@@ -1079,17 +1226,26 @@ fn sync_all_skills_with_mode(
         }
 
         if canonical.is_none() && !entry.tombstoned && !sources.is_empty() {
-            let winner = select_skill_winner(&sources);
+            // Nothing was synced yet, so every copy is an edit.
+            let winner = pick_winner(
+                cfg.conflict_policy.for_kind(SyncItemKind::Skill),
+                &sources,
+                |_| true,
+            )
+            .expect("winner available");
             if let Some(conflict) = conflict_for_variants(
                 &name,
                 SyncItemKind::Skill,
                 &sources,
+                |_| true,
                 winner.tool,
                 winner.digest.body_hash,
             ) {
+                let candidates: Vec<&SkillVariant> = sources.iter().collect();
+                preserve_skill_conflict(cfg, &conflict, &candidates, log_mode, mode)?;
                 conflicts.push(conflict);
             }
-            stats.updated += usize::from(sync_skill_target(
+            let change = sync_skill_target(
                 &winner.path,
                 winner.digest,
                 None,
@@ -1097,10 +1253,17 @@ fn sync_all_skills_with_mode(
                 log_mode,
                 mode,
                 history,
-            )?);
+            )?;
+            stats.record(&name, TOOL_CENTRAL, change);
+            // A plan never writes the import, so adapters preview the winner.
+            let path = if mode == ExecutionMode::Plan {
+                winner.path.clone()
+            } else {
+                central_path.clone()
+            };
             canonical = Some(SkillVariant {
                 tool: TOOL_CENTRAL,
-                path: central_path.clone(),
+                path,
                 digest: winner.digest,
             });
             let action = if mode == ExecutionMode::Plan {
@@ -1131,7 +1294,7 @@ fn sync_all_skills_with_mode(
                 .get(&name)
                 .map(|path| digest_skill_dir(path))
                 .transpose()?;
-            let updated = sync_skill_target(
+            let change = sync_skill_target(
                 &canonical.path,
                 canonical.digest,
                 existing,
@@ -1140,7 +1303,10 @@ fn sync_all_skills_with_mode(
                 mode,
                 history,
             )?;
-            stats.updated += usize::from(updated);
+            let updated = stats.record(&name, location.label, change);
+            if updated && mode == ExecutionMode::Plan {
+                entry.forget_complete_hashes(&location.labels);
+            }
             let complete_hash = (updated && mode == ExecutionMode::Apply)
                 .then(|| digest_complete_skill_identity(&location.path.join(&name)))
                 .transpose()?
@@ -1153,15 +1319,25 @@ fn sync_all_skills_with_mode(
         }
     }
 
-    if mode == ExecutionMode::Apply {
-        save_skill_state(&state_path, &state, history)?;
-    }
+    stats.record_state(save_skill_state(&state_path, &state, mode, history)?);
     Ok(SkillSyncOutcome {
         stats,
         codex_real_skill_names,
     })
 }
 
+/// Central skill names that own their Codex skill directory, as a full skill
+/// pass would report them, for syncs that reconcile commands on their own.
+pub(super) fn codex_real_skill_names(cfg: &Config) -> io::Result<HashSet<String>> {
+    if !codex_skills_target_enabled(cfg) {
+        return Ok(HashSet::new());
+    }
+    Ok(list_skills_if_exists(&cfg.central_skills_dir, true)?
+        .into_keys()
+        .filter(|name| !cfg.is_blacklisted(&format!("skills/{name}"), TOOL_CODEX))
+        .collect())
+}
+
 pub(super) fn codex_skills_target_enabled(cfg: &Config) -> bool {
     cfg.tool_enabled(TOOL_CODEX)
         && (cfg.codex_skills_dir == cfg.central_skills_dir
@@ -1171,13 +1347,6 @@ pub(super) fn codex_skills_target_enabled(cfg: &Config) -> bool {
                 .is_some_and(|parent| parent.exists()))
 }
 
-fn select_skill_winner(variants: &[SkillVariant]) -> &SkillVariant {
-    variants
-        .iter()
-        .max_by_key(|variant| (variant.digest.mtime, tool_order(variant.tool)))
-        .expect("winner available")
-}
-
 fn paths_refer_to_same_entry(left: &Path, right: &Path) -> bool {
     left == right
         || fs::canonicalize(left)
@@ -1216,7 +1385,7 @@ fn adapter_matches_recorded_ownership(
     })
 }
 
-fn adapter_changed_since_sync(entry: &SkillStateEntry, source: &SkillVariant) -> bool {
+fn adapter_changed_since_sync(entry: &SkillStateEntry, source: &SkillVariant<'_>) -> bool {
     if let Some(expected) = entry.adapter_complete_hashes.get(source.tool) {
         return digest_complete_skill_identity(&source.path)
             .map_or(true, |digest| *expected != persisted_hash(digest.body_hash));
@@ -1235,7 +1404,7 @@ fn sync_complete_skill_target(
     log_mode: SyncLogMode,
     mode: ExecutionMode,
     history: &mut Option<HistoryRecorder>,
-) -> io::Result<bool> {
+) -> io::Result<Option<FileChange>> {
     sync_skill_target_with_policy(
         source,
         source_digest,
@@ -1248,6 +1417,27 @@ fn sync_complete_skill_target(
     )
 }
 
+/// Skill conflicts only ever name tools whose copy differs from the winner, so
+/// every listed candidate is a loser that is about to be replaced.
+fn preserve_skill_conflict(
+    cfg: &Config,
+    conflict: &SyncConflict,
+    candidates: &[&SkillVariant],
+    log_mode: SyncLogMode,
+    mode: ExecutionMode,
+) -> io::Result<()> {
+    let losers: Vec<LosingVariant<'_>> = candidates
+        .iter()
+        .filter(|source| conflict.others.iter().any(|tool| tool == source.tool))
+        .map(|source| LosingVariant {
+            tool: source.tool,
+            source: &source.path,
+            copy: LosingCopy::Dir(&source.path),
+        })
+        .collect();
+    preserve_conflict(cfg, conflict, &losers, log_mode, mode)
+}
+
 fn sync_skill_target(
     source: &Path,
     source_digest: DirDigest,
@@ -1256,7 +1446,7 @@ fn sync_skill_target(
     log_mode: SyncLogMode,
     mode: ExecutionMode,
     history: &mut Option<HistoryRecorder>,
-) -> io::Result<bool> {
+) -> io::Result<Option<FileChange>> {
     sync_skill_target_with_policy(
         source,
         source_digest,
@@ -1279,16 +1469,25 @@ fn sync_skill_target_with_policy(
     mode: ExecutionMode,
     history: &mut Option<HistoryRecorder>,
     policy: PackagePolicy,
-) -> io::Result<bool> {
+) -> io::Result<Option<FileChange>> {
     if existing.is_some_and(|digest| digest.body_hash == source_digest.body_hash) {
-        return Ok(false);
+        return Ok(None);
     }
+    let change = FileChange::write(
+        target_path,
+        existing.map(|digest| digest.body_hash),
+        source_digest.body_hash,
+    )
+    .planned(mode, || {
+        planned_skill_files(source, target_path, policy)
+            .map(|files| Some(PlannedContent::Dir(files)))
+    })?;
     if mode == ExecutionMode::Plan {
         log_action(
             log_mode,
             &format!("skills: would update {}", target_path.display()),
         );
-        return Ok(true);
+        return Ok(Some(change));
     }
 
     #[cfg(test)]
@@ -1368,7 +1567,7 @@ fn sync_skill_target_with_policy(
         log_mode,
         &format!("skills: updated {}", target_path.display()),
     );
-    Ok(true)
+    Ok(Some(change))
 }
 
 #[cfg(test)]
@@ -1390,24 +1589,51 @@ pub(super) fn set_skill_assembly_failure_for_test(phase: Option<&'static str>) {
     SKILL_ASSEMBLY_FAILURE_PHASE.set(phase);
 }
 
+/// The files a skill target would hold after sync: the source package under
+/// `policy`, with `SKILL.md` carrying the target's own frontmatter.
+fn planned_skill_files(
+    source: &Path,
+    target_path: &Path,
+    policy: PackagePolicy,
+) -> io::Result<Vec<PlannedFile>> {
+    let mut entries: Vec<(String, PathBuf)> = Vec::new();
+    collect_skill_entries(source, source, &mut entries, policy)?;
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut files = Vec::new();
+    for (rel, path) in entries {
+        let metadata = fs::metadata(&path)?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let bytes = if rel == "SKILL.md" {
+            let label = format!("skills: {}", target_path.join("SKILL.md").display());
+            let target_skill = target_path.join("SKILL.md");
+            match merged_skill_file(&path, &target_skill, SyncLogMode::Quiet, &label)? {
+                Some(merged) => merged.into_bytes(),
+                None => fs::read(&path)?,
+            }
+        } else {
+            fs::read(&path)?
+        };
+        files.push(PlannedFile {
+            rel,
+            mode: file_mode(&metadata),
+            bytes,
+        });
+    }
+    Ok(files)
+}
+
 fn merge_skill_frontmatter(
     source_skill: &Path,
     target_skill: &Path,
     temp_path: &Path,
     log_mode: SyncLogMode,
 ) -> io::Result<()> {
-    if !source_skill.exists() {
-        return Ok(());
-    }
-    let source_doc = read_markdown(source_skill)?;
-    let target_doc = target_skill
-        .exists()
-        .then(|| read_markdown(target_skill))
-        .transpose()?;
     let label = format!("skills: {}", temp_path.join("SKILL.md").display());
-    let frontmatter =
-        select_frontmatter_for_target(&source_doc, target_doc.as_ref(), true, log_mode, &label);
-    let merged = super::shared::merge_frontmatter(frontmatter.as_deref(), &source_doc.body);
+    let Some(merged) = merged_skill_file(source_skill, target_skill, log_mode, &label)? else {
+        return Ok(());
+    };
     let mut history = None;
     write_file(
         &temp_path.join("SKILL.md"),
@@ -1418,6 +1644,28 @@ fn merge_skill_frontmatter(
     Ok(())
 }
 
+fn merged_skill_file(
+    source_skill: &Path,
+    target_skill: &Path,
+    log_mode: SyncLogMode,
+    label: &str,
+) -> io::Result<Option<String>> {
+    if !source_skill.exists() {
+        return Ok(None);
+    }
+    let source_doc = read_markdown(source_skill)?;
+    let target_doc = target_skill
+        .exists()
+        .then(|| read_markdown(target_skill))
+        .transpose()?;
+    let frontmatter =
+        select_frontmatter_for_target(&source_doc, target_doc.as_ref(), true, log_mode, label);
+    Ok(Some(super::shared::merge_frontmatter(
+        frontmatter.as_deref(),
+        &source_doc.body,
+    )))
+}
+
 fn list_skill_dirs_with_policy(
     dir: &Path,
     import_managed: bool,
@@ -1457,21 +1705,27 @@ fn looks_plugin_managed(path: &Path) -> bool {
 }
 
 fn digest_skill_dir(dir: &Path) -> io::Result<DirDigest> {
-    digest_skill_dir_with_policy(dir, PackagePolicy::LegacyVisible, false)
+    digest_skill_dir_with_policy(dir, PackagePolicy::LegacyVisible, false, HashScheme::Sha256)
 }
 
 fn digest_complete_skill_dir(dir: &Path) -> io::Result<DirDigest> {
-    digest_skill_dir_with_policy(dir, PackagePolicy::CompleteStrict, false)
+    digest_skill_dir_with_policy(
+        dir,
+        PackagePolicy::CompleteStrict,
+        false,
+        HashScheme::Sha256,
+    )
 }
 
 fn digest_complete_skill_identity(dir: &Path) -> io::Result<DirDigest> {
-    digest_skill_dir_with_policy(dir, PackagePolicy::CompleteStrict, true)
+    digest_skill_dir_with_policy(dir, PackagePolicy::CompleteStrict, true, HashScheme::Sha256)
 }
 
 fn digest_skill_dir_with_policy(
     dir: &Path,
     policy: PackagePolicy,
     raw_skill_file: bool,
+    scheme: HashScheme,
 ) -> io::Result<DirDigest> {
     let mut entries: Vec<(String, PathBuf)> = Vec::new();
     collect_skill_entries(dir, dir, &mut entries, policy)?;
@@ -1479,13 +1733,14 @@ fn digest_skill_dir_with_policy(
         entries,
         policy == PackagePolicy::CompleteStrict,
         raw_skill_file,
+        scheme,
     )?;
     if policy == PackagePolicy::CompleteStrict {
         let metadata = fs::metadata(dir)?;
-        let mut hasher = DefaultHasher::new();
-        digest.body_hash.hash(&mut hasher);
-        "package-root-mode".hash(&mut hasher);
-        package_entry_mode(&metadata).hash(&mut hasher);
+        let mut hasher = ContentHasher::new(scheme);
+        hasher.u64(digest.body_hash);
+        hasher.str("package-root-mode");
+        hasher.u32(file_mode(&metadata));
         digest.body_hash = hasher.finish();
         digest.mtime = digest.mtime.max(file_mtime_value_from_meta(&metadata));
     }
@@ -1496,10 +1751,11 @@ fn digest_skill_entries(
     mut entries: Vec<(String, PathBuf)>,
     include_modes: bool,
     raw_skill_file: bool,
+    scheme: HashScheme,
 ) -> io::Result<DirDigest> {
     entries.sort_by(|a, b| a.0.cmp(&b.0));
 
-    let mut body_hasher = DefaultHasher::new();
+    let mut body_hasher = ContentHasher::new(scheme);
     let mut mtime = 0u128;
 
     for (rel, path) in entries {
@@ -1509,26 +1765,23 @@ fn digest_skill_entries(
             mtime = entry_mtime;
         }
         if include_modes {
-            package_entry_mode(&meta).hash(&mut body_hasher);
+            body_hasher.u32(file_mode(&meta));
         }
         if meta.is_dir() {
-            rel.hash(&mut body_hasher);
-            "directory".hash(&mut body_hasher);
+            body_hasher.str(&rel);
+            body_hasher.str("directory");
             continue;
         }
         let file_name = path.file_name().and_then(|os| os.to_str()).unwrap_or("");
         if file_name == "SKILL.md" && !raw_skill_file {
             let doc = read_markdown(&path)?;
-            rel.hash(&mut body_hasher);
-            doc.body_hash.hash(&mut body_hasher);
-            required_frontmatter_hash(&doc)
-                .unwrap_or(0)
-                .hash(&mut body_hasher);
+            body_hasher.str(&rel);
+            body_hasher.u64(hash_bytes_with(scheme, doc.body.as_bytes()));
+            body_hasher.u64(required_frontmatter_hash(scheme, &doc).unwrap_or(0));
         } else {
             let bytes = fs::read(&path)?;
-            let hash = hash_bytes(&bytes);
-            rel.hash(&mut body_hasher);
-            hash.hash(&mut body_hasher);
+            body_hasher.str(&rel);
+            body_hasher.u64(hash_bytes_with(scheme, &bytes));
         }
     }
 
@@ -1538,17 +1791,6 @@ fn digest_skill_entries(
     })
 }
 
-#[cfg(unix)]
-fn package_entry_mode(metadata: &fs::Metadata) -> u32 {
-    use std::os::unix::fs::PermissionsExt;
-    metadata.permissions().mode() & 0o7777
-}
-
-#[cfg(not(unix))]
-fn package_entry_mode(_metadata: &fs::Metadata) -> u32 {
-    0
-}
-
 fn collect_skill_entries(
     root: &Path,
     dir: &Path,
@@ -2306,7 +2548,7 @@ mod tests {
         let selected = discover_scoped_skills(&[selected_path])?;
         let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
-        assert!(outcome.stats.updated > 0);
+        assert!(outcome.stats.updated() > 0);
         assert!(conflicts.is_empty());
         assert_eq!(
             read_markdown(&cfg.central_skills_dir.join("plan/SKILL.md"))?.body,
@@ -2328,7 +2570,7 @@ mod tests {
         let selected = discover_scoped_skills(&[selected_path])?;
         let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
-        assert_eq!(outcome.stats.updated, 0);
+        assert_eq!(outcome.stats.updated(), 0);
         assert_eq!(conflicts.len(), 1);
         assert_eq!(
             read_markdown(&cfg.central_skills_dir.join("plan/SKILL.md"))?.body,
@@ -2363,7 +2605,7 @@ mod tests {
         let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
         assert!(conflicts.is_empty());
-        assert!(outcome.stats.updated > 0);
+        assert!(outcome.stats.updated() > 0);
         assert_eq!(
             fs::read_to_string(cfg.claude_skills_dir.join("identical/references/guide.md"))?,
             "same reference"
@@ -2497,7 +2739,7 @@ mod tests {
         let selected = discover_scoped_skills(std::slice::from_ref(&selected_path))?;
         let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
-        assert!(outcome.stats.updated > 0);
+        assert!(outcome.stats.updated() > 0);
         assert!(conflicts.is_empty());
         for root in [
             &cfg.central_skills_dir,
@@ -2533,7 +2775,7 @@ mod tests {
         );
         let (no_op, no_op_conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Plan)?;
         assert!(no_op_conflicts.is_empty());
-        assert_eq!(no_op.stats.updated, 0);
+        assert_eq!(no_op.stats.updated(), 0);
 
         crate::path_cleanup::remove_with_owner_access(&selected_path)?;
         for root in [
@@ -2607,7 +2849,7 @@ mod tests {
                 write_version(&package, body)?;
                 let selected = discover_scoped_skills(std::slice::from_ref(&package))?;
                 let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
-                assert!(outcome.stats.updated > 0);
+                assert!(outcome.stats.updated() > 0);
                 assert!(conflicts.is_empty());
 
                 for root in [
@@ -2706,7 +2948,7 @@ mod tests {
         let selected = discover_scoped_skills(&[external])?;
         let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Plan)?;
 
-        assert_eq!(outcome.stats.updated, 0);
+        assert_eq!(outcome.stats.updated(), 0);
         assert_eq!(conflicts.len(), 1);
         Ok(())
     }
@@ -2798,7 +3040,7 @@ mod tests {
             let selected = discover_scoped_skills(&[external])?;
             let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Plan)?;
 
-            assert_eq!(outcome.stats.updated, 0, "case {case}");
+            assert_eq!(outcome.stats.updated(), 0, "case {case}");
             assert_eq!(conflicts.len(), 1, "case {case}");
             assert!(!cfg.claude_skills_dir.join("identity").exists());
             assert!(!cfg.skill_state_path()?.exists());
@@ -2815,7 +3057,7 @@ mod tests {
         let selected = discover_scoped_skills(&[first, second])?;
         let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
-        assert_eq!(outcome.stats.updated, 8);
+        assert_eq!(outcome.stats.updated(), 8);
         assert!(conflicts.is_empty());
         for name in ["first", "second"] {
             assert!(cfg.central_skills_dir.join(name).join("SKILL.md").exists());
@@ -2890,7 +3132,7 @@ mod tests {
 
         let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
-        assert_eq!(outcome.stats.updated, 0);
+        assert_eq!(outcome.stats.updated(), 0);
         assert_eq!(conflicts.len(), 1);
         assert_eq!(
             read_markdown(&canonical.join("SKILL.md"))?.body,
@@ -3051,7 +3293,7 @@ mod tests {
         let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
         assert!(conflicts.is_empty());
-        assert_eq!(outcome.stats.updated, 2);
+        assert_eq!(outcome.stats.updated(), 2);
         assert!(outcome.codex_real_skill_names.contains("selected"));
         assert!(cfg.central_skills_dir.join("selected/SKILL.md").exists());
         assert!(shared.join("selected/SKILL.md").exists());
@@ -3121,7 +3363,7 @@ mod tests {
 
             let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
-            assert!(outcome.stats.updated > 0);
+            assert!(outcome.stats.updated() > 0);
             assert!(conflicts.is_empty());
             assert_eq!(
                 fs::read_to_string(colliding.join("sentinel.txt"))?,
@@ -3152,6 +3394,66 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn legacy_skill_state_hashes_are_rederived_from_current_content() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let source = TempDir::new()?;
+        let external = write_skill(source.path(), "review", &doc("review", "Review"))?;
+        let selected = discover_scoped_skills(&[external])?;
+        sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
+        let state_path = cfg.skill_state_path()?;
+        let current = load_skill_state(&state_path)?;
+        assert_eq!(current.version, STATE_VERSION);
+
+        // Rewrite the state the way the previous release recorded it, with
+        // one adapter edited since that sync.
+        let mut to_legacy = HashMap::new();
+        for dir in [
+            cfg.central_skills_dir.join("review"),
+            cfg.claude_skills_dir.join("review"),
+        ] {
+            for (policy, raw_skill_file) in [
+                (PackagePolicy::LegacyVisible, false),
+                (PackagePolicy::CompleteStrict, true),
+            ] {
+                let digest =
+                    |scheme| digest_skill_dir_with_policy(&dir, policy, raw_skill_file, scheme);
+                to_legacy.insert(
+                    persisted_hash(digest(HashScheme::Sha256)?.body_hash),
+                    persisted_hash(digest(HashScheme::Legacy)?.body_hash),
+                );
+            }
+        }
+        let mut legacy = load_skill_state(&state_path)?;
+        legacy.version = legacy_state_version();
+        let entry = legacy.skills.get_mut("review").unwrap();
+        entry
+            .canonical_hash
+            .iter_mut()
+            .chain(entry.adapter_hashes.values_mut())
+            .chain(entry.adapter_complete_hashes.values_mut())
+            .for_each(|hash| *hash = to_legacy[hash]);
+        entry.adapter_hashes.insert(TOOL_OPENCODE.to_string(), 7);
+        save_skill_state(&state_path, &legacy, ExecutionMode::Apply, &mut None)?;
+        assert!(!fs::read_to_string(&state_path)?.contains("version = 2"));
+
+        let mut migrated = load_skill_state(&state_path)?;
+        migrate_skill_state(&cfg, &mut migrated)?;
+
+        let mut expected = current;
+        expected
+            .skills
+            .get_mut("review")
+            .unwrap()
+            .adapter_hashes
+            .insert(TOOL_OPENCODE.to_string(), 7);
+        assert_eq!(
+            toml::to_string(&migrated).unwrap(),
+            toml::to_string(&expected).unwrap()
+        );
+        Ok(())
+    }
+
     #[test]
     fn scoped_external_selection_rebuilds_tombstoned_ownership() -> io::Result<()> {
         let (_tmp, cfg) = setup()?;
@@ -3171,7 +3473,12 @@ mod tests {
                 ]),
             },
         );
-        save_skill_state(&cfg.skill_state_path()?, &state, &mut None)?;
+        save_skill_state(
+            &cfg.skill_state_path()?,
+            &state,
+            ExecutionMode::Apply,
+            &mut None,
+        )?;
         let source = TempDir::new()?;
         let external = write_skill(
             source.path(),
@@ -3182,7 +3489,7 @@ mod tests {
 
         let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
-        assert_eq!(outcome.stats.updated, 4);
+        assert_eq!(outcome.stats.updated(), 4);
         assert!(conflicts.is_empty());
         let state = load_skill_state(&cfg.skill_state_path()?)?;
         let entry = &state.skills["restored"];
@@ -3235,7 +3542,7 @@ mod tests {
 
             let (outcome, conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
-            assert_eq!(outcome.stats.updated, 2);
+            assert_eq!(outcome.stats.updated(), 2);
             assert!(conflicts.is_empty());
             assert_eq!(fs::read(legacy.join("SKILL.md"))?, legacy_before);
             assert_eq!(
@@ -3333,7 +3640,12 @@ mod tests {
                     adapter_complete_hashes: BTreeMap::new(),
                 },
             );
-            save_skill_state(&cfg.skill_state_path()?, &state, &mut None)?;
+            save_skill_state(
+                &cfg.skill_state_path()?,
+                &state,
+                ExecutionMode::Apply,
+                &mut None,
+            )?;
             let state_before = fs::read_to_string(cfg.skill_state_path()?)?;
             let adapter_before = fs::read_to_string(retired_adapter.join("SKILL.md"))?;
 
@@ -3394,7 +3706,7 @@ mod tests {
 
         let (apply, apply_conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
-        assert_eq!(plan.stats.updated, apply.stats.updated);
+        assert_eq!(plan.stats.updated(), apply.stats.updated());
         assert_eq!(plan_conflicts, apply_conflicts);
         assert!(cfg.central_skills_dir.join("plan/scripts/run.sh").exists());
         Ok(())
@@ -3450,7 +3762,12 @@ mod tests {
                 adapter_complete_hashes: BTreeMap::new(),
             },
         );
-        save_skill_state(&cfg.skill_state_path()?, &state, &mut None)?;
+        save_skill_state(
+            &cfg.skill_state_path()?,
+            &state,
+            ExecutionMode::Apply,
+            &mut None,
+        )?;
         let state_before = fs::read(cfg.skill_state_path()?)?;
         let source = TempDir::new()?;
         let new = write_skill(source.path(), "new", &doc("new", "New external"))?;
@@ -3468,7 +3785,7 @@ mod tests {
 
         let (apply, apply_conflicts) = sync_selected(&cfg, &selected, ExecutionMode::Apply)?;
 
-        assert_eq!(plan.stats.updated, apply.stats.updated);
+        assert_eq!(plan.stats.updated(), apply.stats.updated());
         assert_eq!(plan_conflicts, apply_conflicts);
         assert_eq!(fs::read(preserved.join("SKILL.md"))?, preserved_before);
         assert_eq!(fs::read(unrelated.join("SKILL.md"))?, unrelated_before);
@@ -3733,7 +4050,12 @@ mod tests {
             .expect("plan state exists")
             .adapter_complete_hashes
             .clear();
-        save_skill_state(&cfg.skill_state_path()?, &state, &mut None)?;
+        save_skill_state(
+            &cfg.skill_state_path()?,
+            &state,
+            ExecutionMode::Apply,
+            &mut None,
+        )?;
 
         fs::remove_dir_all(central)?;
         sync_skills(&cfg, SyncLogMode::Quiet)?;
@@ -3896,8 +4218,8 @@ mod tests {
         assert!(conflicts.contains(&SyncConflict {
             kind: SyncItemKind::Skill,
             name: "plan".to_string(),
-            winner: TOOL_CODEX,
-            others: vec![TOOL_CLAUDE],
+            winner: TOOL_CODEX.to_string(),
+            others: vec![TOOL_CLAUDE.to_string()],
         }));
         Ok(())
     }
@@ -3928,7 +4250,7 @@ mod tests {
         let (_tmp, cfg) = setup()?;
         write_skill(&cfg.central_skills_dir, "plan", &doc("plan", "Body"))?;
         sync_skills(&cfg, SyncLogMode::Quiet)?;
-        let lines = diagnostics(&cfg)?;
+        let lines = diagnostics(&cfg)?.lines();
         assert!(lines[0].contains("count=1"));
         assert!(lines
             .iter()
@@ -4005,7 +4327,7 @@ mod tests {
             ExecutionMode::Apply,
             &mut history,
         )?;
-        assert!(!updated);
+        assert!(updated.is_none());
         Ok(())
     }
 
@@ -4032,8 +4354,8 @@ mod tests {
             vec![SyncConflict {
                 kind: SyncItemKind::Skill,
                 name: "plan".to_string(),
-                winner: TOOL_CODEX,
-                others: vec![TOOL_CLAUDE],
+                winner: TOOL_CODEX.to_string(),
+                others: vec![TOOL_CLAUDE.to_string()],
             }]
         );
         Ok(())
@@ -0,0 +1,242 @@
+use super::shared::{
+    collect_names, conflict_for_variants, list_files, list_if, log_action, read_markdown_variant,
+    select_markdown_winner, update_markdown_target, MarkdownVariant, SyncedHashes, TOOL_CENTRAL,
+};
+use super::{ExecutionMode, LogMode, SyncConflict, SyncItemKind, SyncStats};
+use crate::config::{Config, TOOL_CLAUDE, TOOL_OPENCODE};
+use crate::history::HistoryRecorder;
+use std::collections::HashMap;
+use std::io;
+
+#[cfg(any(test, coverage))]
+pub(crate) fn sync_subagents(cfg: &Config, log_mode: LogMode) -> io::Result<SyncStats> {
+    let mut history = None;
+    let mut conflicts = Vec::new();
+    sync_subagents_with_mode(
+        cfg,
+        log_mode,
+        ExecutionMode::Apply,
+        &mut history,
+        &mut conflicts,
+    )
+}
+
+pub(crate) fn sync_subagents_with_mode(
+    cfg: &Config,
+    log_mode: LogMode,
+    mode: ExecutionMode,
+    history: &mut Option<HistoryRecorder>,
+    conflicts: &mut Vec<SyncConflict>,
+) -> io::Result<SyncStats> {
+    let mut stats = SyncStats::default();
+
+    let claude_enabled = cfg.tool_enabled(TOOL_CLAUDE)
+        && cfg
+            .claude_subagents_dir
+            .parent()
+            .is_some_and(|parent| parent.exists());
+    let opencode_enabled = cfg.tool_enabled(TOOL_OPENCODE)
+        && cfg
+            .opencode_subagents_dir
+            .parent()
+            .is_some_and(|parent| parent.exists());
+
+    let claude = list_if(
+        claude_enabled && cfg.claude_subagents_dir.exists(),
+        &cfg.claude_subagents_dir,
+        list_files,
+    )?;
+    let opencode = list_if(
+        opencode_enabled && cfg.opencode_subagents_dir.exists(),
+        &cfg.opencode_subagents_dir,
+        list_files,
+    )?;
+    let central = if cfg.central_subagents_dir.exists() {
+        list_files(&cfg.central_subagents_dir)?
+    } else {
+        HashMap::new()
+    };
+
+    let state_path = cfg.synced_state_path(SyncItemKind::Subagent)?;
+    let previous_state = SyncedHashes::load(&state_path)?;
+    let mut state = SyncedHashes::default();
+    let changed =
+        |variant: &MarkdownVariant| previous_state.changed(&variant.path, variant.doc.body_hash);
+    let names = collect_names(&[&claude, &opencode, &central]);
+    for name in &names {
+        let blacklist_key = format!("subagents/{name}");
+        let mut variants: Vec<MarkdownVariant> = Vec::new();
+        for (tool, map) in [
+            (TOOL_CENTRAL, &central),
+            (TOOL_CLAUDE, &claude),
+            (TOOL_OPENCODE, &opencode),
+        ] {
+            if let Some(path) = map.get(name) {
+                variants.push(read_markdown_variant(tool, path)?);
+            }
+        }
+        let policy = cfg.conflict_policy.for_kind(SyncItemKind::Subagent);
+        let winner = select_markdown_winner(policy, &variants, changed);
+        if let Some(conflict) = conflict_for_variants(
+            name,
+            SyncItemKind::Subagent,
+            &variants,
+            changed,
+            winner.tool,
+            winner.doc.body_hash,
+        ) {
+            conflicts.push(conflict);
+            log_action(
+                log_mode,
+                &format!(
+                    "warning: subagent '{name}' edited in multiple tools; conflict policy {} chose {}",
+                    policy.label(),
+                    winner.tool
+                ),
+            );
+        }
+        let source = &winner.doc;
+
+        // Only `name:` and `description:` travel between tools; fields such as
+        // `tools` or `model` use tool-specific vocabularies and stay per target.
+        for (tool, enabled, base_dir) in [
+            (TOOL_CENTRAL, true, &cfg.central_subagents_dir),
+            (TOOL_CLAUDE, claude_enabled, &cfg.claude_subagents_dir),
+            (TOOL_OPENCODE, opencode_enabled, &cfg.opencode_subagents_dir),
+        ] {
+            let target_path = base_dir.join(name);
+            let existing = variants
+                .iter()
+                .find(|variant| variant.tool == tool)
+                .map(|variant| &variant.doc);
+            if !enabled || (tool != TOOL_CENTRAL && cfg.is_blacklisted(&blacklist_key, tool)) {
+                if let Some(doc) = existing {
+                    state.insert(&target_path, doc.body_hash);
+                }
+                continue;
+            }
+            let label = format!("subagents: {}", target_path.display());
+            let change = update_markdown_target(
+                source,
+                existing,
+                &target_path,
+                true,
+                log_mode,
+                mode,
+                history,
+                &label,
+            )?;
+            state.insert_written_markdown(&target_path, change.as_ref())?;
+            stats.record(name, tool, change);
+        }
+    }
+    stats.record_state(state.save_if_changed(&previous_state, &state_path, mode, history)?);
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::test_support::{read_body, read_frontmatter, set_mtime, setup, write_plain};
+    use std::fs;
+
+    #[test]
+    fn sync_subagents_mirrors_claude_agent_to_opencode_and_central() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let claude = cfg.claude_subagents_dir.join("reviewer.md");
+        write_plain(
+            &claude,
+            "---\nname: reviewer\ndescription: Reviews diffs\ntools: Read, Grep\n---\nReview carefully.",
+        )?;
+
+        sync_subagents(&cfg, LogMode::Quiet)?;
+
+        for dir in [&cfg.central_subagents_dir, &cfg.opencode_subagents_dir] {
+            assert_eq!(read_body(&dir.join("reviewer.md"))?, "Review carefully.");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sync_subagents_preserves_tool_specific_frontmatter() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let claude = cfg.claude_subagents_dir.join("reviewer.md");
+        let opencode = cfg.opencode_subagents_dir.join("reviewer.md");
+        write_plain(
+            &claude,
+            "---\nname: reviewer\ndescription: Old\nmodel: sonnet\ntools: Read, Grep\n---\nOld body",
+        )?;
+        write_plain(
+            &opencode,
+            "---\nname: reviewer\ndescription: New\nmode: subagent\nmodel: anthropic/claude-sonnet-4\n---\nNew body",
+        )?;
+        set_mtime(&claude, 100)?;
+        set_mtime(&opencode, 200)?;
+
+        sync_subagents(&cfg, LogMode::Quiet)?;
+
+        assert_eq!(read_body(&claude)?, "New body");
+        let frontmatter = read_frontmatter(&claude)?.unwrap();
+        assert!(frontmatter.contains("description: New"));
+        assert!(frontmatter.contains("model: sonnet"));
+        assert!(frontmatter.contains("tools: Read, Grep"));
+        assert!(!frontmatter.contains("mode: subagent"));
+        assert!(read_frontmatter(&opencode)?
+            .unwrap()
+            .contains("model: anthropic/claude-sonnet-4"));
+        Ok(())
+    }
+
+    #[test]
+    fn sync_subagents_blacklist_skips_tool() -> io::Result<()> {
+        let (_tmp, mut cfg) = setup()?;
+        write_plain(
+            &cfg.central_subagents_dir.join("reviewer.md"),
+            "---\nname: reviewer\ndescription: Reviews\n---\nBody",
+        )?;
+        cfg.blacklist
+            .entry("subagents/reviewer.md".to_string())
+            .or_default()
+            .push(TOOL_OPENCODE.to_string());
+
+        sync_subagents(&cfg, LogMode::Quiet)?;
+
+        assert!(cfg.claude_subagents_dir.join("reviewer.md").exists());
+        assert!(!cfg.opencode_subagents_dir.join("reviewer.md").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn sync_subagents_collects_conflict_details() -> io::Result<()> {
+        let (_tmp, cfg) = setup()?;
+        let claude = cfg.claude_subagents_dir.join("reviewer.md");
+        let opencode = cfg.opencode_subagents_dir.join("reviewer.md");
+        write_plain(&claude, "Claude body")?;
+        write_plain(&opencode, "OpenCode body")?;
+        set_mtime(&claude, 100)?;
+        set_mtime(&opencode, 101)?;
+
+        let mut history = None;
+        let mut conflicts = Vec::new();
+        sync_subagents_with_mode(
+            &cfg,
+            LogMode::Quiet,
+            ExecutionMode::Plan,
+            &mut history,
+            &mut conflicts,
+        )?;
+
+        assert_eq!(
+            conflicts,
+            vec![SyncConflict {
+                kind: SyncItemKind::Subagent,
+                name: "reviewer.md".to_string(),
+                winner: TOOL_OPENCODE.to_string(),
+                others: vec![TOOL_CLAUDE.to_string()],
+            }]
+        );
+        assert_eq!(fs::read_to_string(&claude)?, "Claude body");
+        Ok(())
+    }
+}
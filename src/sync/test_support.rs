@@ -1,4 +1,6 @@
-use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_OPENCODE};
+use crate::config::{
+    Config, ProviderConfig, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_GEMINI, TOOL_OPENCODE,
+};
 use crate::sync::shared::read_markdown;
 use filetime::{set_file_mtime, FileTime};
 use std::collections::HashMap;
@@ -13,6 +15,7 @@ pub(crate) fn make_config(tmp: &TempDir) -> Config {
             TOOL_CLAUDE.to_string(),
             TOOL_CODEX.to_string(),
             TOOL_CURSOR.to_string(),
+            TOOL_GEMINI.to_string(),
             TOOL_OPENCODE.to_string(),
         ],
         verified_versions: HashMap::new(),
@@ -21,16 +24,34 @@ pub(crate) fn make_config(tmp: &TempDir) -> Config {
         central_skills_dir: tmp.path().join("central_skills"),
         central_agents_dir: tmp.path().join("central_agents"),
         central_rules_dir: tmp.path().join("central_rules"),
+        central_mcp_dir: tmp.path().join("central_mcp"),
+        central_subagents_dir: tmp.path().join("central_subagents"),
         claude_dir: tmp.path().join("claude_commands"),
         claude_skills_dir: tmp.path().join("claude_skills"),
+        claude_subagents_dir: tmp.path().join("claude_agents"),
+        claude_agents_file: tmp.path().join("claude_home/CLAUDE.md"),
         cursor_dir: tmp.path().join("cursor"),
         opencode_commands_dir: tmp.path().join("opencode/commands"),
         opencode_legacy_commands_dir: None,
         opencode_skills_dir: tmp.path().join("opencode/skills"),
         opencode_agents_file: tmp.path().join("opencode_agents/AGENTS.md"),
+        opencode_subagents_dir: tmp.path().join("opencode/agent"),
         codex_skills_dir: tmp.path().join("codex_skills"),
         codex_rules_file: tmp.path().join("codex_rules/default.rules"),
         codex_agents_file: tmp.path().join("codex_agents/AGENTS.md"),
+        gemini_commands_dir: tmp.path().join("gemini/commands"),
+        gemini_skills_dir: tmp.path().join("central_skills"),
+        gemini_agents_file: tmp.path().join("gemini/GEMINI.md"),
+        claude_mcp_file: tmp.path().join("claude_mcp/.claude.json"),
+        codex_config_file: tmp.path().join("codex_config/config.toml"),
+        cursor_mcp_file: tmp.path().join("cursor_mcp/mcp.json"),
+        opencode_config_file: tmp.path().join("opencode_config/opencode.json"),
+        providers: std::collections::BTreeMap::new(),
+        project_root: None,
+        three_way_merge: false,
+        preserve_conflicts: false,
+        conflict_policy: Default::default(),
+        history: Default::default(),
     }
 }
 
@@ -42,13 +63,20 @@ pub(crate) fn ensure_tool_dirs(cfg: &Config) -> io::Result<()> {
         &cfg.opencode_commands_dir,
         &cfg.opencode_skills_dir,
         &cfg.codex_skills_dir,
+        &cfg.gemini_commands_dir,
     ] {
         fs::create_dir_all(dir)?;
     }
     for file in [
+        &cfg.claude_agents_file,
         &cfg.opencode_agents_file,
         &cfg.codex_rules_file,
         &cfg.codex_agents_file,
+        &cfg.gemini_agents_file,
+        &cfg.claude_mcp_file,
+        &cfg.codex_config_file,
+        &cfg.cursor_mcp_file,
+        &cfg.opencode_config_file,
     ] {
         file.parent().map(fs::create_dir_all).transpose()?;
     }
@@ -62,6 +90,25 @@ pub(crate) fn setup() -> io::Result<(TempDir, Config)> {
     Ok((tmp, cfg))
 }
 
+/// Declares a `windsurf` provider with every ability under `tmp/windsurf`.
+pub(crate) fn add_provider(tmp: &TempDir, cfg: &mut Config) -> io::Result<()> {
+    let root = tmp.path().join("windsurf");
+    fs::create_dir_all(root.join("commands"))?;
+    fs::create_dir_all(root.join("skills"))?;
+    cfg.providers.insert(
+        "windsurf".to_string(),
+        ProviderConfig {
+            label: Some("Windsurf".to_string()),
+            version_bin: None,
+            commands_dir: Some(root.join("commands")),
+            skills_dir: Some(root.join("skills")),
+            agents_file: Some(root.join("AGENTS.md")),
+            rules_file: Some(root.join("rules.md")),
+        },
+    );
+    Ok(())
+}
+
 pub(crate) fn set_mtime(path: &Path, secs: i64) -> io::Result<()> {
     set_file_mtime(path, FileTime::from_unix_time(secs, 0))
 }
@@ -1,14 +1,18 @@
-use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_OPENCODE};
-use std::path::PathBuf;
+use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_GEMINI, TOOL_OPENCODE};
+use crate::sync::CommandFormat;
+use std::path::{Path, PathBuf};
 
 pub(crate) struct ToolDefinition {
     pub id: &'static str,
     pub label: &'static str,
     pub version_bin: Option<&'static str>,
     pub commands_dir: Option<fn(&Config) -> &PathBuf>,
+    pub command_format: CommandFormat,
     pub skills_dir: Option<fn(&Config) -> &PathBuf>,
+    pub subagents_dir: Option<fn(&Config) -> &PathBuf>,
     pub agents_file: Option<fn(&Config) -> &PathBuf>,
     pub rules_file: Option<fn(&Config) -> &PathBuf>,
+    pub mcp_file: Option<fn(&Config) -> &PathBuf>,
 }
 
 fn claude_commands(cfg: &Config) -> &PathBuf {
@@ -19,6 +23,14 @@ fn claude_skills(cfg: &Config) -> &PathBuf {
     &cfg.claude_skills_dir
 }
 
+fn claude_subagents(cfg: &Config) -> &PathBuf {
+    &cfg.claude_subagents_dir
+}
+
+fn claude_agents(cfg: &Config) -> &PathBuf {
+    &cfg.claude_agents_file
+}
+
 fn cursor_commands(cfg: &Config) -> &PathBuf {
     &cfg.cursor_dir
 }
@@ -35,6 +47,34 @@ fn codex_agents(cfg: &Config) -> &PathBuf {
     &cfg.codex_agents_file
 }
 
+fn claude_mcp(cfg: &Config) -> &PathBuf {
+    &cfg.claude_mcp_file
+}
+
+fn codex_config(cfg: &Config) -> &PathBuf {
+    &cfg.codex_config_file
+}
+
+fn cursor_mcp(cfg: &Config) -> &PathBuf {
+    &cfg.cursor_mcp_file
+}
+
+fn opencode_config(cfg: &Config) -> &PathBuf {
+    &cfg.opencode_config_file
+}
+
+fn gemini_commands(cfg: &Config) -> &PathBuf {
+    &cfg.gemini_commands_dir
+}
+
+fn gemini_skills(cfg: &Config) -> &PathBuf {
+    &cfg.gemini_skills_dir
+}
+
+fn gemini_agents(cfg: &Config) -> &PathBuf {
+    &cfg.gemini_agents_file
+}
+
 fn opencode_commands(cfg: &Config) -> &PathBuf {
     &cfg.opencode_commands_dir
 }
@@ -43,73 +83,170 @@ fn opencode_skills(cfg: &Config) -> &PathBuf {
     &cfg.opencode_skills_dir
 }
 
+fn opencode_subagents(cfg: &Config) -> &PathBuf {
+    &cfg.opencode_subagents_dir
+}
+
 fn opencode_agents(cfg: &Config) -> &PathBuf {
     &cfg.opencode_agents_file
 }
 
-pub(crate) const TOOL_DEFINITIONS: [ToolDefinition; 4] = [
+pub(crate) const TOOL_DEFINITIONS: [ToolDefinition; 5] = [
     ToolDefinition {
         id: TOOL_CLAUDE,
         label: "Claude Code",
         version_bin: Some("claude"),
         commands_dir: Some(claude_commands),
+        command_format: CommandFormat::Markdown,
         skills_dir: Some(claude_skills),
-        agents_file: None,
+        subagents_dir: Some(claude_subagents),
+        agents_file: Some(claude_agents),
         rules_file: None,
+        mcp_file: Some(claude_mcp),
     },
     ToolDefinition {
         id: TOOL_CODEX,
         label: "Codex CLI",
         version_bin: Some("codex"),
         commands_dir: None,
+        command_format: CommandFormat::Markdown,
         skills_dir: Some(codex_skills),
+        subagents_dir: None,
         agents_file: Some(codex_agents),
         rules_file: Some(codex_rules),
+        mcp_file: Some(codex_config),
     },
     ToolDefinition {
         id: TOOL_CURSOR,
         label: "Cursor",
         version_bin: Some("cursor"),
         commands_dir: Some(cursor_commands),
+        command_format: CommandFormat::Markdown,
         skills_dir: None,
+        subagents_dir: None,
         agents_file: None,
         rules_file: None,
+        mcp_file: Some(cursor_mcp),
+    },
+    ToolDefinition {
+        id: TOOL_GEMINI,
+        label: "Gemini CLI",
+        version_bin: Some("gemini"),
+        commands_dir: Some(gemini_commands),
+        command_format: CommandFormat::GeminiToml,
+        skills_dir: Some(gemini_skills),
+        subagents_dir: None,
+        agents_file: Some(gemini_agents),
+        rules_file: None,
+        mcp_file: None,
     },
     ToolDefinition {
         id: TOOL_OPENCODE,
         label: "OpenCode",
         version_bin: Some("opencode"),
         commands_dir: Some(opencode_commands),
+        command_format: CommandFormat::Markdown,
         skills_dir: Some(opencode_skills),
+        subagents_dir: Some(opencode_subagents),
         agents_file: Some(opencode_agents),
         rules_file: None,
+        mcp_file: Some(opencode_config),
     },
 ];
 
-fn tool_paths<'a>(cfg: &'a Config, tool: &str) -> Option<Vec<&'a PathBuf>> {
-    let definition = TOOL_DEFINITIONS.iter().find(|spec| spec.id == tool)?;
-    let mut paths = Vec::new();
-    if let Some(getter) = definition.commands_dir {
-        paths.push(getter(cfg));
-    }
-    if let Some(getter) = definition.skills_dir {
-        let path = getter(cfg);
-        if path != &cfg.central_skills_dir {
-            paths.push(path);
-        }
-    }
-    if let Some(getter) = definition.agents_file {
-        paths.push(getter(cfg));
-    }
-    if let Some(getter) = definition.rules_file {
-        paths.push(getter(cfg));
+/// Tools without a definition (the central store, legacy paths) use markdown.
+pub(crate) fn command_format(tool: &str) -> CommandFormat {
+    TOOL_DEFINITIONS
+        .iter()
+        .find(|spec| spec.id == tool)
+        .map_or(CommandFormat::Markdown, |spec| spec.command_format)
+}
+
+/// A tool's abilities resolved against one config, whether it is built in or
+/// declared under `[providers.<id>]`.
+pub(crate) struct ToolEntry<'a> {
+    pub id: &'a str,
+    pub label: &'a str,
+    pub version_bin: Option<&'a str>,
+    pub commands_dir: Option<&'a PathBuf>,
+    pub skills_dir: Option<&'a PathBuf>,
+    pub subagents_dir: Option<&'a PathBuf>,
+    pub agents_file: Option<&'a PathBuf>,
+    pub rules_file: Option<&'a PathBuf>,
+    pub mcp_file: Option<&'a PathBuf>,
+}
+
+impl ToolEntry<'_> {
+    /// Where a single-file ability lives under its central store directory,
+    /// e.g. `<id>/AGENTS.md`; prefixed with the kind it is also the blacklist key.
+    pub(crate) fn central_file_name(&self, file: &Path) -> Option<String> {
+        let file_name = file.file_name()?.to_str()?;
+        Some(format!("{}/{file_name}", self.id))
     }
-    Some(paths)
+}
+
+pub(crate) fn tool_entries(cfg: &Config) -> Vec<ToolEntry<'_>> {
+    let mut entries: Vec<ToolEntry<'_>> = TOOL_DEFINITIONS
+        .iter()
+        .map(|spec| ToolEntry {
+            id: spec.id,
+            label: spec.label,
+            version_bin: spec.version_bin,
+            commands_dir: spec.commands_dir.map(|getter| getter(cfg)),
+            skills_dir: spec.skills_dir.map(|getter| getter(cfg)),
+            subagents_dir: spec.subagents_dir.map(|getter| getter(cfg)),
+            agents_file: spec.agents_file.map(|getter| getter(cfg)),
+            rules_file: spec.rules_file.map(|getter| getter(cfg)),
+            mcp_file: spec.mcp_file.map(|getter| getter(cfg)),
+        })
+        .collect();
+    entries.extend(provider_entries(cfg));
+    entries
+}
+
+/// Only the `[providers.<id>]` entries, for sync code that already handles
+/// each built-in tool explicitly.
+pub(crate) fn provider_entries(cfg: &Config) -> impl Iterator<Item = ToolEntry<'_>> {
+    cfg.providers.iter().map(|(id, provider)| ToolEntry {
+        id,
+        label: provider.label.as_deref().unwrap_or(id),
+        version_bin: provider.version_bin.as_deref(),
+        commands_dir: provider.commands_dir.as_ref(),
+        skills_dir: provider.skills_dir.as_ref(),
+        subagents_dir: None,
+        agents_file: provider.agents_file.as_ref(),
+        rules_file: provider.rules_file.as_ref(),
+        mcp_file: None,
+    })
+}
+
+pub(crate) fn is_known_tool(cfg: &Config, tool: &str) -> bool {
+    TOOL_DEFINITIONS.iter().any(|spec| spec.id == tool) || cfg.providers.contains_key(tool)
+}
+
+fn tool_paths<'a>(cfg: &'a Config, tool: &str) -> Option<(&'a str, Vec<&'a PathBuf>)> {
+    let entry = tool_entries(cfg)
+        .into_iter()
+        .find(|entry| entry.id == tool)?;
+    let skills_dir = entry
+        .skills_dir
+        .filter(|path| *path != &cfg.central_skills_dir);
+    let paths = [
+        entry.commands_dir,
+        skills_dir,
+        entry.subagents_dir,
+        entry.agents_file,
+        entry.rules_file,
+        entry.mcp_file,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    Some((entry.label, paths))
 }
 
 pub(crate) fn tool_expected_paths(cfg: &Config, tool: &str) -> Option<String> {
-    let definition = TOOL_DEFINITIONS.iter().find(|spec| spec.id == tool)?;
-    let paths = tool_paths(cfg, tool)?;
+    let (label, paths) = tool_paths(cfg, tool)?;
     let path_list = paths
         .iter()
         .map(|path| path.display().to_string())
@@ -118,11 +255,11 @@ pub(crate) fn tool_expected_paths(cfg: &Config, tool: &str) -> Option<String> {
     if path_list.is_empty() {
         return None;
     }
-    Some(format!("{} (expected at {})", definition.label, path_list))
+    Some(format!("{label} (expected at {path_list})"))
 }
 
 pub(crate) fn tool_detected(cfg: &Config, tool: &str) -> bool {
     tool_paths(cfg, tool)
-        .map(|paths| paths.iter().any(|path| path.exists()))
+        .map(|(_, paths)| paths.iter().any(|path| path.exists()))
         .unwrap_or(false)
 }
@@ -1,5 +1,5 @@
 use crate::config::Config;
-use crate::tools::{tool_detected, TOOL_DEFINITIONS};
+use crate::tools::{tool_detected, tool_entries};
 use console::style;
 
 #[cfg(not(any(test, coverage)))]
@@ -9,7 +9,7 @@ use std::process::Command;
 
 pub(crate) fn check_versions(cfg: &Config) -> bool {
     let mut mismatch = false;
-    for tool in TOOL_DEFINITIONS {
+    for tool in tool_entries(cfg) {
         if let Some(bin) = tool.version_bin {
             if cfg.tool_enabled(tool.id)
                 && tool_detected(cfg, tool.id)
@@ -155,6 +155,7 @@ pub(crate) fn confirm_version_mismatch() -> std::io::Result<bool> {
 mod tests {
     use super::*;
     use crate::config::{Config, TOOL_CLAUDE, TOOL_CODEX, TOOL_CURSOR, TOOL_OPENCODE};
+    use crate::tools::TOOL_DEFINITIONS;
     use std::fs;
     use tempfile::TempDir;
 
@@ -172,16 +173,34 @@ mod tests {
             central_skills_dir: tmp.path().join("central/skills"),
             central_agents_dir: tmp.path().join("central/agents"),
             central_rules_dir: tmp.path().join("central/rules"),
+            central_mcp_dir: tmp.path().join("central/mcp"),
+            central_subagents_dir: tmp.path().join("central/subagents"),
             claude_dir: tmp.path().join("claude/commands"),
             claude_skills_dir: tmp.path().join("claude/skills"),
+            claude_subagents_dir: tmp.path().join("claude/agents"),
+            claude_agents_file: tmp.path().join("claude/CLAUDE.md"),
             cursor_dir: tmp.path().join("cursor/commands"),
             opencode_commands_dir: tmp.path().join("opencode/commands"),
             opencode_legacy_commands_dir: None,
             opencode_skills_dir: tmp.path().join("opencode/skills"),
             opencode_agents_file: tmp.path().join("opencode/AGENTS.md"),
+            opencode_subagents_dir: tmp.path().join("opencode/agent"),
             codex_skills_dir: tmp.path().join("codex/skills"),
             codex_rules_file: tmp.path().join("codex/rules/default.rules"),
             codex_agents_file: tmp.path().join("codex/AGENTS.md"),
+            gemini_commands_dir: tmp.path().join("gemini/commands"),
+            gemini_skills_dir: tmp.path().join("gemini/skills"),
+            gemini_agents_file: tmp.path().join("gemini/GEMINI.md"),
+            claude_mcp_file: tmp.path().join("claude/.claude.json"),
+            codex_config_file: tmp.path().join("codex/config.toml"),
+            cursor_mcp_file: tmp.path().join("cursor/mcp.json"),
+            opencode_config_file: tmp.path().join("opencode/opencode.json"),
+            providers: std::collections::BTreeMap::new(),
+            project_root: None,
+            three_way_merge: false,
+            preserve_conflicts: false,
+            conflict_policy: Default::default(),
+            history: Default::default(),
         }
     }
 
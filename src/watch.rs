@@ -1,6 +1,6 @@
 use crate::config::{Config, TOOL_OPENCODE};
 use crate::sync::LogMode;
-use crate::tools::{ToolDefinition, TOOL_DEFINITIONS};
+use crate::tools::{provider_entries, tool_entries, ToolEntry};
 use notify::RecursiveMode;
 use std::io;
 use std::path::{Path, PathBuf};
@@ -24,19 +24,21 @@ pub(crate) fn build_watch_list(cfg: &Config) -> Vec<(PathBuf, RecursiveMode)> {
         }
     };
     for (path, mode) in [
-        (&cfg.central_dir, RecursiveMode::NonRecursive),
+        (&cfg.central_dir, RecursiveMode::Recursive),
         (&cfg.central_skills_dir, RecursiveMode::Recursive),
         (&cfg.central_agents_dir, RecursiveMode::Recursive),
         (&cfg.central_rules_dir, RecursiveMode::Recursive),
+        (&cfg.central_mcp_dir, RecursiveMode::NonRecursive),
+        (&cfg.central_subagents_dir, RecursiveMode::NonRecursive),
     ] {
         push_unique(path.clone(), mode);
     }
 
-    for definition in TOOL_DEFINITIONS.iter() {
-        if !cfg.tool_enabled(definition.id) {
+    for entry in tool_entries(cfg) {
+        if !cfg.tool_enabled(entry.id) {
             continue;
         }
-        for (path, mode) in tool_watch_paths(cfg, definition) {
+        for (path, mode) in tool_watch_paths(&entry) {
             push_unique(path, mode);
         }
     }
@@ -51,7 +53,7 @@ pub(crate) fn build_watch_list(cfg: &Config) -> Vec<(PathBuf, RecursiveMode)> {
             .as_ref()
             .filter(|path| path.exists())
         {
-            push_unique(path.clone(), RecursiveMode::NonRecursive);
+            push_unique(path.clone(), RecursiveMode::Recursive);
         }
     }
     paths
@@ -80,16 +82,22 @@ fn classify_origin(cfg: &Config, path: &Path) -> Option<String> {
         }
     }
 
-    let roots: [(&str, &Path); 10] = [
+    let roots: [(&str, &Path); 16] = [
         ("central", &cfg.central_dir),
         ("central_skills", &cfg.central_skills_dir),
         ("central_agents", &cfg.central_agents_dir),
         ("central_rules", &cfg.central_rules_dir),
+        ("central_mcp", &cfg.central_mcp_dir),
+        ("central_subagents", &cfg.central_subagents_dir),
         ("claude", &cfg.claude_dir),
         ("claude_skills", &cfg.claude_skills_dir),
+        ("claude_subagents", &cfg.claude_subagents_dir),
         ("codex_skills", &cfg.codex_skills_dir),
+        ("gemini", &cfg.gemini_commands_dir),
+        ("gemini_skills", &cfg.gemini_skills_dir),
         ("opencode", &cfg.opencode_commands_dir),
         ("opencode_skills", &cfg.opencode_skills_dir),
+        ("opencode_subagents", &cfg.opencode_subagents_dir),
         ("cursor", &cfg.cursor_dir),
     ];
     for (label, root) in roots {
@@ -106,10 +114,16 @@ fn classify_origin(cfg: &Config, path: &Path) -> Option<String> {
         return Some(origin);
     }
 
-    let files: [(&str, &Path); 3] = [
+    let files: [(&str, &Path); 9] = [
+        ("claude_agents", &cfg.claude_agents_file),
         ("opencode_agents", &cfg.opencode_agents_file),
         ("codex_agents", &cfg.codex_agents_file),
+        ("gemini_agents", &cfg.gemini_agents_file),
         ("codex_rules", &cfg.codex_rules_file),
+        ("claude_mcp", &cfg.claude_mcp_file),
+        ("codex_mcp", &cfg.codex_config_file),
+        ("cursor_mcp", &cfg.cursor_mcp_file),
+        ("opencode_mcp", &cfg.opencode_config_file),
     ];
     for (label, file) in files {
         if path == file {
@@ -117,6 +131,26 @@ fn classify_origin(cfg: &Config, path: &Path) -> Option<String> {
         }
     }
 
+    for provider in provider_entries(cfg) {
+        for (suffix, root) in [
+            ("", provider.commands_dir),
+            ("_skills", provider.skills_dir),
+        ] {
+            let label = format!("{}{suffix}", provider.id);
+            if let Some(origin) = root.and_then(|root| format_origin(path, root, &label)) {
+                return Some(origin);
+            }
+        }
+        for (suffix, file) in [
+            ("_agents", provider.agents_file),
+            ("_rules", provider.rules_file),
+        ] {
+            if file.is_some_and(|file| path == file) {
+                return Some(format!("watch:{}{suffix}", provider.id));
+            }
+        }
+    }
+
     if let Ok(import_dirs) = cfg.legacy_skill_import_dirs() {
         for root in import_dirs {
             if let Some(origin) = format_origin(path, &root, "skill_import") {
@@ -128,27 +162,55 @@ fn classify_origin(cfg: &Config, path: &Path) -> Option<String> {
     None
 }
 
-fn tool_watch_paths(cfg: &Config, tool: &ToolDefinition) -> Vec<(PathBuf, RecursiveMode)> {
+fn tool_watch_paths(tool: &ToolEntry<'_>) -> Vec<(PathBuf, RecursiveMode)> {
     let mut paths = Vec::new();
-    if let Some(getter) = tool.commands_dir {
-        paths.push((getter(cfg).clone(), RecursiveMode::NonRecursive));
+    // Commands can be namespaced in subdirectories (`frontend/component.md`).
+    if let Some(dir) = tool.commands_dir {
+        paths.push((dir.clone(), RecursiveMode::Recursive));
     }
-    if let Some(getter) = tool.skills_dir {
-        paths.push((getter(cfg).clone(), RecursiveMode::Recursive));
+    if let Some(dir) = tool.skills_dir {
+        paths.push((dir.clone(), RecursiveMode::Recursive));
     }
-    if let Some(getter) = tool.agents_file {
-        if let Some(parent) = getter(cfg).parent() {
-            paths.push((parent.to_path_buf(), RecursiveMode::NonRecursive));
-        }
+    if let Some(dir) = tool.subagents_dir {
+        paths.push((dir.clone(), RecursiveMode::NonRecursive));
     }
-    if let Some(getter) = tool.rules_file {
-        if let Some(parent) = getter(cfg).parent() {
+    for file in [tool.agents_file, tool.rules_file].into_iter().flatten() {
+        if let Some(parent) = file.parent() {
             paths.push((parent.to_path_buf(), RecursiveMode::NonRecursive));
         }
     }
+    // MCP settings live in shared config files, some directly in `$HOME`
+    // (`~/.claude.json`), so the file itself is watched, not its directory.
+    if let Some(file) = tool.mcp_file {
+        paths.push((file.clone(), RecursiveMode::NonRecursive));
+    }
     paths
 }
 
+/// Config files watched directly. Tools replace these atomically, which
+/// drops the watch on the old inode, so they are registered again after
+/// every sync; files that did not exist at startup are picked up then too.
+fn file_watch_targets(cfg: &Config) -> Vec<PathBuf> {
+    build_watch_list(cfg)
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+#[cfg(not(any(test, coverage)))]
+fn rewatch_files(watcher: &mut RecommendedWatcher, cfg: &Config) {
+    for path in file_watch_targets(cfg) {
+        let _ = watcher.unwatch(&path);
+        if let Err(err) = watcher.watch(&path, RecursiveMode::NonRecursive) {
+            crate::logging::debug(&format!(
+                "watch re-register failed path={}: {err}",
+                path.display()
+            ));
+        }
+    }
+}
+
 #[cfg(not(any(test, coverage)))]
 pub(crate) fn watch(cfg: &Config, debounce_ms: u64, log_mode: LogMode) -> io::Result<()> {
     ensure_migration_watch_targets(cfg)?;
@@ -216,11 +278,22 @@ pub(crate) fn watch(cfg: &Config, debounce_ms: u64, log_mode: LogMode) -> io::Re
         }
         let Some(origin) = watch_origin(cfg, &changed_paths) else {
             crate::logging::debug("watch ignored unrelated event batch");
+            rewatch_files(&mut watcher, cfg);
             continue;
         };
         crate::logging::debug(&format!("watch applying sync origin={origin}"));
-        let _lock = ProcessLock::acquire(&origin)?;
-        let _ = sync::sync_all_with_mode(cfg, log_mode, ExecutionMode::Apply, &origin)?;
+        let _lock = ProcessLock::acquire_for(cfg, &origin)?;
+        match sync::sync_all_with_mode(cfg, log_mode, ExecutionMode::Apply, &origin) {
+            Ok(_) => {}
+            Err(err) if sync::is_conflict_policy_abort(&err) => {
+                crate::logging::debug(&format!("watch skipped sync: {err}"));
+                if log_mode == LogMode::Actions {
+                    println!("warning: {err}; waiting for the next change");
+                }
+            }
+            Err(err) => return Err(err),
+        }
+        rewatch_files(&mut watcher, cfg);
     }
 }
 
@@ -228,6 +301,7 @@ pub(crate) fn watch(cfg: &Config, debounce_ms: u64, log_mode: LogMode) -> io::Re
 pub(crate) fn watch(cfg: &Config, _debounce_ms: u64, _log_mode: LogMode) -> io::Result<()> {
     ensure_migration_watch_targets(cfg)?;
     let _ = build_watch_list(cfg);
+    let _ = file_watch_targets(cfg);
     Ok(())
 }
 
@@ -267,16 +341,34 @@ mod tests {
             central_skills_dir: tmp.path().join("central_skills"),
             central_agents_dir: tmp.path().join("central_agents"),
             central_rules_dir: tmp.path().join("central_rules"),
+            central_mcp_dir: tmp.path().join("central_mcp"),
+            central_subagents_dir: tmp.path().join("central_subagents"),
             claude_dir: tmp.path().join("claude_commands"),
             claude_skills_dir: tmp.path().join("claude_skills"),
+            claude_subagents_dir: tmp.path().join("claude_agents"),
+            claude_agents_file: tmp.path().join("claude_home/CLAUDE.md"),
             cursor_dir: tmp.path().join("cursor"),
             opencode_commands_dir: tmp.path().join("opencode_commands"),
             opencode_legacy_commands_dir: None,
             opencode_skills_dir: tmp.path().join("opencode_skills"),
             opencode_agents_file: tmp.path().join("opencode_agents/AGENTS.md"),
+            opencode_subagents_dir: tmp.path().join("opencode_agent"),
             codex_skills_dir: tmp.path().join("codex_skills"),
             codex_rules_file: tmp.path().join("codex_rules/default.rules"),
             codex_agents_file: tmp.path().join("codex_agents/AGENTS.md"),
+            gemini_commands_dir: tmp.path().join("gemini/commands"),
+            gemini_skills_dir: tmp.path().join("gemini/skills"),
+            gemini_agents_file: tmp.path().join("gemini/GEMINI.md"),
+            claude_mcp_file: tmp.path().join("claude_mcp/.claude.json"),
+            codex_config_file: tmp.path().join("codex_config/config.toml"),
+            cursor_mcp_file: tmp.path().join("cursor_mcp/mcp.json"),
+            opencode_config_file: tmp.path().join("opencode_config/opencode.json"),
+            providers: std::collections::BTreeMap::new(),
+            project_root: None,
+            three_way_merge: false,
+            preserve_conflicts: false,
+            conflict_policy: Default::default(),
+            history: Default::default(),
         }
     }
 
@@ -300,19 +392,16 @@ mod tests {
         fs::create_dir_all(cfg.codex_agents_file.parent().unwrap())?;
 
         let paths = build_watch_list(&cfg);
-        assert!(paths.contains(&(cfg.central_dir.clone(), RecursiveMode::NonRecursive)));
+        assert!(paths.contains(&(cfg.central_dir.clone(), RecursiveMode::Recursive)));
         assert!(paths.contains(&(cfg.central_skills_dir.clone(), RecursiveMode::Recursive)));
         assert!(paths.contains(&(cfg.central_agents_dir.clone(), RecursiveMode::Recursive)));
         assert!(paths.contains(&(cfg.central_rules_dir.clone(), RecursiveMode::Recursive)));
-        assert!(paths.contains(&(cfg.claude_dir.clone(), RecursiveMode::NonRecursive)));
+        assert!(paths.contains(&(cfg.claude_dir.clone(), RecursiveMode::Recursive)));
         assert!(paths.contains(&(cfg.claude_skills_dir.clone(), RecursiveMode::Recursive)));
-        assert!(paths.contains(&(
-            cfg.opencode_commands_dir.clone(),
-            RecursiveMode::NonRecursive
-        )));
+        assert!(paths.contains(&(cfg.opencode_commands_dir.clone(), RecursiveMode::Recursive)));
         assert!(paths.contains(&(
             cfg.opencode_legacy_commands_dir.clone().unwrap(),
-            RecursiveMode::NonRecursive
+            RecursiveMode::Recursive
         )));
         assert!(paths.contains(&(cfg.opencode_skills_dir.clone(), RecursiveMode::Recursive)));
         assert!(paths.contains(&(
@@ -343,6 +432,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn build_watch_list_watches_mcp_files_not_their_directory() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let home = cfg.claude_mcp_file.parent().unwrap().to_path_buf();
+        fs::create_dir_all(&home)?;
+        assert!(!build_watch_list(&cfg).iter().any(|(path, _)| path == &home));
+        assert!(file_watch_targets(&cfg).is_empty());
+
+        fs::write(&cfg.claude_mcp_file, "{}")?;
+
+        let paths = build_watch_list(&cfg);
+        assert!(paths.contains(&(cfg.claude_mcp_file.clone(), RecursiveMode::NonRecursive)));
+        assert!(!paths.iter().any(|(path, _)| path == &home));
+        assert_eq!(file_watch_targets(&cfg), vec![cfg.claude_mcp_file.clone()]);
+        Ok(())
+    }
+
     #[test]
     fn watch_test_mode_runs() -> io::Result<()> {
         let tmp = TempDir::new()?;
@@ -362,10 +469,8 @@ mod tests {
         watch(&cfg, 10, LogMode::Quiet)?;
 
         assert!(cfg.opencode_commands_dir.is_dir());
-        assert!(build_watch_list(&cfg).contains(&(
-            cfg.opencode_commands_dir.clone(),
-            RecursiveMode::NonRecursive
-        )));
+        assert!(build_watch_list(&cfg)
+            .contains(&(cfg.opencode_commands_dir.clone(), RecursiveMode::Recursive)));
         Ok(())
     }
 
@@ -388,6 +493,19 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn watch_origin_marks_provider_paths() -> io::Result<()> {
+        let tmp = TempDir::new()?;
+        let mut cfg = make_config(&tmp);
+        crate::sync::test_support::add_provider(&tmp, &mut cfg)?;
+        let root = tmp.path().join("windsurf");
+        let origin = watch_origin(&cfg, &[root.join("commands/review.md")]);
+        assert_eq!(origin.as_deref(), Some("watch:windsurf:review.md"));
+        let origin = watch_origin(&cfg, &[root.join("rules.md")]);
+        assert_eq!(origin.as_deref(), Some("watch:windsurf_rules"));
+        Ok(())
+    }
+
     #[test]
     fn watch_origin_ignores_unrecognized_path() -> io::Result<()> {
         let tmp = TempDir::new()?;
@@ -406,6 +524,8 @@ mod tests {
             (&cfg.codex_agents_file, "watch:codex_agents"),
             (&cfg.codex_rules_file, "watch:codex_rules"),
             (&cfg.opencode_agents_file, "watch:opencode_agents"),
+            (&cfg.claude_mcp_file, "watch:claude_mcp"),
+            (&cfg.codex_config_file, "watch:codex_mcp"),
         ] {
             assert_eq!(
                 watch_origin(&cfg, std::slice::from_ref(path)).as_deref(),
@@ -19,6 +19,7 @@ fn relay_command(home: &Path) -> Command {
         .env_remove("CODEX_HOME")
         .env_remove("CLAUDE_HOME")
         .env_remove("CURSOR_HOME")
+        .env_remove("GEMINI_HOME")
         .env_remove("OPENCODE_HOME");
     command
 }
@@ -119,7 +120,7 @@ fn capabilities_json_uses_real_entrypoint_without_initialization() -> io::Result
     assert!(output.status.success());
     assert_eq!(
         utf8(&output.stdout),
-        "{\"schema_version\":1,\"capabilities\":{\"skills.sync.scoped\":1}}\n"
+        "{\"schema_version\":1,\"capabilities\":{\"commands.sync.scoped\":1,\"output.json\":1,\"skills.sync.scoped\":1}}\n"
     );
     assert_eq!(output.stderr, b"");
     assert!(!tmp.path().join(".config/relay").exists());
@@ -145,7 +146,7 @@ fn scoped_plan_supports_one_or_many_operands_without_initialization() -> io::Res
         assert_eq!(
             utf8(&output.stdout),
             format!(
-                "plan: commands would_update=0; skills would_update={}; agents would_update=0; rules would_update=0\n",
+                "plan: commands would_update=0; skills would_update={}; agents would_update=0; rules would_update=0; mcp would_update=0; subagents would_update=0\n",
                 count * 2
             )
         );
@@ -326,7 +327,7 @@ fn scoped_verbose_plan_replays_actions_without_writing() -> io::Result<()> {
         tmp.path().join(".claude/skills/verbose").display()
     )));
     assert!(stdout.ends_with(
-        "plan: commands would_update=0; skills would_update=2; agents would_update=0; rules would_update=0\n"
+        "plan: commands would_update=0; skills would_update=2; agents would_update=0; rules would_update=0; mcp would_update=0; subagents would_update=0\n"
     ));
     assert_eq!(
         utf8(&output.stderr),
@@ -868,6 +869,532 @@ fn plain_sync_still_reconciles_every_category_through_real_dispatch() -> io::Res
     Ok(())
 }
 
+#[test]
+fn scoped_command_sync_writes_only_selected_commands() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    initialize_all_tools(tmp.path())?;
+    let claude = tmp.path().join("claude/commands");
+    fs::create_dir_all(claude.join("frontend"))?;
+    fs::write(claude.join("frontend/component.md"), "Component command")?;
+    fs::write(claude.join("other.md"), "Other command")?;
+    fs::create_dir_all(tmp.path().join("cursor/commands"))?;
+    let outside = tmp.path().join("drafts");
+    fs::create_dir_all(&outside)?;
+    fs::write(outside.join("draft.md"), "Draft command")?;
+    fs::create_dir_all(tmp.path().join("codex"))?;
+
+    let component = claude.join("frontend/component.md");
+    let draft = outside.join("draft.md");
+    let planned = relay(
+        tmp.path(),
+        &[
+            "sync",
+            "command",
+            component.to_str().unwrap(),
+            draft.to_str().unwrap(),
+            "--plan",
+        ],
+    )?;
+    assert!(planned.status.success(), "{}", utf8(&planned.stderr));
+    assert!(!tmp.path().join("relay-data/commands").exists());
+
+    let applied = relay(
+        tmp.path(),
+        &[
+            "sync",
+            "command",
+            component.to_str().unwrap(),
+            draft.to_str().unwrap(),
+            "--quiet",
+        ],
+    )?;
+
+    assert!(applied.status.success(), "{}", utf8(&applied.stderr));
+    assert_eq!(applied.stdout, b"");
+    let central = tmp.path().join("relay-data/commands");
+    assert!(central.join("frontend/component.md").exists());
+    assert!(central.join("draft.md").exists());
+    assert!(tmp.path().join("cursor/commands/draft.md").exists());
+    assert!(claude.join("draft.md").exists());
+    assert!(tmp
+        .path()
+        .join("codex/skills/frontend-component/SKILL.md")
+        .exists());
+    assert!(!central.join("other.md").exists());
+    assert!(!tmp.path().join("cursor/commands/other.md").exists());
+    Ok(())
+}
+
+#[test]
+fn sync_json_lists_planned_and_applied_actions() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    initialize(tmp.path())?;
+    fs::create_dir_all(tmp.path().join("claude/commands"))?;
+    fs::write(tmp.path().join("claude/commands/review.md"), "Review")?;
+
+    let planned = relay(tmp.path(), &["sync", "--plan", "--json"])?;
+    assert!(planned.status.success(), "{}", utf8(&planned.stderr));
+    let doc: serde_json::Value = serde_json::from_slice(&planned.stdout)?;
+    assert_eq!(doc["schema_version"], 1);
+    assert_eq!(doc["mode"], "plan");
+    let central = tmp.path().join("relay-data/commands/review.md");
+    assert!(doc["actions"].as_array().unwrap().iter().any(|action| {
+        action["kind"] == "command"
+            && action["name"] == "review.md"
+            && action["op"] == "create"
+            && action["path"] == central.display().to_string()
+            && action["before"].is_null()
+            && action["after"].is_string()
+    }));
+    assert!(doc["history_event_id"].is_null());
+    assert!(!central.exists());
+
+    let applied = relay(tmp.path(), &["sync", "--json"])?;
+    assert!(applied.status.success(), "{}", utf8(&applied.stderr));
+    let doc: serde_json::Value = serde_json::from_slice(&applied.stdout)?;
+    assert_eq!(doc["mode"], "apply");
+    assert_eq!(doc["status"], "completed");
+    let event_id = doc["history_event_id"].as_str().unwrap().to_string();
+    assert!(central.exists());
+
+    let history = relay(tmp.path(), &["history", "--json"])?;
+    assert!(history.status.success(), "{}", utf8(&history.stderr));
+    let doc: serde_json::Value = serde_json::from_slice(&history.stdout)?;
+    assert_eq!(doc["events"][0]["id"], event_id.as_str());
+
+    let conflicting = relay(tmp.path(), &["sync", "--json", "--quiet"])?;
+    assert_eq!(conflicting.status.code(), Some(2));
+    Ok(())
+}
+
+#[test]
+fn sync_json_reports_conflict_aborts_and_exits_nonzero() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    initialize(tmp.path())?;
+    write_skill(
+        &tmp.path().join("relay-data/skills"),
+        "conflict",
+        "Canonical",
+    )?;
+    let conflicting = write_skill(&tmp.path().join("source"), "conflict", "Selected")?;
+
+    let aborted = relay(
+        tmp.path(),
+        &[
+            "sync",
+            "skill",
+            "--json",
+            "--fail-on-conflict",
+            conflicting
+                .to_str()
+                .expect("temporary path should be UTF-8"),
+        ],
+    )?;
+
+    assert!(!aborted.status.success());
+    assert!(utf8(&aborted.stderr).contains("scoped sync aborted"));
+    let doc: serde_json::Value = serde_json::from_slice(&aborted.stdout)?;
+    assert_eq!(doc["schema_version"], 1);
+    assert_eq!(doc["status"], "aborted");
+    assert_eq!(doc["actions"], serde_json::json!([]));
+    assert_eq!(doc["conflicts"][0]["kind"], "skill");
+    assert_eq!(doc["conflicts"][0]["name"], "conflict");
+    assert!(doc["history_event_id"].is_null());
+    assert!(!tmp.path().join("claude/skills/conflict").exists());
+    Ok(())
+}
+
+#[test]
+fn sync_plan_diff_previews_content_without_writing() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    initialize(tmp.path())?;
+    fs::create_dir_all(tmp.path().join("claude/commands"))?;
+    fs::write(tmp.path().join("claude/commands/review.md"), "Review\n")?;
+
+    let output = relay(tmp.path(), &["sync", "--plan", "--diff"])?;
+
+    assert!(output.status.success(), "{}", utf8(&output.stderr));
+    let central = tmp.path().join("relay-data/commands/review.md");
+    let stdout = utf8(&output.stdout);
+    assert!(
+        stdout.contains(&format!(
+            "--- /dev/null\n+++ {}\n@@ -0,0 +1 @@\n+Review\n",
+            central.display()
+        )),
+        "{stdout}"
+    );
+    assert!(
+        !stdout.contains("\u{1b}["),
+        "piped output should not be coloured"
+    );
+    assert!(!central.exists());
+
+    let without_plan = relay(tmp.path(), &["sync", "--diff"])?;
+    assert_eq!(without_plan.status.code(), Some(2));
+    Ok(())
+}
+
+#[test]
+fn history_show_prints_diffs_and_stats_for_an_event() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    initialize(tmp.path())?;
+    let command = tmp.path().join("claude/commands/review.md");
+    fs::create_dir_all(command.parent().unwrap())?;
+    fs::write(&command, "Review\n")?;
+    let applied = relay(tmp.path(), &["sync", "--json"])?;
+    assert!(applied.status.success(), "{}", utf8(&applied.stderr));
+    let doc: serde_json::Value = serde_json::from_slice(&applied.stdout)?;
+    let event_id = doc["history_event_id"].as_str().unwrap().to_string();
+    let central = tmp.path().join("relay-data/commands/review.md");
+
+    let shown = relay(tmp.path(), &["history", "show", &event_id])?;
+    assert!(shown.status.success(), "{}", utf8(&shown.stderr));
+    let stdout = utf8(&shown.stdout);
+    assert!(
+        stdout.starts_with(&format!("event {event_id} ")),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(&format!(
+            "missing->file {0} (command review.md central)\n--- /dev/null\n+++ {0}\n@@ -0,0 +1 @@\n+Review\n",
+            central.display()
+        )),
+        "{stdout}"
+    );
+    assert!(stdout.contains("\nargv: "), "{stdout}");
+
+    let listed = relay(tmp.path(), &["history"])?;
+    let stdout = utf8(&listed.stdout);
+    assert!(
+        stdout.contains(&format!("{event_id} ts_ms=",))
+            && stdout.contains(&format!(" relay={} host=", env!("CARGO_PKG_VERSION"))),
+        "{stdout}"
+    );
+
+    let stat = relay(tmp.path(), &["history", "show", "--stat", &event_id])?;
+    assert!(stat.status.success(), "{}", utf8(&stat.stderr));
+    let stdout = utf8(&stat.stdout);
+    assert!(
+        stdout.contains(&format!("  missing->file {} +1 -0\n", central.display())),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("@@"), "{stdout}");
+
+    let missing = relay(tmp.path(), &["history", "show", "no-such-event"])?;
+    assert!(!missing.status.success());
+    assert!(utf8(&missing.stderr).contains("history event not found"));
+    Ok(())
+}
+
+#[test]
+fn history_filters_select_events_by_path_kind_and_origin() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    initialize(tmp.path())?;
+    let command = tmp.path().join("claude/commands/review.md");
+    fs::create_dir_all(command.parent().unwrap())?;
+    fs::write(&command, "Review\n")?;
+    let applied = relay(tmp.path(), &["sync", "--quiet"])?;
+    assert!(applied.status.success(), "{}", utf8(&applied.stderr));
+    let central = tmp.path().join("relay-data/commands/review.md");
+
+    let by_path = relay(
+        tmp.path(),
+        &["history", "--path", "review.md", "--since", "1h"],
+    )?;
+    assert!(by_path.status.success(), "{}", utf8(&by_path.stderr));
+    let stdout = utf8(&by_path.stdout);
+    assert!(stdout.contains(" origin=sync writes="), "{stdout}");
+    assert!(
+        stdout.ends_with(&format!("  {}\n", central.display())),
+        "{stdout}"
+    );
+    assert!(tmp.path().join("relay-data/history/index.jsonl").exists());
+
+    for args in [
+        &["history", "--kind", "agent"][..],
+        &["history", "--origin", "watch:"],
+        &["history", "--until", "2020-01-01"],
+    ] {
+        let output = relay(tmp.path(), args)?;
+        assert!(output.status.success(), "{}", utf8(&output.stderr));
+        assert_eq!(utf8(&output.stdout), "history: no events\n", "{args:?}");
+    }
+
+    let invalid = relay(tmp.path(), &["history", "--since", "soon"])?;
+    assert_eq!(invalid.status.code(), Some(2));
+    Ok(())
+}
+
+#[test]
+fn history_prune_applies_retention_and_gc_keeps_referenced_blobs() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    initialize(tmp.path())?;
+    let command = tmp.path().join("claude/commands/review.md");
+    fs::create_dir_all(command.parent().unwrap())?;
+    // The first sync also writes skill state, so it stays the latest event
+    // for that path and survives pruning along with the newest event.
+    for body in ["First\n", "Second\n", "Third\n"] {
+        fs::write(&command, body)?;
+        let synced = relay(tmp.path(), &["sync", "--quiet"])?;
+        assert!(synced.status.success(), "{}", utf8(&synced.stderr));
+    }
+    let config = tmp.path().join(".config/relay/config.toml");
+    let mut raw = fs::read_to_string(&config)?;
+    raw.push_str("\n[history]\nmax_events = 1\n");
+    fs::write(&config, raw)?;
+
+    let planned = relay(tmp.path(), &["history", "prune", "--dry-run"])?;
+    assert!(planned.status.success(), "{}", utf8(&planned.stderr));
+    assert!(
+        utf8(&planned.stdout).starts_with("history: would prune 1 events and "),
+        "{}",
+        utf8(&planned.stdout)
+    );
+    let listed = relay(tmp.path(), &["history"])?;
+    assert_eq!(utf8(&listed.stdout).lines().count(), 3);
+
+    let pruned = relay(tmp.path(), &["history", "prune"])?;
+    assert!(pruned.status.success(), "{}", utf8(&pruned.stderr));
+    assert!(utf8(&pruned.stdout).starts_with("history: pruned 1 events and "));
+    let listed = relay(tmp.path(), &["history"])?;
+    assert_eq!(utf8(&listed.stdout).lines().count(), 2);
+
+    let collected = relay(tmp.path(), &["history", "gc"])?;
+    assert!(collected.status.success(), "{}", utf8(&collected.stderr));
+    assert_eq!(
+        utf8(&collected.stdout),
+        "history: removed 0 unreferenced blobs (0 bytes)\n"
+    );
+    let rollback = relay(tmp.path(), &["rollback", "--latest"])?;
+    assert!(rollback.status.success(), "{}", utf8(&rollback.stderr));
+    assert_eq!(
+        fs::read_to_string(tmp.path().join("relay-data/commands/review.md"))?,
+        "Second\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn history_fsck_flags_broken_events_and_repair_quarantines_them() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    initialize(tmp.path())?;
+    let command = tmp.path().join("claude/commands/review.md");
+    fs::create_dir_all(command.parent().unwrap())?;
+    let mut events = Vec::new();
+    for body in ["First\n", "Second\n"] {
+        fs::write(&command, body)?;
+        let synced = relay(tmp.path(), &["sync", "--json"])?;
+        assert!(synced.status.success(), "{}", utf8(&synced.stderr));
+        let doc: serde_json::Value = serde_json::from_slice(&synced.stdout)?;
+        events.push(doc["history_event_id"].as_str().unwrap().to_string());
+    }
+    let history = tmp.path().join("relay-data/history");
+    fs::write(
+        history.join(format!("events/{}.toml", events[1])),
+        "truncated = [",
+    )?;
+
+    let shown = relay(tmp.path(), &["history", "show", &events[1]])?;
+    assert!(!shown.status.success());
+    let checked = relay(tmp.path(), &["history", "fsck"])?;
+    assert!(!checked.status.success());
+    let stdout = utf8(&checked.stdout);
+    assert!(
+        stdout.starts_with("history: checked 2 events and "),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(&format!("broken: {}: ", events[1])),
+        "{stdout}"
+    );
+    assert!(utf8(&checked.stderr).contains("history fsck --repair"));
+
+    let repaired = relay(tmp.path(), &["history", "fsck", "--repair"])?;
+    assert!(repaired.status.success(), "{}", utf8(&repaired.stderr));
+    assert!(utf8(&repaired.stdout).contains("history: quarantined 1 events in "));
+    assert!(history
+        .join(format!("quarantine/{}.toml", events[1]))
+        .exists());
+    let listed = relay(tmp.path(), &["history"])?;
+    assert!(listed.status.success(), "{}", utf8(&listed.stderr));
+    let stdout = utf8(&listed.stdout);
+    assert_eq!(stdout.lines().count(), 1, "{stdout}");
+    assert!(stdout.contains(&events[0]), "{stdout}");
+    let shown = relay(tmp.path(), &["history", "show", &events[0]])?;
+    assert!(shown.status.success(), "{}", utf8(&shown.stderr));
+    Ok(())
+}
+
+#[test]
+fn restore_rewinds_one_path_or_every_path_to_a_moment() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    initialize(tmp.path())?;
+    let command = tmp.path().join("claude/commands/review.md");
+    fs::create_dir_all(command.parent().unwrap())?;
+    let mut events = Vec::new();
+    for body in ["First\n", "Second\n"] {
+        fs::write(&command, body)?;
+        let synced = relay(tmp.path(), &["sync", "--json"])?;
+        assert!(synced.status.success(), "{}", utf8(&synced.stderr));
+        let doc: serde_json::Value = serde_json::from_slice(&synced.stdout)?;
+        events.push(doc["history_event_id"].as_str().unwrap().to_string());
+    }
+    let central = tmp.path().join("relay-data/commands/review.md");
+    let central_arg = central.to_str().unwrap();
+
+    let restored = relay(
+        tmp.path(),
+        &["restore", central_arg, "--event", &events[1], "--before"],
+    )?;
+    assert!(restored.status.success(), "{}", utf8(&restored.stderr));
+    let stdout = utf8(&restored.stdout);
+    assert!(
+        stdout.starts_with(&format!("restore: restored 1 paths\n  {central_arg}\n")),
+        "{stdout}"
+    );
+    assert!(stdout.contains("history: recorded event"), "{stdout}");
+    assert_eq!(fs::read_to_string(&central)?, "First\n");
+    assert_eq!(fs::read_to_string(&command)?, "Second\n");
+
+    let unrelated = relay(
+        tmp.path(),
+        &["restore", central_arg, "--event", "no-such-event"],
+    )?;
+    assert!(!unrelated.status.success());
+
+    let rewound = relay(
+        tmp.path(),
+        &["restore", "--at", "2020-01-01", "--path", "review.md"],
+    )?;
+    assert!(rewound.status.success(), "{}", utf8(&rewound.stderr));
+    assert!(!central.exists());
+    assert_eq!(fs::read_to_string(&command)?, "Second\n");
+
+    let without_source = relay(tmp.path(), &["restore", central_arg])?;
+    assert_eq!(without_source.status.code(), Some(2));
+    Ok(())
+}
+
+#[test]
+fn saved_plan_applies_later_and_refuses_stale_targets() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    initialize(tmp.path())?;
+    let command = tmp.path().join("claude/commands/review.md");
+    fs::create_dir_all(command.parent().unwrap())?;
+    fs::write(&command, "Review\n")?;
+    let plan = tmp.path().join("plan.json");
+    let plan_arg = plan.to_str().unwrap();
+
+    let saved = relay(tmp.path(), &["sync", "--plan", "--out", plan_arg])?;
+    assert!(saved.status.success(), "{}", utf8(&saved.stderr));
+    assert!(utf8(&saved.stdout).contains("plan: saved"));
+    let central = tmp.path().join("relay-data/commands/review.md");
+    assert!(!central.exists());
+
+    let applied = relay(tmp.path(), &["apply", plan_arg])?;
+    assert!(applied.status.success(), "{}", utf8(&applied.stderr));
+    assert!(utf8(&applied.stdout).contains("history: recorded event"));
+    assert_eq!(fs::read_to_string(&central)?, "Review\n");
+
+    fs::write(&command, "Edited\n")?;
+    relay(tmp.path(), &["sync", "--plan", "--out", plan_arg])?;
+    fs::write(&central, "Moved on\n")?;
+    let stale = relay(tmp.path(), &["apply", plan_arg])?;
+    assert!(!stale.status.success());
+    assert!(utf8(&stale.stderr).contains("stale plan"));
+    assert_eq!(fs::read_to_string(&central)?, "Moved on\n");
+
+    let forced = relay(tmp.path(), &["apply", "--force", plan_arg])?;
+    assert!(forced.status.success(), "{}", utf8(&forced.stderr));
+    assert_eq!(fs::read_to_string(&central)?, "Edited\n");
+
+    let without_plan = relay(tmp.path(), &["sync", "--out", plan_arg])?;
+    assert_eq!(without_plan.status.code(), Some(2));
+    Ok(())
+}
+
+#[test]
+fn project_sync_uses_repository_store_without_initialization() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    let home = tmp.path().join("home");
+    let repo = tmp.path().join("repo");
+    fs::create_dir_all(&home)?;
+    fs::create_dir_all(repo.join(".git"))?;
+    fs::create_dir_all(repo.join(".claude/commands"))?;
+    fs::create_dir_all(repo.join(".opencode/commands"))?;
+    fs::write(repo.join(".claude/commands/review.md"), "Review the diff")?;
+    let nested = repo.join("src/module");
+    fs::create_dir_all(&nested)?;
+
+    let output = relay_command(&home)
+        .current_dir(&nested)
+        .args(["sync", "--project", "--quiet"])
+        .output()?;
+
+    assert!(output.status.success(), "{}", utf8(&output.stderr));
+    assert_eq!(
+        fs::read_to_string(repo.join(".relay/commands/review.md"))?,
+        "Review the diff"
+    );
+    assert_eq!(
+        fs::read_to_string(repo.join(".opencode/commands/review.md"))?,
+        "Review the diff"
+    );
+    assert!(repo.join(".relay/history/events").is_dir());
+    assert!(repo.join(".relay/.gitignore").exists());
+    assert!(!home.join(".config/relay").exists());
+    Ok(())
+}
+
+#[test]
+fn project_history_and_rollback_use_repository_store() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    let home = tmp.path().join("home");
+    let repo = tmp.path().join("repo");
+    fs::create_dir_all(&home)?;
+    fs::create_dir_all(repo.join(".git"))?;
+    fs::create_dir_all(repo.join(".claude/commands"))?;
+    fs::write(repo.join(".claude/commands/review.md"), "Review the diff")?;
+    let run = |args: &[&str]| relay_command(&home).current_dir(&repo).args(args).output();
+
+    let synced = run(&["sync", "--project", "--quiet"])?;
+    assert!(synced.status.success(), "{}", utf8(&synced.stderr));
+    assert!(repo.join(".relay/commands/review.md").exists());
+
+    let history = run(&["history", "--project"])?;
+    assert!(history.status.success(), "{}", utf8(&history.stderr));
+    assert!(utf8(&history.stdout).contains("sync"));
+    let fsck = run(&["history", "fsck", "--project"])?;
+    assert!(fsck.status.success(), "{}", utf8(&fsck.stderr));
+    let conflicts = run(&["conflicts", "list", "--project"])?;
+    assert_eq!(utf8(&conflicts.stdout), "conflicts: none\n");
+
+    let rollback = run(&["rollback", "--latest", "--project"])?;
+    assert!(rollback.status.success(), "{}", utf8(&rollback.stderr));
+    assert!(!repo.join(".relay/commands/review.md").exists());
+    assert!(!home.join(".config/relay").exists());
+
+    let global = run(&["history"])?;
+    assert!(!global.status.success());
+    Ok(())
+}
+
+#[test]
+fn project_sync_outside_repository_fails() -> io::Result<()> {
+    let tmp = TempDir::new()?;
+    let dir = tmp.path().join("plain");
+    fs::create_dir_all(&dir)?;
+
+    let output = relay(
+        tmp.path(),
+        &["sync", "--plan", "--project", &dir.to_string_lossy()],
+    )?;
+
+    assert!(!output.status.success());
+    assert!(utf8(&output.stderr).contains("not inside a git repository"));
+    Ok(())
+}
+
 #[cfg(unix)]
 #[test]
 fn scoped_apply_waits_for_process_lock_before_writing() -> io::Result<()> {